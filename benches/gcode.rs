@@ -0,0 +1,115 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nalgebra::Point2;
+use rustslicer::config::SlicerConfig;
+use rustslicer::gcode::GCodeGenerator;
+use rustslicer::geometry::{Island, Polygon};
+use rustslicer::slicer::Layer;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The system allocator, keeping track of the most memory held at once
+struct PeakCounting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakCounting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+            let live = LIVE.fetch_add(new_size, Ordering::Relaxed) + new_size;
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        new
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakCounting = PeakCounting;
+
+/// Most memory in bytes held at once while running `f`, beyond what was
+/// held before it
+fn peak_memory(f: impl FnOnce()) -> usize {
+    let before = LIVE.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    f();
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+fn circle(center: Point2<f64>, radius: f64) -> Polygon {
+    Polygon::new(
+        (0..64)
+            .map(|i| {
+                let angle = 2.0 * std::f64::consts::PI * i as f64 / 64.0;
+                center + nalgebra::Vector2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect(),
+    )
+}
+
+/// `count` layers of a 30 mm block with a round hole through it, as a
+/// tall print's worth of G-code without slicing a mesh
+fn synthetic_layers(count: usize, config: &SlicerConfig) -> Vec<Layer> {
+    let center = Point2::new(110.0, 110.0);
+    let square = Polygon::new(
+        [(-15.0, -15.0), (15.0, -15.0), (15.0, 15.0), (-15.0, 15.0)]
+            .iter()
+            .map(|&(x, y)| center + nalgebra::Vector2::new(x, y))
+            .collect(),
+    );
+    let mut hole = circle(center, 5.0);
+    hole.reverse();
+    let island = Island { outline: square, holes: vec![hole] };
+    (0..count)
+        .map(|i| Layer {
+            z_height: config.first_layer_height + i as f64 * config.layer_height,
+            layer_index: i,
+            islands: vec![island.clone()],
+            warnings: Vec::new(),
+            stitched_points: 0,
+        })
+        .collect()
+}
+
+fn bench_gcode(c: &mut Criterion) {
+    let config = SlicerConfig::default();
+    let layers = synthetic_layers(2_000, &config);
+    let generator = GCodeGenerator::new(config);
+
+    let to_string = peak_memory(|| {
+        black_box(generator.generate_to_string(&layers).unwrap());
+    });
+    let to_writer = peak_memory(|| {
+        black_box(generator.generate_to_writer(&layers, std::io::sink()).unwrap());
+    });
+    println!("peak memory for 2000 layers: {:.1} MB to a string, {:.1} MB to a writer",
+        to_string as f64 / 1e6, to_writer as f64 / 1e6);
+
+    let mut group = c.benchmark_group("generate_gcode");
+    group.sample_size(10);
+    group.bench_with_input(BenchmarkId::new("to_string", 2_000), &layers, |b, layers| {
+        b.iter(|| generator.generate_to_string(black_box(layers)).unwrap())
+    });
+    group.bench_with_input(BenchmarkId::new("to_writer", 2_000), &layers, |b, layers| {
+        b.iter(|| generator.generate_to_writer(black_box(layers), std::io::sink()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_gcode);
+criterion_main!(benches);
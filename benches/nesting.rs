@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nalgebra::Point2;
+use rustslicer::geometry::{Island, Polygon};
+use rustslicer::slicer::ContourTree;
+
+fn circle(center: Point2<f64>, radius: f64) -> Polygon {
+    Polygon::new(
+        (0..64)
+            .map(|i| {
+                let angle = 2.0 * std::f64::consts::PI * i as f64 / 64.0;
+                center + nalgebra::Vector2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect(),
+    )
+}
+
+/// `n` concentric circles, alternating outline and hole
+fn nested_circles(n: usize) -> Vec<Polygon> {
+    (0..n).map(|i| circle(Point2::origin(), 1.0 + 0.1 * i as f64)).collect()
+}
+
+/// A plate perforated by `n` small holes on a grid
+fn perforated_plate(n: usize) -> Vec<Polygon> {
+    let side = (n as f64).sqrt().ceil() as usize;
+    let mut contours = vec![Polygon::new(vec![
+        Point2::new(-1.0, -1.0),
+        Point2::new(2.0 * side as f64, -1.0),
+        Point2::new(2.0 * side as f64, 2.0 * side as f64),
+        Point2::new(-1.0, 2.0 * side as f64),
+    ])];
+    contours.extend((0..n).map(|i| circle(Point2::new(2.0 * (i % side) as f64, 2.0 * (i / side) as f64), 0.5)));
+    contours
+}
+
+/// Nesting by testing every pair of contours, kept as a baseline
+fn naive_nest(contours: &[Polygon]) -> Vec<Island> {
+    let depth = |i: usize| {
+        let probe = contours[i].points[0];
+        (0..contours.len()).filter(|&j| j != i && contours[j].contains_point(&probe)).count()
+    };
+    (0..contours.len())
+        .filter(|&i| depth(i) % 2 == 0)
+        .map(|i| Island::new(contours[i].clone()))
+        .collect()
+}
+
+fn bench_nesting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nest_contours");
+    group.sample_size(10);
+
+    for (name, contours) in [("nested_circles", nested_circles(1_000)), ("perforated_plate", perforated_plate(1_000))] {
+        group.bench_with_input(BenchmarkId::new("contour_tree", name), &contours, |b, contours| {
+            b.iter(|| ContourTree::new(black_box(contours.clone())))
+        });
+        group.bench_with_input(BenchmarkId::new("naive", name), &contours, |b, contours| {
+            b.iter(|| naive_nest(black_box(contours)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_nesting);
+criterion_main!(benches);
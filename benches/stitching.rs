@@ -0,0 +1,74 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nalgebra::Point3;
+use rustslicer::geometry::LineSegment;
+use rustslicer::slicer::build_contours;
+
+/// A single closed loop of `n` segments, emitted in scrambled order
+fn synthetic_layer(n: usize) -> Vec<LineSegment> {
+    let point = |i: usize| {
+        let angle = 2.0 * std::f64::consts::PI * (i % n) as f64 / n as f64;
+        Point3::new(100.0 * angle.cos(), 100.0 * angle.sin(), 1.0)
+    };
+
+    // 7919 is prime, so this visits every segment exactly once
+    (0..n)
+        .map(|i| (i * 7919) % n)
+        .map(|i| LineSegment { start: point(i), end: point(i + 1) })
+        .collect()
+}
+
+/// The previous scan-and-remove stitcher, kept as a baseline
+fn naive_build_contours(segments: &mut Vec<LineSegment>) -> usize {
+    let distance = |a: &Point3<f64>, b: &Point3<f64>| ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt();
+    let mut contours = 0;
+
+    while !segments.is_empty() {
+        let mut chain = vec![segments[0].start, segments[0].end];
+        segments.remove(0);
+
+        let mut progress = true;
+        while progress && !segments.is_empty() {
+            progress = false;
+            let last = *chain.last().unwrap();
+            for i in 0..segments.len() {
+                if distance(&last, &segments[i].start) < 1e-6 {
+                    chain.push(segments[i].end);
+                    segments.remove(i);
+                    progress = true;
+                    break;
+                } else if distance(&last, &segments[i].end) < 1e-6 {
+                    chain.push(segments[i].start);
+                    segments.remove(i);
+                    progress = true;
+                    break;
+                }
+            }
+        }
+        contours += 1;
+    }
+
+    contours
+}
+
+fn bench_stitching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_contours");
+    group.sample_size(10);
+
+    for n in [10_000, 100_000] {
+        let segments = synthetic_layer(n);
+        group.bench_with_input(BenchmarkId::new("spatial_hash", n), &segments, |b, segments| {
+            b.iter(|| build_contours(black_box(segments), 1e-6))
+        });
+    }
+
+    // The quadratic baseline is only practical at the smaller size
+    let segments = synthetic_layer(10_000);
+    group.bench_with_input(BenchmarkId::new("naive", 10_000), &segments, |b, segments| {
+        b.iter(|| naive_build_contours(&mut black_box(segments.clone())))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_stitching);
+criterion_main!(benches);
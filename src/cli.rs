@@ -1,6 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use anyhow::Result;
 use crate::commands;
+use crate::config::CommentLevel;
 
 #[derive(Parser)]
 #[command(name = "rustslicer")]
@@ -12,42 +13,135 @@ pub struct Cli {
     pub command: Commands,
 }
 
-#[derive(Subcommand)]
-pub enum Commands {
-    /// Slice an STL file to G-code
-    Slice {
-        /// Input STL file path
-        #[arg(value_name = "INPUT")]
-        input: String,
+#[derive(Args)]
+pub struct SliceArgs {
+    /// Input STL file paths (may be omitted if the config lists objects)
+    #[arg(value_name = "INPUT")]
+    pub inputs: Vec<String>,
 
-        /// Output G-code file path
-        #[arg(short, long, value_name = "OUTPUT")]
-        output: Option<String>,
+    /// Output G-code file path
+    #[arg(short, long, value_name = "OUTPUT")]
+    pub output: Option<String>,
 
-        /// Layer height in mm
-        #[arg(short = 'l', long, default_value = "0.2")]
-        layer_height: f64,
+    /// Layer height in mm
+    #[arg(short = 'l', long, default_value = "0.2")]
+    pub layer_height: f64,
 
-        /// Infill percentage (0-100)
-        #[arg(short = 'i', long, default_value = "20")]
-        infill: u8,
+    /// Infill percentage (0-100)
+    #[arg(short = 'i', long, default_value = "20")]
+    pub infill: u8,
 
-        /// Print speed in mm/s
-        #[arg(short = 's', long, default_value = "60")]
-        speed: f64,
+    /// Print speed in mm/s
+    #[arg(short = 's', long, default_value = "60")]
+    pub speed: f64,
 
-        /// Nozzle temperature in Celsius
-        #[arg(long, default_value = "210")]
-        nozzle_temp: u16,
+    /// Nozzle temperature in Celsius
+    #[arg(long, default_value = "210")]
+    pub nozzle_temp: u16,
 
-        /// Bed temperature in Celsius
-        #[arg(long, default_value = "60")]
-        bed_temp: u16,
+    /// Bed temperature in Celsius
+    #[arg(long, default_value = "60")]
+    pub bed_temp: u16,
 
-        /// Configuration file path
-        #[arg(short = 'c', long)]
-        config: Option<String>,
-    },
+    /// Configuration file path
+    #[arg(short = 'c', long)]
+    pub config: Option<String>,
+
+    /// Simplify the mesh to at most this many triangles before slicing
+    #[arg(long, value_name = "COUNT")]
+    pub decimate: Option<usize>,
+
+    /// Print a brim this many mm wide around the first layer
+    #[arg(long, value_name = "MM")]
+    pub brim: Option<f64>,
+
+    /// Print support under overhangs
+    #[arg(long)]
+    pub supports: bool,
+
+    /// Center the model on the bed
+    #[arg(long)]
+    pub center: bool,
+
+    /// Scale the model down uniformly if it does not fit the build volume
+    #[arg(long)]
+    pub fit: bool,
+
+    /// Slice even if the model exceeds the build volume
+    #[arg(long)]
+    pub force: bool,
+
+    /// Don't check the G-code against the machine once it is written
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Keep multiple models at their original coordinates instead of arranging them
+    #[arg(long)]
+    pub no_arrange: bool,
+
+    /// Print per-layer diagnostics
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Number of slicing threads (0 = all cores)
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Only slice heights between MIN and MAX mm above the bed
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_z_range)]
+    pub z_range: Option<(f64, f64)>,
+
+    /// Embed a PNG thumbnail WIDTHxHEIGHT pixels in the G-code; may be
+    /// given more than once
+    #[arg(long, value_name = "WxH", value_parser = parse_thumbnail_size)]
+    pub thumbnail: Vec<[u32; 2]>,
+
+    /// Pause before this layer, counted from 0; may be given more than once
+    #[arg(long, value_name = "LAYER")]
+    pub pause_at_layer: Vec<usize>,
+
+    /// Pause before the layer nearest this height in mm; may be given more
+    /// than once
+    #[arg(long, value_name = "MM")]
+    pub pause_at_height: Vec<f64>,
+
+    /// Comments in the G-code: off, normal or verbose
+    #[arg(long, value_name = "LEVEL", value_parser = parse_comment_level)]
+    pub gcode_comments: Option<CommentLevel>,
+}
+
+/// Parse a comment level as the config names it
+fn parse_comment_level(s: &str) -> std::result::Result<CommentLevel, String> {
+    match s {
+        "off" => Ok(CommentLevel::Off),
+        "normal" => Ok(CommentLevel::Normal),
+        "verbose" => Ok(CommentLevel::Verbose),
+        _ => Err(format!("expected off, normal or verbose, got '{}'", s)),
+    }
+}
+
+/// Parse a `WxH` thumbnail size such as `300x300`
+fn parse_thumbnail_size(s: &str) -> std::result::Result<[u32; 2], String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected WxH, got '{}'", s))?;
+    let parse = |v: &str| v.trim().parse::<u32>().map_err(|e| format!("invalid size '{}': {}", v, e));
+    Ok([parse(width)?, parse(height)?])
+}
+
+/// Parse a `MIN:MAX` height range such as `10:25`
+fn parse_z_range(s: &str) -> std::result::Result<(f64, f64), String> {
+    let (min, max) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected MIN:MAX, got '{}'", s))?;
+    let parse = |v: &str| v.trim().parse::<f64>().map_err(|e| format!("invalid height '{}': {}", v, e));
+    Ok((parse(min)?, parse(max)?))
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Slice an STL file to G-code
+    Slice(SliceArgs),
 
     /// Validate an STL file
     Validate {
@@ -68,34 +162,73 @@ pub enum Commands {
         /// Input STL file path
         #[arg(value_name = "INPUT")]
         input: String,
+
+        /// Also report statistics for the slab between MIN and MAX mm above the bed
+        #[arg(long, value_name = "MIN:MAX", value_parser = parse_z_range)]
+        z_range: Option<(f64, f64)>,
+    },
+
+    /// Report where a model overhangs enough to need support
+    Analyze {
+        /// Input STL file path
+        #[arg(value_name = "INPUT")]
+        input: String,
+
+        /// Configuration file path
+        #[arg(short = 'c', long)]
+        config: Option<String>,
+
+        /// Steepest overhang in degrees from vertical that needs no support
+        #[arg(long, value_name = "DEGREES")]
+        overhang_angle: Option<f64>,
+
+        /// Only analyze heights between MIN and MAX mm above the bed
+        #[arg(long, value_name = "MIN:MAX", value_parser = parse_z_range)]
+        z_range: Option<(f64, f64)>,
+    },
+
+    /// Check G-code against the machine's build volume, feedrates and
+    /// temperatures
+    GcodeCheck {
+        /// Input G-code file path
+        #[arg(value_name = "INPUT")]
+        input: String,
+
+        /// Configuration file path
+        #[arg(short = 'c', long)]
+        config: Option<String>,
+    },
+
+    /// Cut an STL file in two at a given height
+    Cut {
+        /// Input STL file path
+        #[arg(value_name = "INPUT")]
+        input: String,
+
+        /// Cut height in mm
+        #[arg(short, long)]
+        z: f64,
+
+        /// Directory for the output files (defaults to the input's directory)
+        #[arg(short, long, value_name = "DIR")]
+        output_dir: Option<String>,
     },
 }
 
 impl Cli {
     pub fn run(&self) -> Result<()> {
         match &self.command {
-            Commands::Slice {
-                input,
-                output,
-                layer_height,
-                infill,
-                speed,
-                nozzle_temp,
-                bed_temp,
-                config,
-            } => commands::slice::execute(
-                input,
-                output.as_deref(),
-                *layer_height,
-                *infill,
-                *speed,
-                *nozzle_temp,
-                *bed_temp,
-                config.as_deref(),
-            ),
+            Commands::Slice(args) => commands::slice::execute(args),
             Commands::Validate { input } => commands::validate::execute(input),
             Commands::Config { output } => commands::config::execute(output),
-            Commands::Info { input } => commands::info::execute(input),
+            Commands::Info { input, z_range } => commands::info::execute(input, *z_range),
+            Commands::Analyze { input, config, overhang_angle, z_range } => {
+                commands::analyze::execute(input, config.as_deref(), *overhang_angle, *z_range)
+            }
+            Commands::GcodeCheck { input, config } => commands::gcode_check::execute(input, config.as_deref()),
+            Commands::Cut { input, z, output_dir } => {
+                commands::cut::execute(input, *z, output_dir.as_deref())
+            }
         }
     }
 }
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use crate::commands::slice::print_filament;
+use crate::config::SlicerConfig;
+use crate::gcode::{format_duration, GCodeGenerator};
+use crate::geometry::Mesh;
+use crate::slicer::{detect_overhangs, generate_supports, Layer, SliceEngine};
+use std::collections::BTreeMap;
+
+/// Layers listed in the worst-overhang table
+const WORST_LAYERS: usize = 5;
+
+pub fn execute(input: &str, config_path: Option<&str>, overhang_angle: Option<f64>, z_range: Option<(f64, f64)>) -> Result<()> {
+    println!("🔎 Overhang Analysis");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("📁 File: {}", input);
+
+    let mut config = match config_path {
+        Some(path) => SlicerConfig::load_from_file(path)?,
+        None => SlicerConfig::default(),
+    };
+    if let Some(angle) = overhang_angle {
+        config.overhang_angle = angle;
+    }
+    config.validate()?;
+    println!("📐 Overhang threshold: {}° from vertical", config.overhang_angle);
+    if config.xy_compensation != 0.0 || config.hole_compensation != 0.0 || config.elephant_foot_compensation > 0.0 {
+        let (outlines, holes) = config.size_compensation(1);
+        println!("📏 Size compensation: outlines {:+.3} mm, holes {:+.3} mm", outlines, holes);
+        if config.elephant_foot_compensation > 0.0 {
+            let (outlines, holes) = config.size_compensation(0);
+            println!("   First layer: outlines {:+.3} mm, holes {:+.3} mm", outlines, holes);
+        }
+    }
+    println!();
+
+    let mut mesh = Mesh::from_stl_file(input)?;
+    mesh.drop_to_bed();
+    let engine = SliceEngine::new(mesh, config.clone())?;
+    // A range is sliced as a print of its own, from its first layer up
+    let layers = match z_range {
+        Some((z_min, z_max)) => {
+            println!("↕️  Z range: {:.3} to {:.3} mm", z_min, z_max);
+            engine.slice_range(z_min, z_max)?
+        }
+        None => engine.slice()?,
+    };
+    let report = detect_overhangs(&layers, &config);
+
+    println!("📊 Unsupported area: {:.2} mm² on {} of {} layers",
+        report.total_area(), report.layers.len(), layers.len());
+
+    let travel = |optimize_travel| {
+        GCodeGenerator::new(SlicerConfig { optimize_travel, ..config.clone() }).measure_travel(layers.iter().map(Ok))
+    };
+    println!("🚚 Travel: {:.1} mm → {:.1} mm after path ordering", travel(false)?, travel(true)?);
+
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, std::io::sink())?;
+    println!("⏱️  Estimated print time: {}", format_duration(stats.print_time));
+    for (role, time) in &stats.role_times {
+        println!("   {:<20} {}", role.name(), format_duration(*time));
+    }
+    println!("   {:<20} {}", "travel and other", format_duration(stats.other_time));
+    let slowest = stats.layer_times.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1));
+    if let Some((layer, time)) = slowest {
+        println!("   Longest layer: {} at {}", layer, format_duration(*time));
+    }
+    print_filament(&stats);
+
+    print_holes(&layers, config.resolution);
+
+    if !config.support_blockers.is_empty() {
+        let supports = generate_supports(&layers, &config);
+        let blocked = supports.iter().fold(0.0, |sum, layer| sum + layer.blocked_volume);
+        println!("🚫 Support removed by blockers: {:.2} mm³", blocked);
+    }
+
+    if report.needs_support() {
+        println!();
+        println!("   Worst layers:");
+        for layer in report.worst_layers(WORST_LAYERS) {
+            println!("   Layer {:>4} (Z {:.3}): {:.2} mm² in {} regions",
+                layer.layer_index, layer.z_height, layer.area, layer.regions.len());
+        }
+        println!();
+        println!("⚠️  Supports recommended (slice with --supports)");
+    } else {
+        println!("✅ No supports needed");
+    }
+
+    Ok(())
+}
+
+/// List the holes through the layers by diameter, the round ones fitted to
+/// circles within `tolerance`, with how many are on a layer at most and the
+/// layers they span
+fn print_holes(layers: &[Layer], tolerance: f64) {
+    // Keyed by diameter in hundredths of a mm, None for holes that aren't round
+    let mut holes: BTreeMap<Option<i64>, (usize, usize, usize)> = BTreeMap::new();
+    for layer in layers {
+        let mut counts: BTreeMap<Option<i64>, usize> = BTreeMap::new();
+        for diameter in layer.hole_diameters(tolerance) {
+            *counts.entry(diameter.map(|d| (d * 100.0).round() as i64)).or_default() += 1;
+        }
+        for (diameter, count) in counts {
+            let (_, last, most) = holes.entry(diameter).or_insert((layer.layer_index, layer.layer_index, 0));
+            *last = layer.layer_index;
+            *most = (*most).max(count);
+        }
+    }
+    if holes.is_empty() {
+        return;
+    }
+
+    println!("🕳️  Holes:");
+    for (diameter, (first, last, most)) in holes {
+        let size = diameter.map_or_else(|| "not round".to_string(), |d| format!("⌀{:.2} mm", d as f64 / 100.0));
+        println!("   {:<12} up to {} per layer on layers {}–{}", size, most, first, last);
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use crate::geometry::Mesh;
+use std::path::Path;
+
+pub fn execute(input: &str, z: f64, output_dir: Option<&str>) -> Result<()> {
+    println!("✂️  Cutting STL file: {}", input);
+    println!("📐 Cut height: {} mm", z);
+    println!();
+
+    let mesh = Mesh::from_stl_file(input)?;
+    mesh.validate()?;
+
+    let (bottom, top) = mesh.cut_at_z(z)?;
+
+    let input_path = Path::new(input);
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    let dir = output_dir
+        .map(Path::new)
+        .or_else(|| input_path.parent())
+        .unwrap_or_else(|| Path::new(""));
+
+    let bottom_path = dir.join(format!("{}_bottom.stl", stem));
+    let top_path = dir.join(format!("{}_top.stl", stem));
+
+    bottom.write_stl_file(&bottom_path.to_string_lossy())?;
+    top.write_stl_file(&top_path.to_string_lossy())?;
+
+    println!("✓ Bottom: {} ({} triangles, {:.2} mm tall)",
+        bottom_path.display(), bottom.triangles.len(), bottom.bounds.dimensions().z);
+    println!("✓ Top:    {} ({} triangles, {:.2} mm tall)",
+        top_path.display(), top.triangles.len(), top.bounds.dimensions().z);
+    println!();
+    println!("✅ Cut complete");
+
+    Ok(())
+}
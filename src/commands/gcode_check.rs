@@ -0,0 +1,48 @@
+use anyhow::Result;
+use crate::config::SlicerConfig;
+use crate::gcode::{verify, VerificationReport, ViolationKind};
+use std::fs::File;
+use std::io::BufReader;
+
+/// Kinds of violation in the order they are reported
+const KINDS: [ViolationKind; 5] = [
+    ViolationKind::OutOfBounds,
+    ViolationKind::BelowBed,
+    ViolationKind::Feedrate,
+    ViolationKind::NoTemperature,
+    ViolationKind::ColdExtrusion,
+];
+
+pub fn execute(input: &str, config_path: Option<&str>) -> Result<()> {
+    println!("🔍 Checking G-code file: {}", input);
+    println!();
+
+    let config = match config_path {
+        Some(path) => SlicerConfig::load_from_file(path)?,
+        None => SlicerConfig::default(),
+    };
+    config.validate()?;
+    let [x, y, z] = config.build_volume;
+    println!("📦 Build volume: {} x {} x {} mm", x, y, z);
+
+    let report = verify(BufReader::new(File::open(input)?), &config)?;
+    println!("   {} moves", report.moves);
+    print_violations(&report);
+    if !report.is_clean() {
+        anyhow::bail!("{} lines of {} break the machine's limits", report.violations.len(), input);
+    }
+
+    println!("✅ G-code stays within the machine's limits");
+    Ok(())
+}
+
+/// A line for each kind of violation in `report`, with the first line it
+/// was found on
+pub fn print_violations(report: &VerificationReport) {
+    for kind in KINDS {
+        let Some(first) = report.violations.iter().find(|violation| violation.kind == kind) else {
+            continue;
+        };
+        println!("⚠️  {}: {} (first at {})", kind.name(), report.count(kind), first);
+    }
+}
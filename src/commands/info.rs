@@ -1,7 +1,10 @@
 use anyhow::Result;
+use crate::config::SlicerConfig;
+use crate::error::SlicerError;
 use crate::geometry::Mesh;
+use crate::slicer::SliceEngine;
 
-pub fn execute(input: &str) -> Result<()> {
+pub fn execute(input: &str, z_range: Option<(f64, f64)>) -> Result<()> {
     println!("ℹ️  STL File Information");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     println!("📁 File: {}", input);
@@ -30,5 +33,40 @@ pub fn execute(input: &str) -> Result<()> {
     println!("📦 Bounding volume: {:.2} mm³ ({:.2} cm³)", 
         volume_estimate, volume_estimate / 1000.0);
 
+    if let Some((z_min, z_max)) = z_range {
+        println!();
+        print_slab(&mesh, z_min, z_max)?;
+    }
+
+    Ok(())
+}
+
+/// Statistics for the part of the model between two heights above its bottom
+fn print_slab(mesh: &Mesh, z_min: f64, z_max: f64) -> Result<()> {
+    let height = mesh.bounds.dimensions().z;
+    if z_min >= z_max || z_min < 0.0 || z_max > height + 1e-9 {
+        return Err(SlicerError::InvalidParameter(format!(
+            "Z range {}:{} must be increasing and within the model (0 to {:.3} mm)", z_min, z_max, height
+        )).into());
+    }
+
+    // Cut away everything outside the range; cuts at the model's ends are no-ops
+    let base = mesh.bounds.min.z;
+    let mut slab = mesh.clone();
+    if z_min > 0.0 {
+        slab = slab.cut_at_z(base + z_min)?.1;
+    }
+    if z_max < height {
+        slab = slab.cut_at_z(base + z_max)?.0;
+    }
+
+    let config = SlicerConfig::default();
+    let engine = SliceEngine::new(slab.clone(), config)?;
+
+    println!("✂️  Z range {} to {} mm:", z_min, z_max);
+    println!("   Triangles:  {}", slab.triangles.len());
+    println!("   Layers:     {} (default layer heights)", engine.layer_count());
+    println!("   Volume:     {:.2} mm³ ({:.2} cm³)", slab.volume(), slab.volume() / 1000.0);
+
     Ok(())
 }
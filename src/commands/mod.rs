@@ -2,3 +2,6 @@ pub mod slice;
 pub mod validate;
 pub mod config;
 pub mod info;
+pub mod cut;
+pub mod analyze;
+pub mod gcode_check;
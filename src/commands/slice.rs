@@ -1,29 +1,31 @@
 use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
 use crate::geometry::Mesh;
-use crate::slicer::Slicer;
-use crate::gcode::GCodeGenerator;
+use crate::geometry::arrange::arrange_around;
+use crate::slicer::SliceEngine;
+use crate::commands::gcode_check::print_violations;
+use crate::gcode::{format_duration, number_lines, post_process, verify, GCodeGenerator, GCodeStats, Metadata, PrintObject};
 use crate::config::SlicerConfig;
-use std::time::Instant;
-
-pub fn execute(
-    input: &str,
-    output: Option<&str>,
-    layer_height: f64,
-    infill: u8,
-    speed: f64,
-    nozzle_temp: u16,
-    bed_temp: u16,
-    config_path: Option<&str>,
-) -> Result<()> {
+use crate::cli::SliceArgs;
+use crate::error::SlicerError;
+use nalgebra::Vector3;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+pub fn execute(args: &SliceArgs) -> Result<()> {
     let start_time = Instant::now();
 
     println!("🦀 RustSlicer v0.1.0");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📁 Input file: {}", input);
+    for (i, input) in args.inputs.iter().enumerate() {
+        println!("📁 Input file {}: {}", i + 1, input);
+    }
 
     // Load configuration
-    let mut config = if let Some(config_path) = config_path {
+    let mut config = if let Some(config_path) = args.config.as_deref() {
         println!("⚙️  Loading configuration from: {}", config_path);
         SlicerConfig::load_from_file(config_path)?
     } else {
@@ -31,19 +33,134 @@ pub fn execute(
     };
 
     // Merge CLI parameters
-    config.merge_with_cli(layer_height, infill, speed, nozzle_temp, bed_temp);
+    config.merge_with_cli(args.layer_height, args.infill, args.speed, args.nozzle_temp, args.bed_temp);
+    if let Some(threads) = args.threads {
+        config.threads = threads;
+    }
+    if let Some(brim) = args.brim {
+        config.brim_width = brim;
+    }
+    if args.supports {
+        config.support_material = true;
+    }
+    if let Some(level) = args.gcode_comments {
+        config.gcode_comments = level;
+    }
+    if !args.thumbnail.is_empty() {
+        config.thumbnail = true;
+        config.thumbnail_sizes = args.thumbnail.clone();
+    }
+    config.validate()?;
+    for warning in config.limit_warnings() {
+        println!("⚠️  {}", warning);
+    }
 
-    println!("📐 Layer height: {} mm", config.layer_height);
+    println!("📐 Layer height: {} mm (first layer {} mm)", config.layer_height, config.first_layer_height);
     println!("🔲 Infill: {}%", config.infill_percentage);
     println!("⚡ Print speed: {} mm/s", config.print_speed);
-    println!("🌡️  Nozzle temp: {}°C", config.nozzle_temperature);
-    println!("🌡️  Bed temp: {}°C", config.bed_temperature);
+    println!("🌡️  Nozzle temp: {}°C (first layer {}°C)", config.nozzle_temperature, config.first_layer_nozzle_temperature());
+    println!("🌡️  Bed temp: {}°C (first layer {}°C)", config.bed_temperature, config.first_layer_bed_temperature());
     println!();
 
-    // Load STL file
-    println!("📥 Loading STL file...");
-    let mesh = Mesh::from_stl_file(input)?;
-    println!("✓ Loaded {} triangles", mesh.triangles.len());
+    // Load STL files
+    println!("📥 Loading STL files...");
+    let mut names = Vec::new();
+    let mut meshes = Vec::new();
+    // Objects given an explicit position in the config are left where they are
+    let mut placed = Vec::new();
+    for input in &args.inputs {
+        let mut mesh = Mesh::from_stl_file(input)?;
+        mesh.drop_to_bed();
+        names.push(input.clone());
+        meshes.push(mesh);
+        placed.push(false);
+    }
+    for object in &config.objects {
+        let copies = object.load(config.arrange_gap)?;
+        let count = copies.len();
+        for (i, mesh) in copies.into_iter().enumerate() {
+            names.push(if count > 1 { format!("{} #{}", object.path, i + 1) } else { object.path.clone() });
+            meshes.push(mesh);
+            placed.push(object.translate.is_some());
+        }
+    }
+    if meshes.is_empty() {
+        anyhow::bail!("No input files given on the command line or in the config's [[objects]]");
+    }
+
+    for (name, mesh) in names.iter().zip(meshes.iter_mut()) {
+        println!("✓ Loaded {} triangles from {}", mesh.triangles.len(), name);
+
+        if let Some(target) = args.decimate {
+            let before = mesh.triangles.len();
+            mesh.decimate(target);
+            println!("✓ Decimated {} → {} triangles", before, mesh.triangles.len());
+        }
+
+        if config.z_offset < 0.0 {
+            mesh.translate(Vector3::new(0.0, 0.0, config.z_offset));
+            println!("⬇️  Sunk model {:.2} mm into the bed", -config.z_offset);
+        }
+    }
+
+    let arranging = meshes.len() > 1 && !args.no_arrange && placed.contains(&false);
+    if arranging {
+        let free = placed.iter().filter(|&&placed| !placed).count();
+        if free == meshes.len() {
+            println!("🧩 Arranging {} models...", free);
+        } else {
+            println!("🧩 Arranging {} models around the {} placed ones...", free, meshes.len() - free);
+        }
+        arrange_around(&mut meshes, &placed, config.build_volume, config.arrange_gap)?;
+    }
+    if !args.force && !args.fit && !args.center {
+        // Objects put where the config says are checked one by one, to say
+        // which is off the bed. The rest were arranged on it, or are moved
+        // by --fit and --center and checked with the whole model below.
+        for (i, (name, mesh)) in names.iter().zip(&meshes).enumerate().filter(|&(i, _)| placed[i]) {
+            mesh.bounds.check_fit(config.build_volume)
+                .map_err(|e| anyhow::anyhow!("Object {} ({}): {}", i + 1, name, e))?;
+        }
+    }
+
+    let mut header_comments = Vec::new();
+    if meshes.len() > 1 {
+        for (i, (name, mesh)) in names.iter().zip(&meshes).enumerate() {
+            let center = mesh.bounds.center();
+            let placement = format!("Object {} ({}) at X{:.2} Y{:.2}", i + 1, name, center.x, center.y);
+            println!("   {}", placement);
+            header_comments.push(placement);
+        }
+    }
+    // Each model's triangles, to tell them apart once merged
+    let triangle_counts: Vec<usize> = meshes.iter().map(|mesh| mesh.triangles.len()).collect();
+    let mut mesh = Mesh::merge(meshes);
+
+    let [bed_x, bed_y, bed_z] = config.build_volume;
+    if args.fit {
+        let dims = mesh.bounds.dimensions();
+        let factor = (bed_x / dims.x).min(bed_y / dims.y).min(bed_z / dims.z);
+        if factor < 1.0 {
+            mesh.scale(factor);
+            println!("↘️  Scaled model by {:.3} to fit the build volume", factor);
+        }
+    }
+    if args.center || args.fit {
+        mesh.center_xy(bed_x / 2.0, bed_y / 2.0);
+    }
+
+    // Objects by the file they came from, where they ended up on the plate
+    let mut objects = Vec::new();
+    if config.exclude_objects {
+        let mut start = 0;
+        let mut models = Vec::new();
+        for (name, count) in names.iter().zip(&triangle_counts) {
+            let stem = Path::new(name).file_stem().map_or_else(|| name.clone(), |stem| stem.to_string_lossy().into_owned());
+            models.push((stem, Mesh::new(mesh.triangles[start..start + count].to_vec())));
+            start += count;
+        }
+        objects = PrintObject::from_meshes(models.iter().map(|(name, mesh)| (name.as_str(), mesh)));
+    }
 
     let dims = mesh.bounds.dimensions();
     println!("📏 Model dimensions: {:.2} x {:.2} x {:.2} mm", dims.x, dims.y, dims.z);
@@ -56,24 +173,151 @@ pub fn execute(
     println!();
 
     // Slice the model
-    let slicer = Slicer::new(mesh, config.layer_height)?;
-    println!("🔪 Slicing model...");
-    let layers = slicer.slice()?;
-    println!("✓ Generated {} layers", layers.len());
-    println!();
-
-    // Generate G-code
-    let output_path = output.unwrap_or_else(|| {
-        let input_stem = std::path::Path::new(input)
+    let slicer = SliceEngine::new(mesh, config.clone())?;
+    config.pause_layers.extend(&args.pause_at_layer);
+    for &height in &args.pause_at_height {
+        let layer = slicer.layer_at_height(height)
+            .ok_or_else(|| anyhow::anyhow!("Can't pause at {} mm: the model has no layers", height))?;
+        println!("⏸️  Pausing before layer {} (Z {:.3}) for {} mm", layer, slicer.layer_bounds(layer).1, height);
+        config.pause_layers.push(layer);
+    }
+    if !args.force {
+        slicer.check_fit(config.build_volume).map_err(|e| {
+            anyhow::anyhow!("{}\n   Hint: use --center to place the model on the bed, --fit to scale it down, or --force to slice anyway", e)
+        })?;
+    }
+    let first_input = args.inputs.first().unwrap_or_else(|| &config.objects[0].path).clone();
+    let output_path = args.output.as_deref().unwrap_or_else(|| {
+        let input_stem = std::path::Path::new(&first_input)
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("output");
         Box::leak(Box::new(format!("{}.gcode", input_stem))) as &str
     });
 
-    println!("📝 Generating G-code...");
-    let generator = GCodeGenerator::new(config);
-    generator.generate(&layers, output_path)?;
+    // Ctrl-C stops the slice cleanly instead of killing the process mid-layer
+    let cancel = Arc::new(AtomicBool::new(false));
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let handler_flag = Arc::clone(&cancel);
+        if let Err(e) = ctrlc::set_handler(move || handler_flag.store(true, Ordering::Relaxed)) {
+            log::warn!("Could not install Ctrl-C handler: {}", e);
+        }
+    }
+
+    let layers = match args.z_range {
+        Some((z_min, z_max)) => slicer.layers_iter_range(z_min, z_max)?,
+        None => slicer.layers_iter(),
+    };
+    println!("🔪 Slicing model into {} layers and writing G-code...", layers.layer_count());
+
+    // Layers are streamed straight to the file; keep only what the summary needs
+    let mut layer_count = 0;
+    let mut open_layers = 0;
+    let (mut points_before, mut points_after) = (0, 0);
+    let (mut islands, mut perimeter, mut largest_area) = (0, 0.0, 0.0_f64);
+    let mut warnings = Vec::new();
+    let mut layers = layers.cancel_on(&cancel);
+    let inspected = layers.by_ref().inspect(|result| {
+        if let Ok(layer) = result {
+            layer_count += 1;
+            if layer.open_contours() > 0 {
+                open_layers += 1;
+            }
+            points_before += layer.stitched_points;
+            points_after += layer.point_count();
+            islands += layer.island_count();
+            perimeter += layer.perimeter_length();
+            largest_area = largest_area.max(layer.total_area());
+            for warning in &layer.warnings {
+                warnings.push(format!("Layer {} (Z {:.3}): {}", layer.layer_index, layer.z_height, warning));
+            }
+        }
+    });
+
+    // The profile is named after the file it was loaded from
+    let metadata = Metadata {
+        profile: args.config.as_deref().and_then(|path| Path::new(path).file_stem()).map(|stem| stem.to_string_lossy().into_owned()),
+        timestamp: Some(SystemTime::now()),
+    };
+    // Scripts get the G-code unnumbered, and it is numbered after them so
+    // the checksums cover what they changed
+    let number_after = config.line_numbers_and_checksums && !config.post_process.is_empty();
+    let gcode_config = SlicerConfig { line_numbers_and_checksums: config.line_numbers_and_checksums && config.post_process.is_empty(), ..config.clone() };
+    let generator = GCodeGenerator::new(gcode_config).with_header_comments(header_comments).with_metadata(metadata).with_objects(objects);
+    let stats = match generator.generate_streaming(inspected, output_path) {
+        Err(SlicerError::Cancelled) => {
+            println!();
+            println!("🛑 Slicing cancelled");
+            std::process::exit(130);
+        }
+        result => result?,
+    };
+    println!("✓ Generated {} layers", layer_count);
+    println!("   {} moves, {:.1} KB", stats.moves, stats.bytes as f64 / 1024.0);
+    println!("⏱️  Estimated print time: {}", format_duration(stats.print_time));
+    print_filament(&stats);
+    if let Some(tower) = &stats.wipe_tower {
+        let size = tower.max - tower.min;
+        println!("🗼 Wipe tower at X{:.1} Y{:.1}, {:.1} x {:.1} mm", tower.min.x, tower.min.y, size.x, size.y);
+    }
+    for warning in &stats.warnings {
+        println!("⚠️  {}", warning);
+    }
+    if stats.clamped_moves > 0 {
+        println!("⚠️  {} moves slowed to the machine's max feedrate", stats.clamped_moves);
+    }
+    if !stats.slowed_layers.is_empty() {
+        println!("🐢 Slowed {} layers down for the minimum layer time", stats.slowed_layers.len());
+    }
+
+    if open_layers > 0 {
+        println!("⚠️  {} layers have unclosed contours", open_layers);
+    }
+    // Layers left with nothing to print at the top aren't printed, but
+    // what they dropped is still worth knowing
+    for layer in layers.trimmed() {
+        for warning in &layer.warnings {
+            println!("⚠️  Above the last layer, Z {:.3}: {}", layer.z_height, warning);
+        }
+    }
+    for command in &config.post_process {
+        post_process(output_path, command, &config)?;
+        println!("🔧 Post-processed with {}", command);
+    }
+    if number_after {
+        number_lines(output_path, config.first_line_number)?;
+    }
+    if !args.no_verify {
+        let report = verify(BufReader::new(File::open(output_path)?), &config)?;
+        print_violations(&report);
+        if report.out_of_bounds() && !args.force {
+            anyhow::bail!(
+                "G-code in {} moves outside the build volume\n   Hint: use --force to keep it anyway, or --no-verify to skip the check",
+                output_path
+            );
+        }
+    }
+    if args.verbose {
+        println!("   Contour points: {} → {} after simplification", points_before, points_after);
+        println!("   Islands: {}, perimeter length: {:.1} mm, largest layer area: {:.2} mm²",
+            islands, perimeter, largest_area);
+        // The layers are sliced again rather than all kept from the first
+        // pass, so a tall model needs no more memory with --verbose
+        let layers = match args.z_range {
+            Some((z_min, z_max)) => slicer.layers_iter_range(z_min, z_max)?,
+            None => slicer.layers_iter(),
+        };
+        let unordered = GCodeGenerator::new(SlicerConfig { optimize_travel: false, ..config })
+            .measure_travel(layers.cancel_on(&cancel))?;
+        println!("   Travel: {:.1} mm → {:.1} mm after path ordering", unordered, generator.travel_distance());
+        for (layer, factor) in &stats.slowed_layers {
+            println!("   Layer {}: slowed to {:.0}%", layer, factor * 100.0);
+        }
+        for warning in &warnings {
+            println!("   {}", warning);
+        }
+    }
     println!("✓ G-code written to: {}", output_path);
     println!();
 
@@ -83,3 +327,13 @@ pub fn execute(
 
     Ok(())
 }
+
+/// Print the filament a print uses
+pub fn print_filament(stats: &GCodeStats) {
+    print!("🧵 Filament: {:.2} m, {:.2} cm³, {:.1} g",
+        stats.filament_length / 1000.0, stats.filament_volume, stats.filament_weight);
+    match stats.filament_cost {
+        Some(cost) => println!(", costing {:.2}", cost),
+        None => println!(),
+    }
+}
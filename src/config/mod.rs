@@ -2,74 +2,1203 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 use crate::error::{SlicerError, Result};
+use crate::geometry::{Island, Mesh, Polygon};
+use nalgebra::{Matrix4, Point2, Rotation3, Vector3};
+
+/// Thickest combined sparse infill, as a fraction of the nozzle diameter
+const MAX_COMBINED_INFILL: f64 = 0.8;
+
+/// Largest thumbnail side in pixels, well beyond any printer screen
+const MAX_THUMBNAIL_SIZE: u32 = 1024;
+
+/// Distance in mm of the prime line from the front and left edges of the
+/// bed
+pub(crate) const PRIME_LINE_INSET: f64 = 5.0;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SlicerConfig {
     #[serde(default = "default_layer_height")]
     pub layer_height: f64,
 
+    /// Thickness of the first layer in mm
+    #[serde(default = "default_first_layer_height")]
+    pub first_layer_height: f64,
+
+    /// Inset in mm of the first layer's outlines, and outset of its holes,
+    /// against the squished first layer bulging out
+    #[serde(default)]
+    pub elephant_foot_compensation: f64,
+
+    /// Distance in mm every layer's outlines are grown by, shrinking holes
+    /// (negative shrinks outlines and grows holes)
+    #[serde(default)]
+    pub xy_compensation: f64,
+
+    /// Distance in mm holes are grown by on top of `xy_compensation`
+    /// (negative shrinks them)
+    #[serde(default)]
+    pub hole_compensation: f64,
+
+    /// Height within each layer at which the cross-section is taken. The
+    /// layer is still printed at its top; for the first layer the plane lies
+    /// within `first_layer_height` rather than `layer_height`.
+    #[serde(default)]
+    pub slice_position: SlicePosition,
+
+    /// Fraction of the layer height by which a slicing plane is lowered when
+    /// it passes exactly through mesh vertices. It is lowered by at least
+    /// 1e-9 mm even at 0, so faces in the plane aren't cut edge-on.
+    #[serde(default = "default_plane_epsilon")]
+    pub plane_epsilon: f64,
+
     #[serde(default = "default_infill")]
     pub infill_percentage: u8,
 
+    #[serde(default)]
+    pub infill_pattern: InfillPattern,
+
+    /// Direction of rectilinear infill lines on the first layer, in degrees
+    /// from the X axis
+    #[serde(default = "default_infill_angle")]
+    pub infill_angle: f64,
+
+    /// Degrees the infill direction turns by from one layer to the next
+    #[serde(default = "default_infill_angle_increment")]
+    pub infill_angle_increment: f64,
+
+    /// Layers the honeycomb stays put before shifting half a cell, so its
+    /// walls stack (0 never shifts)
+    #[serde(default)]
+    pub honeycomb_shift_layers: usize,
+
+    /// How far infill reaches into the innermost wall, in percent of the
+    /// line width
+    #[serde(default = "default_infill_overlap_percent")]
+    pub infill_overlap_percent: f64,
+
+    /// Layers of sparse infill printed as one thicker extrusion on the top
+    /// one of them (1 prints infill on every layer)
+    #[serde(default = "default_infill_combine_every")]
+    pub infill_combine_every: usize,
+
+    /// Print top surface skin as lines laid one after another across the
+    /// surface, all the same way, instead of zigzags
+    #[serde(default)]
+    pub monotonic_top_infill: bool,
+
+    /// Speed in mm/s for infill (defaults to `print_speed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infill_speed: Option<f64>,
+
+    /// Speed in mm/s for solid skin (defaults to `infill_speed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solid_infill_speed: Option<f64>,
+
+    /// Speed in mm/s for bridges (defaults to half the infill speed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bridge_speed: Option<f64>,
+
     #[serde(default = "default_print_speed")]
     pub print_speed: f64,
 
     #[serde(default = "default_travel_speed")]
     pub travel_speed: f64,
 
+    /// Speed in mm/s for lifting the nozzle off a retracted travel and
+    /// dropping it back (defaults to `max_feedrate_z`, or `travel_speed`
+    /// without one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub travel_speed_z: Option<f64>,
+
+    /// Visit islands, wall loops and infill lines nearest first instead of
+    /// in the order they were found
+    #[serde(default = "default_optimize_travel")]
+    pub optimize_travel: bool,
+
+    /// Which travel moves are routed around inside the part instead of
+    /// straight across it with the filament retracted
+    #[serde(default)]
+    pub combing: Combing,
+
+    /// Longest combed travel in mm; longer ones retract and go straight
+    /// (defaults to no limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comb_max_distance: Option<f64>,
+
+    /// Speed in mm/s for inner walls (defaults to `print_speed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perimeter_speed: Option<f64>,
+
+    /// Speed in mm/s for the outermost wall (defaults to the perimeter speed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_perimeter_speed: Option<f64>,
+
+    /// Speed in mm/s for gap fill between walls (defaults to half of
+    /// `perimeter_speed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gap_fill_speed: Option<f64>,
+
+    /// Speed in mm/s for wall loops shorter than `small_perimeter_length`
+    /// (defaults to half of `external_perimeter_speed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small_perimeter_speed: Option<f64>,
+
+    /// Length in mm below which a wall loop is printed at
+    /// `small_perimeter_speed` (defaults to 30 nozzle diameters)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small_perimeter_length: Option<f64>,
+
+    /// Speed in mm/s for everything printed on the first layer, overriding
+    /// the others (unset prints it like any other layer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_layer_speed: Option<f64>,
+
     #[serde(default = "default_nozzle_temp")]
     pub nozzle_temperature: u16,
 
     #[serde(default = "default_bed_temp")]
     pub bed_temperature: u16,
 
+    /// Nozzle temperature in Celsius for the first layer (defaults to
+    /// `nozzle_temperature`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_layer_nozzle_temperature: Option<u16>,
+
+    /// Bed temperature in Celsius for the first layer (defaults to
+    /// `bed_temperature`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_layer_bed_temperature: Option<u16>,
+
+    /// Order the bed and nozzle are heated in before printing
+    #[serde(default)]
+    pub heat_order: HeatOrder,
+
+    /// Cooling fan speed in percent
+    #[serde(default = "default_fan_speed")]
+    pub fan_speed: f64,
+
+    /// Number of layers printed with the fan off
+    #[serde(default = "default_disable_fan_first_layers")]
+    pub disable_fan_first_layers: usize,
+
+    /// Number of layers over which the fan speeds up to `fan_speed` once it
+    /// comes on
+    #[serde(default)]
+    pub fan_ramp_layers: usize,
+
+    /// Shortest time in seconds a layer may take; faster layers are slowed
+    /// down so they can cool (0 disables)
+    #[serde(default)]
+    pub min_layer_time: f64,
+
+    /// Slowest speed in mm/s a layer is slowed down to for the minimum
+    /// layer time
+    #[serde(default = "default_min_print_speed")]
+    pub min_print_speed: f64,
+
+    /// Wait out what is left of the minimum layer time when even the
+    /// slowest speed leaves a layer short of it
+    #[serde(default)]
+    pub min_layer_time_dwell: bool,
+
+    /// G-code written at every layer change, with `{layer_num}`, `{layer_z}`
+    /// and any setting's `{name}` filled in
+    #[serde(default)]
+    pub layer_change_gcode: String,
+
+    /// G-code written before the first layer's paths
+    #[serde(default)]
+    pub before_object_gcode: String,
+
+    /// G-code written after the last layer, before the end sequence
+    #[serde(default)]
+    pub after_object_gcode: String,
+
+    /// Layers to pause before, e.g. to drop magnets or nuts in
+    #[serde(default)]
+    pub pause_layers: Vec<usize>,
+
+    /// Layers to change filament before
+    #[serde(default)]
+    pub color_change_layers: Vec<usize>,
+
+    /// X and Y the nozzle is parked at for a pause or filament change
+    #[serde(default)]
+    pub pause_park_position: [f64; 2],
+
+    /// Height in mm the nozzle is lifted by before parking
+    #[serde(default = "default_pause_park_lift")]
+    pub pause_park_lift: f64,
+
+    /// Filament in mm primed after a pause or filament change, before
+    /// printing goes on
+    #[serde(default = "default_pause_prime_length")]
+    pub pause_prime_length: f64,
+
+    /// Firmware the G-code is written for
+    #[serde(default)]
+    pub gcode_flavor: FirmwareFlavor,
+
+    /// How much the G-code is commented
+    #[serde(default)]
+    pub gcode_comments: CommentLevel,
+
+    /// Digits after the point of X, Y and Z (and arc offsets)
+    #[serde(default = "default_xyz_decimals")]
+    pub xyz_decimals: usize,
+
+    /// Digits after the point of E
+    #[serde(default = "default_e_decimals")]
+    pub e_decimals: usize,
+
+    /// Write numbers without trailing zeros and moves without spaces
+    /// between their words, and leave out the coordinates a move shares
+    /// with the one before, for smaller G-code
+    #[serde(default)]
+    pub compact_gcode: bool,
+
+    /// Number every line of G-code and end it with a checksum, for
+    /// streaming over a serial link the firmware can ask to resend lines on
+    #[serde(default)]
+    pub line_numbers_and_checksums: bool,
+
+    /// Number of the first line after the M110 that resets the count
+    #[serde(default = "default_first_line_number")]
+    pub first_line_number: u64,
+
+    /// Commands the G-code file is run through once it is written, in
+    /// order, each given its path and replacing it with what it prints.
+    /// Lines are numbered after the last one.
+    #[serde(default)]
+    pub post_process: Vec<String>,
+
+    /// Retract with G10/G11 and leave the distance to the firmware (defaults
+    /// to what the flavor expects)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware_retraction: Option<bool>,
+
+    /// Acceleration limit in mm/s² set at the start (defaults to the
+    /// firmware's own)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acceleration: Option<f64>,
+
+    /// Acceleration in mm/s² for walls, brims and skirts (defaults to
+    /// `acceleration`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perimeter_acceleration: Option<f64>,
+
+    /// Acceleration in mm/s² for infill, bridges and support (defaults to
+    /// `acceleration`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infill_acceleration: Option<f64>,
+
+    /// Acceleration in mm/s² for travel (defaults to `acceleration`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub travel_acceleration: Option<f64>,
+
+    /// Acceleration in mm/s² for everything printed on the first layer
+    /// (defaults to the feature's own)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_layer_acceleration: Option<f64>,
+
+    /// Highest acceleration in mm/s² the machine takes; higher settings
+    /// are clamped to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_acceleration: Option<f64>,
+
+    /// X and Y jerk in mm/s set at the start (defaults to the firmware's
+    /// own)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jerk: Option<f64>,
+
+    /// Highest jerk in mm/s the machine takes; a higher `jerk` is clamped
+    /// to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_jerk: Option<f64>,
+
+    /// Pressure advance for the filament, set at the start with Klipper's
+    /// SET_PRESSURE_ADVANCE, Marlin's M900 or RepRapFirmware's M572
+    /// (defaults to the firmware's own)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pressure_advance: Option<f64>,
+
+    /// Marlin's linear advance K, used in place of `pressure_advance` on
+    /// Marlin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linear_advance_k: Option<f64>,
+
+    /// Pressure advance for infill, bridges and support (defaults to
+    /// `pressure_advance`, which has to be set with it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infill_pressure_advance: Option<f64>,
+
+    /// Fastest the machine moves in mm/s along X and Y; faster moves are
+    /// slowed to it, and print time is estimated with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_feedrate: Option<f64>,
+
+    /// Fastest the machine moves in mm/s along Z alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_feedrate_z: Option<f64>,
+
+    /// Fastest the machine moves the filament in mm/s on its own, as when
+    /// retracting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_feedrate_e: Option<f64>,
+
+    /// Hottest the machine's nozzle may be set in Celsius; higher settings
+    /// are refused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_nozzle_temperature: Option<u16>,
+
+    /// Hottest the machine's bed may be set in Celsius
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bed_temperature: Option<u16>,
+
+    /// Coldest the nozzle may be set in Celsius for the filament to move;
+    /// G-code extruding colder fails its check
+    #[serde(default = "default_min_extrusion_temperature")]
+    pub min_extrusion_temperature: u16,
+
+    /// Mark where each object's paths start and end on every layer, so the
+    /// firmware can cancel one and print on with the rest (Klipper only)
+    #[serde(default)]
+    pub exclude_objects: bool,
+
+    /// Write the estimated print time in the header and M73 progress at
+    /// every layer change
+    #[serde(default = "default_progress_updates")]
+    pub progress_updates: bool,
+
+    /// Show the layer being printed out of the total on the printer's
+    /// display at every layer change
+    #[serde(default)]
+    pub display_progress: bool,
+
+    /// Embed PNG thumbnails of the print, seen from above, in the G-code
+    /// header for printer screens and web interfaces
+    #[serde(default)]
+    pub thumbnail: bool,
+
+    /// Width and height in pixels of each thumbnail
+    #[serde(default = "default_thumbnail_sizes")]
+    pub thumbnail_sizes: Vec<[u32; 2]>,
+
+    /// Purge two lines side by side along the front of the bed before the
+    /// first layer, unless the first layer comes too close to them
+    #[serde(default)]
+    pub prime_line: bool,
+
+    /// Length in mm of each of the prime lines
+    #[serde(default = "default_prime_line_length")]
+    pub prime_line_length: f64,
+
+    /// Write curved paths as G2/G3 arcs, within `resolution` of the path,
+    /// for firmwares that take them
+    #[serde(default)]
+    pub arc_fitting: bool,
+
     #[serde(default = "default_nozzle_diameter")]
     pub nozzle_diameter: f64,
 
     #[serde(default = "default_filament_diameter")]
     pub filament_diameter: f64,
 
+    /// Scale on the filament fed for every extrusion, to tune for a
+    /// filament that runs thick or thin
+    #[serde(default = "default_flow_rate")]
+    pub flow_rate: f64,
+
+    /// Further scale on the filament fed on the first layer, so it squashes
+    /// into the bed
+    #[serde(default = "default_first_layer_flow")]
+    pub first_layer_flow: f64,
+
+    /// Filament density in g/cm³, for the filament weight
+    #[serde(default = "default_filament_density")]
+    pub filament_density: f64,
+
+    /// Filament price per kilogram, for the filament cost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filament_cost_per_kg: Option<f64>,
+
     #[serde(default = "default_retraction_distance")]
     pub retraction_distance: f64,
 
     #[serde(default = "default_retraction_speed")]
     pub retraction_speed: f64,
 
+    /// Travel moves shorter than this in mm never retract
+    #[serde(default = "default_retraction_minimum_travel")]
+    pub retraction_minimum_travel: f64,
+
+    /// Only retract when a straight travel leaves the island it starts in
+    #[serde(default)]
+    pub retraction_only_when_crossing_perimeters: bool,
+
+    /// Height in mm the nozzle is lifted by while travelling retracted
+    /// (0 travels at the layer height)
+    #[serde(default)]
+    pub retraction_z_lift: f64,
+
+    /// Filament in mm primed on top of the retraction once travel ends
+    #[serde(default)]
+    pub retraction_extra_restart: f64,
+
+    /// Length in mm at the end of each extrusion moved over without
+    /// extruding, so the pressure left in the nozzle finishes the path.
+    /// Paths shorter than twice this are printed in full.
+    #[serde(default)]
+    pub coast_distance: f64,
+
+    /// Retract while moving back along the path just printed, wiping the
+    /// nozzle on it
+    #[serde(default)]
+    pub wipe: bool,
+
+    /// Length in mm of the path wiped along
+    #[serde(default = "default_wipe_distance")]
+    pub wipe_distance: f64,
+
     #[serde(default = "default_wall_thickness")]
     pub wall_thickness: f64,
 
+    /// Number of walls around each island (defaults to as many line widths
+    /// as fit in `wall_thickness`, at least one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perimeters: Option<usize>,
+
+    /// Whether walls are printed from the outside in or the inside out
+    #[serde(default)]
+    pub perimeter_order: PerimeterOrder,
+
+    /// Length in mm below which a wall loop enclosing less than half a
+    /// square nozzle diameter is dropped (defaults to 4 nozzle diameters)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_loop_length: Option<f64>,
+
+    /// Print features narrower than two walls as a single line along their
+    /// middle, sized to their width, instead of walls that overlap or vanish
+    #[serde(default = "default_detect_thin_walls")]
+    pub detect_thin_walls: bool,
+
+    /// Where each wall loop starts and ends
+    #[serde(default)]
+    pub seam_position: SeamPosition,
+
+    /// Put seams in concave corners where a loop has any
+    #[serde(default = "default_hide_seam_in_corners")]
+    pub hide_seam_in_corners: bool,
+
+    /// Roughen external walls above the first layer by moving their points
+    /// in and out at random
+    #[serde(default)]
+    pub fuzzy_skin: bool,
+
+    /// Furthest in mm a fuzzy skin point moves either way
+    #[serde(default = "default_fuzzy_skin_thickness")]
+    pub fuzzy_skin_thickness: f64,
+
+    /// Spacing in mm of the points fuzzy skin moves
+    #[serde(default = "default_fuzzy_skin_point_distance")]
+    pub fuzzy_skin_point_distance: f64,
+
+    /// Seed for fuzzy skin, which comes out the same for the same seed
+    #[serde(default)]
+    pub fuzzy_skin_seed: u64,
+
     #[serde(default = "default_top_bottom_thickness")]
     pub top_bottom_thickness: f64,
+
+    /// Width in mm of the brim printed around the first layer (0 disables)
+    #[serde(default)]
+    pub brim_width: f64,
+
+    /// Only lay the brim around the outside of each island, not inside holes
+    #[serde(default = "default_brim_only_outside")]
+    pub brim_only_outside: bool,
+
+    /// Surround the print with a one-line wall that blocks drafts
+    #[serde(default)]
+    pub draft_shield: bool,
+
+    /// Distance in mm from the print to the draft shield
+    #[serde(default = "default_draft_shield_distance")]
+    pub draft_shield_distance: f64,
+
+    /// How high the draft shield goes: "full" or a height in mm
+    #[serde(default)]
+    pub draft_shield_height: DraftShieldHeight,
+
+    /// Purge after every tool change and filament change on a column
+    /// beside the print, rather than into the air
+    #[serde(default)]
+    pub wipe_tower: bool,
+
+    /// Front left corner of the wipe tower in mm; without it the tower is
+    /// placed beside the print
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wipe_tower_position: Option<[f64; 2]>,
+
+    /// Width of the wipe tower in mm along X; its depth follows from the
+    /// purges a layer needs
+    #[serde(default = "default_wipe_tower_width")]
+    pub wipe_tower_width: f64,
+
+    /// Filament in mm³ purged on the wipe tower after each change
+    #[serde(default = "default_wipe_tower_purge_volume")]
+    pub wipe_tower_purge_volume: f64,
+
+    /// Steepest overhang in degrees from vertical that prints without
+    /// support
+    #[serde(default = "default_overhang_angle")]
+    pub overhang_angle: f64,
+
+    /// Print support under overhangs steeper than `overhang_angle`
+    #[serde(default)]
+    pub support_material: bool,
+
+    /// Support infill percentage (0-100)
+    #[serde(default = "default_support_density")]
+    pub support_density: u8,
+
+    /// Horizontal gap in mm between support and the model
+    #[serde(default = "default_support_xy_gap")]
+    pub support_xy_gap: f64,
+
+    /// Layers left empty between the top of the support and the model, so
+    /// it breaks away
+    #[serde(default = "default_support_z_gap_layers")]
+    pub support_z_gap_layers: usize,
+
+    /// Layers of dense interface at the top of the support, under the model
+    #[serde(default = "default_support_interface_layers")]
+    pub support_interface_layers: usize,
+
+    /// Layers of dense interface where support stands on the model
+    #[serde(default)]
+    pub support_bottom_interface_layers: usize,
+
+    /// Interface infill percentage (0-100); its lines cross the support's
+    #[serde(default = "default_support_interface_density")]
+    pub support_interface_density: u8,
+
+    /// Boxes support is never printed in, as listed in `[[support_blockers]]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub support_blockers: Vec<SupportBox>,
+
+    /// Boxes whose part of the model is always supported, as listed in
+    /// `[[support_enforcers]]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub support_enforcers: Vec<SupportBox>,
+
+    /// Speed in mm/s for support (defaults to `print_speed`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub support_speed: Option<f64>,
+
+    /// Solid layers under top surfaces (defaults to as many layers as fit
+    /// in `top_bottom_thickness`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_solid_layers: Option<usize>,
+
+    /// Solid layers over bottom surfaces (defaults to as many layers as fit
+    /// in `top_bottom_thickness`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bottom_solid_layers: Option<usize>,
+
+    /// Print the layers above the bottom solid layers as a single outside
+    /// wall rising in one continuous spiral, with no seam, infill or top
+    #[serde(default)]
+    pub spiral_vase: bool,
+
+    /// Largest deviation in mm allowed when simplifying contours; points
+    /// closer than this to the simplified outline are dropped
+    #[serde(default = "default_resolution")]
+    pub resolution: f64,
+
+    /// Worker threads used for slicing; 0 uses all cores
+    #[serde(default)]
+    pub threads: usize,
+
+    /// Grid in mm that slice intersection points are snapped to; 0 disables snapping
+    #[serde(default = "default_snap_grid")]
+    pub snap_grid: f64,
+
+    /// Distance in mm below which segment endpoints are joined when building
+    /// contours (defaults to a thousandth of the nozzle diameter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contour_merge_tolerance: Option<f64>,
+
+    /// Largest gap in mm between the ends of an open contour that is closed
+    /// automatically (defaults to half the nozzle diameter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_gap_close: Option<f64>,
+
+    /// Side in mm of the smallest square feature worth printing; islands and
+    /// holes with less area are dropped (defaults to the nozzle diameter,
+    /// 0 keeps everything)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_feature_size: Option<f64>,
+
+    /// Gaps in mm narrower than this between islands of a layer are closed,
+    /// merging the islands. Off by default, as closing also blunts corners
+    /// sharper than about 60°; a quarter of the nozzle diameter closes
+    /// hairline gaps between touching parts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_gaps: Option<f64>,
+
+    /// Printable volume in mm (X, Y, Z), with the bed spanning from the origin
+    #[serde(default = "default_build_volume")]
+    pub build_volume: [f64; 3],
+
+    /// Spacing in mm between parts when arranging several models on the bed
+    #[serde(default = "default_arrange_gap")]
+    pub arrange_gap: f64,
+
+    /// Vertical offset applied after dropping the model to the bed; negative values sink it
+    #[serde(default = "default_z_offset")]
+    pub z_offset: f64,
+
+    /// Extruder, counting from 0, printing walls, brims, skirts and draft
+    /// shields
+    #[serde(default)]
+    pub perimeter_extruder: usize,
+
+    /// Extruder printing infill and bridges
+    #[serde(default)]
+    pub infill_extruder: usize,
+
+    /// Extruder printing support
+    #[serde(default)]
+    pub support_extruder: usize,
+
+    /// Leave the extruders' offsets to the firmware's tool offsets instead
+    /// of shifting the coordinates by them
+    #[serde(default)]
+    pub firmware_tool_offsets: bool,
+
+    /// The printer's extruders, as listed in `[[extruders]]`; without any,
+    /// everything is printed with the one the settings above describe.
+    /// Tools are changed with `T<n>`, which Klipper needs macros for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extruders: Vec<Extruder>,
+
+    /// Models making up the plate, each with its own transform
+    #[serde(default)]
+    pub objects: Vec<ObjectSettings>,
+
+    /// Volumes with their own settings, as listed in `[[modifiers]]`; where
+    /// they overlap, the later one wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<Modifier>,
+
+    /// Heights printed at their own temperature or fan speed, as listed in
+    /// `[[overrides]]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<HeightOverride>,
+}
+
+/// Where the slicing plane sits within a layer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlicePosition {
+    /// Halfway between the layer's bottom and top
+    Middle,
+    /// At the layer's top, where the nozzle deposits it
+    #[default]
+    Top,
+}
+
+/// Pattern used to fill island interiors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InfillPattern {
+    /// Parallel lines, linked into zigzags
+    #[default]
+    Rectilinear,
+    /// Hexagonal cells
+    Honeycomb,
+}
+
+/// Order in which an island's walls are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PerimeterOrder {
+    /// External wall first, for the most accurate outer dimensions
+    Outer,
+    /// Innermost wall first, so the external one is laid against support
+    #[default]
+    Inner,
+}
+
+/// Firmware dialect of the G-code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirmwareFlavor {
+    #[default]
+    Marlin,
+    Klipper,
+    RepRapFirmware,
+}
+
+/// How much the G-code is commented
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentLevel {
+    /// No comments at all, for the smallest file
+    Off,
+    /// Sections, layer markers and what each command is for
+    #[default]
+    Normal,
+    /// Also the feature type, line width and layer height of every path,
+    /// as PrusaSlicer writes them for G-code viewers to color by
+    Verbose,
+}
+
+/// Order the bed and nozzle are heated in before printing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatOrder {
+    /// Start heating the bed, then the nozzle, and wait for both
+    #[default]
+    Together,
+    /// Heat the bed and wait for it before heating the nozzle, so the
+    /// nozzle doesn't ooze while the bed warms up
+    BedFirst,
+    /// Heat the nozzle and wait for it before heating the bed
+    NozzleFirst,
+}
+
+/// How high the draft shield goes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "HeightValue", into = "HeightValue")]
+pub enum DraftShieldHeight {
+    /// As high as the print
+    #[default]
+    Full,
+    /// Up to a height in mm
+    Height(f64),
+}
+
+impl DraftShieldHeight {
+    /// Whether the shield reaches a layer at `z`
+    pub fn covers(&self, z: f64) -> bool {
+        match self {
+            DraftShieldHeight::Full => true,
+            DraftShieldHeight::Height(height) => z <= height + 1e-9,
+        }
+    }
+}
+
+/// A height as written in the config: a name or a number
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum HeightValue {
+    Name(String),
+    Height(f64),
+}
+
+impl TryFrom<HeightValue> for DraftShieldHeight {
+    type Error = String;
+
+    fn try_from(value: HeightValue) -> std::result::Result<Self, String> {
+        match value {
+            HeightValue::Name(name) if name == "full" => Ok(DraftShieldHeight::Full),
+            HeightValue::Name(name) => Err(format!("expected \"full\" or a height in mm, got \"{}\"", name)),
+            HeightValue::Height(height) => Ok(DraftShieldHeight::Height(height)),
+        }
+    }
+}
+
+impl From<DraftShieldHeight> for HeightValue {
+    fn from(height: DraftShieldHeight) -> Self {
+        match height {
+            DraftShieldHeight::Full => HeightValue::Name("full".to_string()),
+            DraftShieldHeight::Height(height) => HeightValue::Height(height),
+        }
+    }
+}
+
+/// Travel moves kept inside the part
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Combing {
+    /// Every travel goes straight
+    #[default]
+    Off,
+    /// Travel within an island's infill is combed
+    WithinInfill,
+    /// Travel anywhere inside an island's external wall is combed
+    All,
+}
+
+/// Axis-aligned box in build volume coordinates that blocks or enforces support
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SupportBox {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl SupportBox {
+    /// Whether a layer at `z` falls within the box's Z range
+    pub fn spans(&self, z: f64) -> bool {
+        (self.min[2]..=self.max[2]).contains(&z)
+    }
+
+    /// The box's outline seen from above
+    pub fn footprint(&self) -> Island {
+        let ([x0, y0, _], [x1, y1, _]) = (self.min, self.max);
+        Island::new(Polygon::new(vec![
+            Point2::new(x0, y0),
+            Point2::new(x1, y0),
+            Point2::new(x1, y1),
+            Point2::new(x0, y1),
+        ]))
+    }
+}
+
+/// Where wall loops start, and so where the seam between their ends shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeamPosition {
+    /// Wherever is closest to the nozzle, for the shortest travel
+    Nearest,
+    /// Closest to the same point on every layer, lining seams up
+    #[default]
+    Aligned,
+    /// At the back of each loop, facing away from the front of the bed
+    Rear,
+    /// Scattered along each loop
+    Random,
+}
+
+/// Shape of a modifier volume
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModifierShape {
+    /// Axis-aligned box from `min` to `max`
+    #[default]
+    Box,
+}
+
+/// Volume in build volume coordinates where some settings are overridden
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Modifier {
+    #[serde(default)]
+    pub shape: ModifierShape,
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+
+    /// Sparse infill percentage (0-100) inside the volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infill_density: Option<u8>,
+
+    /// Wall loops for the parts of islands inside the volume, which get
+    /// walls of their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perimeters: Option<usize>,
+}
+
+impl Modifier {
+    /// Whether a layer at `z` falls within the volume's Z range
+    pub fn spans(&self, z: f64) -> bool {
+        (self.min[2]..=self.max[2]).contains(&z)
+    }
+
+    /// The volume's outline seen from above
+    pub fn footprint(&self) -> Island {
+        match self.shape {
+            ModifierShape::Box => SupportBox { min: self.min, max: self.max }.footprint(),
+        }
+    }
+}
+
+/// Heights from `z_min` up to `z_max` in mm printed at their own nozzle
+/// temperature or fan speed, e.g. for a temperature tower
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeightOverride {
+    pub z_min: f64,
+    pub z_max: f64,
+
+    /// Nozzle temperature in Celsius
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<u16>,
+
+    /// Fan speed in percent (0-100)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan_speed: Option<f64>,
+}
+
+impl HeightOverride {
+    /// Whether a layer whose top is at `z` falls within the range: one
+    /// topping out at `z_min` is still below it
+    pub fn spans(&self, z: f64) -> bool {
+        self.z_min < z && z <= self.z_max
+    }
+}
+
+/// One of the printer's extruders, with what sets it apart from the
+/// settings for all of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extruder {
+    /// Where the nozzle sits in mm relative to the first extruder's
+    #[serde(default)]
+    pub offset: [f64; 2],
+
+    /// Nozzle diameter in mm, which the support lines it prints are as wide
+    /// as (defaults to `nozzle_diameter`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nozzle_diameter: Option<f64>,
+
+    /// Diameter in mm of the filament loaded (defaults to
+    /// `filament_diameter`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filament_diameter: Option<f64>,
+
+    /// Nozzle temperature in Celsius (defaults to the layer's)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<u16>,
+
+    /// Temperature in Celsius the nozzle is left at while another extruder
+    /// prints (defaults to keeping it at its temperature)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub standby_temperature: Option<u16>,
+}
+
+/// One model on the plate, as listed in `[[objects]]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectSettings {
+    pub path: String,
+
+    /// Offset in mm applied after the model is dropped to the bed.
+    /// Objects without one are arranged automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<[f64; 3]>,
+
+    /// Rotation in degrees about the X, Y, and Z axes through the model's center
+    #[serde(default)]
+    pub rotate: [f64; 3],
+
+    #[serde(default = "default_object_scale")]
+    pub scale: f64,
+
+    /// Number of instances; copies are placed side by side along X
+    #[serde(default = "default_object_copies")]
+    pub copies: usize,
 }
 
 fn default_layer_height() -> f64 { 0.2 }
+fn default_first_layer_height() -> f64 { 0.3 }
+fn default_plane_epsilon() -> f64 { 1e-6 }
+fn default_resolution() -> f64 { 0.0125 }
+fn default_snap_grid() -> f64 { 0.001 }
 fn default_infill() -> u8 { 20 }
+fn default_infill_angle() -> f64 { 45.0 }
+fn default_infill_angle_increment() -> f64 { 90.0 }
+fn default_infill_overlap_percent() -> f64 { 15.0 }
+fn default_infill_combine_every() -> usize { 1 }
 fn default_print_speed() -> f64 { 60.0 }
 fn default_travel_speed() -> f64 { 120.0 }
+fn default_optimize_travel() -> bool { true }
 fn default_nozzle_temp() -> u16 { 210 }
 fn default_bed_temp() -> u16 { 60 }
+fn default_fan_speed() -> f64 { 100.0 }
+fn default_disable_fan_first_layers() -> usize { 1 }
+fn default_min_print_speed() -> f64 { 10.0 }
 fn default_nozzle_diameter() -> f64 { 0.4 }
 fn default_filament_diameter() -> f64 { 1.75 }
+fn default_flow_rate() -> f64 { 1.0 }
+fn default_first_layer_flow() -> f64 { 1.0 }
+fn default_filament_density() -> f64 { 1.24 }
 fn default_retraction_distance() -> f64 { 5.0 }
 fn default_retraction_speed() -> f64 { 40.0 }
+fn default_retraction_minimum_travel() -> f64 { 2.0 }
+fn default_wipe_distance() -> f64 { 2.0 }
 fn default_wall_thickness() -> f64 { 0.8 }
+fn default_detect_thin_walls() -> bool { true }
+fn default_hide_seam_in_corners() -> bool { true }
+fn default_fuzzy_skin_thickness() -> f64 { 0.3 }
+fn default_fuzzy_skin_point_distance() -> f64 { 0.8 }
 fn default_top_bottom_thickness() -> f64 { 0.8 }
+fn default_brim_only_outside() -> bool { true }
+fn default_min_extrusion_temperature() -> u16 { 170 }
+fn default_progress_updates() -> bool { true }
+fn default_thumbnail_sizes() -> Vec<[u32; 2]> { vec![[32, 32], [300, 300]] }
+fn default_prime_line_length() -> f64 { 100.0 }
+fn default_draft_shield_distance() -> f64 { 10.0 }
+fn default_wipe_tower_width() -> f64 { 60.0 }
+fn default_wipe_tower_purge_volume() -> f64 { 70.0 }
+fn default_overhang_angle() -> f64 { 45.0 }
+fn default_support_density() -> u8 { 15 }
+fn default_support_xy_gap() -> f64 { 0.6 }
+fn default_support_z_gap_layers() -> usize { 1 }
+fn default_support_interface_layers() -> usize { 2 }
+fn default_support_interface_density() -> u8 { 80 }
+fn default_build_volume() -> [f64; 3] { [220.0, 220.0, 250.0] }
+fn default_pause_park_lift() -> f64 { 5.0 }
+fn default_pause_prime_length() -> f64 { 3.0 }
+fn default_xyz_decimals() -> usize { 3 }
+fn default_e_decimals() -> usize { 5 }
+fn default_first_line_number() -> u64 { 1 }
+fn default_arrange_gap() -> f64 { 5.0 }
+fn default_z_offset() -> f64 { 0.0 }
+fn default_object_scale() -> f64 { 1.0 }
+fn default_object_copies() -> usize { 1 }
 
 impl Default for SlicerConfig {
     fn default() -> Self {
         Self {
             layer_height: default_layer_height(),
+            first_layer_height: default_first_layer_height(),
+            elephant_foot_compensation: 0.0,
+            xy_compensation: 0.0,
+            hole_compensation: 0.0,
+            slice_position: SlicePosition::default(),
+            plane_epsilon: default_plane_epsilon(),
             infill_percentage: default_infill(),
+            infill_pattern: InfillPattern::default(),
+            infill_angle: default_infill_angle(),
+            infill_angle_increment: default_infill_angle_increment(),
+            honeycomb_shift_layers: 0,
+            infill_overlap_percent: default_infill_overlap_percent(),
+            infill_combine_every: default_infill_combine_every(),
+            monotonic_top_infill: false,
+            infill_speed: None,
+            solid_infill_speed: None,
+            bridge_speed: None,
             print_speed: default_print_speed(),
             travel_speed: default_travel_speed(),
+            travel_speed_z: None,
+            optimize_travel: default_optimize_travel(),
+            combing: Combing::default(),
+            comb_max_distance: None,
+            perimeter_speed: None,
+            external_perimeter_speed: None,
+            gap_fill_speed: None,
+            small_perimeter_speed: None,
+            small_perimeter_length: None,
+            first_layer_speed: None,
             nozzle_temperature: default_nozzle_temp(),
             bed_temperature: default_bed_temp(),
+            first_layer_nozzle_temperature: None,
+            first_layer_bed_temperature: None,
+            heat_order: HeatOrder::default(),
+            fan_speed: default_fan_speed(),
+            disable_fan_first_layers: default_disable_fan_first_layers(),
+            fan_ramp_layers: 0,
+            min_layer_time: 0.0,
+            min_print_speed: default_min_print_speed(),
+            min_layer_time_dwell: false,
+            layer_change_gcode: String::new(),
+            before_object_gcode: String::new(),
+            after_object_gcode: String::new(),
+            pause_layers: Vec::new(),
+            color_change_layers: Vec::new(),
+            pause_park_position: [0.0, 0.0],
+            pause_park_lift: default_pause_park_lift(),
+            pause_prime_length: default_pause_prime_length(),
+            gcode_flavor: FirmwareFlavor::default(),
+            gcode_comments: CommentLevel::default(),
+            xyz_decimals: default_xyz_decimals(),
+            e_decimals: default_e_decimals(),
+            compact_gcode: false,
+            line_numbers_and_checksums: false,
+            first_line_number: default_first_line_number(),
+            post_process: Vec::new(),
+            firmware_retraction: None,
+            acceleration: None,
+            perimeter_acceleration: None,
+            infill_acceleration: None,
+            travel_acceleration: None,
+            first_layer_acceleration: None,
+            max_acceleration: None,
+            jerk: None,
+            max_jerk: None,
+            pressure_advance: None,
+            linear_advance_k: None,
+            infill_pressure_advance: None,
+            max_feedrate: None,
+            max_feedrate_z: None,
+            max_feedrate_e: None,
+            max_nozzle_temperature: None,
+            max_bed_temperature: None,
+            min_extrusion_temperature: default_min_extrusion_temperature(),
+            exclude_objects: false,
+            progress_updates: default_progress_updates(),
+            display_progress: false,
+            thumbnail: false,
+            thumbnail_sizes: default_thumbnail_sizes(),
+            prime_line: false,
+            prime_line_length: default_prime_line_length(),
+            arc_fitting: false,
             nozzle_diameter: default_nozzle_diameter(),
             filament_diameter: default_filament_diameter(),
+            flow_rate: default_flow_rate(),
+            first_layer_flow: default_first_layer_flow(),
+            filament_density: default_filament_density(),
+            filament_cost_per_kg: None,
             retraction_distance: default_retraction_distance(),
             retraction_speed: default_retraction_speed(),
+            retraction_minimum_travel: default_retraction_minimum_travel(),
+            retraction_only_when_crossing_perimeters: false,
+            retraction_z_lift: 0.0,
+            retraction_extra_restart: 0.0,
+            coast_distance: 0.0,
+            wipe: false,
+            wipe_distance: default_wipe_distance(),
             wall_thickness: default_wall_thickness(),
+            perimeters: None,
+            perimeter_order: PerimeterOrder::default(),
+            min_loop_length: None,
+            detect_thin_walls: default_detect_thin_walls(),
+            seam_position: SeamPosition::default(),
+            hide_seam_in_corners: default_hide_seam_in_corners(),
+            fuzzy_skin: false,
+            fuzzy_skin_thickness: default_fuzzy_skin_thickness(),
+            fuzzy_skin_point_distance: default_fuzzy_skin_point_distance(),
+            fuzzy_skin_seed: 0,
             top_bottom_thickness: default_top_bottom_thickness(),
+            brim_width: 0.0,
+            brim_only_outside: default_brim_only_outside(),
+            draft_shield: false,
+            draft_shield_distance: default_draft_shield_distance(),
+            draft_shield_height: DraftShieldHeight::default(),
+            wipe_tower: false,
+            wipe_tower_position: None,
+            wipe_tower_width: default_wipe_tower_width(),
+            wipe_tower_purge_volume: default_wipe_tower_purge_volume(),
+            overhang_angle: default_overhang_angle(),
+            support_material: false,
+            support_density: default_support_density(),
+            support_xy_gap: default_support_xy_gap(),
+            support_z_gap_layers: default_support_z_gap_layers(),
+            support_interface_layers: default_support_interface_layers(),
+            support_bottom_interface_layers: 0,
+            support_interface_density: default_support_interface_density(),
+            support_blockers: Vec::new(),
+            support_enforcers: Vec::new(),
+            support_speed: None,
+            top_solid_layers: None,
+            bottom_solid_layers: None,
+            spiral_vase: false,
+            resolution: default_resolution(),
+            threads: 0,
+            snap_grid: default_snap_grid(),
+            contour_merge_tolerance: None,
+            max_gap_close: None,
+            min_feature_size: None,
+            close_gaps: None,
+            build_volume: default_build_volume(),
+            arrange_gap: default_arrange_gap(),
+            z_offset: default_z_offset(),
+            perimeter_extruder: 0,
+            infill_extruder: 0,
+            support_extruder: 0,
+            firmware_tool_offsets: false,
+            extruders: Vec::new(),
+            objects: Vec::new(),
+            modifiers: Vec::new(),
+            overrides: Vec::new(),
         }
     }
 }
@@ -91,6 +1220,678 @@ impl SlicerConfig {
             .map_err(|e| SlicerError::ConfigError(format!("Failed to write config file: {}", e)))
     }
 
+    pub fn validate(&self) -> Result<()> {
+        if self.layer_height <= 0.0 || self.first_layer_height <= 0.0 {
+            return Err(SlicerError::ConfigError(
+                "layer_height and first_layer_height must be positive".to_string()
+            ));
+        }
+
+        if self.nozzle_diameter <= 0.0 || self.filament_diameter <= 0.0 {
+            return Err(SlicerError::ConfigError(
+                "nozzle_diameter and filament_diameter must be positive".to_string()
+            ));
+        }
+
+        if self.flow_rate <= 0.0 || self.first_layer_flow <= 0.0 {
+            return Err(SlicerError::ConfigError(format!(
+                "flow_rate and first_layer_flow must be positive (got {} and {})",
+                self.flow_rate, self.first_layer_flow
+            )));
+        }
+
+        if self.filament_density <= 0.0 || self.filament_cost_per_kg.is_some_and(|cost| cost < 0.0) {
+            return Err(SlicerError::ConfigError(
+                "filament_density must be positive and filament_cost_per_kg can't be negative".to_string()
+            ));
+        }
+
+        if let Some([width, height]) = self.thumbnail_sizes.iter().find(|size| !size.iter().all(|side| (1..=MAX_THUMBNAIL_SIZE).contains(side))) {
+            return Err(SlicerError::ConfigError(format!(
+                "thumbnail sizes must be between 1 and {} pixels a side (got {}x{})",
+                MAX_THUMBNAIL_SIZE, width, height
+            )));
+        }
+
+        if self.prime_line_length <= 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("prime_line_length must be positive (got {})", self.prime_line_length)
+            ));
+        }
+        if self.prime_line && 2.0 * PRIME_LINE_INSET + self.prime_line_length > self.build_volume[0] {
+            return Err(SlicerError::ConfigError(format!(
+                "prime_line_length of {} mm doesn't fit along the {} mm bed with {} mm to spare at each end",
+                self.prime_line_length, self.build_volume[0], PRIME_LINE_INSET
+            )));
+        }
+
+        if !(0.0..=100.0).contains(&self.fan_speed) {
+            return Err(SlicerError::ConfigError(
+                format!("fan_speed must be between 0 and 100 percent (got {})", self.fan_speed)
+            ));
+        }
+
+        if self.min_layer_time < 0.0 || self.min_print_speed <= 0.0 {
+            return Err(SlicerError::ConfigError(format!(
+                "min_layer_time must not be negative and min_print_speed must be positive (got {} and {})",
+                self.min_layer_time, self.min_print_speed
+            )));
+        }
+
+        let limits = [
+            self.acceleration,
+            self.perimeter_acceleration,
+            self.infill_acceleration,
+            self.travel_acceleration,
+            self.first_layer_acceleration,
+            self.max_acceleration,
+            self.jerk,
+            self.max_jerk,
+            self.max_feedrate,
+            self.max_feedrate_z,
+            self.max_feedrate_e,
+        ];
+        if limits.iter().flatten().any(|&limit| limit <= 0.0) {
+            return Err(SlicerError::ConfigError(
+                "accelerations, jerk and max feedrates must be positive".to_string()
+            ));
+        }
+
+        if let Some(max) = self.max_nozzle_temperature {
+            let extruders = self.extruders.iter().flat_map(|extruder| [extruder.temperature, extruder.standby_temperature]);
+            let overrides = self.overrides.iter().map(|range| range.temperature);
+            let nozzle = [Some(self.nozzle_temperature), self.first_layer_nozzle_temperature].into_iter().chain(extruders).chain(overrides);
+            if let Some(temperature) = nozzle.flatten().find(|&temperature| temperature > max) {
+                return Err(SlicerError::ConfigError(format!(
+                    "nozzle temperature of {}°C is above the max_nozzle_temperature of {}°C", temperature, max
+                )));
+            }
+        }
+        if let Some(max) = self.max_bed_temperature {
+            if let Some(temperature) = [self.bed_temperature, self.first_layer_bed_temperature()].into_iter().find(|&temperature| temperature > max) {
+                return Err(SlicerError::ConfigError(format!(
+                    "bed temperature of {}°C is above the max_bed_temperature of {}°C", temperature, max
+                )));
+            }
+        }
+
+        let advances = [self.pressure_advance, self.linear_advance_k, self.infill_pressure_advance];
+        if advances.iter().flatten().any(|&advance| advance < 0.0) {
+            return Err(SlicerError::ConfigError(
+                "pressure_advance, linear_advance_k and infill_pressure_advance must not be negative".to_string()
+            ));
+        }
+        // Walls would keep the infill's advance, having none to go back to
+        if self.infill_pressure_advance.is_some() && self.pressure_advance().is_none() {
+            return Err(SlicerError::ConfigError(
+                "infill_pressure_advance needs pressure_advance (or linear_advance_k on marlin) for the other extrusions".to_string()
+            ));
+        }
+
+        if self.build_volume.iter().any(|&v| v <= 0.0) {
+            return Err(SlicerError::ConfigError(
+                format!("build_volume must be positive on every axis (got {:?})", self.build_volume)
+            ));
+        }
+
+        let speeds = [
+            self.perimeter_speed,
+            self.external_perimeter_speed,
+            self.gap_fill_speed,
+            self.small_perimeter_speed,
+            self.first_layer_speed,
+            self.infill_speed,
+            self.solid_infill_speed,
+            self.bridge_speed,
+            self.support_speed,
+            self.travel_speed_z,
+        ];
+        if speeds.iter().flatten().any(|&speed| speed <= 0.0) {
+            return Err(SlicerError::ConfigError(
+                "perimeter_speed, external_perimeter_speed, gap_fill_speed, small_perimeter_speed, first_layer_speed, infill_speed, solid_infill_speed, bridge_speed, support_speed and travel_speed_z must be positive".to_string()
+            ));
+        }
+
+        if [self.small_perimeter_length, self.min_loop_length].iter().flatten().any(|&length| length < 0.0) {
+            return Err(SlicerError::ConfigError(
+                "small_perimeter_length and min_loop_length must not be negative".to_string()
+            ));
+        }
+
+        if self.infill_percentage > 100 {
+            return Err(SlicerError::ConfigError(
+                format!("infill_percentage must be at most 100 (got {})", self.infill_percentage)
+            ));
+        }
+
+        if self.xyz_decimals > 9 || self.e_decimals > 9 {
+            return Err(SlicerError::ConfigError(
+                format!("xyz_decimals and e_decimals must be at most 9 (got {} and {})", self.xyz_decimals, self.e_decimals)
+            ));
+        }
+
+        if self.first_line_number == 0 {
+            return Err(SlicerError::ConfigError(
+                "first_line_number must be at least 1, as M110 sets the line before it".to_string()
+            ));
+        }
+
+        if self.post_process.iter().any(|command| command.trim().is_empty()) {
+            return Err(SlicerError::ConfigError(
+                "post_process commands must not be empty".to_string()
+            ));
+        }
+
+        if self.infill_combine_every == 0 {
+            return Err(SlicerError::ConfigError(
+                "infill_combine_every must be at least 1".to_string()
+            ));
+        }
+
+        if self.brim_width < 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("brim_width must not be negative (got {})", self.brim_width)
+            ));
+        }
+
+        if self.spiral_vase && (self.support_material || self.draft_shield || self.wipe_tower) {
+            return Err(SlicerError::ConfigError(
+                "spiral_vase can't be combined with support_material, draft_shield or wipe_tower, as the nozzle never leaves the wall".to_string()
+            ));
+        }
+
+        if self.draft_shield_distance < 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("draft_shield_distance must not be negative (got {})", self.draft_shield_distance)
+            ));
+        }
+
+        if let DraftShieldHeight::Height(height) = self.draft_shield_height {
+            if height <= 0.0 {
+                return Err(SlicerError::ConfigError(
+                    format!("draft_shield_height must be positive (got {})", height)
+                ));
+            }
+        }
+
+        // Room for the tower's wall on either side and a purge line between
+        if self.wipe_tower_width < 4.0 * self.nozzle_diameter {
+            return Err(SlicerError::ConfigError(format!(
+                "wipe_tower_width must be at least 4 nozzle diameters, {} mm (got {})",
+                4.0 * self.nozzle_diameter, self.wipe_tower_width
+            )));
+        }
+
+        if self.wipe_tower_purge_volume <= 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("wipe_tower_purge_volume must be positive (got {})", self.wipe_tower_purge_volume)
+            ));
+        }
+
+        if let Some([x, y]) = self.wipe_tower_position {
+            if x < 0.0 || x + self.wipe_tower_width > self.build_volume[0] || !(0.0..self.build_volume[1]).contains(&y) {
+                return Err(SlicerError::ConfigError(format!(
+                    "wipe_tower_position [{}, {}] must leave the {} mm wide tower on the {}x{} mm bed",
+                    x, y, self.wipe_tower_width, self.build_volume[0], self.build_volume[1]
+                )));
+            }
+        }
+
+        if !(self.overhang_angle >= 0.0 && self.overhang_angle < 90.0) {
+            return Err(SlicerError::ConfigError(
+                format!("overhang_angle must be at least 0 and below 90 degrees (got {})", self.overhang_angle)
+            ));
+        }
+
+        if self.support_density > 100 || self.support_interface_density > 100 {
+            return Err(SlicerError::ConfigError(format!(
+                "support_density and support_interface_density must be at most 100 (got {} and {})",
+                self.support_density, self.support_interface_density
+            )));
+        }
+
+        if self.retraction_minimum_travel < 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("retraction_minimum_travel must not be negative (got {})", self.retraction_minimum_travel)
+            ));
+        }
+
+        if self.retraction_z_lift < 0.0 || self.retraction_extra_restart < 0.0 || self.coast_distance < 0.0 || self.wipe_distance < 0.0 {
+            return Err(SlicerError::ConfigError(
+                "retraction_z_lift, retraction_extra_restart, coast_distance and wipe_distance must not be negative".to_string()
+            ));
+        }
+
+        if self.pause_park_lift < 0.0 || self.pause_prime_length < 0.0 {
+            return Err(SlicerError::ConfigError(
+                "pause_park_lift and pause_prime_length must not be negative".to_string()
+            ));
+        }
+
+        let [park_x, park_y] = self.pause_park_position;
+        if !(0.0..=self.build_volume[0]).contains(&park_x) || !(0.0..=self.build_volume[1]).contains(&park_y) {
+            return Err(SlicerError::ConfigError(format!(
+                "pause_park_position X{} Y{} is off the bed", park_x, park_y
+            )));
+        }
+
+        if self.comb_max_distance.is_some_and(|distance| distance <= 0.0) {
+            return Err(SlicerError::ConfigError(
+                "comb_max_distance must be positive".to_string()
+            ));
+        }
+
+        if self.support_xy_gap < 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("support_xy_gap must not be negative (got {})", self.support_xy_gap)
+            ));
+        }
+
+        let boxes = self.support_blockers.iter().chain(&self.support_enforcers);
+        for support_box in boxes {
+            let inside = (0..3).all(|axis| {
+                0.0 <= support_box.min[axis]
+                    && support_box.min[axis] < support_box.max[axis]
+                    && support_box.max[axis] <= self.build_volume[axis]
+            });
+            if !inside {
+                return Err(SlicerError::ConfigError(format!(
+                    "support blocker or enforcer from {:?} to {:?} must be a non-empty box inside the build volume {:?}",
+                    support_box.min, support_box.max, self.build_volume
+                )));
+            }
+        }
+
+        for modifier in &self.modifiers {
+            let inside = (0..3).all(|axis| {
+                0.0 <= modifier.min[axis] && modifier.min[axis] < modifier.max[axis] && modifier.max[axis] <= self.build_volume[axis]
+            });
+            if !inside {
+                return Err(SlicerError::ConfigError(format!(
+                    "modifier from {:?} to {:?} must be a non-empty box inside the build volume {:?}",
+                    modifier.min, modifier.max, self.build_volume
+                )));
+            }
+            if let Some(density) = modifier.infill_density.filter(|&density| density > 100) {
+                return Err(SlicerError::ConfigError(
+                    format!("modifier infill_density must be at most 100 (got {})", density)
+                ));
+            }
+        }
+
+        let extruders = self.extruders.len().max(1);
+        let mapped = [
+            ("perimeter_extruder", self.perimeter_extruder),
+            ("infill_extruder", self.infill_extruder),
+            ("support_extruder", self.support_extruder),
+        ];
+        if let Some((name, index)) = mapped.iter().find(|(_, index)| *index >= extruders) {
+            return Err(SlicerError::ConfigError(
+                format!("{} must be below the number of extruders, {} (got {})", name, extruders, index)
+            ));
+        }
+        for extruder in &self.extruders {
+            if extruder.nozzle_diameter.into_iter().chain(extruder.filament_diameter).any(|d| d <= 0.0) {
+                return Err(SlicerError::ConfigError(
+                    "extruder nozzle_diameter and filament_diameter must be positive".to_string()
+                ));
+            }
+        }
+
+        let mut overrides: Vec<_> = self.overrides.iter().collect();
+        overrides.sort_by(|a, b| a.z_min.total_cmp(&b.z_min));
+        for range in &overrides {
+            if !(0.0 <= range.z_min && range.z_min < range.z_max) {
+                return Err(SlicerError::ConfigError(format!(
+                    "override from {} to {} mm must be a non-empty range above the bed", range.z_min, range.z_max
+                )));
+            }
+            if let Some(fan) = range.fan_speed.filter(|fan| !(0.0..=100.0).contains(fan)) {
+                return Err(SlicerError::ConfigError(
+                    format!("override fan_speed must be between 0 and 100 percent (got {})", fan)
+                ));
+            }
+        }
+        if let Some(pair) = overrides.windows(2).find(|pair| pair[0].z_max > pair[1].z_min) {
+            return Err(SlicerError::ConfigError(format!(
+                "overrides from {} to {} mm and from {} to {} mm overlap",
+                pair[0].z_min, pair[0].z_max, pair[1].z_min, pair[1].z_max
+            )));
+        }
+
+        if !(0.0..=100.0).contains(&self.infill_overlap_percent) {
+            return Err(SlicerError::ConfigError(
+                format!("infill_overlap_percent must be between 0 and 100 (got {})", self.infill_overlap_percent)
+            ));
+        }
+
+        if self.resolution < 0.0 || self.snap_grid < 0.0 || self.plane_epsilon < 0.0 {
+            return Err(SlicerError::ConfigError(
+                "resolution, snap_grid and plane_epsilon must not be negative".to_string()
+            ));
+        }
+
+        if self.fuzzy_skin_thickness < 0.0 || self.fuzzy_skin_point_distance <= 0.0 {
+            return Err(SlicerError::ConfigError(
+                "fuzzy_skin_thickness must not be negative and fuzzy_skin_point_distance must be positive".to_string()
+            ));
+        }
+
+        if self.elephant_foot_compensation < 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("elephant_foot_compensation must not be negative (got {})", self.elephant_foot_compensation)
+            ));
+        }
+
+        if self.min_feature_size.is_some_and(|size| size < 0.0) {
+            return Err(SlicerError::ConfigError(
+                "min_feature_size must not be negative".to_string()
+            ));
+        }
+
+        if self.close_gaps.is_some_and(|gap| gap < 0.0) {
+            return Err(SlicerError::ConfigError(
+                "close_gaps must not be negative".to_string()
+            ));
+        }
+
+        if self.contour_merge_tolerance.is_some_and(|t| t <= 0.0) {
+            return Err(SlicerError::ConfigError(
+                "contour_merge_tolerance must be positive".to_string()
+            ));
+        }
+
+        for (i, object) in self.objects.iter().enumerate() {
+            if object.scale <= 0.0 || object.copies == 0 {
+                return Err(SlicerError::ConfigError(format!(
+                    "Object {} ({}) needs a positive scale and at least one copy",
+                    i + 1, object.path
+                )));
+            }
+        }
+
+        if self.z_offset > 0.0 {
+            return Err(SlicerError::ConfigError(
+                format!("z_offset must not lift the model off the bed (got {} mm)", self.z_offset)
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn contour_merge_tolerance(&self) -> f64 {
+        self.contour_merge_tolerance.unwrap_or(self.nozzle_diameter / 1000.0)
+    }
+
+    pub fn max_gap_close(&self) -> f64 {
+        self.max_gap_close.unwrap_or(self.nozzle_diameter / 2.0)
+    }
+
+    /// Distances in mm that outlines and holes move outward by on a layer,
+    /// with all size compensation applied; a hole moving outward grows
+    pub fn size_compensation(&self, layer_index: usize) -> (f64, f64) {
+        let elephant_foot = if layer_index == 0 { self.elephant_foot_compensation } else { 0.0 };
+        (self.xy_compensation - elephant_foot, self.hole_compensation - self.xy_compensation + elephant_foot)
+    }
+
+    /// Area in mm² below which islands and holes are dropped
+    pub fn min_feature_area(&self) -> f64 {
+        self.min_feature_size.unwrap_or(self.nozzle_diameter).powi(2)
+    }
+
+    /// Infill direction on a layer in degrees, in [0, 180)
+    pub fn layer_infill_angle(&self, layer_index: usize) -> f64 {
+        (self.infill_angle + layer_index as f64 * self.infill_angle_increment).rem_euclid(180.0)
+    }
+
+    /// Layers combined into each sparse infill extrusion: at most
+    /// `infill_combine_every`, and few enough that the extrusion stays
+    /// under `MAX_COMBINED_INFILL` of the nozzle diameter thick
+    pub fn infill_combine_layers(&self) -> usize {
+        let fit = (MAX_COMBINED_INFILL * self.nozzle_diameter / self.layer_height + 1e-9).floor() as usize;
+        self.infill_combine_every.min(fit).max(1)
+    }
+
+    /// How far infill reaches into the innermost wall, in mm
+    pub fn infill_overlap_mm(&self) -> f64 {
+        self.nozzle_diameter * self.infill_overlap_percent / 100.0
+    }
+
+    pub fn first_layer_nozzle_temperature(&self) -> u16 {
+        self.first_layer_nozzle_temperature.unwrap_or(self.nozzle_temperature)
+    }
+
+    pub fn first_layer_bed_temperature(&self) -> u16 {
+        self.first_layer_bed_temperature.unwrap_or(self.bed_temperature)
+    }
+
+    /// Fan speed in percent for a layer: off for the first
+    /// `disable_fan_first_layers`, then rising in even steps to `fan_speed`
+    /// over `fan_ramp_layers`
+    pub fn layer_fan_speed(&self, layer_index: usize) -> f64 {
+        if layer_index < self.disable_fan_first_layers {
+            return 0.0;
+        }
+        let ramped = (layer_index - self.disable_fan_first_layers + 1) as f64;
+        self.fan_speed * (ramped / self.fan_ramp_layers.max(1) as f64).min(1.0)
+    }
+
+    /// Override for a layer whose top is at `z`, if one spans it
+    pub fn height_override(&self, z: f64) -> Option<&HeightOverride> {
+        self.overrides.iter().find(|range| range.spans(z))
+    }
+
+    /// Nozzle temperature for layer `layer_index` with its top at `z`: an
+    /// override's, or the first layer's or regular temperature
+    pub fn layer_nozzle_temperature(&self, layer_index: usize, z: f64) -> u16 {
+        self.height_override(z).and_then(|range| range.temperature).unwrap_or(if layer_index == 0 {
+            self.first_layer_nozzle_temperature()
+        } else {
+            self.nozzle_temperature
+        })
+    }
+
+    /// Fan speed in percent for layer `layer_index` with its top at `z`:
+    /// an override's, or the [`SlicerConfig::layer_fan_speed`]
+    pub fn layer_fan_speed_at(&self, layer_index: usize, z: f64) -> f64 {
+        self.height_override(z).and_then(|range| range.fan_speed).unwrap_or_else(|| self.layer_fan_speed(layer_index))
+    }
+
+    /// Nozzle diameter in mm of extruder `index`
+    pub fn extruder_nozzle_diameter(&self, index: usize) -> f64 {
+        self.extruders.get(index).and_then(|extruder| extruder.nozzle_diameter).unwrap_or(self.nozzle_diameter)
+    }
+
+    /// Filament diameter in mm of extruder `index`
+    pub fn extruder_filament_diameter(&self, index: usize) -> f64 {
+        self.extruders.get(index).and_then(|extruder| extruder.filament_diameter).unwrap_or(self.filament_diameter)
+    }
+
+    /// Nozzle temperature of extruder `index` for layer `layer_index` with
+    /// its top at `z`: the extruder's own, or the
+    /// [`SlicerConfig::layer_nozzle_temperature`]
+    pub fn extruder_temperature(&self, index: usize, layer_index: usize, z: f64) -> u16 {
+        self.extruders
+            .get(index)
+            .and_then(|extruder| extruder.temperature)
+            .unwrap_or_else(|| self.layer_nozzle_temperature(layer_index, z))
+    }
+
+    /// Settings beyond what the machine takes, which are clamped to its
+    /// maxima or left out of the G-code
+    pub fn limit_warnings(&self) -> Vec<String> {
+        let accelerations = [
+            ("acceleration", self.acceleration),
+            ("perimeter_acceleration", self.perimeter_acceleration),
+            ("infill_acceleration", self.infill_acceleration),
+            ("travel_acceleration", self.travel_acceleration),
+            ("first_layer_acceleration", self.first_layer_acceleration),
+        ];
+        let mut warnings = Vec::new();
+        if let Some(max) = self.max_acceleration {
+            for (name, value) in accelerations {
+                if let Some(value) = value.filter(|&value| value > max) {
+                    warnings.push(format!("{} of {} mm/s² is clamped to the max_acceleration of {} mm/s²", name, value, max));
+                }
+            }
+        }
+        if let (Some(jerk), Some(max)) = (self.jerk, self.max_jerk) {
+            if jerk > max {
+                warnings.push(format!("jerk of {} mm/s is clamped to the max_jerk of {} mm/s", jerk, max));
+            }
+        }
+        let speeds = [
+            ("print_speed", Some(self.print_speed)),
+            ("travel_speed", Some(self.travel_speed)),
+            ("perimeter_speed", self.perimeter_speed),
+            ("external_perimeter_speed", self.external_perimeter_speed),
+            ("gap_fill_speed", self.gap_fill_speed),
+            ("small_perimeter_speed", self.small_perimeter_speed),
+            ("first_layer_speed", self.first_layer_speed),
+            ("infill_speed", self.infill_speed),
+            ("solid_infill_speed", self.solid_infill_speed),
+            ("bridge_speed", self.bridge_speed),
+            ("support_speed", self.support_speed),
+        ];
+        if let Some(max) = self.max_feedrate {
+            for (name, speed) in speeds {
+                if let Some(speed) = speed.filter(|&speed| speed > max) {
+                    warnings.push(format!("{} of {} mm/s is clamped to the max_feedrate of {} mm/s", name, speed, max));
+                }
+            }
+        }
+        if let Some(max) = self.max_feedrate_e.filter(|&max| self.retraction_speed > max) {
+            warnings.push(format!("retraction_speed of {} mm/s is clamped to the max_feedrate_e of {} mm/s", self.retraction_speed, max));
+        }
+        if self.exclude_objects && self.gcode_flavor != FirmwareFlavor::Klipper {
+            warnings.push("exclude_objects is left out: it only applies to the klipper flavor".to_string());
+        }
+        if self.wipe_tower && self.extruders.len() < 2 && self.color_change_layers.is_empty() {
+            warnings.push("wipe_tower is left out: there are no tool changes or filament changes to purge for".to_string());
+        }
+        if self.linear_advance_k.is_some() && self.gcode_flavor != FirmwareFlavor::Marlin {
+            warnings.push("linear_advance_k is left out: it only applies to the marlin flavor".to_string());
+        }
+        warnings
+    }
+
+    /// `acceleration` or, if it is higher, the machine's maximum
+    fn clamp_acceleration(&self, acceleration: Option<f64>) -> Option<f64> {
+        acceleration.map(|acceleration| self.max_acceleration.map_or(acceleration, |max| acceleration.min(max)))
+    }
+
+    /// Pressure advance set at the start: Marlin's linear advance K where
+    /// it applies, or the pressure advance
+    pub fn pressure_advance(&self) -> Option<f64> {
+        match self.gcode_flavor {
+            FirmwareFlavor::Marlin => self.linear_advance_k.or(self.pressure_advance),
+            _ => self.pressure_advance,
+        }
+    }
+
+    pub fn infill_pressure_advance(&self) -> Option<f64> {
+        self.infill_pressure_advance.or_else(|| self.pressure_advance())
+    }
+
+    pub fn default_acceleration(&self) -> Option<f64> {
+        self.clamp_acceleration(self.acceleration)
+    }
+
+    pub fn perimeter_acceleration(&self) -> Option<f64> {
+        self.clamp_acceleration(self.perimeter_acceleration.or(self.acceleration))
+    }
+
+    pub fn infill_acceleration(&self) -> Option<f64> {
+        self.clamp_acceleration(self.infill_acceleration.or(self.acceleration))
+    }
+
+    pub fn travel_acceleration(&self) -> Option<f64> {
+        self.clamp_acceleration(self.travel_acceleration.or(self.acceleration))
+    }
+
+    /// The first layer's acceleration, if it has its own
+    pub fn first_layer_acceleration(&self) -> Option<f64> {
+        self.clamp_acceleration(self.first_layer_acceleration)
+    }
+
+    pub fn jerk(&self) -> Option<f64> {
+        self.jerk.map(|jerk| self.max_jerk.map_or(jerk, |max| jerk.min(max)))
+    }
+
+    pub fn support_speed(&self) -> f64 {
+        self.support_speed.unwrap_or(self.print_speed)
+    }
+
+    pub fn infill_speed(&self) -> f64 {
+        self.infill_speed.unwrap_or(self.print_speed)
+    }
+
+    pub fn solid_infill_speed(&self) -> f64 {
+        self.solid_infill_speed.unwrap_or_else(|| self.infill_speed())
+    }
+
+    pub fn perimeter_speed(&self) -> f64 {
+        self.perimeter_speed.unwrap_or(self.print_speed)
+    }
+
+    pub fn external_perimeter_speed(&self) -> f64 {
+        self.external_perimeter_speed.unwrap_or_else(|| self.perimeter_speed())
+    }
+
+    pub fn gap_fill_speed(&self) -> f64 {
+        self.gap_fill_speed.unwrap_or_else(|| self.perimeter_speed() / 2.0)
+    }
+
+    pub fn small_perimeter_speed(&self) -> f64 {
+        self.small_perimeter_speed.unwrap_or_else(|| self.external_perimeter_speed() / 2.0)
+    }
+
+    pub fn small_perimeter_length(&self) -> f64 {
+        self.small_perimeter_length.unwrap_or(self.nozzle_diameter * 30.0)
+    }
+
+    /// Length in mm below which a wall loop too small in area is dropped
+    pub fn min_loop_length(&self) -> f64 {
+        self.min_loop_length.unwrap_or(self.nozzle_diameter * 4.0)
+    }
+
+    pub fn travel_speed_z(&self) -> f64 {
+        self.travel_speed_z.or(self.max_feedrate_z).unwrap_or(self.travel_speed)
+    }
+
+    pub fn bridge_speed(&self) -> f64 {
+        self.bridge_speed.unwrap_or_else(|| self.infill_speed() / 2.0)
+    }
+
+    /// Number of walls printed around each island
+    pub fn perimeters(&self) -> usize {
+        self.perimeters
+            .unwrap_or_else(|| ((self.wall_thickness / self.nozzle_diameter).round() as usize).max(1))
+    }
+
+    pub fn top_solid_layers(&self) -> usize {
+        self.top_solid_layers.unwrap_or_else(|| self.solid_layers())
+    }
+
+    pub fn bottom_solid_layers(&self) -> usize {
+        self.bottom_solid_layers.unwrap_or_else(|| self.solid_layers())
+    }
+
+    /// Whether layer `layer_index` is part of the spiral, above the bottom
+    /// solid layers (and never the first layer) when spiral_vase is on
+    pub fn spiral_layer(&self, layer_index: usize) -> bool {
+        self.spiral_vase && layer_index >= self.bottom_solid_layers().max(1)
+    }
+
+    fn solid_layers(&self) -> usize {
+        (self.top_bottom_thickness / self.layer_height).round() as usize
+    }
+
+    /// Widest gap in mm closed between islands
+    pub fn close_gaps(&self) -> f64 {
+        self.close_gaps.unwrap_or(0.0)
+    }
+
     pub fn merge_with_cli(&mut self, layer_height: f64, infill: u8, speed: f64, nozzle_temp: u16, bed_temp: u16) {
         self.layer_height = layer_height;
         self.infill_percentage = infill;
@@ -99,3 +1900,38 @@ impl SlicerConfig {
         self.bed_temperature = bed_temp;
     }
 }
+
+impl ObjectSettings {
+    /// Load the object's mesh and apply its transform, returning one mesh per copy
+    pub fn load(&self, gap: f64) -> Result<Vec<Mesh>> {
+        let mut mesh = Mesh::from_stl_file(&self.path)?;
+
+        if self.scale != 1.0 {
+            mesh.scale(self.scale);
+        }
+
+        if self.rotate != [0.0; 3] {
+            let center = mesh.bounds.center().coords;
+            let [rx, ry, rz] = self.rotate.map(f64::to_radians);
+            let rotation = Rotation3::from_euler_angles(rx, ry, rz);
+            let m = Matrix4::new_translation(&center)
+                * rotation.to_homogeneous()
+                * Matrix4::new_translation(&-center);
+            mesh.apply_matrix(&m);
+        }
+
+        mesh.drop_to_bed();
+        if let Some([x, y, z]) = self.translate {
+            mesh.translate(Vector3::new(x, y, z));
+        }
+
+        let step = mesh.bounds.dimensions().x + gap;
+        Ok((0..self.copies)
+            .map(|i| {
+                let mut copy = mesh.clone();
+                copy.translate(Vector3::new(i as f64 * step, 0.0, 0.0));
+                copy
+            })
+            .collect())
+    }
+}
@@ -5,6 +5,9 @@ pub enum SlicerError {
     #[error("Failed to read STL file: {0}")]
     StlReadError(String),
 
+    #[error("Failed to write STL file: {0}")]
+    StlWriteError(String),
+
     #[error("Invalid STL geometry: {0}")]
     InvalidGeometry(String),
 
@@ -22,6 +25,9 @@ pub enum SlicerError {
 
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
 }
 
 pub type Result<T> = std::result::Result<T, SlicerError>;
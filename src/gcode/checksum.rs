@@ -0,0 +1,119 @@
+use crate::error::{Result, SlicerError};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Number the lines of the G-code file at `path` and end them with
+/// checksums, from `first` on, as `line_numbers_and_checksums` has the
+/// generator write them. This is for a file changed after it was written,
+/// such as by `post_process`, so the checksums cover the lines as they end
+/// up. The framed copy is written beside it and then takes its place.
+/// `first` is at least 1, as the M110 before it sets the line before.
+pub fn number_lines(path: impl AsRef<Path>, first: u64) -> Result<()> {
+    let path = path.as_ref();
+    if first == 0 {
+        return Err(SlicerError::GCodeError("Lines are numbered from 1 or higher, as M110 sets the line before the first".to_string()));
+    }
+    let failed = |e: io::Error| SlicerError::GCodeError(format!("Failed to number the lines of {}: {}", path.display(), e));
+    let mut name = path.file_name().map_or_else(OsString::new, OsString::from);
+    name.push(".numbering");
+    let framed = path.with_file_name(name);
+    let result = frame_file(path, &framed, first);
+    if result.is_err() {
+        let _ = fs::remove_file(&framed);
+    }
+    result.and_then(|()| fs::rename(&framed, path)).map_err(failed)
+}
+
+fn frame_file(path: &Path, framed: &Path, first: u64) -> io::Result<()> {
+    let mut writer = LineNumberWriter::new(BufWriter::new(File::create(framed)?), first);
+    for line in BufReader::new(File::open(path)?).lines() {
+        writeln!(writer, "{}", line?)?;
+    }
+    writer.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+/// Frames each line of G-code passed through to `inner` as the RepRap
+/// serial protocol has it, `N<number> <command>*<checksum>`, so firmware
+/// reading it off a serial link can tell a garbled line and ask for it
+/// again. The count is reset with an M110 before the first numbered line.
+/// A comment after a command is dropped, as the checksum has to end the
+/// line; a line with only a comment goes through as it is, unnumbered.
+pub(super) struct LineNumberWriter<W: Write> {
+    inner: W,
+    /// Bytes of the line being written so far
+    line: Vec<u8>,
+    /// Number for the next line, or None before the M110
+    next: Option<u64>,
+    first: u64,
+}
+
+impl<W: Write> LineNumberWriter<W> {
+    /// A writer into `inner` numbering lines from `first`, which is at
+    /// least 1 as the M110 before it takes the number before
+    pub fn new(inner: W, first: u64) -> Self {
+        LineNumberWriter { inner, line: Vec::new(), next: None, first }
+    }
+
+    /// `inner`, with any last line left without a newline framed and
+    /// written
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.write_line(&String::from_utf8_lossy(&line))?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let code = line.split_once(';').map_or(line, |(code, _)| code).trim();
+        if code.is_empty() {
+            return writeln!(self.inner, "{}", line);
+        }
+        let number = match self.next {
+            Some(number) => number,
+            None => {
+                let reset = self.first - 1;
+                self.write_numbered(reset, &format!("M110 N{}", reset))?;
+                self.first
+            }
+        };
+        self.write_numbered(number, code)?;
+        self.next = Some(number + 1);
+        Ok(())
+    }
+
+    fn write_numbered(&mut self, number: u64, code: &str) -> io::Result<()> {
+        let framed = format!("N{} {}", number, code);
+        writeln!(self.inner, "{}*{}", framed, checksum(&framed))
+    }
+}
+
+impl<W: Write> Write for LineNumberWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            if byte == b'\n' {
+                // Taken out to write it and put back for the next line
+                let mut line = std::mem::take(&mut self.line);
+                self.write_line(&String::from_utf8_lossy(&line))?;
+                line.clear();
+                self.line = line;
+            } else {
+                self.line.push(byte);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// XOR of every byte of `line`, the checksum the RepRap protocol puts
+/// after the `*`
+fn checksum(line: &str) -> u8 {
+    line.bytes().fold(0, |sum, byte| sum ^ byte)
+}
@@ -0,0 +1,70 @@
+use nalgebra::Point2;
+use crate::geometry::{Island, LineSegment2D, Polygon};
+
+/// How far outside a region a travel endpoint may be and still count as in
+/// it, in mm
+const TOLERANCE: f64 = 1e-6;
+
+/// Travel from `from` to `to` that stays inside one of `regions`, as a
+/// polyline from `from` to `to`, or None when no region holds both ends.
+///
+/// Where the straight line leaves the region, the path follows the
+/// region's edge from where the line first crosses it to where the line
+/// last crosses it, going round whichever way is shorter.
+pub fn comb_path(from: Point2<f64>, to: Point2<f64>, regions: &[Island]) -> Option<Vec<Point2<f64>>> {
+    let inside = |region: &Island, p: &Point2<f64>| region.signed_distance(p) <= TOLERANCE;
+    let region = regions.iter().find(|region| inside(region, &from) && inside(region, &to))?;
+
+    let travel = LineSegment2D::new(from, to);
+    let polygons: Vec<&Polygon> = std::iter::once(&region.outline).chain(&region.holes).collect();
+
+    // Where the line meets each edge: fraction along the line, polygon,
+    // edge and point
+    let mut crossings = Vec::new();
+    for (k, polygon) in polygons.iter().enumerate() {
+        let n = polygon.points.len();
+        for i in 0..n {
+            let edge = LineSegment2D::new(polygon.points[i], polygon.points[(i + 1) % n]);
+            if let Some(point) = travel.intersect(&edge) {
+                let t = (point - from).norm() / travel.length().max(f64::EPSILON);
+                crossings.push((t, k, i, point));
+            }
+        }
+    }
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut path = vec![from];
+    let mut next = 0;
+    while next < crossings.len() {
+        let (_, k, first_edge, entry) = crossings[next];
+        let last = crossings.iter().rposition(|crossing| crossing.1 == k).unwrap();
+        let (_, _, last_edge, exit) = crossings[last];
+        path.push(entry);
+        path.extend(walk(polygons[k], first_edge, last_edge, entry, exit));
+        path.push(exit);
+        next = last + 1;
+    }
+    path.push(to);
+    path.dedup_by(|a, b| (*a - *b).norm() < TOLERANCE);
+    Some(path)
+}
+
+/// Corners passed going round `polygon` from `entry` on edge `first` to
+/// `exit` on edge `last`, whichever way is shorter
+fn walk(polygon: &Polygon, first: usize, last: usize, entry: Point2<f64>, exit: Point2<f64>) -> Vec<Point2<f64>> {
+    let points = &polygon.points;
+    let n = points.len();
+    if first == last {
+        return Vec::new();
+    }
+
+    // Forwards the corners after `first` up to and including `last`'s start,
+    // backwards from `first`'s start down to just after `last`
+    let forward: Vec<Point2<f64>> = (1..=(last + n - first) % n).map(|k| points[(first + k) % n]).collect();
+    let backward: Vec<Point2<f64>> = (0..(first + n - last) % n).map(|k| points[(first + n - k) % n]).collect();
+    let length = |corners: &[Point2<f64>]| {
+        let stops: Vec<Point2<f64>> = std::iter::once(entry).chain(corners.iter().copied()).chain(std::iter::once(exit)).collect();
+        stops.windows(2).map(|pair| (pair[1] - pair[0]).norm()).sum::<f64>()
+    };
+    if length(&forward) <= length(&backward) { forward } else { backward }
+}
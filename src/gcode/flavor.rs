@@ -0,0 +1,267 @@
+use crate::config::FirmwareFlavor;
+
+/// A heater the G-code sets the temperature of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heater {
+    /// The nozzle of the extruder printing
+    Nozzle,
+    /// The nozzle of an extruder by its tool number, printing or not
+    Tool(usize),
+    Bed,
+}
+
+/// The commands that differ between printer firmwares. Everything else is
+/// written the same for all of them.
+pub trait GCodeFlavor {
+    /// Lines written after the units and positioning are set up, given
+    /// the number of layers printed
+    fn preamble(&self, _layer_count: usize) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Command starting to heat `heater` to `temperature` without waiting
+    fn set_temperature(&self, heater: Heater, temperature: u16) -> String {
+        match heater {
+            Heater::Nozzle => format!("M104 S{}", temperature),
+            Heater::Tool(tool) => format!("M104 T{} S{}", tool, temperature),
+            Heater::Bed => format!("M140 S{}", temperature),
+        }
+    }
+
+    /// Command waiting for `heater` to reach `temperature`
+    fn wait_for_temperature(&self, heater: Heater, temperature: u16) -> String {
+        match heater {
+            Heater::Nozzle => format!("M109 S{}", temperature),
+            Heater::Tool(tool) => format!("M109 T{} S{}", tool, temperature),
+            Heater::Bed => format!("M190 S{}", temperature),
+        }
+    }
+
+    /// Command setting the cooling fan to a PWM value from 0 to 255
+    fn fan(&self, pwm: u8) -> String {
+        if pwm == 0 {
+            "M107".to_string()
+        } else {
+            format!("M106 S{}", pwm)
+        }
+    }
+
+    /// Command limiting acceleration to `acceleration` mm/s²
+    fn acceleration(&self, acceleration: f64) -> String {
+        format!("M204 S{}", acceleration)
+    }
+
+    /// Command setting the X and Y jerk to `jerk` mm/s
+    fn jerk(&self, jerk: f64) -> String {
+        format!("M205 X{0} Y{0}", jerk)
+    }
+
+    /// Command setting pressure advance to `advance` for extruder `tool`,
+    /// if the firmware has one
+    fn pressure_advance(&self, _tool: usize, _advance: f64) -> Option<String> {
+        None
+    }
+
+    /// Whether the firmware takes G2/G3 arc moves out of the box
+    fn supports_arcs(&self) -> bool {
+        false
+    }
+
+    /// Whether the firmware takes EXCLUDE_OBJECT markers, to cancel one
+    /// object and print on with the rest
+    fn supports_object_exclusion(&self) -> bool {
+        false
+    }
+
+    /// Whether retraction is left to the firmware (G10/G11) unless the
+    /// config says otherwise
+    fn firmware_retraction(&self) -> bool {
+        false
+    }
+
+    /// Line telling the firmware how far the print is, in percent and
+    /// minutes left
+    fn progress(&self, percent: u8, remaining_minutes: u32) -> Option<String> {
+        Some(format!("M73 P{} R{}", percent, remaining_minutes))
+    }
+
+    /// Line telling the firmware a layer has started, for its progress
+    /// display
+    fn layer_progress(&self, _layer_index: usize) -> Option<String> {
+        None
+    }
+
+    /// Command showing `message` on the printer's display
+    fn display_message(&self, message: &str) -> String {
+        format!("M117 {}", message)
+    }
+
+    /// Command pausing the print until it is resumed on the printer
+    fn pause(&self) -> String {
+        "M601".to_string()
+    }
+
+    /// Command asking for the filament to be changed
+    fn color_change(&self) -> String {
+        "M600".to_string()
+    }
+
+    /// Lines ending the print once the filament is retracted
+    fn end_sequence(&self) -> Vec<String> {
+        vec![
+            "G28 X0 Y0 ; Home X and Y axes".to_string(),
+            format!("{} ; Turn off nozzle heater", self.set_temperature(Heater::Nozzle, 0)),
+            format!("{} ; Turn off bed heater", self.set_temperature(Heater::Bed, 0)),
+            format!("{} ; Fan off", self.fan(0)),
+            "M84 ; Disable motors".to_string(),
+        ]
+    }
+}
+
+/// Marlin and the many firmwares that speak its G-code
+pub struct Marlin;
+
+impl GCodeFlavor for Marlin {
+    /// Linear advance, where the firmware is built with it. It applies to
+    /// the tool selected.
+    fn pressure_advance(&self, _tool: usize, advance: f64) -> Option<String> {
+        Some(format!("M900 K{}", advance))
+    }
+
+    fn supports_arcs(&self) -> bool {
+        true
+    }
+}
+
+/// Klipper, with its extended commands for heaters, limits and print
+/// stats. Arcs need its `[gcode_arcs]` section, so they aren't written.
+/// It has no T0, T1 and so on of its own: with several extruders the
+/// printer's config needs a macro for each.
+pub struct Klipper;
+
+impl GCodeFlavor for Klipper {
+    fn preamble(&self, layer_count: usize) -> Vec<String> {
+        vec![format!("SET_PRINT_STATS_INFO TOTAL_LAYER_COUNT={}", layer_count)]
+    }
+
+    fn supports_object_exclusion(&self) -> bool {
+        true
+    }
+
+    fn set_temperature(&self, heater: Heater, temperature: u16) -> String {
+        format!("SET_HEATER_TEMPERATURE HEATER={} TARGET={}", klipper_heater(heater), temperature)
+    }
+
+    fn wait_for_temperature(&self, heater: Heater, temperature: u16) -> String {
+        format!("TEMPERATURE_WAIT SENSOR={} MINIMUM={}", klipper_heater(heater), temperature)
+    }
+
+    fn acceleration(&self, acceleration: f64) -> String {
+        format!("SET_VELOCITY_LIMIT ACCEL={}", acceleration)
+    }
+
+    /// Klipper's nearest to jerk is the speed it takes square corners at
+    fn jerk(&self, jerk: f64) -> String {
+        format!("SET_VELOCITY_LIMIT SQUARE_CORNER_VELOCITY={}", jerk)
+    }
+
+    /// Set for the extruder selected
+    fn pressure_advance(&self, _tool: usize, advance: f64) -> Option<String> {
+        Some(format!("SET_PRESSURE_ADVANCE ADVANCE={}", advance))
+    }
+
+    /// Klipper's M73 takes no time left
+    fn progress(&self, percent: u8, _remaining_minutes: u32) -> Option<String> {
+        Some(format!("M73 P{}", percent))
+    }
+
+    fn display_message(&self, message: &str) -> String {
+        format!("SET_DISPLAY_TEXT MSG=\"{}\"", message)
+    }
+
+    /// Klipper's pause is a macro, which most configs park in themselves
+    fn pause(&self) -> String {
+        "PAUSE".to_string()
+    }
+
+    /// Klipper counts layers from 1
+    fn layer_progress(&self, layer_index: usize) -> Option<String> {
+        Some(format!("SET_PRINT_STATS_INFO CURRENT_LAYER={}", layer_index + 1))
+    }
+
+    fn end_sequence(&self) -> Vec<String> {
+        vec![
+            "G28 X0 Y0 ; Home X and Y axes".to_string(),
+            "TURN_OFF_HEATERS".to_string(),
+            format!("{} ; Fan off", self.fan(0)),
+            "M84 ; Disable motors".to_string(),
+        ]
+    }
+}
+
+/// Name of a heater in Klipper's config, where the extruders after the
+/// first are numbered. The printing one is taken to be the first, as
+/// everywhere there is only one.
+fn klipper_heater(heater: Heater) -> String {
+    match heater {
+        Heater::Nozzle | Heater::Tool(0) => "extruder".to_string(),
+        Heater::Tool(tool) => format!("extruder{}", tool),
+        Heater::Bed => "heater_bed".to_string(),
+    }
+}
+
+/// RepRapFirmware, which retracts in firmware and keeps printer-specific
+/// start and stop moves in its own macros
+pub struct RepRapFirmware;
+
+impl GCodeFlavor for RepRapFirmware {
+    /// The fan speed as a fraction, as RepRapFirmware reads it
+    fn fan(&self, pwm: u8) -> String {
+        format!("M106 P0 S{:.2}", pwm as f64 / 255.0)
+    }
+
+    fn acceleration(&self, acceleration: f64) -> String {
+        format!("M204 P{0} T{0}", acceleration)
+    }
+
+    /// RepRapFirmware takes jerk in mm/min
+    fn jerk(&self, jerk: f64) -> String {
+        format!("M566 X{0} Y{0}", jerk * 60.0)
+    }
+
+    /// Set for the extruder drive, numbered as the tools are
+    fn pressure_advance(&self, tool: usize, advance: f64) -> Option<String> {
+        Some(format!("M572 D{} S{}", tool, advance))
+    }
+
+    fn supports_arcs(&self) -> bool {
+        true
+    }
+
+    fn firmware_retraction(&self) -> bool {
+        true
+    }
+
+    /// M226 runs the printer's pause.g macro once the moves before it are
+    /// done
+    fn pause(&self) -> String {
+        "M226".to_string()
+    }
+
+    /// M0 runs the printer's stop.g macro and turns the heaters off
+    fn end_sequence(&self) -> Vec<String> {
+        vec![
+            format!("{} ; Fan off", self.fan(0)),
+            "M0 ; Stop".to_string(),
+        ]
+    }
+}
+
+/// The flavor to write G-code in for `flavor`
+pub fn flavor(flavor: FirmwareFlavor) -> Box<dyn GCodeFlavor> {
+    match flavor {
+        FirmwareFlavor::Marlin => Box::new(Marlin),
+        FirmwareFlavor::Klipper => Box::new(Klipper),
+        FirmwareFlavor::RepRapFirmware => Box::new(RepRapFirmware),
+    }
+}
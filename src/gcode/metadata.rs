@@ -0,0 +1,57 @@
+use crate::config::SlicerConfig;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where G-code comes from, for its header: left out where not given, so
+/// the same print gives the same G-code
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// Name of the profile the settings were loaded from
+    pub profile: Option<String>,
+    /// When the G-code was generated
+    pub timestamp: Option<SystemTime>,
+}
+
+/// `time` in UTC, as PrusaSlicer writes it: `2024-05-01 at 12:00:00 UTC`
+pub(super) fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, rest) = ((seconds / 86_400) as i64, seconds % 86_400);
+    // Days since 1970 to a date in the proleptic Gregorian calendar, after
+    // Howard Hinnant's civil_from_days, with years starting in March
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} at {:02}:{:02}:{:02} UTC", year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+/// The settings print hosts read from the end of the file, under the names
+/// PrusaSlicer gives them, with the values the G-code was generated with
+pub(super) fn settings(config: &SlicerConfig, profile: Option<&str>) -> Vec<(&'static str, String)> {
+    let pattern = toml::Value::try_from(config.infill_pattern).ok();
+    let mut settings = vec![
+        ("bed_temperature", config.bed_temperature.to_string()),
+        ("external_perimeter_speed", config.external_perimeter_speed().to_string()),
+        ("filament_diameter", config.filament_diameter.to_string()),
+        ("fill_density", format!("{}%", config.infill_percentage)),
+        ("fill_pattern", pattern.as_ref().and_then(toml::Value::as_str).unwrap_or_default().to_string()),
+        ("first_layer_bed_temperature", config.first_layer_bed_temperature().to_string()),
+        ("first_layer_height", config.first_layer_height.to_string()),
+        ("first_layer_temperature", config.first_layer_nozzle_temperature().to_string()),
+        ("infill_speed", config.infill_speed().to_string()),
+        ("layer_height", config.layer_height.to_string()),
+        ("nozzle_diameter", config.nozzle_diameter.to_string()),
+        ("perimeter_speed", config.perimeter_speed().to_string()),
+        ("solid_infill_speed", config.solid_infill_speed().to_string()),
+        ("temperature", config.nozzle_temperature.to_string()),
+        ("travel_speed", config.travel_speed.to_string()),
+    ];
+    if let Some(profile) = profile {
+        settings.push(("print_settings_id", profile.to_string()));
+    }
+    settings
+}
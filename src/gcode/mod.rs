@@ -1,117 +1,1605 @@
-use crate::slicer::Layer;
-use crate::config::SlicerConfig;
+mod checksum;
+mod comb;
+mod flavor;
+mod metadata;
+mod number;
+mod objects;
+mod post_process;
+mod spill;
+mod stats;
+mod template;
+mod thumbnail;
+mod verify;
+mod wipe_tower;
+
+pub use checksum::number_lines;
+pub use comb::comb_path;
+pub use flavor::{GCodeFlavor, Heater, Klipper, Marlin, RepRapFirmware};
+pub use metadata::Metadata;
+pub use objects::PrintObject;
+pub use post_process::post_process;
+pub use stats::{format_duration, GCodeStats};
+pub use verify::{verify, VerificationReport, Violation, ViolationKind};
+
+use checksum::LineNumberWriter;
+use spill::SpillBuffer;
+use stats::{is_layer_start, StatsWriter};
+use wipe_tower::WipeTower;
+
+use crate::slicer::{InfillRegions, Layer};
+use crate::geometry::boolean::union;
+use crate::geometry::arc_fit::{fit_polyline_arcs, Arc, PathElement};
+use crate::geometry::arrange::place_beside;
+use crate::geometry::{BoundingBox2D, Island, LineSegment2D};
+use crate::config::{Combing, CommentLevel, HeatOrder, SlicerConfig, PRIME_LINE_INSET};
 use crate::error::{SlicerError, Result};
+use crate::toolpath::{LayerPaths, PathPlanner, PathRole, ToolPath};
+use nalgebra::{Point2, Vector2};
+use std::borrow::Borrow;
+use std::cell::{Cell, RefCell};
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufWriter, Write};
 use std::path::Path;
 
+/// Line standing in for the layer display message until the layer count
+/// is known
+const LAYER_DISPLAY: &str = "; Layer display";
+
+/// Line standing in for the flavor's preamble, which may give the layer
+/// count, until it is known
+const PREAMBLE: &str = "; Preamble";
+
+/// Changes made to the whole of the G-code once it is finished
+pub type PostProcessor = Box<dyn Fn(String) -> Result<String>>;
+
 pub struct GCodeGenerator {
     config: SlicerConfig,
+    header_comments: Vec<String>,
+    metadata: Metadata,
+    /// Models on the plate, for the firmware to cancel one by one
+    objects: Vec<PrintObject>,
+    /// What the finished G-code is passed through before it is written
+    post_processor: Option<PostProcessor>,
+    /// Firmware the G-code is written for
+    flavor: Box<dyn GCodeFlavor>,
+    /// Settings by name, for placeholders in custom G-code
+    placeholders: Vec<(String, String)>,
+    /// Where the last extrusion left the nozzle
+    position: Cell<Point2<f64>>,
+    /// Length in mm of the travel moves written so far
+    travel: Cell<f64>,
+    /// E the extruder is at, in mm of filament since it was last reset
+    extruded: Cell<f64>,
+    /// Height of the layer being written
+    z: Cell<f64>,
+    /// Feedrate in mm/min the printer was last given
+    last_feedrate: Cell<f64>,
+    /// X, Y and Z the printer was last given, in units of their last
+    /// digit, NaN where it may have moved since
+    last_axes: Cell<[f64; 3]>,
+    /// Moves slowed to the machine's max feedrate
+    clamped_moves: Cell<usize>,
+    /// Layers slowed down for the minimum layer time, with their factors
+    slowed_layers: RefCell<Vec<(usize, f64)>>,
+    /// What was left out of the G-code, and why
+    warnings: RefCell<Vec<String>>,
+    /// Role of the extrusion being written, None between extrusions
+    role: Cell<Option<PathRole>>,
+    /// Acceleration in mm/s² the printer was last given
+    acceleration: Cell<Option<f64>>,
+    /// Pressure advance the printer was last given
+    pressure_advance: Cell<Option<f64>>,
+    /// Cooling fan PWM value the printer was last given
+    fan: Cell<u8>,
+    /// Nozzle temperature the printer was last given
+    nozzle_temperature: Cell<u16>,
+    /// Extruder printing, by its tool number
+    tool: Cell<usize>,
+    /// E each extruder was left at, retracted, when it was last put away
+    tool_extruded: RefCell<Vec<f64>>,
+    /// Offset in mm of the printing extruder, taken off the coordinates
+    tool_offset: Cell<Vector2<f64>>,
+    /// Whether a tool change left the printing extruder retracted
+    tool_retracted: Cell<bool>,
+    /// Column tool changes purge on, when there is one
+    wipe_tower: RefCell<Option<WipeTower>>,
+    /// Purges on the wipe tower so far on the current layer
+    tower_purges: Cell<usize>,
+    /// The last extrusion's points, to wipe back along
+    last_extrusion: RefCell<Vec<Point2<f64>>>,
+    /// Whether the last layer ended on a spiral loop, for the next to carry
+    /// straight on from
+    spiralling: Cell<bool>,
+    /// Where travel on the current layer may be combed through
+    comb_regions: RefCell<Vec<Island>>,
+    /// Islands of the current layer, for telling whether travel crosses
+    /// their perimeters
+    islands: RefCell<Vec<Island>>,
+    /// Height and islands of every layer written, for the thumbnails
+    thumbnail_layers: RefCell<Vec<(f64, Vec<Island>)>>,
+    /// Buffer the moves are put together in, kept between them
+    line: RefCell<Vec<u8>>,
 }
 
 impl GCodeGenerator {
     pub fn new(config: SlicerConfig) -> Self {
-        GCodeGenerator { config }
+        GCodeGenerator {
+            flavor: flavor::flavor(config.gcode_flavor),
+            placeholders: template::config_values(&config),
+            config,
+            header_comments: Vec::new(),
+            metadata: Metadata::default(),
+            objects: Vec::new(),
+            post_processor: None,
+            position: Cell::new(Point2::origin()),
+            travel: Cell::new(0.0),
+            extruded: Cell::new(0.0),
+            z: Cell::new(0.0),
+            last_feedrate: Cell::new(0.0),
+            last_axes: Cell::new([f64::NAN; 3]),
+            clamped_moves: Cell::new(0),
+            slowed_layers: RefCell::new(Vec::new()),
+            warnings: RefCell::new(Vec::new()),
+            role: Cell::new(None),
+            acceleration: Cell::new(None),
+            pressure_advance: Cell::new(None),
+            fan: Cell::new(0),
+            nozzle_temperature: Cell::new(0),
+            tool: Cell::new(0),
+            tool_extruded: RefCell::new(Vec::new()),
+            tool_offset: Cell::new(Vector2::zeros()),
+            tool_retracted: Cell::new(false),
+            wipe_tower: RefCell::new(None),
+            tower_purges: Cell::new(0),
+            last_extrusion: RefCell::new(Vec::new()),
+            spiralling: Cell::new(false),
+            comb_regions: RefCell::new(Vec::new()),
+            islands: RefCell::new(Vec::new()),
+            thumbnail_layers: RefCell::new(Vec::new()),
+            line: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Extra lines written as comments at the end of the header block
+    pub fn with_header_comments(mut self, comments: Vec<String>) -> Self {
+        self.header_comments = comments;
+        self
+    }
+
+    /// Profile name and timestamp to write in the header
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Objects on the plate, marked for the firmware to cancel when
+    /// `exclude_objects` is on
+    pub fn with_objects(mut self, objects: Vec<PrintObject>) -> Self {
+        self.objects = objects;
+        self
+    }
+
+    /// Pass the finished G-code through `post_processor` before it is
+    /// written, for embedders to change it as a post-processing script
+    /// would. Line numbers and checksums are added after it.
+    pub fn with_post_processor(mut self, post_processor: PostProcessor) -> Self {
+        self.post_processor = Some(post_processor);
+        self
+    }
+
+    /// G-code for the given layers, either borrowed (`&layers`) or owned
+    /// (such as a `SliceEngine::layers_iter` that has been unwrapped)
+    pub fn generate_to_string<I>(&self, layers: I) -> Result<String>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Layer>,
+    {
+        let mut output = Vec::new();
+        self.generate_to_writer(layers, &mut output)?;
+        String::from_utf8(output).map_err(|e| SlicerError::GCodeError(format!("Generated invalid UTF-8: {}", e)))
     }
 
-    pub fn generate<P: AsRef<Path>>(&self, layers: &[Layer], output_path: P) -> Result<()> {
+    /// Stream G-code for the given layers to `writer` through a buffer
+    pub fn generate_to_writer<I, W>(&self, layers: I, writer: W) -> Result<GCodeStats>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Layer>,
+        W: Write,
+    {
+        self.write_to(layers.into_iter().map(Ok), writer)
+    }
+
+    /// Write G-code for the given layers to a file at `output_path`
+    pub fn generate_to_file<I, P>(&self, layers: I, output_path: P) -> Result<GCodeStats>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Layer>,
+        P: AsRef<Path>,
+    {
+        self.write_file(layers.into_iter().map(Ok), output_path)
+    }
+
+    /// Write G-code while the layers are still being sliced, so only a few
+    /// are in memory at once. If slicing fails the partial file is removed.
+    pub fn generate_streaming<I, P>(&self, layers: I, output_path: P) -> Result<GCodeStats>
+    where
+        I: IntoIterator<Item = Result<Layer>>,
+        P: AsRef<Path>,
+    {
+        let result = self.write_file(layers, output_path.as_ref());
+        if result.is_err() {
+            let _ = std::fs::remove_file(output_path);
+        }
+        result
+    }
+
+    /// Total length in mm of the travel moves in the last G-code written
+    pub fn travel_distance(&self) -> f64 {
+        self.travel.get()
+    }
+
+    /// Length in mm of the travel moves the G-code for `layers` would have,
+    /// without writing it anywhere. Layers may be streamed in as they are
+    /// sliced, as for `generate_streaming`.
+    pub fn measure_travel<I, L>(&self, layers: I) -> Result<f64>
+    where
+        I: IntoIterator<Item = Result<L>>,
+        L: Borrow<Layer>,
+    {
+        self.write_gcode(&mut std::io::sink(), layers)?;
+        Ok(self.travel_distance())
+    }
+
+    fn write_file<I, L, P>(&self, layers: I, output_path: P) -> Result<GCodeStats>
+    where
+        I: IntoIterator<Item = Result<L>>,
+        L: Borrow<Layer>,
+        P: AsRef<Path>,
+    {
         let file = File::create(output_path)
             .map_err(|e| SlicerError::GCodeError(format!("Failed to create output file: {}", e)))?;
-        
-        let mut writer = BufWriter::new(file);
-
-        // Write header
-        self.write_header(&mut writer)?;
+        self.write_to(layers, file)
+    }
 
-        // Write layers
-        for (i, layer) in layers.iter().enumerate() {
-            self.write_layer(&mut writer, layer, i)?;
+    fn write_to<I, L, W>(&self, layers: I, writer: W) -> Result<GCodeStats>
+    where
+        I: IntoIterator<Item = Result<L>>,
+        L: Borrow<Layer>,
+        W: Write,
+    {
+        let flush_error = |e| SlicerError::GCodeError(format!("Failed to flush output: {}", e));
+        // The header and progress need figures for the whole print, so the
+        // G-code is written in full, out of memory once it is large, before
+        // it is read back and goes out with them added
+        let mut buffer = StatsWriter::new(SpillBuffer::new(), &self.config, &self.role);
+        self.write_gcode(&mut buffer, layers)?;
+        let (gcode, mut stats) = buffer.finish().map_err(flush_error)?;
+        let thumbnails = self.thumbnails()?;
+        let gcode = gcode.into_reader().map_err(flush_error)?;
+        let mut writer = StatsWriter::new(BufWriter::new(writer), &self.config, &self.role);
+        if self.config.line_numbers_and_checksums {
+            // Framed last, so the checksums cover the lines as they are sent
+            let mut numbered = LineNumberWriter::new(&mut writer, self.config.first_line_number);
+            self.write_finished(&mut numbered, gcode, &stats, &thumbnails)?;
+            numbered.finish().map_err(flush_error)?;
+        } else {
+            self.write_finished(&mut writer, gcode, &stats, &thumbnails)?;
         }
+        stats.bytes = writer.finish().map_err(flush_error)?.1.bytes;
+        stats.slowed_layers = self.slowed_layers.take();
+        stats.warnings = self.warnings.take();
+        stats.clamped_moves = self.clamped_moves.replace(0);
+        stats.wipe_tower = self.wipe_tower.borrow().as_ref().map(WipeTower::bounds);
+        Ok(stats)
+    }
 
-        // Write footer
-        self.write_footer(&mut writer)?;
+    /// Write `gcode` with its summary, through the post-processor if there
+    /// is one
+    fn write_finished(&self, writer: &mut dyn Write, gcode: impl BufRead, stats: &GCodeStats, thumbnails: &[(u32, u32, Vec<u8>)]) -> Result<()> {
+        let Some(post_processor) = &self.post_processor else {
+            return self.write_summary(writer, gcode, stats, thumbnails);
+        };
+        let mut finished = Vec::new();
+        self.write_summary(&mut finished, gcode, stats, thumbnails)?;
+        let finished = String::from_utf8(finished).map_err(|e| SlicerError::GCodeError(format!("Generated invalid UTF-8: {}", e)))?;
+        writer.write_all(post_processor(finished)?.as_bytes())?;
+        Ok(())
+    }
 
-        writer.flush()
-            .map_err(|e| SlicerError::GCodeError(format!("Failed to flush output: {}", e)))?;
+    /// PNG thumbnails of the layers written, with their widths and heights,
+    /// if they are wanted
+    fn thumbnails(&self) -> Result<Vec<(u32, u32, Vec<u8>)>> {
+        let layers = self.thumbnail_layers.take();
+        if !self.config.thumbnail {
+            return Ok(Vec::new());
+        }
+        self.config
+            .thumbnail_sizes
+            .iter()
+            .map(|&[width, height]| {
+                let png = thumbnail::encode_png(&thumbnail::render(&layers, width, height), width, height)?;
+                Ok((width, height, png))
+            })
+            .collect()
+    }
 
+    /// Write `gcode` with the filament it uses and `thumbnails` in its
+    /// header, and with the estimated print time and progress at every
+    /// layer change when progress updates are on, from `stats` estimated
+    /// for it, and with the layer out of the total for the display. Comments
+    /// are left out here when they are off, as the layer markers were
+    /// needed to estimate the time.
+    fn write_summary(&self, writer: &mut dyn Write, mut gcode: impl BufRead, stats: &GCodeStats, thumbnails: &[(u32, u32, Vec<u8>)]) -> Result<()> {
+        let total = stats.print_time;
+        let progress = |elapsed: f64| {
+            let percent = if total > 0.0 { (elapsed / total * 100.0).floor().min(100.0) as u8 } else { 0 };
+            self.flavor.progress(percent, ((total - elapsed).max(0.0) / 60.0).round() as u32)
+        };
+        let comments = self.config.gcode_comments != CommentLevel::Off;
+        let mut layer = 0;
+        let mut line = String::new();
+        for i in 0.. {
+            line.clear();
+            if gcode.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end_matches('\n');
+            let comment = line.strip_prefix(';').unwrap_or_default();
+            let elapsed = if is_layer_start(comment) {
+                // Everything before the layer is written
+                writer.flush()?;
+                layer += 1;
+                Some(stats.time_before_layer(layer - 1))
+            } else if line == "; End sequence" {
+                Some(total)
+            } else {
+                None
+            };
+            if let Some(progress) = elapsed.filter(|_| self.config.progress_updates).and_then(progress) {
+                writeln!(writer, "{}", progress)?;
+            }
+            // Trailing empty layers are left out as they come, so the count
+            // is only certain now
+            if line == LAYER_DISPLAY {
+                let message = format!("Layer {}/{}", layer, stats.layer_times.len());
+                writeln!(writer, "{}", self.flavor.display_message(&message))?;
+            } else if line == PREAMBLE {
+                for line in self.flavor.preamble(stats.layer_times.len()) {
+                    writeln!(writer, "{}", line)?;
+                }
+            } else if comments {
+                writeln!(writer, "{}", line)?;
+            } else {
+                let code = line.split_once(';').map_or(line, |(code, _)| code).trim_end();
+                if !code.is_empty() {
+                    writeln!(writer, "{}", code)?;
+                }
+            }
+            if i == 0 {
+                if self.config.progress_updates && comments {
+                    writeln!(writer, "; estimated printing time = {}", format_duration(total))?;
+                }
+                if comments {
+                    // Named as PrusaSlicer names them, for tools that read them
+                    writeln!(writer, "; filament used [mm] = {:.2}", stats.filament_length)?;
+                    writeln!(writer, "; filament used [cm3] = {:.2}", stats.filament_volume)?;
+                    writeln!(writer, "; filament used [g] = {:.2}", stats.filament_weight)?;
+                    if let Some(cost) = stats.filament_cost {
+                        writeln!(writer, "; filament cost = {:.2}", cost)?;
+                    }
+                    // As Cura writes them, for the printer to plan its
+                    // bed mesh and object exclusion around
+                    writeln!(writer, ";LAYER_COUNT:{}", stats.layer_times.len())?;
+                    if let Some(bounds) = &stats.extrusion_bounds {
+                        for (name, corner) in [("MIN", bounds.min), ("MAX", bounds.max)] {
+                            for (axis, value) in ["X", "Y", "Z"].iter().zip(corner.iter()) {
+                                writeln!(writer, ";{}{}:{:.3}", name, axis, value)?;
+                            }
+                        }
+                    }
+                }
+                for (width, height, png) in thumbnails {
+                    thumbnail::write_block(writer, png, *width, *height)?;
+                }
+            }
+        }
         Ok(())
     }
 
-    fn write_header(&self, writer: &mut BufWriter<File>) -> Result<()> {
-        writeln!(writer, "; Generated by RustSlicer")?;
+    fn write_gcode<I, L>(&self, writer: &mut dyn Write, layers: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<L>>,
+        L: Borrow<Layer>,
+    {
+        self.position.set(Point2::origin());
+        self.travel.set(0.0);
+        self.extruded.set(0.0);
+        self.slowed_layers.borrow_mut().clear();
+        self.warnings.borrow_mut().clear();
+        self.clamped_moves.set(0);
+        self.spiralling.set(false);
+        self.thumbnail_layers.borrow_mut().clear();
+
+        // Write header
+        let layers = layers.into_iter();
+        self.write_header(writer)?;
+
+        self.write_custom(writer, &self.config.before_object_gcode, None)?;
+        let mut planner = PathPlanner::new(&self.config);
+        *self.wipe_tower.borrow_mut() = None;
+        if self.config.wipe_tower && (self.config.extruders.len() > 1 || !self.config.color_change_layers.is_empty()) {
+            // The tower goes up to the last layer that purges on it and is
+            // as deep as the busiest one needs. The layers are kept, as for
+            // support, and planned once for no more than where the tools
+            // change and what they cover, then again to be written.
+            let layers: Vec<L> = layers.collect::<Result<_>>()?;
+            let mut survey = PurgeSurvey { tool: self.config.perimeter_extruder, bands: 0, top_layer: None, occupied: None };
+            PathPlanner::new(&self.config).plan(layers.iter().map(|layer| Ok(layer.borrow())), |_, _, paths| {
+                self.survey_purges(&mut survey, &paths);
+                Ok(())
+            })?;
+            *self.wipe_tower.borrow_mut() = self.plan_wipe_tower(survey)?;
+            planner.plan(layers.iter().map(|layer| Ok(layer.borrow())), |islands, infill, paths| self.write_layer(writer, islands, infill, &paths))?;
+        } else {
+            planner.plan(layers, |islands, infill, paths| self.write_layer(writer, islands, infill, &paths))?;
+        }
+        self.write_custom(writer, &self.config.after_object_gcode, None)?;
+
+        // Write footer
+        self.write_footer(writer)
+    }
+
+    fn write_header(&self, writer: &mut dyn Write) -> Result<()> {
+        match self.metadata.timestamp {
+            Some(time) => writeln!(writer, "; Generated by RustSlicer {} on {}", env!("CARGO_PKG_VERSION"), metadata::format_timestamp(time))?,
+            None => writeln!(writer, "; Generated by RustSlicer {}", env!("CARGO_PKG_VERSION"))?,
+        }
+        if let Some(profile) = &self.metadata.profile {
+            writeln!(writer, "; Profile: {}", profile)?;
+        }
         writeln!(writer, "; Layer height: {} mm", self.config.layer_height)?;
         writeln!(writer, "; Infill: {}%", self.config.infill_percentage)?;
         writeln!(writer, "; Print speed: {} mm/s", self.config.print_speed)?;
+        for comment in &self.header_comments {
+            writeln!(writer, "; {}", comment)?;
+        }
         writeln!(writer)?;
         writeln!(writer, "G21 ; Set units to millimeters")?;
+        writeln!(writer, "{} ; Fan off", self.flavor.fan(0))?;
+        self.fan.set(0);
         writeln!(writer, "G90 ; Use absolute coordinates")?;
         writeln!(writer, "M82 ; Use absolute distances for extrusion")?;
+        writeln!(writer, "{}", PREAMBLE)?;
+        if self.excludes_objects() {
+            for object in &self.objects {
+                let center = object.center();
+                let outline: Vec<String> = object.outline.points.iter().map(|p| format!("[{:.3},{:.3}]", p.x, p.y)).collect();
+                writeln!(writer, "EXCLUDE_OBJECT_DEFINE NAME={} CENTER={:.3},{:.3} POLYGON=[{}]", object.name, center.x, center.y, outline.join(","))?;
+            }
+        }
+        self.acceleration.set(self.config.default_acceleration());
+        if let Some(acceleration) = self.acceleration.get() {
+            writeln!(writer, "{} ; Set acceleration", self.flavor.acceleration(acceleration))?;
+        }
+        if let Some(jerk) = self.config.jerk() {
+            writeln!(writer, "{} ; Set jerk", self.flavor.jerk(jerk))?;
+        }
+        let advance = self.config.pressure_advance();
+        let command = advance.and_then(|advance| self.flavor.pressure_advance(self.config.perimeter_extruder, advance));
+        if let Some(command) = &command {
+            writeln!(writer, "{} ; Set pressure advance", command)?;
+        }
+        self.pressure_advance.set(advance.filter(|_| command.is_some()));
         writeln!(writer)?;
         writeln!(writer, "; Heating")?;
-        writeln!(writer, "M104 S{} ; Set nozzle temperature", self.config.nozzle_temperature)?;
-        writeln!(writer, "M140 S{} ; Set bed temperature", self.config.bed_temperature)?;
-        writeln!(writer, "M109 S{} ; Wait for nozzle temperature", self.config.nozzle_temperature)?;
-        writeln!(writer, "M190 S{} ; Wait for bed temperature", self.config.bed_temperature)?;
+        // With several extruders, the one printing the first walls is
+        // heated and selected
+        let multi_extruder = self.config.extruders.len() > 1;
+        let tool = self.config.perimeter_extruder;
+        let heater = if multi_extruder { Heater::Tool(tool) } else { Heater::Nozzle };
+        let nozzle = self.config.extruders.get(tool).and_then(|extruder| extruder.temperature)
+            .unwrap_or_else(|| self.config.first_layer_nozzle_temperature());
+        let bed = self.config.first_layer_bed_temperature();
+        let heat_nozzle = format!("{} ; Set nozzle temperature", self.flavor.set_temperature(heater, nozzle));
+        let wait_nozzle = format!("{} ; Wait for nozzle temperature", self.flavor.wait_for_temperature(heater, nozzle));
+        let heat_bed = format!("{} ; Set bed temperature", self.flavor.set_temperature(Heater::Bed, bed));
+        let wait_bed = format!("{} ; Wait for bed temperature", self.flavor.wait_for_temperature(Heater::Bed, bed));
+        let sequence = match self.config.heat_order {
+            HeatOrder::Together => [heat_bed, heat_nozzle, wait_bed, wait_nozzle],
+            HeatOrder::BedFirst => [heat_bed, wait_bed, heat_nozzle, wait_nozzle],
+            HeatOrder::NozzleFirst => [heat_nozzle, wait_nozzle, heat_bed, wait_bed],
+        };
+        for command in &sequence {
+            writeln!(writer, "{}", command)?;
+        }
+        self.nozzle_temperature.set(nozzle);
         writeln!(writer)?;
         writeln!(writer, "; Start sequence")?;
         writeln!(writer, "G28 ; Home all axes")?;
-        writeln!(writer, "G1 Z15.0 F6000 ; Move platform down 15mm")?;
+        self.last_axes.set([f64::NAN; 3]);
+        writeln!(writer, "G1 Z15.0 F{} ; Move platform down 15mm", self.limited(Axis::Z, 6000.0))?;
+        if multi_extruder {
+            writeln!(writer, "T{} ; Select tool", tool)?;
+        }
+        self.tool.set(tool);
+        self.tool_retracted.set(false);
+        *self.tool_extruded.borrow_mut() = vec![-self.config.retraction_distance; self.config.extruders.len().max(1)];
+        self.tool_offset.set(self.extruder_offset(tool));
         writeln!(writer, "G92 E0 ; Reset extruder")?;
-        writeln!(writer, "G1 F200 E3 ; Extrude 3mm of filament")?;
+        let prime = self.limited(Axis::E, 200.0);
+        writeln!(writer, "G1 F{} E3 ; Extrude 3mm of filament", prime)?;
+        self.last_feedrate.set(prime);
         writeln!(writer, "G92 E0 ; Reset extruder")?;
         writeln!(writer)?;
 
         Ok(())
     }
 
-    fn write_layer(&self, writer: &mut BufWriter<File>, layer: &Layer, layer_index: usize) -> Result<()> {
+    /// Write a layer's paths, with its islands and their infill regions to
+    /// route travel through
+    fn write_layer(&self, writer: &mut dyn Write, islands: &[Island], infill: &[InfillRegions], layer_paths: &LayerPaths) -> Result<()> {
+        let layer_index = layer_paths.layer_index;
+        self.tower_purges.set(0);
+        // The first layer's temperatures give way to the others', and
+        // overrides to the settings, without waiting for them
+        let nozzle = self.config.extruder_temperature(self.tool.get(), layer_index, layer_paths.z);
+        if self.nozzle_temperature.replace(nozzle) != nozzle {
+            writeln!(writer, "{} ; Set nozzle temperature", self.flavor.set_temperature(Heater::Nozzle, nozzle))?;
+        }
+        if layer_index == 1 && self.config.first_layer_bed_temperature() != self.config.bed_temperature {
+            writeln!(writer, "{} ; Set bed temperature", self.flavor.set_temperature(Heater::Bed, self.config.bed_temperature))?;
+        }
+        if layer_index == 0 && self.config.prime_line {
+            self.write_prime_line(writer, &layer_paths.paths)?;
+        }
         writeln!(writer, "; Layer {}", layer_index)?;
-        writeln!(writer, "G1 Z{:.3} F{}", layer.z, self.config.print_speed * 60.0)?;
+        if self.config.thumbnail {
+            self.thumbnail_layers.borrow_mut().push((layer_paths.z, islands.to_vec()));
+        }
+        if let Some(progress) = self.flavor.layer_progress(layer_index) {
+            writeln!(writer, "{}", progress)?;
+        }
+        self.write_custom(writer, &self.config.layer_change_gcode, Some((layer_index, layer_paths.z)))?;
+        if self.config.display_progress {
+            writeln!(writer, "{}", LAYER_DISPLAY)?;
+        }
+        self.write_pause(writer, layer_index)?;
+        let fan = fan_pwm(self.config.layer_fan_speed_at(layer_index, layer_paths.z));
+        if fan != self.fan.get() {
+            writeln!(writer, "{}", self.flavor.fan(fan))?;
+            self.fan.set(fan);
+        }
+        // A spiral climbs to the layer's height along its loop instead
+        let spiral = self.config.spiral_layer(layer_index) && self.is_spiral(&layer_paths.paths, layer_index);
+        if !spiral {
+            self.write_move(writer, "G1", &[(b'Z', layer_paths.z, self.config.xyz_decimals)], self.config.print_speed * 60.0, None)?;
+            self.z.set(layer_paths.z);
+        }
+        // Out of the flat layer below, the first loop's flow rises from
+        // nothing as it climbs
+        let (continued, ramp) = (spiral && self.spiralling.get(), spiral && !self.spiralling.get());
+        self.spiralling.set(spiral);
+        let verbose = self.config.gcode_comments == CommentLevel::Verbose;
+        // The nozzle has left the paths of the layer below
+        self.last_extrusion.borrow_mut().clear();
 
-        for contour in &layer.contours {
-            if contour.points.is_empty() {
-                continue;
+        *self.comb_regions.borrow_mut() = self.comb_regions(islands, infill);
+        *self.islands.borrow_mut() = islands.to_vec();
+        // Each object's paths come together, to be marked as its own
+        let grouped;
+        let (paths, owners) = if self.excludes_objects() {
+            grouped = objects::group_by_object(&layer_paths.paths, &self.objects);
+            (&grouped.0, &grouped.1[..])
+        } else {
+            (&layer_paths.paths, &[][..])
+        };
+        let mut object = None;
+        let (slowdown, wait) = self.layer_slowdown(paths, layer_index);
+        if slowdown < 1.0 {
+            writeln!(writer, "; Slowed to {:.0}% for the minimum layer time", slowdown * 100.0)?;
+            self.slowed_layers.borrow_mut().push((layer_index, slowdown));
+        }
+        let mut section = None;
+        // Lines are as thick as the layer, with the first layer's flow on it
+        let (height, flow) = if layer_index == 0 {
+            (self.config.first_layer_height, self.config.flow_rate * self.config.first_layer_flow)
+        } else {
+            (self.config.layer_height, self.config.flow_rate)
+        };
+        if verbose {
+            writeln!(writer, ";HEIGHT:{:.3}", height)?;
+        }
+        let tower = self.wipe_tower.borrow().as_ref().is_some_and(|tower| layer_index <= tower.top_layer);
+        // New filament is purged on the tower like a new tool's
+        if tower && self.config.color_change_layers.contains(&layer_index) {
+            self.enter_section(writer, &mut section, section_label(PathRole::WipeTower))?;
+            self.write_purge(writer, layer_index, height, flow)?;
+        }
+        // Feature type and width last annotated on this layer
+        let mut feature = None;
+        for (i, path) in paths.iter().enumerate() {
+            // A travel belongs to the section of the extrusion it leads to
+            let role = paths[i..].iter().find_map(ToolPath::role);
+            // A new tool purges on the tower before it goes on, outside
+            // any object
+            let tool = role.map(|role| self.role_extruder(role)).filter(|&tool| tool != self.tool.get());
+            if let Some(tool) = tool.filter(|_| tower) {
+                self.write_object_change(writer, object, None)?;
+                object = None;
+                self.enter_section(writer, &mut section, section_label(PathRole::WipeTower))?;
+                self.write_tool_change(writer, tool, layer_index, layer_paths.z)?;
+                self.write_purge(writer, layer_index, height, flow)?;
+                feature = None;
             }
+            let owner = owners.get(i).copied().flatten();
+            if owner != object {
+                self.write_object_change(writer, object, owner)?;
+                object = owner;
+            }
+            self.enter_section(writer, &mut section, role.and_then(section_label))?;
+            if let Some(role) = role {
+                self.write_tool_change(writer, self.role_extruder(role), layer_index, layer_paths.z)?;
+            }
+            match path {
+                // The spiral never leaves the wall
+                ToolPath::Travel { .. } if continued => {}
+                ToolPath::Travel { to, .. } => {
+                    self.role.set(None);
+                    self.set_acceleration(writer, self.config.travel_acceleration())?;
+                    self.write_travel(writer, *to)?;
+                }
+                ToolPath::Extrude { points, role, width, flow_multiplier } => {
+                    // Prime after a tool change with no travel to undo it
+                    if let Some(&start) = points.first().filter(|_| self.tool_retracted.get()) {
+                        self.write_travel(writer, start)?;
+                    }
+                    self.role.set(Some(*role));
+                    if verbose {
+                        let (feature_type, width) = (role.feature_type(), *width);
+                        if feature.map(|(last, _)| last) != Some(feature_type) {
+                            writeln!(writer, ";TYPE:{}", feature_type)?;
+                        }
+                        if feature.map(|(_, last)| last) != Some(width) {
+                            writeln!(writer, ";WIDTH:{:.3}", width)?;
+                        }
+                        feature = Some((feature_type, width));
+                    }
+                    self.set_acceleration(writer, self.role_acceleration(*role, layer_index))?;
+                    self.set_pressure_advance(writer, self.role_pressure_advance(*role))?;
+                    let speed = self.slowed(self.path_speed(*role, points, layer_index), slowdown);
+                    let filament = self.config.extruder_filament_diameter(self.tool.get());
+                    let feed = |length| extrusion_length(length, *width, height, flow * flow_multiplier, filament);
+                    let coast = self.config.coast_distance;
+                    // A spiral runs on into the next loop without stopping
+                    let split = (!spiral && coast > 0.0 && path_length(points) >= 2.0 * coast)
+                        .then(|| split_at_length(points, path_length(points) - coast));
+                    let printed = split.as_ref().map_or(&points[..], |(printed, _)| printed);
+                    if spiral {
+                        self.write_spiral(writer, points, layer_paths.z, speed, feed, ramp)?;
+                    } else if self.arc_fitting() {
+                        for element in fit_polyline_arcs(printed, self.config.resolution) {
+                            match element {
+                                PathElement::Line(line) => self.write_lines(writer, &line, speed, feed)?,
+                                PathElement::Arc(arc) => self.write_arc(writer, &arc, speed, feed)?,
+                            }
+                        }
+                    } else {
+                        self.write_lines(writer, printed, speed, feed)?;
+                    }
+                    // The rest of the path at the same speed, without
+                    // extruding
+                    if let Some((_, coasted)) = &split {
+                        for point in coasted.iter().skip(1) {
+                            self.write_move(writer, "G1", &[(b'X', point.x, self.config.xyz_decimals), (b'Y', point.y, self.config.xyz_decimals)], speed * 60.0, Some("Coast"))?;
+                        }
+                    }
+                    if let Some(last) = points.last() {
+                        self.position.set(*last);
+                    }
+                    if self.config.wipe {
+                        self.last_extrusion.borrow_mut().clone_from(points);
+                    }
+                }
+            }
+        }
+        self.role.set(None);
+        self.write_object_change(writer, object, None)?;
+        if tower {
+            self.enter_section(writer, &mut section, section_label(PathRole::WipeTower))?;
+            self.write_tower_fill(writer, layer_index, height, flow)?;
+        }
+        self.enter_section(writer, &mut section, None)?;
+        if wait > 0.0 && self.config.min_layer_time_dwell {
+            writeln!(writer, "G4 P{:.0} ; Wait for the layer to cool", wait * 1000.0)?;
+        }
 
-            // Move to start of contour (travel move)
-            let first = &contour.points[0];
-            writeln!(writer, "G1 X{:.3} Y{:.3} F{}", 
-                first.x, first.y, self.config.travel_speed * 60.0)?;
+        writeln!(writer)?;
+        Ok(())
+    }
 
-            // Extrude along contour
-            let mut e = 0.0;
-            for point in &contour.points[1..] {
-                // Simplified extrusion calculation
-                e += 0.1; // This should be calculated based on distance and line width
-                writeln!(writer, "G1 X{:.3} Y{:.3} E{:.5} F{}",
-                    point.x, point.y, e, self.config.print_speed * 60.0)?;
+    /// Add the tool changes of the planned `layer_paths`, in the order they
+    /// are written, and the area its paths cover to `survey`
+    fn survey_purges(&self, survey: &mut PurgeSurvey, layer_paths: &LayerPaths) {
+        let grouped;
+        let paths = if self.excludes_objects() {
+            grouped = objects::group_by_object(&layer_paths.paths, &self.objects).0;
+            &grouped
+        } else {
+            &layer_paths.paths
+        };
+        let mut purges = usize::from(self.config.color_change_layers.contains(&layer_paths.layer_index));
+        for path in paths {
+            if let ToolPath::Extrude { points, role, .. } = path {
+                let next = self.role_extruder(*role);
+                purges += usize::from(next != survey.tool);
+                survey.tool = next;
+                for point in points {
+                    survey.occupied = Some(survey.occupied.map_or(BoundingBox2D { min: *point, max: *point }, |bounds| bounds.expand(point)));
+                }
             }
+        }
+        if purges > 0 {
+            survey.bands = survey.bands.max(purges);
+            survey.top_layer = Some(layer_paths.layer_index);
+        }
+    }
+
+    /// The wipe tower for the layers in `survey`, standing where it was put
+    /// or else beside the print, if any layer purges on it
+    fn plan_wipe_tower(&self, survey: PurgeSurvey) -> Result<Option<WipeTower>> {
+        let PurgeSurvey { bands, top_layer: Some(top_layer), occupied: Some(occupied), .. } = survey else {
+            return Ok(None);
+        };
+
+        // A band holds the lines for a purge on the thinnest layer
+        let (line_width, width) = (self.config.nozzle_diameter, self.config.wipe_tower_width);
+        let length = self.config.wipe_tower_purge_volume / (line_width * self.config.first_layer_height.min(self.config.layer_height));
+        let band_lines = (length / (width - 3.0 * line_width)).ceil() as usize;
+        let mut tower = WipeTower { origin: Point2::origin(), width, line_width, bands, band_lines, top_layer };
+        let [bed_x, bed_y, _] = self.config.build_volume;
+        let size = [width, tower.depth()];
+        tower.origin = match self.config.wipe_tower_position {
+            Some([x, y]) => Point2::new(x, y),
+            None => place_beside(&occupied, size, [bed_x, bed_y], self.config.arrange_gap).ok_or_else(|| {
+                SlicerError::GCodeError(format!("No room on the bed beside the print for the {:.1}x{:.1} mm wipe tower", size[0], size[1]))
+            })?,
+        };
+        let bounds = tower.bounds();
+        if bounds.min.x < 0.0 || bounds.min.y < 0.0 || bounds.max.x > bed_x || bounds.max.y > bed_y {
+            return Err(SlicerError::GCodeError(format!(
+                "The {:.1}x{:.1} mm wipe tower at X{:.1} Y{:.1} doesn't fit on the {}x{} mm bed",
+                size[0], size[1], bounds.min.x, bounds.min.y, bed_x, bed_y
+            )));
+        }
+        if bounds.intersects(&occupied) {
+            return Err(SlicerError::GCodeError(format!(
+                "The {:.1}x{:.1} mm wipe tower at X{:.1} Y{:.1} overlaps the print",
+                size[0], size[1], bounds.min.x, bounds.min.y
+            )));
+        }
+        Ok(Some(tower))
+    }
+
+    /// Purge the wipe tower's volume on the next band of the tower, with
+    /// its wall first on the layer's first purge. The volume is purged as
+    /// it is, whatever the flow.
+    fn write_purge(&self, writer: &mut dyn Write, layer_index: usize, height: f64, flow: f64) -> Result<()> {
+        let tower = self.wipe_tower.borrow();
+        let Some(tower) = tower.as_ref() else {
+            return Ok(());
+        };
+        let band = self.tower_purges.get();
+        self.tower_purges.set(band + 1);
+        let (width, filament) = (tower.line_width, self.config.extruder_filament_diameter(self.tool.get()));
+        if band == 0 {
+            self.write_tower_path(writer, &tower.wall(), layer_index, |length| extrusion_length(length, width, height, flow, filament))?;
+        }
+        let length = self.config.wipe_tower_purge_volume / (width * height);
+        let purge = tower.purge(band.min(tower.bands - 1), length);
+        let scale = length / path_length(&purge);
+        self.write_tower_path(writer, &purge, layer_index, |part| extrusion_length(part, width, height, scale, filament))
+    }
+
+    /// Fill the bands of the wipe tower the layer didn't purge on sparsely,
+    /// with its wall if nothing did
+    fn write_tower_fill(&self, writer: &mut dyn Write, layer_index: usize, height: f64, flow: f64) -> Result<()> {
+        let tower = self.wipe_tower.borrow();
+        let Some(tower) = tower.as_ref() else {
+            return Ok(());
+        };
+        let purges = self.tower_purges.get();
+        let (width, filament) = (tower.line_width, self.config.extruder_filament_diameter(self.tool.get()));
+        let feed = |length| extrusion_length(length, width, height, flow, filament);
+        if purges == 0 {
+            self.write_tower_path(writer, &tower.wall(), layer_index, feed)?;
+        }
+        self.write_tower_path(writer, &tower.sparse(purges.min(tower.bands)..tower.bands), layer_index, feed)
+    }
+
+    /// Travel to the start of `points` on the wipe tower and extrude along
+    /// them, feeding `feed(length)` mm of filament for each move
+    fn write_tower_path(&self, writer: &mut dyn Write, points: &[Point2<f64>], layer_index: usize, feed: impl Fn(f64) -> f64) -> Result<()> {
+        let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+            return Ok(());
+        };
+        self.role.set(None);
+        self.write_travel(writer, first)?;
+        let role = PathRole::WipeTower;
+        self.role.set(Some(role));
+        if self.config.gcode_comments == CommentLevel::Verbose {
+            writeln!(writer, ";TYPE:{}", role.feature_type())?;
+            writeln!(writer, ";WIDTH:{:.3}", self.config.nozzle_diameter)?;
+        }
+        self.set_acceleration(writer, self.role_acceleration(role, layer_index))?;
+        self.set_pressure_advance(writer, self.role_pressure_advance(role))?;
+        self.write_lines(writer, points, self.layer_speed(self.role_base_speed(role), layer_index), feed)?;
+        self.position.set(last);
+        if self.config.wipe {
+            *self.last_extrusion.borrow_mut() = points.to_vec();
+        }
+        self.role.set(None);
+        Ok(())
+    }
 
-            // Close contour
-            if contour.points.len() > 2 {
-                e += 0.1;
-                writeln!(writer, "G1 X{:.3} Y{:.3} E{:.5} F{}",
-                    first.x, first.y, e, self.config.print_speed * 60.0)?;
+    /// Purge two lines side by side along the front of the bed at the
+    /// first layer's height, speed and flow, unless the first layer's
+    /// `paths` come within a line's width of them
+    fn write_prime_line(&self, writer: &mut dyn Write, paths: &[ToolPath]) -> Result<()> {
+        let width = self.config.extruder_nozzle_diameter(self.tool.get());
+        let (start, end, y) = (PRIME_LINE_INSET, PRIME_LINE_INSET + self.config.prime_line_length, PRIME_LINE_INSET);
+        let points = [Point2::new(start, y), Point2::new(end, y), Point2::new(end, y + width), Point2::new(start, y + width)];
+        let clearance = BoundingBox2D { min: Point2::new(start - width, y - width), max: Point2::new(end + width, y + 2.0 * width) };
+        let collides = paths.iter().any(|path| match path {
+            ToolPath::Extrude { points, .. } => points.first().is_some_and(|&first| {
+                points.iter().fold(BoundingBox2D { min: first, max: first }, |bounds, p| bounds.expand(p)).intersects(&clearance)
+            }),
+            ToolPath::Travel { .. } => false,
+        });
+        if collides {
+            self.warnings.borrow_mut().push("Prime line left out: the first layer reaches into its place along the front of the bed".to_string());
+            return Ok(());
+        }
+
+        let travel = self.config.travel_speed * 60.0;
+        let height = self.config.first_layer_height;
+        writeln!(writer, "; Prime line")?;
+        self.write_move(writer, "G1", &[(b'X', start, self.config.xyz_decimals), (b'Y', y, self.config.xyz_decimals)], travel, None)?;
+        self.write_move(writer, "G1", &[(b'Z', height, self.config.xyz_decimals)], travel, None)?;
+        let (flow, filament) = (self.config.flow_rate * self.config.first_layer_flow, self.config.extruder_filament_diameter(self.tool.get()));
+        let speed = self.layer_speed(self.config.print_speed, 0);
+        self.write_lines(writer, &points, speed, |length| extrusion_length(length, width, height, flow, filament))?;
+        self.position.set(points[3]);
+        Ok(())
+    }
+
+    /// Write straight extrusion moves along `points` at `speed`, feeding
+    /// `feed(length)` mm of filament for each
+    fn write_lines(&self, writer: &mut dyn Write, points: &[Point2<f64>], speed: f64, feed: impl Fn(f64) -> f64) -> Result<()> {
+        for pair in points.windows(2) {
+            let e = self.extruded.get() + feed((pair[1] - pair[0]).norm());
+            self.extruded.set(e);
+            self.write_move(writer, "G1", &[(b'X', pair[1].x, self.config.xyz_decimals), (b'Y', pair[1].y, self.config.xyz_decimals), (b'E', e, self.config.e_decimals)], speed * 60.0, None)?;
+        }
+        Ok(())
+    }
+
+    /// Write an extrusion move along `arc` at `speed` as G2 (clockwise) or
+    /// G3, with its center relative to its start and the filament for its
+    /// true length
+    fn write_arc(&self, writer: &mut dyn Write, arc: &Arc, speed: f64, feed: impl Fn(f64) -> f64) -> Result<()> {
+        let (end, offset) = (arc.end(), arc.center - arc.start());
+        let e = self.extruded.get() + feed(arc.length());
+        self.extruded.set(e);
+        let words = [(b'X', end.x, self.config.xyz_decimals), (b'Y', end.y, self.config.xyz_decimals), (b'I', offset.x, self.config.xyz_decimals), (b'J', offset.y, self.config.xyz_decimals), (b'E', e, self.config.e_decimals)];
+        self.write_move(writer, if arc.clockwise { "G2" } else { "G3" }, &words, speed * 60.0, None)
+    }
+
+    /// Whether layer `layer_index` of the spiral is a single loop, to climb
+    /// along. A layer with more than one is printed flat, with a warning the
+    /// first time.
+    fn is_spiral(&self, paths: &[ToolPath], layer_index: usize) -> bool {
+        let loops = paths.iter().filter(|path| path.role().is_some()).count();
+        if loops > 1 && !self.warnings.borrow().iter().any(|warning| warning.starts_with("spiral_vase")) {
+            self.warnings.borrow_mut().push(format!("spiral_vase steps up at layer {}, which has more than one wall to print", layer_index));
+        }
+        loops == 1
+    }
+
+    /// Write extrusion moves around the loop `points`, climbing steadily from
+    /// the height the nozzle is at to `z` as it goes, so the loop closes a
+    /// layer up. It starts wherever the nozzle is, near the loop's start.
+    /// With `ramp` the flow rises from nothing as the layer under it does.
+    fn write_spiral(&self, writer: &mut dyn Write, path: &[Point2<f64>], z: f64, speed: f64, feed: impl Fn(f64) -> f64, ramp: bool) -> Result<()> {
+        let (start, resolution) = (self.z.get(), self.config.resolution);
+        // Each move climbs less than a micron on a fine loop, so Z has two
+        // more digits than the other coordinates to show that it does
+        let z_decimals = self.config.xyz_decimals + 2;
+        let Some((&end, inner)) = path.split_last() else {
+            return Ok(());
+        };
+        // Points closer together than the resolution, as where slices meet,
+        // are merged so every move climbs
+        let mut points = vec![self.position.get()];
+        for &point in inner.iter().skip(1) {
+            if (point - points[points.len() - 1]).norm() >= resolution {
+                points.push(point);
             }
         }
+        if points.len() > 1 && (end - points[points.len() - 1]).norm() < resolution {
+            points.pop();
+        }
+        points.push(end);
+        let total = path_length(&points).max(f64::MIN_POSITIVE);
+        let mut climbed = 0.0;
+        for pair in points.windows(2) {
+            let length = (pair[1] - pair[0]).norm();
+            let before = climbed / total;
+            climbed += length;
+            let after = climbed / total;
+            let flow = if ramp { (before + after) / 2.0 } else { 1.0 };
+            let e = self.extruded.get() + feed(length) * flow;
+            self.extruded.set(e);
+            let words = [(b'X', pair[1].x, self.config.xyz_decimals), (b'Y', pair[1].y, self.config.xyz_decimals), (b'Z', start + (z - start) * after, z_decimals), (b'E', e, self.config.e_decimals)];
+            self.write_move(writer, "G1", &words, speed * 60.0, None)?;
+        }
+        self.z.set(z);
+        Ok(())
+    }
 
-        writeln!(writer)?;
+    /// Whether each object's paths are marked for the firmware to cancel
+    /// it: when asked for, with objects to mark and a firmware that
+    /// takes the markers
+    fn excludes_objects(&self) -> bool {
+        self.config.exclude_objects && !self.objects.is_empty() && self.flavor.supports_object_exclusion()
+    }
+
+    /// Mark the end of object `from`'s paths and the start of `to`'s,
+    /// where there are objects
+    fn write_object_change(&self, writer: &mut dyn Write, from: Option<usize>, to: Option<usize>) -> Result<()> {
+        if let Some(from) = from {
+            writeln!(writer, "EXCLUDE_OBJECT_END NAME={}", self.objects[from].name)?;
+        }
+        if let Some(to) = to {
+            writeln!(writer, "EXCLUDE_OBJECT_START NAME={}", self.objects[to].name)?;
+        }
+        Ok(())
+    }
+
+    /// Whether extrusion is written with arc moves where paths fit them:
+    /// when asked for and the firmware takes them
+    fn arc_fitting(&self) -> bool {
+        self.config.arc_fitting && self.flavor.supports_arcs()
+    }
+
+    /// Write a custom G-code snippet with its placeholders filled in, and
+    /// the layer's number and height when it is written for a layer
+    fn write_custom(&self, writer: &mut dyn Write, gcode: &str, layer: Option<(usize, f64)>) -> Result<()> {
+        if gcode.trim().is_empty() {
+            return Ok(());
+        }
+        let layer_values: Vec<(String, String)> = layer
+            .map(|(index, z)| vec![("layer_num".to_string(), index.to_string()), ("layer_z".to_string(), format!("{:.3}", z))])
+            .unwrap_or_default();
+        let rendered = template::render(gcode, |name| {
+            layer_values.iter().chain(&self.placeholders).find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+        });
+        writeln!(writer, "{}", rendered.trim_matches('\n'))?;
+        // The snippet may have moved the nozzle
+        self.last_axes.set([f64::NAN; 3]);
+        Ok(())
+    }
+
+    /// Label the paths that follow when their section changes. Bridges sag
+    /// unless cooled hard, so the fan runs at full speed for them and goes
+    /// back to the layer's speed after.
+    fn enter_section(&self, writer: &mut dyn Write, section: &mut Option<&'static str>, label: Option<&'static str>) -> Result<()> {
+        if *section == label {
+            return Ok(());
+        }
+        if *section == Some("Bridge") && self.fan.get() != u8::MAX {
+            writeln!(writer, "{}", self.flavor.fan(self.fan.get()))?;
+        }
+        if let Some(label) = label {
+            writeln!(writer, "; {}", label)?;
+            if label == "Bridge" && self.fan.get() != u8::MAX {
+                writeln!(writer, "{}", self.flavor.fan(u8::MAX))?;
+            }
+        }
+        *section = label;
+        Ok(())
+    }
+
+    /// Areas of a layer that travel may cross without retracting: inside the
+    /// external walls' centerlines, or only the infill
+    fn comb_regions(&self, islands: &[Island], infill: &[InfillRegions]) -> Vec<Island> {
+        match self.config.combing {
+            Combing::Off => Vec::new(),
+            Combing::WithinInfill => infill
+                .iter()
+                .flat_map(|regions| {
+                    let bridges = regions.bridges.iter().map(|bridge| bridge.region.clone());
+                    let areas: Vec<Island> = regions.solid.iter().chain(&regions.sparse).cloned().chain(bridges).collect();
+                    union(&areas, &[])
+                })
+                .collect(),
+            Combing::All => {
+                let inset = -self.config.nozzle_diameter / 2.0;
+                islands.iter().flat_map(|island| island.offset(inset)).collect()
+            }
+        }
+    }
+
+    /// Speed for an extrusion of `role` along `points` on a layer. Short
+    /// wall loops are slowed down so each layer has time to cool.
+    fn path_speed(&self, role: PathRole, points: &[Point2<f64>], layer_index: usize) -> f64 {
+        let wall = matches!(role, PathRole::ExternalPerimeter | PathRole::Perimeter);
+        let closed = points.len() > 2 && points.first() == points.last();
+        if wall && closed && path_length(points) < self.config.small_perimeter_length() {
+            let speed = self.role_base_speed(role).min(self.config.small_perimeter_speed());
+            return self.layer_speed(speed, layer_index);
+        }
+        self.layer_speed(self.role_base_speed(role), layer_index)
+    }
+
+    /// Factor to scale a layer's extrusion speeds by so that it takes at
+    /// least the minimum layer time, and the seconds still left over when
+    /// even the minimum print speed is too fast
+    fn layer_slowdown(&self, paths: &[ToolPath], layer_index: usize) -> (f64, f64) {
+        let min_time = self.config.min_layer_time;
+        if min_time <= 0.0 || self.layer_time(paths, layer_index, 1.0) >= min_time {
+            return (1.0, 0.0);
+        }
+        // Below this factor every extrusion is at the minimum print speed
+        let fastest = paths
+            .iter()
+            .filter_map(|path| match path {
+                ToolPath::Extrude { points, role, .. } => Some(self.path_speed(*role, points, layer_index)),
+                ToolPath::Travel { .. } => None,
+            })
+            .fold(0.0, f64::max);
+        if fastest <= 0.0 {
+            // Nothing is printed, so there is nothing to let cool
+            return (1.0, 0.0);
+        }
+        let floor = (self.config.min_print_speed / fastest).min(1.0);
+        let slowest = self.layer_time(paths, layer_index, floor);
+        if slowest <= min_time {
+            return (floor, min_time - slowest);
+        }
+        // The layer takes longer the lower the factor
+        let (mut low, mut high) = (floor, 1.0);
+        for _ in 0..50 {
+            let factor = (low + high) / 2.0;
+            if self.layer_time(paths, layer_index, factor) >= min_time {
+                low = factor;
+            } else {
+                high = factor;
+            }
+        }
+        (low, 0.0)
+    }
+
+    /// Seconds a layer's paths take with the extrusion speeds scaled by
+    /// `slowdown`, with straight travel between them
+    fn layer_time(&self, paths: &[ToolPath], layer_index: usize, slowdown: f64) -> f64 {
+        let mut position = self.position.get();
+        let mut time = 0.0;
+        for path in paths {
+            match path {
+                ToolPath::Travel { to, .. } => {
+                    time += (to - position).norm() / self.config.travel_speed;
+                    position = *to;
+                }
+                ToolPath::Extrude { points, role, .. } => {
+                    time += path_length(points) / self.slowed(self.path_speed(*role, points, layer_index), slowdown);
+                    position = points.last().copied().unwrap_or(position);
+                }
+            }
+        }
+        time
+    }
+
+    /// `speed` scaled by `slowdown`, though not below the minimum print
+    /// speed unless it already was
+    fn slowed(&self, speed: f64, slowdown: f64) -> f64 {
+        (speed * slowdown).max(self.config.min_print_speed.min(speed))
+    }
+
+    /// Acceleration for an extrusion of `role` on a layer, if one is set
+    fn role_acceleration(&self, role: PathRole, layer_index: usize) -> Option<f64> {
+        let first_layer = self.config.first_layer_acceleration().filter(|_| layer_index == 0);
+        first_layer.or_else(|| match role {
+            PathRole::ExternalPerimeter
+            | PathRole::Perimeter
+            | PathRole::GapFill
+            | PathRole::Brim
+            | PathRole::Skirt
+            | PathRole::DraftShield => self.config.perimeter_acceleration(),
+            PathRole::Infill | PathRole::SolidInfill | PathRole::Bridge | PathRole::Support | PathRole::WipeTower => {
+                self.config.infill_acceleration()
+            }
+        })
+    }
+
+    /// Give the printer `acceleration` if it is set and differs from what
+    /// it has
+    fn set_acceleration(&self, writer: &mut dyn Write, acceleration: Option<f64>) -> Result<()> {
+        if let Some(acceleration) = acceleration.filter(|&a| Some(a) != self.acceleration.get()) {
+            writeln!(writer, "{}", self.flavor.acceleration(acceleration))?;
+            self.acceleration.set(Some(acceleration));
+        }
+        Ok(())
+    }
+
+    /// Pressure advance for an extrusion of `role`, if one is set
+    fn role_pressure_advance(&self, role: PathRole) -> Option<f64> {
+        match role {
+            PathRole::Infill | PathRole::SolidInfill | PathRole::Bridge | PathRole::Support | PathRole::WipeTower => {
+                self.config.infill_pressure_advance()
+            }
+            _ => self.config.pressure_advance(),
+        }
+    }
+
+    /// Give the printer `advance` if it is set, differs from what it has
+    /// and the firmware takes it
+    fn set_pressure_advance(&self, writer: &mut dyn Write, advance: Option<f64>) -> Result<()> {
+        let Some(advance) = advance.filter(|&a| Some(a) != self.pressure_advance.get()) else {
+            return Ok(());
+        };
+        if let Some(command) = self.flavor.pressure_advance(self.tool.get(), advance) {
+            writeln!(writer, "{}", command)?;
+            self.pressure_advance.set(Some(advance));
+        }
+        Ok(())
+    }
+
+    /// Speed for an extrusion of `role`, before the layer is considered
+    fn role_base_speed(&self, role: PathRole) -> f64 {
+        match role {
+            PathRole::ExternalPerimeter | PathRole::Brim | PathRole::Skirt | PathRole::DraftShield => self.config.external_perimeter_speed(),
+            PathRole::Perimeter => self.config.perimeter_speed(),
+            PathRole::GapFill => self.config.gap_fill_speed(),
+            PathRole::Infill | PathRole::WipeTower => self.config.infill_speed(),
+            PathRole::SolidInfill => self.config.solid_infill_speed(),
+            PathRole::Support => self.config.support_speed(),
+            PathRole::Bridge => self.config.bridge_speed(),
+        }
+    }
+
+    /// `speed`, unless a first layer speed overrides it on layer 0
+    fn layer_speed(&self, speed: f64, layer_index: usize) -> f64 {
+        if layer_index == 0 { self.config.first_layer_speed.unwrap_or(speed) } else { speed }
+    }
+
+    /// Travel to `to`, combed through the layer when allowed and otherwise
+    /// straight, with the filament retracted unless the move is too short
+    /// or stays within its island
+    fn write_travel(&self, writer: &mut dyn Write, to: Point2<f64>) -> Result<()> {
+        let from = self.position.get();
+        let combed = match self.config.combing {
+            Combing::Off => None,
+            Combing::WithinInfill | Combing::All => comb_path(from, to, &self.comb_regions.borrow())
+                .filter(|path| self.config.comb_max_distance.is_none_or(|max| path_length(path) <= max)),
+        };
+
+        let feedrate = self.config.travel_speed * 60.0;
+        // A tool change leaves the new extruder retracted for the travel to
+        // undo
+        let tool_changed = self.tool_retracted.replace(false);
+        let retract = !tool_changed && combed.is_none() && self.needs_retraction(from, to);
+        let (lift, z) = (self.config.retraction_z_lift, self.z.get());
+        let firmware = self.firmware_retraction();
+        if retract && firmware {
+            writeln!(writer, "G10 ; Retract")?;
+        } else if retract {
+            writeln!(writer, "G92 E0")?;
+            let wiped = if self.config.wipe { self.write_wipe(writer)? } else { 0.0 };
+            if wiped < self.config.retraction_distance {
+                self.write_move(writer, "G1", &[(b'E', -self.config.retraction_distance, self.config.e_decimals)], self.config.retraction_speed * 60.0, Some("Retract"))?;
+            }
+            self.extruded.set(-self.config.retraction_distance);
+        }
+        let lift_feedrate = self.config.travel_speed_z() * 60.0;
+        if retract && lift > 0.0 {
+            self.write_move(writer, "G1", &[(b'Z', z + lift, self.config.xyz_decimals)], lift_feedrate, Some("Lift"))?;
+        }
+        // A wipe leaves the nozzle back along the path
+        let path = combed.unwrap_or_else(|| vec![self.position.get(), to]);
+        for point in path.iter().skip(1) {
+            self.write_move(writer, "G1", &[(b'X', point.x, self.config.xyz_decimals), (b'Y', point.y, self.config.xyz_decimals)], feedrate, None)?;
+        }
+        if retract && lift > 0.0 {
+            self.write_move(writer, "G1", &[(b'Z', z, self.config.xyz_decimals)], lift_feedrate, Some("Drop"))?;
+        }
+        if retract || tool_changed {
+            if firmware {
+                writeln!(writer, "G11 ; Unretract")?;
+            } else {
+                let e = self.extruded.get() + self.config.retraction_distance + self.config.retraction_extra_restart;
+                self.write_move(writer, "G1", &[(b'E', e, self.config.e_decimals)], self.config.retraction_speed * 60.0, Some("Unretract"))?;
+                self.extruded.set(e);
+            }
+        }
+
+        self.travel.set(self.travel.get() + path_length(&path));
+        self.position.set(to);
+        Ok(())
+    }
+
+    /// Park the nozzle and pause, ask for a filament change or both when
+    /// layer `layer_index` is listed for them, then prime it again. This
+    /// comes before the layer moves to its height, so never inside a path.
+    fn write_pause(&self, writer: &mut dyn Write, layer_index: usize) -> Result<()> {
+        let color_change = self.config.color_change_layers.contains(&layer_index);
+        let pause = self.config.pause_layers.contains(&layer_index);
+        if !color_change && !pause {
+            return Ok(());
+        }
+        let (travel, retraction) = (self.config.travel_speed * 60.0, self.config.retraction_speed * 60.0);
+        let firmware = self.firmware_retraction();
+        writeln!(writer, "; {}", if color_change { "Filament change" } else { "Pause" })?;
+        if firmware {
+            writeln!(writer, "G10 ; Retract")?;
+        } else {
+            writeln!(writer, "G92 E0")?;
+            self.write_move(writer, "G1", &[(b'E', -self.config.retraction_distance, self.config.e_decimals)], retraction, Some("Retract"))?;
+        }
+        let lift = self.config.travel_speed_z() * 60.0;
+        self.write_move(writer, "G1", &[(b'Z', self.z.get() + self.config.pause_park_lift, self.config.xyz_decimals)], lift, Some("Lift"))?;
+        // The park position is the machine's, whichever extruder prints
+        let [x, y] = self.config.pause_park_position;
+        let (x_text, y_text) = (self.number(x, self.config.xyz_decimals), self.number(y, self.config.xyz_decimals));
+        writeln!(writer, "G1 X{} Y{}{} ; Park", x_text, y_text, self.feedrate(self.limited(Axis::Xy, travel)))?;
+        self.last_axes.set([f64::NAN; 3]);
+        if color_change {
+            writeln!(writer, "{} ; Change filament", self.flavor.color_change())?;
+        }
+        if pause {
+            writeln!(writer, "{} ; Pause", self.flavor.pause())?;
+        }
+
+        // Whatever oozed out while waiting, or the new filament, is primed
+        // on top of undoing the retraction
+        let prime = if firmware {
+            writeln!(writer, "G11 ; Unretract")?;
+            self.config.pause_prime_length
+        } else {
+            self.config.retraction_distance + self.config.pause_prime_length
+        };
+        writeln!(writer, "G92 E0")?;
+        // The firmware may have moved at its own feedrate while paused
+        self.last_feedrate.set(f64::NAN);
+        self.write_move(writer, "G1", &[(b'E', prime, self.config.e_decimals)], retraction, Some("Prime"))?;
+        writeln!(writer, "G92 E0")?;
+        self.extruded.set(0.0);
+        self.position.set(Point2::new(x, y));
+        Ok(())
+    }
+
+    /// Switch to extruder `tool` for layer `layer_index` with its top at
+    /// `z`, if it isn't printing already. The one printing is retracted
+    /// and left at its standby temperature; `tool` is heated and waited
+    /// for, and picks up at the E it was left at, each keeping its own. It
+    /// is primed once it has travelled to what it prints.
+    fn write_tool_change(&self, writer: &mut dyn Write, tool: usize, layer_index: usize, z: f64) -> Result<()> {
+        let previous = self.tool.get();
+        if previous == tool {
+            return Ok(());
+        }
+        // Unless the config was validated, the tools may not all be there
+        let count = self.tool_extruded.borrow().len();
+        if let Some(index) = [previous, tool].into_iter().find(|&index| index >= count) {
+            return Err(SlicerError::ConfigError(format!("Extruder {} is used, but there are only {}", index, count)));
+        }
+        self.tool.set(tool);
+        let (distance, retraction) = (self.config.retraction_distance, self.config.retraction_speed * 60.0);
+        let firmware = self.firmware_retraction();
+        writeln!(writer, "; Tool change")?;
+        if firmware {
+            writeln!(writer, "G10 ; Retract")?;
+        } else {
+            let e = self.extruded.get() - distance;
+            self.write_move(writer, "G1", &[(b'E', e, self.config.e_decimals)], retraction, Some("Retract"))?;
+            self.tool_extruded.borrow_mut()[previous] = e;
+        }
+        if let Some(standby) = self.config.extruders.get(previous).and_then(|extruder| extruder.standby_temperature) {
+            writeln!(writer, "{} ; Standby temperature", self.flavor.set_temperature(Heater::Tool(previous), standby))?;
+        }
+        let temperature = self.config.extruder_temperature(tool, layer_index, z);
+        writeln!(writer, "{} ; Set nozzle temperature", self.flavor.set_temperature(Heater::Tool(tool), temperature))?;
+        writeln!(writer, "T{}", tool)?;
+        writeln!(writer, "{} ; Wait for nozzle temperature", self.flavor.wait_for_temperature(Heater::Tool(tool), temperature))?;
+        self.nozzle_temperature.set(temperature);
+        self.tool_offset.set(self.extruder_offset(tool));
+        // The new extruder is given its pressure advance with what it prints
+        self.pressure_advance.set(None);
+        // The firmware may have moved at its own feedrate to change tools,
+        // and moved the nozzle
+        self.last_feedrate.set(f64::NAN);
+        self.last_axes.set([f64::NAN; 3]);
+        if !firmware {
+            let e = self.tool_extruded.borrow()[tool];
+            writeln!(writer, "G92 E{}", self.number(e, self.config.e_decimals))?;
+            self.extruded.set(e);
+        }
+        self.tool_retracted.set(true);
         Ok(())
     }
 
-    fn write_footer(&self, writer: &mut BufWriter<File>) -> Result<()> {
+    /// Extruder printing an extrusion of `role`
+    fn role_extruder(&self, role: PathRole) -> usize {
+        match role {
+            PathRole::Infill | PathRole::SolidInfill | PathRole::Bridge => self.config.infill_extruder,
+            PathRole::Support => self.config.support_extruder,
+            _ => self.config.perimeter_extruder,
+        }
+    }
+
+    /// What the coordinates are shifted by for extruder `tool`: its offset,
+    /// unless the firmware applies it
+    fn extruder_offset(&self, tool: usize) -> Vector2<f64> {
+        match self.config.extruders.get(tool) {
+            Some(extruder) if !self.config.firmware_tool_offsets => Vector2::from(extruder.offset),
+            _ => Vector2::zeros(),
+        }
+    }
+
+    /// Whether the firmware retracts on G10/G11, in which case the G-code
+    /// leaves out the retraction distance, extra restart and wipe
+    fn firmware_retraction(&self) -> bool {
+        self.config.firmware_retraction.unwrap_or_else(|| self.flavor.firmware_retraction())
+    }
+
+    /// `value` with `decimals` digits after the point, as in a move
+    fn number(&self, value: f64, decimals: usize) -> String {
+        let mut text = Vec::new();
+        number::push_fixed(&mut text, value, decimals);
+        if self.config.compact_gcode {
+            number::trim_zeros(&mut text, 0);
+        }
+        String::from_utf8_lossy(&text).into_owned()
+    }
+
+    /// ` F<feedrate>` for a move, or nothing when the printer already moves
+    /// at that feedrate
+    fn feedrate(&self, feedrate: f64) -> String {
+        if self.last_feedrate.replace(feedrate) == feedrate { String::new() } else { format!(" F{}", feedrate) }
+    }
+
+    /// `feedrate` in mm/min, or the machine's max feedrate along `axis` if
+    /// that is slower, counting the moves slowed to it
+    fn limited(&self, axis: Axis, feedrate: f64) -> f64 {
+        let max = match axis {
+            Axis::Xy => self.config.max_feedrate,
+            Axis::Z => self.config.max_feedrate_z,
+            Axis::E => self.config.max_feedrate_e,
+        };
+        match max.map(|max| max * 60.0).filter(|&max| feedrate > max) {
+            Some(max) => {
+                self.clamped_moves.set(self.clamped_moves.get() + 1);
+                max
+            }
+            None => feedrate,
+        }
+    }
+
+    /// Write a move: `command` with each word's letter and value to so many
+    /// decimals, then the feedrate if it changed and `comment`. Moves make
+    /// up most of the G-code, so they are put together in a reused buffer
+    /// rather than formatted.
+    fn write_move(&self, writer: &mut dyn Write, command: &str, words: &[(u8, f64, usize)], feedrate: f64, comment: Option<&str>) -> Result<()> {
+        // Extrusions are held to the X and Y limit; their E is always far
+        // slower
+        let axis = if words.iter().any(|&(letter, ..)| letter == b'X' || letter == b'Y') {
+            Axis::Xy
+        } else if words.iter().any(|&(letter, ..)| letter == b'Z') {
+            Axis::Z
+        } else {
+            Axis::E
+        };
+        let feedrate = self.limited(axis, feedrate);
+        let mut line = self.line.borrow_mut();
+        line.clear();
+        line.extend_from_slice(command.as_bytes());
+        let offset = self.tool_offset.get();
+        let compact = self.config.compact_gcode;
+        let mut last_axes = self.last_axes.get();
+        for &(letter, value, decimals) in words {
+            let value = match letter {
+                b'X' => value - offset.x,
+                b'Y' => value - offset.y,
+                _ => value,
+            };
+            if let Some(axis) = [b'X', b'Y', b'Z'].iter().position(|&axis| axis == letter) {
+                let written = (value * 10f64.powi(decimals as i32)).round();
+                let unchanged = last_axes[axis] == written;
+                last_axes[axis] = written;
+                // Arcs keep their end point whole
+                if compact && unchanged && command == "G1" {
+                    continue;
+                }
+            }
+            // Compact G-code runs the words together, as firmware reads them
+            // just as well
+            if !compact {
+                line.push(b' ');
+            }
+            line.push(letter);
+            let start = line.len();
+            number::push_fixed(&mut line, value, decimals);
+            if compact {
+                number::trim_zeros(&mut line, start);
+            }
+        }
+        self.last_axes.set(last_axes);
+        if self.last_feedrate.replace(feedrate) != feedrate {
+            write!(line, "{}F{}", if compact { "" } else { " " }, feedrate)?;
+        }
+        // Nothing left to move
+        if line.len() == command.len() {
+            return Ok(());
+        }
+        if let Some(comment) = comment {
+            line.extend_from_slice(b" ; ");
+            line.extend_from_slice(comment.as_bytes());
+        }
+        line.push(b'\n');
+        writer.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Move back along the last extrusion for up to the wipe distance while
+    /// retracting, in step with it. Returns how far the filament was
+    /// retracted, short of the retraction distance where the path is shorter.
+    fn write_wipe(&self, writer: &mut dyn Write) -> Result<f64> {
+        let (distance, retraction) = (self.config.wipe_distance, self.config.retraction_distance);
+        if distance <= 0.0 {
+            return Ok(0.0);
+        }
+        let feedrate = self.config.travel_speed * 60.0;
+        let path = self.last_extrusion.borrow();
+        let mut wiped = 0.0;
+        for pair in path.windows(2).rev() {
+            let (start, end) = (pair[1], pair[0]);
+            let length = (end - start).norm();
+            let step = length.min(distance - wiped);
+            let point = if step < length { start + (end - start) * (step / length) } else { end };
+            wiped += step;
+            let words = [(b'X', point.x, self.config.xyz_decimals), (b'Y', point.y, self.config.xyz_decimals), (b'E', -retraction * wiped / distance, self.config.e_decimals)];
+            self.write_move(writer, "G1", &words, feedrate, Some("Wipe"))?;
+            self.position.set(point);
+            if wiped >= distance {
+                break;
+            }
+        }
+        Ok(retraction * wiped / distance)
+    }
+
+    /// Whether straight travel from `from` to `to` retracts: when it is at
+    /// least the minimum length and, if only crossing perimeters retracts,
+    /// doesn't lie wholly within one island
+    fn needs_retraction(&self, from: Point2<f64>, to: Point2<f64>) -> bool {
+        let travel = LineSegment2D::new(from, to);
+        if self.config.retraction_distance <= 0.0 || travel.length() < self.config.retraction_minimum_travel {
+            return false;
+        }
+        if !self.config.retraction_only_when_crossing_perimeters {
+            return true;
+        }
+        !self.islands.borrow().iter().any(|island| {
+            let inside = island.clip_segment(&travel).iter().fold(0.0, |sum, piece| sum + piece.length());
+            inside >= travel.length() - 1e-6
+        })
+    }
+
+    fn write_footer(&self, writer: &mut dyn Write) -> Result<()> {
         writeln!(writer, "; End sequence")?;
-        writeln!(writer, "G92 E0 ; Reset extruder")?;
-        writeln!(writer, "G1 E-{} F{} ; Retract filament", 
-            self.config.retraction_distance, 
-            self.config.retraction_speed * 60.0)?;
-        writeln!(writer, "G28 X0 Y0 ; Home X and Y axes")?;
-        writeln!(writer, "M104 S0 ; Turn off nozzle heater")?;
-        writeln!(writer, "M140 S0 ; Turn off bed heater")?;
-        writeln!(writer, "M84 ; Disable motors")?;
+        if self.firmware_retraction() {
+            writeln!(writer, "G10 ; Retract filament")?;
+        } else {
+            writeln!(writer, "G92 E0 ; Reset extruder")?;
+            writeln!(writer, "G1 E-{}{} ; Retract filament",
+                self.config.retraction_distance,
+                self.feedrate(self.limited(Axis::E, self.config.retraction_speed * 60.0)))?;
+        }
+        // The end sequence turns off the printing extruder's heater
+        if self.config.extruders.len() > 1 {
+            for tool in (0..self.config.extruders.len()).filter(|&tool| tool != self.tool.get()) {
+                writeln!(writer, "{} ; Turn off nozzle heater", self.flavor.set_temperature(Heater::Tool(tool), 0))?;
+            }
+        }
+        for line in self.flavor.end_sequence() {
+            writeln!(writer, "{}", line)?;
+        }
         writeln!(writer)?;
         writeln!(writer, "; Print complete")?;
+        writeln!(writer)?;
+        writeln!(writer, "; prusaslicer_config = begin")?;
+        for (name, value) in metadata::settings(&self.config, self.metadata.profile.as_deref()) {
+            writeln!(writer, "; {} = {}", name, value)?;
+        }
+        writeln!(writer, "; prusaslicer_config = end")?;
 
         Ok(())
     }
 }
+
+/// What the wipe tower is sized and placed by, gathered a layer at a time
+struct PurgeSurvey {
+    /// Extruder printing at the end of the layers so far
+    tool: usize,
+    /// Most purges on any one layer
+    bands: usize,
+    /// Last layer with a purge
+    top_layer: Option<usize>,
+    /// Area the print covers
+    occupied: Option<BoundingBox2D>,
+}
+
+/// Axes a move runs along, for the machine's max feedrate on them
+#[derive(Debug, Clone, Copy)]
+enum Axis {
+    /// X and Y, with or without the others
+    Xy,
+    /// Z alone
+    Z,
+    /// The filament alone
+    E,
+}
+
+/// Length in mm of filament `filament_diameter` thick that fills a line
+/// `length` mm long, `width` wide and `height` high, scaled by `flow`
+pub fn extrusion_length(length: f64, width: f64, height: f64, flow: f64, filament_diameter: f64) -> f64 {
+    let filament_area = std::f64::consts::PI * (filament_diameter / 2.0).powi(2);
+    length * width * height * flow / filament_area
+}
+
+/// PWM value from 0 to 255 for a fan speed in percent, rounded to the
+/// nearest step, so 50% is S128
+pub fn fan_pwm(percent: f64) -> u8 {
+    (percent.clamp(0.0, 100.0) * 255.0 / 100.0).round() as u8
+}
+
+/// Length of a polyline
+fn path_length(points: &[Point2<f64>]) -> f64 {
+    points.windows(2).fold(0.0, |sum, pair| sum + (pair[1] - pair[0]).norm())
+}
+
+/// A polyline cut `length` along it into the part before and the part
+/// after, both holding the point where it is cut
+fn split_at_length(points: &[Point2<f64>], length: f64) -> (Vec<Point2<f64>>, Vec<Point2<f64>>) {
+    let mut travelled = 0.0;
+    for (i, pair) in points.windows(2).enumerate() {
+        let segment = (pair[1] - pair[0]).norm();
+        if travelled + segment >= length {
+            // Cut on a point rather than next to it
+            if travelled + segment - length < 1e-9 {
+                return (points[..=i + 1].to_vec(), points[i + 1..].to_vec());
+            }
+            let cut = pair[0] + (pair[1] - pair[0]) * ((length - travelled) / segment);
+            let mut before = points[..=i].to_vec();
+            before.push(cut);
+            let mut after = vec![cut];
+            after.extend_from_slice(&points[i + 1..]);
+            return (before, after);
+        }
+        travelled += segment;
+    }
+    (points.to_vec(), points.last().into_iter().copied().collect())
+}
+
+/// Comment heading the paths of `role`, for those printed as a section of
+/// their own
+fn section_label(role: PathRole) -> Option<&'static str> {
+    match role {
+        PathRole::Brim => Some("Brim"),
+        PathRole::Skirt => Some("Skirt"),
+        PathRole::DraftShield => Some("Draft shield"),
+        PathRole::Infill | PathRole::SolidInfill => Some("Infill"),
+        PathRole::Support => Some("Support"),
+        PathRole::Bridge => Some("Bridge"),
+        PathRole::WipeTower => Some("Wipe tower"),
+        PathRole::ExternalPerimeter | PathRole::Perimeter | PathRole::GapFill => None,
+    }
+}
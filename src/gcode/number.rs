@@ -0,0 +1,45 @@
+use std::io::Write;
+
+/// Append `value` with `decimals` digits after the point, as `{:.N}` would
+/// but without going through the formatting machinery, for the numbers of
+/// every move. Values rounding to zero are written without a sign.
+pub(super) fn push_fixed(out: &mut Vec<u8>, value: f64, decimals: usize) {
+    let scaled = (value * 10f64.powi(decimals as i32)).round();
+    // Not a number, or beyond what the digits below hold
+    if scaled.is_nan() || scaled.abs() >= 1e18 {
+        let _ = write!(out, "{:.*}", decimals, value);
+        return;
+    }
+    if scaled < 0.0 {
+        out.push(b'-');
+    }
+    let mut rest = scaled.abs() as u64;
+    let mut digits = [0u8; 20];
+    let mut len = 0;
+    // At least one digit before the point
+    while len <= decimals || rest > 0 {
+        digits[len] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+        len += 1;
+    }
+    for (i, digit) in digits[..len].iter().rev().enumerate() {
+        if decimals > 0 && i == len - decimals {
+            out.push(b'.');
+        }
+        out.push(*digit);
+    }
+}
+
+/// Drop the trailing zeros of the number written from `start` on, and its
+/// point if no digits are left after it
+pub(super) fn trim_zeros(out: &mut Vec<u8>, start: usize) {
+    if !out[start..].contains(&b'.') {
+        return;
+    }
+    while out.last() == Some(&b'0') {
+        out.pop();
+    }
+    if out.last() == Some(&b'.') {
+        out.pop();
+    }
+}
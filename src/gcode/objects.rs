@@ -0,0 +1,105 @@
+use crate::geometry::{Mesh, Polygon};
+use crate::toolpath::{PathRole, ToolPath};
+use nalgebra::Point2;
+
+/// A model on the plate, which the firmware can cancel on its own by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintObject {
+    /// Name the firmware knows the object by, without spaces
+    pub name: String,
+    /// Convex hull of the object seen from above
+    pub outline: Polygon,
+}
+
+impl PrintObject {
+    /// The object `mesh` makes, under `name` with anything but letters,
+    /// digits, `-`, `_` and `.` turned into underscores
+    pub fn from_mesh(name: &str, mesh: &Mesh) -> Self {
+        let name = name.chars().map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect();
+        PrintObject { name, outline: mesh.footprint_hull() }
+    }
+
+    /// An object for each shell of each mesh, by its name. The shells of a
+    /// mesh that has several are numbered after it, as are copies of a
+    /// name already taken.
+    pub fn from_meshes<'a>(meshes: impl IntoIterator<Item = (&'a str, &'a Mesh)>) -> Vec<Self> {
+        let mut objects: Vec<PrintObject> = Vec::new();
+        for (name, mesh) in meshes {
+            let shells = mesh.shells();
+            let named: Vec<(String, &Mesh)> = if shells.len() == 1 {
+                vec![(name.to_string(), mesh)]
+            } else {
+                shells.iter().enumerate().map(|(i, shell)| (format!("{}_{}", name, i + 1), shell)).collect()
+            };
+            for (name, mesh) in named {
+                let mut object = PrintObject::from_mesh(&name, mesh);
+                let base = object.name.clone();
+                let mut copy = 1;
+                while objects.iter().any(|other| other.name == object.name) {
+                    copy += 1;
+                    object.name = format!("{}_{}", base, copy);
+                }
+                objects.push(object);
+            }
+        }
+        objects
+    }
+
+    /// Middle of the object's outline
+    pub fn center(&self) -> Point2<f64> {
+        self.outline.bounds().map_or(Point2::origin(), |bounds| nalgebra::center(&bounds.min, &bounds.max))
+    }
+}
+
+/// `paths` with each object's brought together, in the order the objects
+/// come up, and the object each path belongs to. A travel goes with the
+/// extrusion it leads to; the skirt, brim and draft shield, around all of
+/// the objects, belong to none.
+pub(super) fn group_by_object(paths: &[ToolPath], objects: &[PrintObject]) -> (Vec<ToolPath>, Vec<Option<usize>>) {
+    // Runs of paths from one travel to the next, each with its object
+    let mut runs: Vec<(Option<usize>, &[ToolPath])> = Vec::new();
+    let mut start = 0;
+    for end in 1..=paths.len() {
+        if end < paths.len() && !matches!(paths[end], ToolPath::Travel { .. }) {
+            continue;
+        }
+        let run = &paths[start..end];
+        let first = run.iter().find_map(|path| match path {
+            ToolPath::Extrude { points, role, .. } => Some((points.first().copied(), *role)),
+            ToolPath::Travel { .. } => None,
+        });
+        let object = match first {
+            Some((_, PathRole::Skirt | PathRole::Brim | PathRole::DraftShield)) => None,
+            Some((Some(point), _)) => object_at(objects, point),
+            // Travel at the end of the layer stays where it is
+            _ => runs.last().and_then(|(object, _)| *object),
+        };
+        runs.push((object, run));
+        start = end;
+    }
+
+    let mut order: Vec<Option<usize>> = Vec::new();
+    for (object, _) in &runs {
+        if !order.contains(object) {
+            order.push(*object);
+        }
+    }
+    let mut grouped = Vec::with_capacity(paths.len());
+    let mut owners = Vec::with_capacity(paths.len());
+    for object in order {
+        for (_, run) in runs.iter().filter(|(owner, _)| *owner == object) {
+            grouped.extend_from_slice(run);
+            owners.extend(std::iter::repeat_n(object, run.len()));
+        }
+    }
+    (grouped, owners)
+}
+
+/// The object whose outline `point` lies in, or else the nearest
+fn object_at(objects: &[PrintObject], point: Point2<f64>) -> Option<usize> {
+    objects.iter().position(|object| object.outline.contains_point(&point)).or_else(|| {
+        (0..objects.len()).min_by(|&a, &b| {
+            objects[a].outline.distance_to_point(&point).total_cmp(&objects[b].outline.distance_to_point(&point))
+        })
+    })
+}
@@ -0,0 +1,56 @@
+use super::{metadata, template};
+use crate::config::SlicerConfig;
+use crate::error::{Result, SlicerError};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Run the G-code file at `path` through `command`, as PrusaSlicer runs
+/// its post-processing scripts: the path goes after the command's own
+/// arguments and the settings in `config` go in `SLIC3R_<NAME>` variables.
+/// The file is also given on stdin, and what the command prints replaces
+/// it; a command that prints nothing is taken to have edited it in place.
+pub fn post_process(path: impl AsRef<Path>, command: &str, config: &SlicerConfig) -> Result<()> {
+    let path = path.as_ref();
+    let failed = |reason: String| SlicerError::GCodeError(format!("Post-processing with `{}` failed: {}", command, reason));
+    let input = File::open(path).map_err(|e| failed(e.to_string()))?;
+    let output = shell(command, path)
+        .envs(environment(config, path))
+        .stdin(Stdio::from(input))
+        .output()
+        .map_err(|e| failed(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(failed(format!("{}{}", output.status, if stderr.trim().is_empty() { String::new() } else { format!(": {}", stderr.trim()) })));
+    }
+    if !output.stdout.is_empty() {
+        fs::write(path, &output.stdout).map_err(|e| failed(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// `command` run by the system's shell with `path` added to its arguments
+fn shell(command: &str, path: &Path) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(format!("{} \"{}\"", command, path.display()));
+        shell
+    } else {
+        // The path goes in as "$1" so the shell doesn't split or expand it
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(format!("{} \"$1\"", command)).arg("sh").arg(path);
+        shell
+    }
+}
+
+/// Every setting under its own name and, where it has one, the name
+/// PrusaSlicer gives it, and the output path as PrusaSlicer passes it
+fn environment(config: &SlicerConfig, path: &Path) -> Vec<(String, String)> {
+    let named = template::config_values(config).into_iter();
+    let prusa = metadata::settings(config, None).into_iter().map(|(name, value)| (name.to_string(), value));
+    named
+        .chain(prusa)
+        .map(|(name, value)| (format!("SLIC3R_{}", name.to_uppercase()), value))
+        .chain([("SLIC3R_PP_OUTPUT_NAME".to_string(), path.display().to_string())])
+        .collect()
+}
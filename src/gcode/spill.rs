@@ -0,0 +1,102 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Bytes kept in memory before they go to a temporary file
+const MEMORY_LIMIT: usize = 4 * 1024 * 1024;
+
+/// WebAssembly has no temporary directory to spill to, so everything stays
+/// in memory there
+const SPILLS: bool = cfg!(not(target_arch = "wasm32"));
+
+/// Number for the next temporary file, so generators writing at once in
+/// one process don't share one
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// G-code held to be read back once it is complete: in memory while it is
+/// small, and in a temporary file once it outgrows `MEMORY_LIMIT`, so a long
+/// print's G-code doesn't have to fit in memory. On WebAssembly it is all
+/// kept in memory.
+pub(super) struct SpillBuffer {
+    memory: Vec<u8>,
+    file: Option<BufWriter<TempFile>>,
+}
+
+impl SpillBuffer {
+    pub fn new() -> Self {
+        SpillBuffer { memory: Vec::new(), file: None }
+    }
+
+    /// Everything written, from the start
+    pub fn into_reader(self) -> io::Result<BufReader<Box<dyn Read>>> {
+        let reader: Box<dyn Read> = match self.file {
+            Some(file) => {
+                let mut file = file.into_inner().map_err(|e| e.into_error())?;
+                file.file.seek(SeekFrom::Start(0))?;
+                Box::new(file)
+            }
+            None => Box::new(Cursor::new(self.memory)),
+        };
+        Ok(BufReader::new(reader))
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if SPILLS && self.file.is_none() && self.memory.len() + buf.len() > MEMORY_LIMIT {
+            let mut file = BufWriter::new(TempFile::create()?);
+            file.write_all(&self.memory)?;
+            self.memory = Vec::new();
+            self.file = Some(file);
+        }
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => self.memory.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A file in the system's temporary directory, removed when dropped
+struct TempFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create() -> io::Result<Self> {
+        let name = format!("rustslicer-{}-{}.gcode", std::process::id(), NEXT_FILE.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        Ok(TempFile { file, path })
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::config::SlicerConfig;
+use crate::geometry::{BoundingBox, BoundingBox2D};
+use crate::toolpath::PathRole;
+use nalgebra::Point3;
+use std::cell::Cell;
+use std::io::{self, Write};
+
+/// Figures for a G-code file, gathered as it is written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GCodeStats {
+    /// Size of the G-code in bytes
+    pub bytes: u64,
+    /// Number of G0, G1, G2 and G3 moves
+    pub moves: usize,
+    /// Estimated print time in seconds, with every move speeding up and
+    /// slowing down at the printer's acceleration
+    pub print_time: f64,
+    /// Length in mm of filament fed while moving
+    pub filament_length: f64,
+    /// Volume in cm³ of the filament fed
+    pub filament_volume: f64,
+    /// Weight in grams of the filament fed
+    pub filament_weight: f64,
+    /// Cost of the filament fed, when the filament's price is known
+    pub filament_cost: Option<f64>,
+    /// Layers slowed down for the minimum layer time, with the factor their
+    /// speeds were scaled by
+    pub slowed_layers: Vec<(usize, f64)>,
+    /// Estimated seconds each layer takes, in the order they are written
+    pub layer_times: Vec<f64>,
+    /// Estimated seconds spent extruding each role, in the order the roles
+    /// first come up
+    pub role_times: Vec<(PathRole, f64)>,
+    /// Estimated seconds spent outside extrusions: on travel, retraction,
+    /// waits and the start and end sequences
+    pub other_time: f64,
+    /// What was left out of the G-code, and why
+    pub warnings: Vec<String>,
+    /// Moves slowed to the machine's max feedrate, for profiles asking
+    /// for more than it does
+    pub clamped_moves: usize,
+    /// Box around everything extruded, None if nothing was
+    pub extrusion_bounds: Option<BoundingBox>,
+    /// Where the wipe tower stands on the bed, None without one
+    pub wipe_tower: Option<BoundingBox2D>,
+}
+
+impl GCodeStats {
+    /// Figures for G-code that has already been written, estimated with
+    /// the machine limits in `config`. Roles aren't known, so all time
+    /// counts as other time.
+    pub fn from_gcode(gcode: &str, config: &SlicerConfig) -> Self {
+        let role = Cell::new(None);
+        let mut writer = StatsWriter::new(io::sink(), config, &role);
+        // Writing to a sink can't fail
+        let _ = writer.write_all(gcode.as_bytes());
+        writer.finish().map(|(_, stats)| stats).unwrap_or_default()
+    }
+
+    /// Estimated seconds from the start until layer `index` of
+    /// `layer_times` begins
+    pub fn time_before_layer(&self, index: usize) -> f64 {
+        let after = self.layer_times.iter().skip(index).fold(0.0, |sum, time| sum + time);
+        // Whatever follows the last layer is counted in it
+        (self.print_time - after).max(0.0)
+    }
+}
+
+/// A move read but not yet timed, waiting for the one after it to know how
+/// fast it can leave its end
+struct PendingMove {
+    length: f64,
+    /// Speed in mm/s the move runs at, at most
+    speed: f64,
+    /// Speed in mm/s the move starts at
+    entry: f64,
+    /// Unit direction of travel in XYZ, or None for filament alone
+    direction: Option<[f64; 3]>,
+    acceleration: Option<f64>,
+    role: Option<PathRole>,
+    layer: Option<usize>,
+}
+
+/// Passes G-code through to `inner`, reading each line as it goes by to
+/// keep count of what it does
+pub(super) struct StatsWriter<'a, W: Write> {
+    inner: W,
+    /// Bytes of the line being written so far
+    line: Vec<u8>,
+    stats: GCodeStats,
+    /// X, Y, Z and E of the nozzle after the last move
+    position: [f64; 4],
+    /// Feedrate in mm/min, which carries over from move to move
+    feedrate: f64,
+    /// Acceleration in mm/s² the printer was last given
+    acceleration: Option<f64>,
+    /// Extruder last selected, whose filament is being fed
+    tool: usize,
+    /// Machine limits and filament the estimates are made for
+    config: &'a SlicerConfig,
+    /// What is being printed as the lines are written
+    role: &'a Cell<Option<PathRole>>,
+    /// Index into the layer times of the layer being written
+    layer: Option<usize>,
+    pending: Option<PendingMove>,
+}
+
+impl<'a, W: Write> StatsWriter<'a, W> {
+    /// A writer into `inner`, estimating time with the machine limits in
+    /// `config` and counting it against whichever role `role` holds
+    pub fn new(inner: W, config: &'a SlicerConfig, role: &'a Cell<Option<PathRole>>) -> Self {
+        StatsWriter {
+            inner,
+            line: Vec::new(),
+            stats: GCodeStats::default(),
+            position: [0.0; 4],
+            feedrate: 0.0,
+            acceleration: None,
+            tool: 0,
+            config,
+            role,
+            layer: None,
+            pending: None,
+        }
+    }
+
+    /// `inner` and what was written to it, flushing it first
+    pub fn finish(mut self) -> io::Result<(W, GCodeStats)> {
+        self.inner.flush()?;
+        let line = std::mem::take(&mut self.line);
+        self.read_line(&String::from_utf8_lossy(&line));
+        self.settle(0.0);
+        self.stats.filament_weight = self.stats.filament_volume * self.config.filament_density;
+        self.stats.filament_cost = self.config.filament_cost_per_kg.map(|cost| cost * self.stats.filament_weight / 1000.0);
+        Ok((self.inner, self.stats))
+    }
+
+    fn read_line(&mut self, line: &str) {
+        let (code, comment) = line.split_once(';').unwrap_or((line, ""));
+        if is_layer_start(comment) {
+            self.layer = Some(self.stats.layer_times.len());
+            self.stats.layer_times.push(0.0);
+        }
+        let mut words = words(code);
+        let command = words.next();
+        let value = |word: &str| word[1..].parse::<f64>().ok();
+        match command {
+            Some(command @ ("G0" | "G1" | "G2" | "G3")) => {
+                self.stats.moves += 1;
+                let mut target = self.position;
+                // Arc center relative to the start
+                let mut center = [0.0; 2];
+                for word in words {
+                    let axis = match word.as_bytes()[0] {
+                        b'X' => 0,
+                        b'Y' => 1,
+                        b'Z' => 2,
+                        b'E' => 3,
+                        b'I' | b'J' => {
+                            center[(word.as_bytes()[0] - b'I') as usize] = value(word).unwrap_or_default();
+                            continue;
+                        }
+                        b'F' => {
+                            self.feedrate = value(word).unwrap_or(self.feedrate);
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    target[axis] = value(word).unwrap_or(target[axis]);
+                }
+                let [dx, dy, dz, de] = [0, 1, 2, 3].map(|axis| target[axis] - self.position[axis]);
+                let flat = match command {
+                    "G2" | "G3" => arc_length(center, [dx, dy], command == "G2"),
+                    _ => (dx * dx + dy * dy).sqrt(),
+                };
+                let travel = (flat * flat + dz * dz).sqrt();
+                if travel > 0.0 && de > 0.0 {
+                    self.stats.filament_length += de;
+                    // Each extruder's filament may be a different thickness
+                    let filament_area = std::f64::consts::PI * (self.config.extruder_filament_diameter(self.tool) / 2.0).powi(2);
+                    self.stats.filament_volume += de * filament_area / 1000.0;
+                    let mut reached = vec![self.position, target];
+                    // An arc bulges out to each axis it sweeps across
+                    if matches!(command, "G2" | "G3") {
+                        let [x, y, z, e] = self.position;
+                        reached.extend(arc_extremes(center, [dx, dy], command == "G2").into_iter().map(|[ax, ay]| [x + ax, y + ay, z, e]));
+                    }
+                    let reached: Vec<Point3<f64>> = reached.into_iter().map(|[x, y, z, _]| Point3::new(x, y, z)).collect();
+                    let before = self.stats.extrusion_bounds.take().map(|bounds| [bounds.min, bounds.max]);
+                    self.stats.extrusion_bounds = Some(BoundingBox::from_vertices(before.iter().flatten().chain(&reached)));
+                }
+                // A move of the filament alone takes as long as it runs
+                let (length, direction) = if travel > 0.0 {
+                    let chord = (dx * dx + dy * dy + dz * dz).sqrt().max(f64::MIN_POSITIVE);
+                    (travel, Some([dx / chord, dy / chord, dz / chord]))
+                } else {
+                    (de.abs(), None)
+                };
+                if length > 0.0 && self.feedrate > 0.0 {
+                    self.push_move(length, direction);
+                }
+                self.position = target;
+            }
+            Some("G4") => {
+                self.settle(0.0);
+                for word in words {
+                    let wait = match word.as_bytes()[0] {
+                        b'P' => value(word).unwrap_or_default() / 1000.0,
+                        b'S' => value(word).unwrap_or_default(),
+                        _ => continue,
+                    };
+                    self.count_time(wait, None, self.layer);
+                }
+            }
+            Some("G92") => {
+                for word in words.filter(|word| word.starts_with('E')) {
+                    self.position[3] = value(word).unwrap_or(self.position[3]);
+                }
+            }
+            Some("M204") => {
+                // Marlin's S, or RepRapFirmware's P for printing moves
+                if let Some(word) = words.find(|word| word.starts_with('S') || word.starts_with('P')) {
+                    self.acceleration = value(word).or(self.acceleration);
+                }
+            }
+            Some("SET_VELOCITY_LIMIT") => {
+                if let Some(acceleration) = words.find_map(|word| word.strip_prefix("ACCEL=")) {
+                    self.acceleration = acceleration.parse().ok().or(self.acceleration);
+                }
+            }
+            Some(command) if command.starts_with('T') => {
+                self.tool = command[1..].parse().unwrap_or(self.tool);
+            }
+            _ => {}
+        }
+    }
+
+    /// Queue a move, timing the one before it now that it is known how
+    /// fast that one can hand over to this one
+    fn push_move(&mut self, length: f64, direction: Option<[f64; 3]>) {
+        let speed = self.config.max_feedrate.map_or(self.feedrate / 60.0, |max| (self.feedrate / 60.0).min(max));
+        let acceleration = match (self.acceleration, self.config.max_acceleration) {
+            (Some(acceleration), Some(max)) => Some(acceleration.min(max)),
+            (acceleration, max) => acceleration.or(max),
+        };
+        let junction = match (&self.pending, direction) {
+            (Some(pending), Some(direction)) => pending.direction.map_or(0.0, |previous| {
+                // Straight on keeps the speed; a right angle or sharper stops
+                let cos = previous.iter().zip(direction).fold(0.0, |sum, (a, b)| sum + a * b);
+                pending.speed.min(speed) * cos.max(0.0)
+            }),
+            _ => 0.0,
+        };
+        let entry = self.settle(junction);
+        self.pending = Some(PendingMove {
+            length,
+            speed,
+            entry,
+            direction,
+            acceleration,
+            role: self.role.get(),
+            layer: self.layer,
+        });
+    }
+
+    /// Time the queued move, leaving it at `exit` mm/s or as near as it can
+    /// get. Returns the speed it actually leaves at.
+    fn settle(&mut self, exit: f64) -> f64 {
+        let Some(pending) = self.pending.take() else {
+            return 0.0;
+        };
+        let PendingMove { length, speed, entry, acceleration, role, layer, .. } = pending;
+        let (time, exit) = match acceleration {
+            Some(a) if a > 0.0 => {
+                let exit = exit.min(speed).min((entry * entry + 2.0 * a * length).sqrt());
+                // Speeding up to full speed and slowing down to the exit
+                let ramps = (2.0 * speed * speed - entry * entry - exit * exit) / (2.0 * a);
+                let time = if ramps <= length {
+                    (2.0 * speed - entry - exit) / a + (length - ramps) / speed
+                } else {
+                    // Too short to reach full speed
+                    let peak = ((2.0 * a * length + entry * entry + exit * exit) / 2.0).sqrt();
+                    if peak >= entry.max(exit) {
+                        (2.0 * peak - entry - exit) / a
+                    } else {
+                        2.0 * length / (entry + exit)
+                    }
+                };
+                (time, exit)
+            }
+            _ => (length / speed, exit.min(speed)),
+        };
+        self.count_time(time, role, layer);
+        exit
+    }
+
+    fn count_time(&mut self, time: f64, role: Option<PathRole>, layer: Option<usize>) {
+        self.stats.print_time += time;
+        if let Some(layer) = layer {
+            self.stats.layer_times[layer] += time;
+        }
+        match role {
+            Some(role) => match self.stats.role_times.iter_mut().find(|(r, _)| *r == role) {
+                Some((_, total)) => *total += time,
+                None => self.stats.role_times.push((role, time)),
+            },
+            None => self.stats.other_time += time,
+        }
+    }
+}
+
+/// Whether a comment marks the start of a layer
+pub(super) fn is_layer_start(comment: &str) -> bool {
+    comment.trim().strip_prefix("Layer ").is_some_and(|index| index.parse::<usize>().is_ok())
+}
+
+/// Words of a line of G-code, letter and number, also where compact G-code
+/// runs them together: `G1X10.5E3` is `G1`, `X10.5` and `E3`. Any other
+/// word, such as Klipper's `SET_FAN_SPEED` or `SPEED=0.5`, is kept whole.
+pub(super) fn words(code: &str) -> impl Iterator<Item = &str> {
+    let numeric = |byte: &u8| byte.is_ascii_digit() || b"-+.".contains(byte);
+    code.split_whitespace().flat_map(move |word| {
+        let bytes = word.as_bytes();
+        let run_together = bytes.iter().enumerate().all(|(i, byte)| match byte.is_ascii_uppercase() {
+            true => bytes.get(i + 1).is_some_and(numeric),
+            false => i > 0 && numeric(byte),
+        });
+        let mut rest = word;
+        std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let end = match run_together {
+                true => rest[1..].find(|c: char| c.is_ascii_uppercase()).map_or(rest.len(), |i| i + 1),
+                false => rest.len(),
+            };
+            let (word, tail) = rest.split_at(end);
+            rest = tail;
+            Some(word)
+        })
+    })
+}
+
+/// Length of an arc from the origin to `end` around `center`, clockwise
+/// or not; one that ends where it starts is a full circle
+fn arc_length(center: [f64; 2], end: [f64; 2], clockwise: bool) -> f64 {
+    center[0].hypot(center[1]) * arc_sweep(center, end, clockwise).1
+}
+
+/// Points of the same arc furthest along +X, +Y, -X and -Y, of those it
+/// passes on its way
+fn arc_extremes(center: [f64; 2], end: [f64; 2], clockwise: bool) -> Vec<[f64; 2]> {
+    let radius = center[0].hypot(center[1]);
+    let (start_angle, sweep) = arc_sweep(center, end, clockwise);
+    (0..4)
+        .map(|quarter| quarter as f64 * std::f64::consts::FRAC_PI_2)
+        .filter(|&angle| {
+            let turned = if clockwise { start_angle - angle } else { angle - start_angle };
+            turned.rem_euclid(2.0 * std::f64::consts::PI) <= sweep
+        })
+        .map(|angle| [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()])
+        .collect()
+}
+
+/// Angle the same arc starts at and how far it turns, in radians
+fn arc_sweep(center: [f64; 2], end: [f64; 2], clockwise: bool) -> (f64, f64) {
+    let start_angle = (-center[1]).atan2(-center[0]);
+    let end_angle = (end[1] - center[1]).atan2(end[0] - center[0]);
+    let mut sweep = end_angle - start_angle;
+    if clockwise {
+        sweep = -sweep;
+    }
+    sweep = sweep.rem_euclid(2.0 * std::f64::consts::PI);
+    if sweep < 1e-9 {
+        sweep = 2.0 * std::f64::consts::PI;
+    }
+    (start_angle, sweep)
+}
+
+/// `seconds` as hours, minutes and seconds, leaving out leading zero units
+pub fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+impl<W: Write> Write for StatsWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.stats.bytes += written as u64;
+        for &byte in &buf[..written] {
+            if byte == b'\n' {
+                // Taken out to read it and put back for the next line
+                let mut line = std::mem::take(&mut self.line);
+                self.read_line(&String::from_utf8_lossy(&line));
+                line.clear();
+                self.line = line;
+            } else {
+                self.line.push(byte);
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,45 @@
+use crate::config::SlicerConfig;
+
+/// Values for `{name}` placeholders in custom G-code: every setting that
+/// is a single number, flag or string, by its name
+pub(super) fn config_values(config: &SlicerConfig) -> Vec<(String, String)> {
+    let Ok(toml::Value::Table(table)) = toml::Value::try_from(config) else {
+        return Vec::new();
+    };
+    table
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                _ => return None,
+            };
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// `template` with each `{name}` replaced by the value `values` gives for
+/// it. Unknown names are left as written.
+pub(super) fn render<'a>(template: &str, values: impl Fn(&str) -> Option<&'a str>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}').and_then(|close| Some((close, values(&after[..close])?))) {
+            Some((close, value)) => {
+                rendered.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
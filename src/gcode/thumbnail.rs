@@ -0,0 +1,128 @@
+use crate::error::{Result, SlicerError};
+use crate::geometry::Island;
+use base64::Engine;
+use std::io::Write;
+
+/// Color of the model, darkened towards the bed
+const MODEL_COLOR: [f64; 3] = [236.0, 122.0, 36.0];
+/// Brightness of the lowest part of the model, the highest being full
+const LOWEST_SHADE: f64 = 0.45;
+/// Brightness kept at the edge of a step down to a lower part
+const EDGE_SHADE: f64 = 0.7;
+/// Characters of base64 per comment line, as PrusaSlicer writes them
+const LINE_LENGTH: usize = 78;
+
+/// Render `layers`, each the islands printed at a height, seen from above
+/// as a `width` by `height` RGBA image. Higher layers cover lower ones and
+/// are drawn lighter; what isn't model is transparent.
+pub(super) fn render(layers: &[(f64, Vec<Island>)], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let mut heights = vec![f64::NEG_INFINITY; width * height];
+    let bounds = layers
+        .iter()
+        .flat_map(|(_, islands)| islands)
+        .filter_map(|island| island.outline.bounds())
+        .reduce(|a, b| a.union(&b));
+    let mut pixels = vec![0; width * height * 4];
+    let Some(bounds) = bounds else {
+        return pixels;
+    };
+
+    // Fit the model with a pixel to spare on each side, centered
+    let size = bounds.dimensions();
+    let scale = ((width as f64 - 2.0) / size.x).min((height as f64 - 2.0) / size.y);
+    let scale = if scale.is_finite() && scale > 0.0 { scale } else { 1.0 };
+    let center = bounds.min + size / 2.0;
+    let y_at = |row: usize| center.y - (row as f64 + 0.5 - height as f64 / 2.0) / scale;
+    // First column whose center lies at or beyond `x`
+    let column_at = |x: f64| ((x - center.x) * scale + width as f64 / 2.0 - 0.5).ceil().clamp(0.0, width as f64) as usize;
+
+    for (z, islands) in layers {
+        for row in 0..height {
+            let y = y_at(row);
+            for island in islands {
+                for span in crossings(island, y).chunks_exact(2) {
+                    for pixel in &mut heights[row * width..][column_at(span[0])..column_at(span[1])] {
+                        *pixel = pixel.max(*z);
+                    }
+                }
+            }
+        }
+    }
+
+    let (lowest, highest) = heights
+        .iter()
+        .filter(|z| z.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &z| (low.min(z), high.max(z)));
+    let step = layers.windows(2).map(|pair| (pair[1].0 - pair[0].0).abs()).fold(0.0, f64::max);
+    for row in 0..height {
+        for column in 0..width {
+            let z = heights[row * width + column];
+            if !z.is_finite() {
+                continue;
+            }
+            let t = if highest > lowest { (z - lowest) / (highest - lowest) } else { 1.0 };
+            let mut shade = LOWEST_SHADE + (1.0 - LOWEST_SHADE) * t;
+            // Outline where the model steps down, so flat tops stand out
+            let neighbors = [(row.wrapping_sub(1), column), (row + 1, column), (row, column.wrapping_sub(1)), (row, column + 1)];
+            let steps_down = neighbors.iter().any(|&(r, c)| {
+                r >= height || c >= width || heights[r * width + c] < z - step.max(1e-6)
+            });
+            if steps_down {
+                shade *= EDGE_SHADE;
+            }
+            let pixel = &mut pixels[(row * width + column) * 4..][..4];
+            for (channel, color) in pixel.iter_mut().zip(MODEL_COLOR) {
+                *channel = (color * shade).round() as u8;
+            }
+            pixel[3] = 255;
+        }
+    }
+    pixels
+}
+
+/// X coordinates where the horizontal line at `y` crosses the island's
+/// outline and holes, in order, so that each pair bounds a span inside it
+fn crossings(island: &Island, y: f64) -> Vec<f64> {
+    let mut xs = Vec::new();
+    for polygon in std::iter::once(&island.outline).chain(&island.holes) {
+        let n = polygon.points.len();
+        for i in 0..n {
+            let a = polygon.points[i];
+            let b = polygon.points[(i + 1) % n];
+            // Half-open in Y so a line through a vertex is counted once
+            if (a.y <= y) != (b.y <= y) {
+                xs.push(a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y));
+            }
+        }
+    }
+    xs.sort_by(f64::total_cmp);
+    xs
+}
+
+/// PNG file of a `width` by `height` RGBA image
+pub(super) fn encode_png(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let error = |e: png::EncodingError| SlicerError::GCodeError(format!("Failed to encode thumbnail: {}", e));
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(error)?;
+    writer.write_image_data(pixels).map_err(error)?;
+    writer.finish().map_err(error)?;
+    Ok(data)
+}
+
+/// Write `png` as a thumbnail comment block, base64 encoded, the way
+/// PrusaSlicer does and printer screens and web interfaces read it
+pub(super) fn write_block(writer: &mut dyn Write, png: &[u8], width: u32, height: u32) -> std::io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(png);
+    writeln!(writer, ";")?;
+    writeln!(writer, "; thumbnail begin {}x{} {}", width, height, encoded.len())?;
+    for line in encoded.as_bytes().chunks(LINE_LENGTH) {
+        // Base64 is ASCII, so every chunk is valid UTF-8
+        writeln!(writer, "; {}", String::from_utf8_lossy(line))?;
+    }
+    writeln!(writer, "; thumbnail end")?;
+    writeln!(writer, ";")
+}
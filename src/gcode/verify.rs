@@ -0,0 +1,205 @@
+use super::stats::words;
+use crate::config::SlicerConfig;
+use crate::error::Result;
+use std::fmt;
+use std::io::BufRead;
+
+/// What is wrong with a line of G-code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A move ends outside the build volume
+    OutOfBounds,
+    /// A move takes the nozzle below the bed
+    BelowBed,
+    /// A move runs faster than the machine's max feedrate for its axes
+    Feedrate,
+    /// Filament moves before the nozzle was given any temperature
+    NoTemperature,
+    /// Filament moves while the nozzle is set below the lowest temperature
+    /// it may be extruded at
+    ColdExtrusion,
+}
+
+impl ViolationKind {
+    /// Name for reports
+    pub fn name(self) -> &'static str {
+        match self {
+            ViolationKind::OutOfBounds => "Moves outside the build volume",
+            ViolationKind::BelowBed => "Moves below the bed",
+            ViolationKind::Feedrate => "Moves above the max feedrate",
+            ViolationKind::NoTemperature => "Extrusion before any temperature is set",
+            ViolationKind::ColdExtrusion => "Extrusion below the minimum temperature",
+        }
+    }
+}
+
+/// A line of G-code the machine shouldn't run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Line number, counted from 1
+    pub line: usize,
+    pub kind: ViolationKind,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// What checking G-code against a machine found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerificationReport {
+    /// Number of G0, G1, G2 and G3 moves read
+    pub moves: usize,
+    /// Every line that breaks a limit, in order
+    pub violations: Vec<Violation>,
+}
+
+impl VerificationReport {
+    /// Whether nothing was found
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// How many lines break the limit of `kind`
+    pub fn count(&self, kind: ViolationKind) -> usize {
+        self.violations.iter().filter(|violation| violation.kind == kind).count()
+    }
+
+    /// Whether any move leaves the build volume or goes below the bed
+    pub fn out_of_bounds(&self) -> bool {
+        self.violations.iter().any(|violation| matches!(violation.kind, ViolationKind::OutOfBounds | ViolationKind::BelowBed))
+    }
+}
+
+/// Run through `gcode` as the machine in `config` would, keeping track of
+/// where the nozzle is, what E is at and the temperature each extruder is
+/// set to, and report every line that breaks the machine's limits. The
+/// G-code is read a line at a time, so a long print's needn't fit in memory.
+pub fn verify(gcode: impl BufRead, config: &SlicerConfig) -> Result<VerificationReport> {
+    let mut report = VerificationReport::default();
+    let mut position = [0.0; 4];
+    let mut absolute = true;
+    let mut absolute_e = true;
+    let mut feedrate = 0.0;
+    let mut tool = 0;
+    // Temperature each extruder was last set to, None before it was
+    let mut temperatures: Vec<Option<f64>> = Vec::new();
+
+    for (index, line) in gcode.lines().enumerate() {
+        let line = line?;
+        let code = line.split(';').next().unwrap_or_default();
+        // Past any line number and checksum the line is framed in
+        let code = code.split('*').next().unwrap_or_default();
+        let mut words = words(code).skip_while(|word| word.starts_with('N') && word[1..].parse::<u64>().is_ok());
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let words: Vec<&str> = words.collect();
+        let value = |letter: char| {
+            words.iter().find(|word| word.starts_with(letter)).and_then(|word| word[1..].parse::<f64>().ok())
+        };
+        let mut violation = |kind, message: String| report.violations.push(Violation { line: index + 1, kind, message });
+        match command {
+            "G0" | "G1" | "G2" | "G3" => {
+                let mut target = position;
+                for (axis, letter) in ['X', 'Y', 'Z', 'E'].into_iter().enumerate() {
+                    if let Some(value) = value(letter) {
+                        let relative = if axis == 3 { !absolute_e } else { !absolute };
+                        target[axis] = if relative { position[axis] + value } else { value };
+                    }
+                }
+                feedrate = value('F').unwrap_or(feedrate);
+
+                let moved = |axis: usize| target[axis] != position[axis];
+                let [x, y, z] = config.build_volume;
+                let outside = |value: f64, max: f64| value < -1e-3 || value > max + 1e-3;
+                if !(moved(0) || moved(1) || moved(2)) {
+                    // Only the filament moves, where the last move was checked
+                } else if target[2] < -1e-3 {
+                    violation(ViolationKind::BelowBed, format!("Z{} is below the bed", target[2]));
+                } else if outside(target[0], x) || outside(target[1], y) || target[2] > z + 1e-3 {
+                    violation(ViolationKind::OutOfBounds, format!("X{} Y{} Z{} is outside the {}x{}x{} mm build volume", target[0], target[1], target[2], x, y, z));
+                }
+
+                let (max, axes) = if moved(0) || moved(1) {
+                    (config.max_feedrate, "X and Y")
+                } else if moved(2) {
+                    (config.max_feedrate_z, "Z")
+                } else {
+                    (config.max_feedrate_e, "E")
+                };
+                if let Some(max) = max.filter(|&max| feedrate > max * 60.0 + 1e-6) {
+                    violation(ViolationKind::Feedrate, format!("F{} is above the {} mm/min the machine moves {} at", feedrate, max * 60.0, axes));
+                }
+
+                if moved(3) {
+                    match temperatures.get(tool).copied().flatten() {
+                        None => violation(ViolationKind::NoTemperature, format!("E moves before tool {} is given a temperature", tool)),
+                        Some(temperature) if temperature < config.min_extrusion_temperature as f64 => violation(
+                            ViolationKind::ColdExtrusion,
+                            format!("E moves with tool {} set to {}°C, below {}°C", tool, temperature, config.min_extrusion_temperature),
+                        ),
+                        Some(_) => {}
+                    }
+                }
+                report.moves += 1;
+                position = target;
+            }
+            "G28" => {
+                // Homing only the axes named, or all of them
+                let named = ['X', 'Y', 'Z'].map(|letter| words.iter().any(|word| word.starts_with(letter)));
+                for (axis, named_axis) in named.into_iter().enumerate() {
+                    if named_axis || named == [false; 3] {
+                        position[axis] = 0.0;
+                    }
+                }
+            }
+            "G90" => absolute = true,
+            "G91" => absolute = false,
+            "M82" => absolute_e = true,
+            "M83" => absolute_e = false,
+            "G92" => {
+                for (axis, letter) in ['X', 'Y', 'Z', 'E'].into_iter().enumerate() {
+                    position[axis] = value(letter).unwrap_or(position[axis]);
+                }
+            }
+            "M104" | "M109" => {
+                if let Some(temperature) = value('S') {
+                    let heater = value('T').map_or(tool, |tool| tool as usize);
+                    set_temperature(&mut temperatures, heater, temperature);
+                }
+            }
+            "SET_HEATER_TEMPERATURE" | "TEMPERATURE_WAIT" => {
+                let setting = |name: &str| words.iter().find_map(|word| word.strip_prefix(name)).map(str::to_string);
+                let heater = setting("HEATER=").or_else(|| setting("SENSOR="));
+                let temperature = setting("TARGET=").or_else(|| setting("MINIMUM=")).and_then(|value| value.parse().ok());
+                // Klipper numbers the extruders after the first
+                let extruder = heater.as_deref().and_then(|heater| heater.strip_prefix("extruder")).map(|number| number.parse().unwrap_or(0));
+                if let (Some(extruder), Some(temperature)) = (extruder, temperature) {
+                    set_temperature(&mut temperatures, extruder, temperature);
+                }
+            }
+            "TURN_OFF_HEATERS" => {
+                for temperature in temperatures.iter_mut() {
+                    *temperature = Some(0.0);
+                }
+            }
+            _ => {
+                if let Some(number) = command.strip_prefix('T').and_then(|number| number.parse().ok()) {
+                    tool = number;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn set_temperature(temperatures: &mut Vec<Option<f64>>, tool: usize, temperature: f64) {
+    if temperatures.len() <= tool {
+        temperatures.resize(tool + 1, None);
+    }
+    temperatures[tool] = Some(temperature);
+}
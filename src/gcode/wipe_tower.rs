@@ -0,0 +1,95 @@
+use crate::geometry::BoundingBox2D;
+use nalgebra::Point2;
+use std::ops::Range;
+
+/// Distance in line widths between the lines filling the tower where
+/// nothing is purged
+const SPARSE_SPACING: f64 = 8.0;
+
+/// The column tool changes purge on, beside the print. Each of its layers
+/// is a wall around bands lying front to back, one for each purge the
+/// busiest layer needs. Purges fill the bands in turn with lines along X;
+/// bands a layer has no purge for are filled sparsely, front to back, to
+/// hold up the lines above.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct WipeTower {
+    /// Front left corner
+    pub origin: Point2<f64>,
+    /// Width along X
+    pub width: f64,
+    pub line_width: f64,
+    /// Bands on each layer
+    pub bands: usize,
+    /// Purge lines each band has room for
+    pub band_lines: usize,
+    /// Last layer with a purge, where the tower stops
+    pub top_layer: usize,
+}
+
+impl WipeTower {
+    /// Depth along Y: the bands with the wall in front and behind
+    pub fn depth(&self) -> f64 {
+        (self.bands * self.band_lines + 2) as f64 * self.line_width
+    }
+
+    pub fn bounds(&self) -> BoundingBox2D {
+        BoundingBox2D { min: self.origin, max: Point2::new(self.origin.x + self.width, self.origin.y + self.depth()) }
+    }
+
+    /// The wall around the tower, closed
+    pub fn wall(&self) -> Vec<Point2<f64>> {
+        let inset = self.line_width / 2.0;
+        let bounds = self.bounds();
+        let (left, right, front, back) = (bounds.min.x + inset, bounds.max.x - inset, bounds.min.y + inset, bounds.max.y - inset);
+        vec![
+            Point2::new(left, front),
+            Point2::new(right, front),
+            Point2::new(right, back),
+            Point2::new(left, back),
+            Point2::new(left, front),
+        ]
+    }
+
+    /// Lines back and forth along `band` to lay `length` mm of line, as
+    /// many as it takes and the band holds
+    pub fn purge(&self, band: usize, length: f64) -> Vec<Point2<f64>> {
+        let (left, right) = self.line_ends();
+        let lines = ((length / (right - left)).ceil() as usize).clamp(1, self.band_lines);
+        let front = self.band_front(band) + self.line_width / 2.0;
+        let mut points = Vec::with_capacity(2 * lines);
+        for line in 0..lines {
+            let y = front + line as f64 * self.line_width;
+            let (start, end) = if line % 2 == 0 { (left, right) } else { (right, left) };
+            points.extend([Point2::new(start, y), Point2::new(end, y)]);
+        }
+        points
+    }
+
+    /// Lines front to back, far apart, across `bands`
+    pub fn sparse(&self, bands: Range<usize>) -> Vec<Point2<f64>> {
+        let (front, back) = (self.band_front(bands.start) + self.line_width / 2.0, self.band_front(bands.end) - self.line_width / 2.0);
+        if back <= front {
+            return Vec::new();
+        }
+        let (left, right) = self.line_ends();
+        let spacing = SPARSE_SPACING * self.line_width;
+        let mut points = Vec::new();
+        for line in 0..=((right - left) / spacing) as usize {
+            let x = left + line as f64 * spacing;
+            let (start, end) = if line % 2 == 0 { (front, back) } else { (back, front) };
+            points.extend([Point2::new(x, start), Point2::new(x, end)]);
+        }
+        points
+    }
+
+    /// X where lines inside the wall start and end, a line's width clear
+    /// of it so they don't pile up on it
+    fn line_ends(&self) -> (f64, f64) {
+        (self.origin.x + 1.5 * self.line_width, self.origin.x + self.width - 1.5 * self.line_width)
+    }
+
+    /// Front edge of `band`
+    fn band_front(&self, band: usize) -> f64 {
+        self.origin.y + (1 + band * self.band_lines) as f64 * self.line_width
+    }
+}
@@ -0,0 +1,185 @@
+use nalgebra::Point2;
+use std::f64::consts::PI;
+use super::Polygon;
+
+/// Fewest contour points an arc must replace to be worth emitting
+const MIN_ARC_POINTS: usize = 4;
+
+/// One piece of a contour rewritten as lines and arcs
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathElement {
+    /// Straight moves through `points`, the first being where the previous
+    /// element ended
+    Line(Vec<Point2<f64>>),
+    Arc(Arc),
+}
+
+/// Circular arc from `start_angle` to `end_angle` (radians from +X).
+/// Angles are unwrapped: counter-clockwise arcs have `end_angle` above
+/// `start_angle`, clockwise ones below, and a full circle spans 2π.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arc {
+    pub center: Point2<f64>,
+    pub radius: f64,
+    pub start_angle: f64,
+    pub end_angle: f64,
+    pub clockwise: bool,
+}
+
+impl Arc {
+    pub fn start(&self) -> Point2<f64> {
+        self.point_at(self.start_angle)
+    }
+
+    pub fn end(&self) -> Point2<f64> {
+        self.point_at(self.end_angle)
+    }
+
+    /// Angle swept, always positive
+    pub fn sweep(&self) -> f64 {
+        (self.end_angle - self.start_angle).abs()
+    }
+
+    pub fn is_full_circle(&self) -> bool {
+        self.sweep() >= 2.0 * PI - 1e-9
+    }
+
+    pub fn length(&self) -> f64 {
+        self.radius * self.sweep()
+    }
+
+    fn point_at(&self, angle: f64) -> Point2<f64> {
+        self.center + nalgebra::Vector2::new(angle.cos(), angle.sin()) * self.radius
+    }
+}
+
+/// Rewrite a closed contour as lines and arcs, starting and ending at its
+/// first point.
+///
+/// Runs of consecutive points are grown one point at a time while a circle
+/// through the run's first, middle and last points stays within `tolerance`
+/// of every point in the run and of every edge between them, so the arcs
+/// never stray further than that from the contour. Use the configured
+/// resolution as the tolerance.
+pub fn fit_arcs(polygon: &Polygon, tolerance: f64) -> Vec<PathElement> {
+    if polygon.points.len() < 2 {
+        return Vec::new();
+    }
+    // Walk the loop back to where it started
+    let points: Vec<Point2<f64>> = polygon.points.iter().chain(&polygon.points[..1]).copied().collect();
+    fit_polyline_arcs(&points, tolerance)
+}
+
+/// Center and radius of the circle `polygon` follows, if it is one: its
+/// contour fits arcs of the same circle within `tolerance` all the way round
+pub fn fit_circle(polygon: &Polygon, tolerance: f64) -> Option<(Point2<f64>, f64)> {
+    let elements = fit_arcs(polygon, tolerance);
+    let arcs: Vec<&Arc> = elements
+        .iter()
+        .map(|element| match element {
+            PathElement::Arc(arc) => Some(arc),
+            PathElement::Line(_) => None,
+        })
+        .collect::<Option<_>>()?;
+    let first = arcs.first()?;
+    let one_circle = arcs
+        .iter()
+        .all(|arc| (arc.center - first.center).norm() <= tolerance && (arc.radius - first.radius).abs() <= tolerance);
+    let sweep: f64 = arcs.iter().map(|arc| arc.sweep()).sum();
+    let radius = arcs.iter().map(|arc| arc.radius).sum::<f64>() / arcs.len() as f64;
+    (one_circle && sweep >= 2.0 * PI - 1e-6).then_some((first.center, radius))
+}
+
+/// Rewrite a path through `points`, open or closed, as lines and arcs the
+/// same way as [`fit_arcs`]
+pub fn fit_polyline_arcs(points: &[Point2<f64>], tolerance: f64) -> Vec<PathElement> {
+    if points.len() < 2 {
+        return Vec::new();
+    }
+    let n = points.len() - 1;
+
+    let mut elements = Vec::new();
+    let mut line = vec![points[0]];
+    let mut i = 0;
+    while i < n {
+        // Longest run from here that still fits an arc
+        let mut best = None;
+        let mut end = i + MIN_ARC_POINTS - 1;
+        while end <= n {
+            match fit_run(&points[i..=end], tolerance) {
+                Some(arc) => best = Some((end, arc)),
+                None => break,
+            }
+            end += 1;
+        }
+
+        match best {
+            Some((end, arc)) => {
+                if line.len() > 1 {
+                    elements.push(PathElement::Line(std::mem::take(&mut line)));
+                }
+                elements.push(PathElement::Arc(arc));
+                line = vec![points[end]];
+                i = end;
+            }
+            None => {
+                line.push(points[i + 1]);
+                i += 1;
+            }
+        }
+    }
+    if line.len() > 1 {
+        elements.push(PathElement::Line(line));
+    }
+
+    elements
+}
+
+/// Arc through every point of `run`, in order and turning one way
+fn fit_run(run: &[Point2<f64>], tolerance: f64) -> Option<Arc> {
+    let (first, last) = (run[0], run[run.len() - 1]);
+    // A closed run (a full circle) ends where it starts, so fit to points
+    // spread around it instead
+    let (b, c) = if first == last {
+        (run[run.len() / 3], run[2 * run.len() / 3])
+    } else {
+        (run[run.len() / 2], last)
+    };
+    let (center, radius) = circumcircle(first, b, c)?;
+
+    let mut sweep = 0.0;
+    for pair in run.windows(2) {
+        let (a, b) = (pair[0] - center, pair[1] - center);
+        if (b.norm() - radius).abs() > tolerance {
+            return None;
+        }
+        // The arc bulges away from each edge by the sagitta
+        let half_chord = (pair[1] - pair[0]).norm() / 2.0;
+        if half_chord >= radius || radius - (radius * radius - half_chord * half_chord).sqrt() > tolerance {
+            return None;
+        }
+        let step = (a.x * b.y - a.y * b.x).atan2(a.dot(&b));
+        if step == 0.0 || (sweep != 0.0 && step.signum() != f64::signum(sweep)) {
+            return None;
+        }
+        sweep += step;
+    }
+    if sweep.abs() > 2.0 * PI + 1e-9 {
+        return None;
+    }
+
+    let start_angle = (first.y - center.y).atan2(first.x - center.x);
+    Some(Arc { center, radius, start_angle, end_angle: start_angle + sweep, clockwise: sweep < 0.0 })
+}
+
+/// Circle through three points, or None if they are (nearly) collinear
+fn circumcircle(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>) -> Option<(Point2<f64>, f64)> {
+    let (ab, ac) = (b - a, c - a);
+    let d = 2.0 * (ab.x * ac.y - ab.y * ac.x);
+    if d.abs() <= f64::EPSILON * ab.norm_squared().max(ac.norm_squared()) {
+        return None;
+    }
+    let (ab2, ac2) = (ab.norm_squared(), ac.norm_squared());
+    let offset = nalgebra::Vector2::new(ac.y * ab2 - ab.y * ac2, ab.x * ac2 - ac.x * ab2) / d;
+    Some((a + offset, offset.norm()))
+}
@@ -0,0 +1,118 @@
+use nalgebra::{Point2, Vector3};
+use crate::error::{SlicerError, Result};
+use super::{BoundingBox2D, Mesh};
+
+/// Lay out meshes on the bed without overlapping, using shelf packing.
+///
+/// Parts are sorted by depth and placed left to right in rows, keeping
+/// `gap_mm` between neighbours; the whole arrangement is then centered on
+/// the bed. Fails with the (1-based) numbers of the parts that don't fit.
+pub fn arrange(meshes: &mut [Mesh], build_volume: [f64; 3], gap_mm: f64) -> Result<()> {
+    arrange_around(meshes, &vec![false; meshes.len()], build_volume, gap_mm)
+}
+
+/// Lay out the meshes not marked `fixed` as `arrange` does, keeping
+/// `gap_mm` clear of the fixed ones, which stay where they are. With any
+/// fixed, the arrangement starts from the bed's front left corner rather
+/// than being centered, as centering could move it onto them.
+pub fn arrange_around(meshes: &mut [Mesh], fixed: &[bool], build_volume: [f64; 3], gap_mm: f64) -> Result<()> {
+    let [bed_x, bed_y, bed_z] = build_volume;
+    let obstacles: Vec<BoundingBox2D> = meshes
+        .iter()
+        .zip(fixed)
+        .filter(|(_, &fixed)| fixed)
+        .map(|(mesh, _)| BoundingBox2D {
+            min: Point2::new(mesh.bounds.min.x - gap_mm, mesh.bounds.min.y - gap_mm),
+            max: Point2::new(mesh.bounds.max.x + gap_mm, mesh.bounds.max.y + gap_mm),
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..meshes.len()).filter(|&i| !fixed[i]).collect();
+    order.sort_by(|&a, &b| {
+        let da = meshes[a].bounds.dimensions().y;
+        let db = meshes[b].bounds.dimensions().y;
+        db.total_cmp(&da)
+    });
+
+    let mut x = 0.0;
+    let mut y = 0.0;
+    let mut row_depth: f64 = 0.0;
+    let mut used_x: f64 = 0.0;
+    let mut misfits = Vec::new();
+
+    for i in order {
+        let dims = meshes[i].bounds.dimensions();
+
+        loop {
+            if x > 0.0 && x + dims.x > bed_x {
+                y += row_depth + gap_mm;
+                x = 0.0;
+                row_depth = 0.0;
+            }
+            if dims.x > bed_x || y + dims.y > bed_y || dims.z > bed_z {
+                break;
+            }
+            let spot = BoundingBox2D { min: Point2::new(x, y), max: Point2::new(x + dims.x, y + dims.y) };
+            // Strictly overlapping, as touching the gap around one is fine
+            let blocked = obstacles.iter().find(|o| {
+                o.min.x < spot.max.x && spot.min.x < o.max.x && o.min.y < spot.max.y && spot.min.y < o.max.y
+            });
+            let Some(obstacle) = blocked else { break };
+            // Past it along the row, and the row is as deep as it so the
+            // next one clears it
+            x = obstacle.max.x;
+            row_depth = row_depth.max(obstacle.max.y - gap_mm - y);
+        }
+        if dims.x > bed_x || y + dims.y > bed_y || dims.z > bed_z {
+            misfits.push(i + 1);
+            continue;
+        }
+
+        let min = meshes[i].bounds.min;
+        meshes[i].translate(Vector3::new(x - min.x, y - min.y, 0.0));
+
+        x += dims.x + gap_mm;
+        used_x = used_x.max(x - gap_mm);
+        row_depth = row_depth.max(dims.y);
+    }
+
+    if !misfits.is_empty() {
+        misfits.sort_unstable();
+        let names: Vec<String> = misfits.iter().map(|i| format!("part {}", i)).collect();
+        return Err(SlicerError::SlicingError(format!(
+            "Could not fit {} on the {}x{} mm bed",
+            names.join(", "), bed_x, bed_y
+        )));
+    }
+
+    if obstacles.is_empty() {
+        let used_y = y + row_depth;
+        let shift = Vector3::new((bed_x - used_x) / 2.0, (bed_y - used_y) / 2.0, 0.0);
+        for mesh in meshes.iter_mut() {
+            mesh.translate(shift);
+        }
+    }
+
+    Ok(())
+}
+
+/// Front left corner for a box `size` mm across, `gap_mm` clear of
+/// `occupied` and on a bed `bed` mm across: to its right, left, back or
+/// front, whichever comes first with room, lined up with its back or right
+/// edge where the bed allows. None when there is room on no side.
+pub fn place_beside(occupied: &BoundingBox2D, size: [f64; 2], bed: [f64; 2], gap_mm: f64) -> Option<Point2<f64>> {
+    let [width, depth] = size;
+    let clamp = |value: f64, length: f64, bed: f64| value.min(bed - length).max(0.0);
+    let (back, right) = (clamp(occupied.max.y - depth, depth, bed[1]), clamp(occupied.max.x - width, width, bed[0]));
+    let candidates = [
+        Point2::new(occupied.max.x + gap_mm, back),
+        Point2::new(occupied.min.x - gap_mm - width, back),
+        Point2::new(right, occupied.max.y + gap_mm),
+        Point2::new(right, occupied.min.y - gap_mm - depth),
+    ];
+    // Each candidate is the gap away from the box on its side
+    candidates.into_iter().find(|corner| {
+        let placed = BoundingBox2D { min: *corner, max: Point2::new(corner.x + width, corner.y + depth) };
+        placed.min.x >= 0.0 && placed.min.y >= 0.0 && placed.max.x <= bed[0] && placed.max.y <= bed[1] && !placed.intersects(occupied)
+    })
+}
@@ -0,0 +1,37 @@
+//! Boolean operations on layer regions.
+//!
+//! Inputs may overlap themselves and each other and needn't be normalized.
+//! Results are normalized islands in position order; regions that touch
+//! along an edge are merged.
+
+use super::overlay::{into_islands, island_paths, overlay};
+use super::Island;
+
+/// Area covered by `a`, `b`, or both
+pub fn union(a: &[Island], b: &[Island]) -> Vec<Island> {
+    combine(a, b, |a, b| a != 0 || b != 0)
+}
+
+/// Area covered by both `a` and `b`
+pub fn intersection(a: &[Island], b: &[Island]) -> Vec<Island> {
+    combine(a, b, |a, b| a != 0 && b != 0)
+}
+
+/// Area covered by `a` but not by `b`
+pub fn difference(a: &[Island], b: &[Island]) -> Vec<Island> {
+    combine(a, b, |a, b| a != 0 && b == 0)
+}
+
+/// Morphological close: merge islands and fill holes separated by gaps
+/// narrower than `distance`, leaving wider gaps and holes as they were
+pub fn close_gaps(islands: &[Island], distance: f64) -> Vec<Island> {
+    if distance <= 0.0 || islands.is_empty() {
+        return islands.to_vec();
+    }
+    let grown: Vec<Island> = islands.iter().flat_map(|island| island.offset(distance / 2.0)).collect();
+    union(&grown, &[]).iter().flat_map(|island| island.offset(-distance / 2.0)).collect()
+}
+
+fn combine(a: &[Island], b: &[Island], keep: impl Fn(i32, i32) -> bool) -> Vec<Island> {
+    into_islands(overlay(&island_paths(a), &island_paths(b), keep))
+}
@@ -0,0 +1,100 @@
+use super::{BoundingBox2D, Island, Polygon};
+
+/// Closed contours of a layer arranged by containment
+#[derive(Debug, Clone, Default)]
+pub struct ContourTree {
+    /// Largest first, so every node comes after its parent
+    nodes: Vec<ContourNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContourNode {
+    pub contour: Polygon,
+    pub bounds: BoundingBox2D,
+    pub area: f64,
+    /// Smallest contour enclosing this one
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Number of contours enclosing this one; even for island outlines and
+    /// odd for holes
+    pub depth: usize,
+}
+
+impl ContourTree {
+    /// Nest closed, non-intersecting contours; empty ones are dropped.
+    ///
+    /// Contours are visited largest first, so a contour's parent is the
+    /// smallest one already placed that contains it. Only contours whose
+    /// bounds enclose its bounds are tested, with a single point-in-polygon
+    /// check on one of its vertices.
+    pub fn new(contours: Vec<Polygon>) -> Self {
+        let mut nodes: Vec<ContourNode> = contours
+            .into_iter()
+            .filter_map(|contour| {
+                let bounds = contour.bounds()?;
+                let area = contour.area();
+                Some(ContourNode { contour, bounds, area, parent: None, children: Vec::new(), depth: 0 })
+            })
+            .collect();
+        nodes.sort_by(|a, b| b.area.total_cmp(&a.area));
+
+        for i in 0..nodes.len() {
+            let probe = nodes[i].contour.points[0];
+            let parent = (0..i).rev().find(|&j| {
+                nodes[j].bounds.contains(&nodes[i].bounds) && nodes[j].contour.contains_point_in_bounds(&probe)
+            });
+
+            if let Some(parent) = parent {
+                nodes[i].parent = Some(parent);
+                nodes[i].depth = nodes[parent].depth + 1;
+                nodes[parent].children.push(i);
+            }
+        }
+
+        ContourTree { nodes }
+    }
+
+    pub fn nodes(&self) -> &[ContourNode] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Indices of the contours no other contour encloses
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&i| self.nodes[i].parent.is_none())
+    }
+
+    /// Contours at even depth become island outlines and those at odd depth
+    /// holes of their parent; a contour inside a hole starts a new island
+    pub fn into_islands(self) -> Vec<Island> {
+        let mut islands: Vec<Island> = Vec::new();
+        // Island an outline contour became
+        let mut island_of: Vec<usize> = Vec::with_capacity(self.nodes.len());
+
+        for node in self.nodes {
+            if node.depth % 2 == 0 {
+                island_of.push(islands.len());
+                islands.push(Island::new(node.contour));
+            } else {
+                let parent = node.parent.expect("a hole has an enclosing contour");
+                islands[island_of[parent]].holes.push(node.contour);
+                island_of.push(usize::MAX);
+            }
+        }
+
+        islands
+    }
+}
+
+/// Group closed, non-intersecting contours into islands by containment,
+/// see [`ContourTree`]
+pub fn nest_contours(contours: Vec<Polygon>) -> Vec<Island> {
+    ContourTree::new(contours).into_islands()
+}
@@ -0,0 +1,339 @@
+use nalgebra::{Point2, Point3, Vector3};
+use std::collections::HashMap;
+use crate::error::{SlicerError, Result};
+use super::{Mesh, Triangle};
+
+type Key2 = (u64, u64);
+
+impl Mesh {
+    /// Cut the mesh with the horizontal plane at height z.
+    ///
+    /// Returns the lower and upper halves, each closed with a triangulated
+    /// cap over its own cross-section. The mesh must be watertight with
+    /// consistently oriented faces. The plane may pass through vertices and
+    /// along edges; faces lying exactly in it are dropped and replaced by
+    /// the caps, which is why each half gets its own.
+    pub fn cut_at_z(&self, z: f64) -> Result<(Mesh, Mesh)> {
+        if z <= self.bounds.min.z || z >= self.bounds.max.z {
+            return Err(SlicerError::InvalidParameter(format!(
+                "Cut height {:.3} mm is outside the model (Z {:.3} to {:.3})",
+                z, self.bounds.min.z, self.bounds.max.z
+            )));
+        }
+
+        let mut lower = Vec::new();
+        let mut upper = Vec::new();
+        // Cap edges of each half, directed so the lower cap faces +Z and
+        // the upper one -Z when seen from outside
+        let mut lower_edges: Vec<(Point2<f64>, Point2<f64>)> = Vec::new();
+        let mut upper_edges: Vec<(Point2<f64>, Point2<f64>)> = Vec::new();
+
+        for triangle in &self.triangles {
+            let d = triangle.vertices.map(|v| v.z - z);
+
+            if d.iter().all(|&d| d == 0.0) {
+                continue;
+            }
+            if d.iter().all(|&d| d <= 0.0) {
+                // An edge lying in the plane still borders the cap
+                plane_edges(&triangle.vertices, z, &mut lower_edges, true);
+                lower.push(triangle.clone());
+                continue;
+            }
+            if d.iter().all(|&d| d >= 0.0) {
+                plane_edges(&triangle.vertices, z, &mut upper_edges, false);
+                upper.push(triangle.clone());
+                continue;
+            }
+
+            let below = clip_triangle(triangle, z, true);
+            let above = clip_triangle(triangle, z, false);
+            plane_edges(&below, z, &mut lower_edges, true);
+            plane_edges(&above, z, &mut upper_edges, false);
+
+            fan(&below, triangle.normal, &mut lower);
+            fan(&above, triangle.normal, &mut upper);
+        }
+
+        for cap in triangulate_loops(stitch_directed(&lower_edges)?)? {
+            let [a, b, c] = cap.map(|p| Point3::new(p.x, p.y, z));
+            lower.push(Triangle { vertices: [a, b, c], normal: Vector3::new(0.0, 0.0, 1.0) });
+        }
+        for cap in triangulate_loops(stitch_directed(&upper_edges)?)? {
+            let [a, b, c] = cap.map(|p| Point3::new(p.x, p.y, z));
+            upper.push(Triangle { vertices: [a, c, b], normal: Vector3::new(0.0, 0.0, -1.0) });
+        }
+
+        Ok((Mesh::new(lower), Mesh::new(upper)))
+    }
+}
+
+/// Add the edges of the piece `points` that lie in the plane at z to
+/// `edges`, as counter-clockwise cap outlines seen from above. The lower
+/// piece runs along them the other way from its cap, so they are `reversed`;
+/// the upper piece runs along them the way its cap is traced from above.
+fn plane_edges(points: &[Point3<f64>], z: f64, edges: &mut Vec<(Point2<f64>, Point2<f64>)>, reversed: bool) {
+    for i in 0..points.len() {
+        let p = points[i];
+        let q = points[(i + 1) % points.len()];
+        if p.z == z && q.z == z {
+            let (p, q) = (Point2::new(p.x, p.y), Point2::new(q.x, q.y));
+            edges.push(if reversed { (q, p) } else { (p, q) });
+        }
+    }
+}
+
+/// Clip a triangle against the plane, keeping the part below (or above) it.
+/// Vertices on the plane are kept on both sides.
+fn clip_triangle(triangle: &Triangle, z: f64, keep_below: bool) -> Vec<Point3<f64>> {
+    let inside = |p: &Point3<f64>| if keep_below { p.z <= z } else { p.z >= z };
+    let mut out = Vec::with_capacity(4);
+
+    for i in 0..3 {
+        let a = triangle.vertices[i];
+        let b = triangle.vertices[(i + 1) % 3];
+
+        if inside(&a) {
+            out.push(a);
+        }
+        if (a.z < z && b.z > z) || (a.z > z && b.z < z) {
+            out.push(edge_point(a, b, z));
+        }
+    }
+
+    out
+}
+
+/// Interpolate the plane crossing of an edge. Endpoints are ordered first so
+/// both triangles sharing the edge get a bit-identical point.
+fn edge_point(a: Point3<f64>, b: Point3<f64>, z: f64) -> Point3<f64> {
+    let (a, b) = if (a.x, a.y, a.z) <= (b.x, b.y, b.z) { (a, b) } else { (b, a) };
+    let t = (z - a.z) / (b.z - a.z);
+    Point3::new(a.x + t * (b.x - a.x), a.y + t * (b.y - a.y), z)
+}
+
+fn fan(points: &[Point3<f64>], normal: Vector3<f64>, out: &mut Vec<Triangle>) {
+    for i in 1..points.len().saturating_sub(1) {
+        out.push(Triangle {
+            vertices: [points[0], points[i], points[i + 1]],
+            normal,
+        });
+    }
+}
+
+fn key(p: &Point2<f64>) -> Key2 {
+    (p.x.to_bits(), p.y.to_bits())
+}
+
+/// Chain directed edges into closed loops
+fn stitch_directed(edges: &[(Point2<f64>, Point2<f64>)]) -> Result<Vec<Vec<Point2<f64>>>> {
+    let mut next: HashMap<Key2, Vec<usize>> = HashMap::new();
+    for (i, (start, _)) in edges.iter().enumerate() {
+        next.entry(key(start)).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut loops = Vec::new();
+
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+
+        let start = key(&edges[first].0);
+        let mut points = vec![edges[first].0];
+        let mut current = edges[first].1;
+
+        while key(&current) != start {
+            let candidate = next
+                .get(&key(&current))
+                .and_then(|ids| ids.iter().copied().find(|&i| !used[i]))
+                .ok_or_else(|| SlicerError::InvalidGeometry(
+                    "Cut cross-section is not closed; the mesh is not watertight".to_string()
+                ))?;
+            used[candidate] = true;
+            points.push(current);
+            current = edges[candidate].1;
+        }
+
+        if points.len() >= 3 {
+            loops.push(points);
+        }
+    }
+
+    Ok(loops)
+}
+
+fn signed_area(points: &[Point2<f64>]) -> f64 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let a = points[i];
+            let b = points[(i + 1) % n];
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+fn cross(o: Point2<f64>, a: Point2<f64>, b: Point2<f64>) -> f64 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+fn inside_polygon(p: Point2<f64>, polygon: &[Point2<f64>]) -> bool {
+    let mut inside = false;
+    let n = polygon.len();
+    for i in 0..n {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % n];
+        if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Triangulate a set of cap loops: counter-clockwise outlines with clockwise holes
+fn triangulate_loops(loops: Vec<Vec<Point2<f64>>>) -> Result<Vec<[Point2<f64>; 3]>> {
+    let (mut outers, holes): (Vec<_>, Vec<_>) = loops
+        .into_iter()
+        .partition(|l| signed_area(l) > 0.0);
+
+    // Smallest outlines first so a hole goes to the tightest enclosing one
+    outers.sort_by(|a, b| signed_area(a).total_cmp(&signed_area(b)));
+    let mut assigned: Vec<Vec<Vec<Point2<f64>>>> = vec![Vec::new(); outers.len()];
+
+    for hole in holes {
+        let parent = outers
+            .iter()
+            .position(|outer| inside_polygon(hole[0], outer))
+            .ok_or_else(|| SlicerError::InvalidGeometry(
+                "Cut cross-section has a hole outside any outline".to_string()
+            ))?;
+        assigned[parent].push(hole);
+    }
+
+    let mut triangles = Vec::new();
+    for (outer, holes) in outers.into_iter().zip(assigned) {
+        let polygon = bridge_holes(outer, holes);
+        ear_clip(&polygon, &mut triangles);
+    }
+
+    Ok(triangles)
+}
+
+/// Splice holes into the outline with zero-width bridges (Eberly's method)
+fn bridge_holes(mut outer: Vec<Point2<f64>>, mut holes: Vec<Vec<Point2<f64>>>) -> Vec<Point2<f64>> {
+    let max_x = |h: &Vec<Point2<f64>>| h.iter().map(|p| p.x).fold(f64::MIN, f64::max);
+    holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+
+    for hole in holes {
+        let hi = (0..hole.len())
+            .max_by(|&a, &b| hole[a].x.total_cmp(&hole[b].x))
+            .unwrap();
+        let m = hole[hi];
+
+        // Closest outline edge hit by a ray from m towards +X
+        let n = outer.len();
+        let mut best: Option<(f64, usize)> = None;
+        for i in 0..n {
+            let a = outer[i];
+            let b = outer[(i + 1) % n];
+            if (a.y > m.y) == (b.y > m.y) {
+                continue;
+            }
+            let x = a.x + (m.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if x >= m.x && best.is_none_or(|(bx, _)| x < bx) {
+                best = Some((x, i));
+            }
+        }
+        let Some((hit_x, edge)) = best else { continue };
+
+        let hit = Point2::new(hit_x, m.y);
+        let a = edge;
+        let b = (edge + 1) % n;
+        let mut pi = if outer[a].x > outer[b].x { a } else { b };
+
+        // A reflex vertex inside the triangle (m, hit, p) would block the
+        // bridge; take the one with the smallest angle to the ray instead.
+        let mut best_angle = f64::MAX;
+        for i in 0..n {
+            let prev = outer[(i + n - 1) % n];
+            let next = outer[(i + 1) % n];
+            let v = outer[i];
+            if i == pi || cross(prev, v, next) >= 0.0 {
+                continue;
+            }
+            if point_in_triangle(v, m, hit, outer[pi]) {
+                let angle = (v.y - m.y).abs().atan2(v.x - m.x);
+                if angle < best_angle {
+                    best_angle = angle;
+                    pi = i;
+                }
+            }
+        }
+
+        let mut spliced = Vec::with_capacity(outer.len() + hole.len() + 2);
+        spliced.extend_from_slice(&outer[..=pi]);
+        spliced.extend(hole[hi..].iter().chain(hole[..=hi].iter()).copied());
+        spliced.extend_from_slice(&outer[pi..]);
+        outer = spliced;
+    }
+
+    outer
+}
+
+fn point_in_triangle(p: Point2<f64>, a: Point2<f64>, b: Point2<f64>, c: Point2<f64>) -> bool {
+    let d1 = cross(a, b, p);
+    let d2 = cross(b, c, p);
+    let d3 = cross(c, a, p);
+    let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_neg && has_pos)
+}
+
+/// Ear-clip a counter-clockwise simple polygon
+fn ear_clip(polygon: &[Point2<f64>], out: &mut Vec<[Point2<f64>; 3]>) {
+    let mut indices: Vec<usize> = (0..polygon.len()).collect();
+
+    while indices.len() > 3 {
+        let n = indices.len();
+        let mut clipped = false;
+
+        for i in 0..n {
+            let a = polygon[indices[(i + n - 1) % n]];
+            let b = polygon[indices[i]];
+            let c = polygon[indices[(i + 1) % n]];
+
+            if cross(a, b, c) <= 0.0 {
+                continue;
+            }
+
+            let blocked = indices.iter().any(|&j| {
+                let p = polygon[j];
+                p != a && p != b && p != c && point_in_triangle(p, a, b, c)
+            });
+            if blocked {
+                continue;
+            }
+
+            out.push([a, b, c]);
+            indices.remove(i);
+            clipped = true;
+            break;
+        }
+
+        // Degenerate remainder (collinear or self-touching); drop a vertex to make progress
+        if !clipped {
+            indices.remove(0);
+        }
+    }
+
+    if indices.len() == 3 {
+        let [a, b, c] = [polygon[indices[0]], polygon[indices[1]], polygon[indices[2]]];
+        if cross(a, b, c) > 0.0 {
+            out.push([a, b, c]);
+        }
+    }
+}
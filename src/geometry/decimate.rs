@@ -0,0 +1,186 @@
+use nalgebra::{Point3, Vector3};
+use std::collections::HashMap;
+use super::{Mesh, Triangle};
+
+impl Mesh {
+    /// Reduce the triangle count to at most `target_triangles` by collapsing
+    /// the shortest edges first.
+    ///
+    /// Edges touching an open boundary are never collapsed, and a collapse is
+    /// rejected if it would flip a neighbouring face or make the surface
+    /// non-manifold, so the result may stay above the target. Normals and
+    /// bounds are recomputed.
+    pub fn decimate(&mut self, target_triangles: usize) {
+        if self.triangles.len() <= target_triangles {
+            return;
+        }
+
+        let mut indexed = IndexedMesh::from_triangles(&self.triangles);
+        let mut alive = indexed.faces.len();
+
+        while alive > target_triangles {
+            let collapsed = indexed.collapse_pass(alive - target_triangles);
+            if collapsed == 0 {
+                break;
+            }
+            alive -= collapsed;
+        }
+
+        *self = Mesh::new(indexed.into_triangles());
+    }
+}
+
+struct IndexedMesh {
+    positions: Vec<Point3<f64>>,
+    faces: Vec<Option<[usize; 3]>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl IndexedMesh {
+    fn from_triangles(triangles: &[Triangle]) -> Self {
+        let mut index: HashMap<(u64, u64, u64), usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut faces = Vec::with_capacity(triangles.len());
+
+        for triangle in triangles {
+            let face = triangle.vertices.map(|v| {
+                *index.entry((v.x.to_bits(), v.y.to_bits(), v.z.to_bits()))
+                    .or_insert_with(|| {
+                        positions.push(v);
+                        positions.len() - 1
+                    })
+            });
+            faces.push(Some(face));
+        }
+
+        let mut vertex_faces = vec![Vec::new(); positions.len()];
+        for (f, face) in faces.iter().enumerate() {
+            for &v in face.as_ref().unwrap() {
+                vertex_faces[v].push(f);
+            }
+        }
+
+        IndexedMesh { positions, faces, vertex_faces }
+    }
+
+    /// Collapse up to `budget` triangles worth of edges, shortest first.
+    /// Vertices are collapsed at most once per pass so adjacency stays simple.
+    /// Returns the number of triangles removed.
+    fn collapse_pass(&mut self, budget: usize) -> usize {
+        let mut edge_faces: HashMap<(usize, usize), usize> = HashMap::new();
+        for face in self.faces.iter().flatten() {
+            for i in 0..3 {
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                *edge_faces.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        let mut boundary = vec![false; self.positions.len()];
+        for (&(a, b), &count) in &edge_faces {
+            if count != 2 {
+                boundary[a] = true;
+                boundary[b] = true;
+            }
+        }
+
+        let mut edges: Vec<(f64, usize, usize)> = edge_faces
+            .keys()
+            .map(|&(a, b)| ((self.positions[a] - self.positions[b]).norm_squared(), a, b))
+            .collect();
+        edges.sort_by(|x, y| x.0.total_cmp(&y.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+
+        let mut touched = vec![false; self.positions.len()];
+        let mut removed = 0;
+
+        for (_, a, b) in edges {
+            if removed >= budget {
+                break;
+            }
+            if touched[a] || touched[b] || boundary[a] || boundary[b] {
+                continue;
+            }
+            if let Some(count) = self.try_collapse(a, b) {
+                removed += count;
+                for &f in &self.vertex_faces[b] {
+                    for &v in self.faces[f].as_ref().unwrap() {
+                        touched[v] = true;
+                    }
+                }
+            }
+        }
+
+        removed
+    }
+
+    fn neighbours(&self, v: usize) -> Vec<usize> {
+        let mut result: Vec<usize> = self.vertex_faces[v]
+            .iter()
+            .flat_map(|&f| self.faces[f].unwrap())
+            .filter(|&n| n != v)
+            .collect();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /// Merge vertex `a` into `b` at the edge midpoint
+    fn try_collapse(&mut self, a: usize, b: usize) -> Option<usize> {
+        // Link condition: the endpoints may only share the two opposite vertices
+        let na = self.neighbours(a);
+        let nb = self.neighbours(b);
+        let shared = na.iter().filter(|v| nb.binary_search(v).is_ok()).count();
+        if shared != 2 {
+            return None;
+        }
+
+        let target = nalgebra::center(&self.positions[a], &self.positions[b]);
+
+        for &f in self.vertex_faces[a].iter().chain(&self.vertex_faces[b]) {
+            let face = self.faces[f].unwrap();
+            if face.contains(&a) && face.contains(&b) {
+                continue;
+            }
+            let before = face.map(|v| self.positions[v]);
+            let after = face.map(|v| if v == a || v == b { target } else { self.positions[v] });
+            if face_normal(&before).dot(&face_normal(&after)) <= 0.0 {
+                return None;
+            }
+        }
+
+        let mut removed = 0;
+        for f in std::mem::take(&mut self.vertex_faces[a]) {
+            let face = self.faces[f].unwrap();
+            if face.contains(&b) {
+                for v in face {
+                    self.vertex_faces[v].retain(|&g| g != f);
+                }
+                self.faces[f] = None;
+                removed += 1;
+            } else {
+                self.faces[f] = Some(face.map(|v| if v == a { b } else { v }));
+                self.vertex_faces[b].push(f);
+            }
+        }
+
+        self.positions[b] = target;
+        Some(removed)
+    }
+
+    fn into_triangles(self) -> Vec<Triangle> {
+        self.faces
+            .iter()
+            .flatten()
+            .map(|face| {
+                let vertices = face.map(|v| self.positions[v]);
+                Triangle { vertices, normal: face_normal(&vertices) }
+            })
+            .collect()
+    }
+}
+
+fn face_normal(v: &[Point3<f64>; 3]) -> Vector3<f64> {
+    (v[1] - v[0])
+        .cross(&(v[2] - v[0]))
+        .try_normalize(f64::EPSILON)
+        .unwrap_or_else(Vector3::zeros)
+}
@@ -0,0 +1,52 @@
+use nalgebra::Point2;
+use super::{Mesh, Polygon};
+
+/// Convex hull of a point set (Andrew's monotone chain), counter-clockwise
+/// and without collinear points.
+///
+/// Fewer than three distinct points, or points all on one line, give a
+/// degenerate polygon: the distinct points, or the two ends of the line.
+pub fn convex_hull(points: &[Point2<f64>]) -> Polygon {
+    let mut points = points.to_vec();
+    points.sort_by(|p, q| p.x.total_cmp(&q.x).then(p.y.total_cmp(&q.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return Polygon::new(points);
+    }
+
+    let cross = |o: Point2<f64>, a: Point2<f64>, b: Point2<f64>| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+
+    // Lower chain left to right, then upper chain right to left
+    let mut hull: Vec<Point2<f64>> = Vec::with_capacity(2 * points.len());
+    for &p in &points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    let lower = hull.len() + 1;
+    for &p in points.iter().rev().skip(1) {
+        while hull.len() >= lower && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    // The upper chain ends back at the first point
+    hull.pop();
+
+    Polygon::new(hull)
+}
+
+impl Mesh {
+    /// Convex hull of the mesh projected onto the XY plane
+    pub fn footprint_hull(&self) -> Polygon {
+        let points: Vec<Point2<f64>> = self
+            .triangles
+            .iter()
+            .flat_map(|t| t.vertices.iter().map(|v| Point2::new(v.x, v.y)))
+            .collect();
+        convex_hull(&points)
+    }
+}
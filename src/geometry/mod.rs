@@ -1,8 +1,25 @@
-use nalgebra::{Point3, Vector3};
+use nalgebra::{Isometry3, Matrix3, Matrix4, Point3, Vector3};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter, Write};
 use crate::error::{SlicerError, Result};
 
+pub mod arc_fit;
+pub mod arrange;
+pub mod boolean;
+mod contour_tree;
+mod cut;
+mod decimate;
+mod hull;
+mod offset;
+mod overlay;
+mod polygon;
+
+pub use contour_tree::{nest_contours, ContourNode, ContourTree};
+pub use hull::convex_hull;
+pub use polygon::{BoundingBox2D, Island, LineSegment2D, Polygon};
+pub(crate) use polygon::compare_position;
+
 #[derive(Debug, Clone)]
 pub struct Triangle {
     pub vertices: [Point3<f64>; 3],
@@ -15,7 +32,7 @@ pub struct Mesh {
     pub bounds: BoundingBox,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BoundingBox {
     pub min: Point3<f64>,
     pub max: Point3<f64>,
@@ -37,8 +54,6 @@ impl Mesh {
             .map_err(|e| SlicerError::StlReadError(format!("Failed to parse STL: {}", e)))?;
 
         let mut triangles = Vec::new();
-        let mut min = Point3::new(f64::MAX, f64::MAX, f64::MAX);
-        let mut max = Point3::new(f64::MIN, f64::MIN, f64::MIN);
 
         for face in stl.faces {
             let vertices = [
@@ -65,16 +80,6 @@ impl Mesh {
                 face.normal.coords[2] as f64,
             );
 
-            // Update bounding box
-            for vertex in &vertices {
-                min.x = min.x.min(vertex.x);
-                min.y = min.y.min(vertex.y);
-                min.z = min.z.min(vertex.z);
-                max.x = max.x.max(vertex.x);
-                max.y = max.y.max(vertex.y);
-                max.z = max.z.max(vertex.z);
-            }
-
             triangles.push(Triangle { vertices, normal });
         }
 
@@ -82,10 +87,163 @@ impl Mesh {
             return Err(SlicerError::InvalidGeometry("STL file contains no triangles".to_string()));
         }
 
-        Ok(Mesh {
-            triangles,
-            bounds: BoundingBox { min, max },
-        })
+        Ok(Mesh::new(triangles))
+    }
+
+    /// Build a mesh from a list of triangles, computing its bounding box
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let bounds = BoundingBox::from_vertices(triangles.iter().flat_map(|t| t.vertices.iter()));
+        Mesh { triangles, bounds }
+    }
+
+    /// Apply a rigid transform to the mesh
+    pub fn apply_transform(&mut self, m: &Isometry3<f64>) {
+        self.apply_matrix(&m.to_homogeneous());
+    }
+
+    /// Apply an affine transform given as a homogeneous matrix.
+    ///
+    /// Normals are transformed with the inverse-transpose and renormalized.
+    /// Mirroring transforms also flip the winding so faces keep pointing outward.
+    pub fn apply_matrix(&mut self, m: &Matrix4<f64>) {
+        let linear: Matrix3<f64> = m.fixed_view::<3, 3>(0, 0).into_owned();
+        let normal_matrix = linear.try_inverse().map(|inv| inv.transpose());
+        let mirrored = linear.determinant() < 0.0;
+
+        for triangle in &mut self.triangles {
+            for vertex in &mut triangle.vertices {
+                *vertex = m.transform_point(vertex);
+            }
+            if mirrored {
+                triangle.vertices.swap(1, 2);
+            }
+
+            let [a, b, c] = triangle.vertices;
+            triangle.normal = normal_matrix
+                .and_then(|n| (n * triangle.normal).try_normalize(f64::EPSILON))
+                .or_else(|| (b - a).cross(&(c - a)).try_normalize(f64::EPSILON))
+                .unwrap_or_else(Vector3::zeros);
+        }
+
+        self.bounds = BoundingBox::from_vertices(self.triangles.iter().flat_map(|t| t.vertices.iter()));
+    }
+
+    /// Combine several meshes into one
+    pub fn merge(meshes: Vec<Mesh>) -> Self {
+        Mesh::new(meshes.into_iter().flat_map(|m| m.triangles).collect())
+    }
+
+    /// The mesh split into its shells, the sets of triangles joined through
+    /// shared vertices, in the order their first triangles come
+    pub fn shells(&self) -> Vec<Mesh> {
+        // Union-find over the triangles, each pointing towards the first
+        // one of its shell
+        let mut parent: Vec<usize> = (0..self.triangles.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        let mut first_with: HashMap<PointKey, usize> = HashMap::new();
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            for vertex in &triangle.vertices {
+                let other = *first_with.entry(point_key(vertex)).or_insert(i);
+                let (a, b) = (root(&mut parent, i), root(&mut parent, other));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+
+        let mut shells: Vec<Vec<Triangle>> = Vec::new();
+        let mut shell_of = HashMap::new();
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            let index = *shell_of.entry(root(&mut parent, i)).or_insert_with(|| {
+                shells.push(Vec::new());
+                shells.len() - 1
+            });
+            shells[index].push(triangle.clone());
+        }
+        shells.into_iter().map(Mesh::new).collect()
+    }
+
+    /// Move every vertex by the given offset
+    pub fn translate(&mut self, offset: Vector3<f64>) {
+        self.apply_transform(&Isometry3::translation(offset.x, offset.y, offset.z));
+    }
+
+    /// Translate the mesh so its lowest point rests on Z=0
+    pub fn drop_to_bed(&mut self) {
+        let dz = -self.bounds.min.z;
+        self.translate(Vector3::new(0.0, 0.0, dz));
+    }
+
+    /// Center the mesh on the given XY position, leaving Z untouched
+    pub fn center_xy(&mut self, x: f64, y: f64) {
+        let center = self.bounds.center();
+        self.translate(Vector3::new(x - center.x, y - center.y, 0.0));
+    }
+
+    /// Scale uniformly about the center of the bounding box's bottom face
+    pub fn scale(&mut self, factor: f64) {
+        let center = self.bounds.center();
+        let pivot = Vector3::new(center.x, center.y, self.bounds.min.z);
+        let m = Matrix4::new_translation(&pivot)
+            * Matrix4::new_scaling(factor)
+            * Matrix4::new_translation(&-pivot);
+        self.apply_matrix(&m);
+    }
+
+    /// Write the mesh as a binary STL file
+    pub fn write_stl_file(&self, path: &str) -> Result<()> {
+        let file = File::create(path)
+            .map_err(|e| SlicerError::StlWriteError(format!("Failed to create file: {}", e)))?;
+        let mut writer = BufWriter::new(file);
+
+        let faces: Vec<stl_io::Triangle> = self.triangles
+            .iter()
+            .map(|t| stl_io::Triangle {
+                normal: stl_io::Normal::new([t.normal.x as f32, t.normal.y as f32, t.normal.z as f32]),
+                vertices: t.vertices.map(|v| stl_io::Vertex::new([v.x as f32, v.y as f32, v.z as f32])),
+            })
+            .collect();
+
+        stl_io::write_stl(&mut writer, faces.iter())
+            .and_then(|_| writer.flush())
+            .map_err(|e| SlicerError::StlWriteError(format!("Failed to write {}: {}", path, e)))?;
+
+        Ok(())
+    }
+
+    /// Enclosed volume in mm³, computed from signed tetrahedra (assumes a closed, outward-facing mesh)
+    pub fn volume(&self) -> f64 {
+        self.triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.vertices;
+                a.coords.dot(&b.coords.cross(&c.coords)) / 6.0
+            })
+            .sum::<f64>()
+            .abs()
+    }
+
+    /// True if every edge is shared by exactly two triangles with opposite directions
+    pub fn is_watertight(&self) -> bool {
+        let mut edges: HashMap<(PointKey, PointKey), i32> = HashMap::new();
+
+        for triangle in &self.triangles {
+            for i in 0..3 {
+                let a = point_key(&triangle.vertices[i]);
+                let b = point_key(&triangle.vertices[(i + 1) % 3]);
+                if a < b {
+                    *edges.entry((a, b)).or_insert(0) += 1;
+                } else {
+                    *edges.entry((b, a)).or_insert(0) -= 1;
+                }
+            }
+        }
+
+        edges.values().all(|&count| count == 0)
     }
 
     pub fn validate(&self) -> Result<()> {
@@ -111,57 +269,142 @@ impl Mesh {
 }
 
 impl Triangle {
-    /// Intersect triangle with a plane at height z
+    /// Intersect triangle with a plane at height z.
+    ///
+    /// Vertices within a tiny epsilon of the plane count as lying on it, and
+    /// each topological case yields at most one segment:
+    /// - no vertex on the plane: the two crossing edges, if any
+    /// - one vertex on the plane: that vertex and the crossing of the opposite
+    ///   edge if the other two straddle the plane, otherwise nothing
+    /// - an edge in the plane: the edge, but only from the triangle whose third
+    ///   vertex is below, so the two triangles sharing it don't both emit it
+    /// - the whole triangle in the plane: nothing
     pub fn intersect_plane(&self, z: f64) -> Option<LineSegment> {
-        let mut intersections = Vec::new();
+        let side = self.vertices.map(|v| plane_side(v.z, z));
+        let on_plane: Vec<usize> = (0..3).filter(|&i| side[i] == 0).collect();
 
-        // Check each edge of the triangle
-        for i in 0..3 {
-            let v1 = self.vertices[i];
-            let v2 = self.vertices[(i + 1) % 3];
-
-            if let Some(point) = intersect_edge_with_plane(v1, v2, z) {
-                intersections.push(point);
+        let (start, end) = match on_plane.as_slice() {
+            [] => {
+                let crossings: Vec<Point3<f64>> = (0..3)
+                    .filter(|&i| side[i] != side[(i + 1) % 3])
+                    .map(|i| edge_crossing(self.vertices[i], self.vertices[(i + 1) % 3], z))
+                    .collect();
+                match crossings.as_slice() {
+                    [a, b] => (*a, *b),
+                    _ => return None,
+                }
+            }
+            [i] => {
+                let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+                if side[j] == side[k] {
+                    return None;
+                }
+                (self.vertices[*i], edge_crossing(self.vertices[j], self.vertices[k], z))
+            }
+            [i, j] => {
+                let k = 3 - i - j;
+                if side[k] > 0 {
+                    return None;
+                }
+                (self.vertices[*i], self.vertices[*j])
             }
+            _ => return None,
+        };
+
+        Some(LineSegment { start, end })
+    }
+
+    /// Intersect with the plane and snap the segment's endpoints onto a grid
+    /// of `grid` mm, so crossings of an edge shared by two triangles land on
+    /// exactly the same point. Segments that collapse to a point are dropped.
+    pub fn intersect_plane_snapped(&self, z: f64, grid: f64) -> Option<LineSegment> {
+        let segment = self.intersect_plane(z)?;
+        if grid <= 0.0 {
+            return Some(segment);
         }
 
-        // We need exactly 2 intersection points to form a line segment
-        if intersections.len() == 2 {
-            Some(LineSegment {
-                start: intersections[0],
-                end: intersections[1],
-            })
-        } else {
+        let snap = |p: Point3<f64>| Point3::new((p.x / grid).round() * grid, (p.y / grid).round() * grid, p.z);
+        let (start, end) = (snap(segment.start), snap(segment.end));
+        if start.x == end.x && start.y == end.y {
             None
+        } else {
+            Some(LineSegment { start, end })
         }
     }
 }
 
-fn intersect_edge_with_plane(v1: Point3<f64>, v2: Point3<f64>, z: f64) -> Option<Point3<f64>> {
+/// Which side of the plane a height lies on: -1 below, 0 on, 1 above
+fn plane_side(vz: f64, z: f64) -> i8 {
     let epsilon = 1e-10;
-
-    // Check if edge crosses the plane
-    if (v1.z - z).abs() < epsilon {
-        return Some(v1);
-    }
-    if (v2.z - z).abs() < epsilon {
-        return Some(v2);
+    if (vz - z).abs() < epsilon {
+        0
+    } else if vz < z {
+        -1
+    } else {
+        1
     }
+}
 
-    if (v1.z < z && v2.z < z) || (v1.z > z && v2.z > z) {
-        return None; // Edge doesn't cross plane
-    }
+/// Crossing of an edge whose ends lie strictly on opposite sides of the plane
+fn edge_crossing(v1: Point3<f64>, v2: Point3<f64>, z: f64) -> Point3<f64> {
+    // Interpolate from a fixed end so both triangles sharing the edge agree
+    let (v1, v2) = if (v1.x, v1.y, v1.z) <= (v2.x, v2.y, v2.z) { (v1, v2) } else { (v2, v1) };
 
     // Linear interpolation to find intersection point
     let t = (z - v1.z) / (v2.z - v1.z);
-    Some(Point3::new(
+    Point3::new(
         v1.x + t * (v2.x - v1.x),
         v1.y + t * (v2.y - v1.y),
         z,
-    ))
+    )
 }
 
 impl BoundingBox {
+    pub fn from_vertices<'a, I: IntoIterator<Item = &'a Point3<f64>>>(vertices: I) -> Self {
+        let mut min = Point3::new(f64::MAX, f64::MAX, f64::MAX);
+        let mut max = Point3::new(f64::MIN, f64::MIN, f64::MIN);
+
+        for vertex in vertices {
+            min.x = min.x.min(vertex.x);
+            min.y = min.y.min(vertex.y);
+            min.z = min.z.min(vertex.z);
+            max.x = max.x.max(vertex.x);
+            max.y = max.y.max(vertex.y);
+            max.z = max.z.max(vertex.z);
+        }
+
+        BoundingBox { min, max }
+    }
+
+    /// Check that the box fits a build volume spanning from the origin.
+    /// Boxes may extend below Z=0, since sunk models are clipped at the bed.
+    pub fn check_fit(&self, build_volume: [f64; 3]) -> Result<()> {
+        let mut overflows = Vec::new();
+
+        for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+            let below = if axis < 2 { (-self.min[axis]).max(0.0) } else { 0.0 };
+            let above = (self.max[axis] - build_volume[axis]).max(0.0);
+            let excess = below + above;
+            if excess > 1e-9 {
+                overflows.push(format!("{} by {:.2} mm", name, excess));
+            }
+        }
+
+        if overflows.is_empty() {
+            Ok(())
+        } else {
+            Err(SlicerError::SlicingError(format!(
+                "Model exceeds the {}x{}x{} mm build volume: {}",
+                build_volume[0], build_volume[1], build_volume[2],
+                overflows.join(", ")
+            )))
+        }
+    }
+
+    pub fn center(&self) -> Point3<f64> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
     pub fn dimensions(&self) -> Vector3<f64> {
         Vector3::new(
             self.max.x - self.min.x,
@@ -170,3 +413,9 @@ impl BoundingBox {
         )
     }
 }
+
+type PointKey = (u64, u64, u64);
+
+fn point_key(p: &Point3<f64>) -> PointKey {
+    (p.x.to_bits(), p.y.to_bits(), p.z.to_bits())
+}
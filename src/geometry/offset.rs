@@ -0,0 +1,88 @@
+use nalgebra::{Point2, Vector2};
+use super::overlay::{into_islands, island_paths, overlay};
+use super::{Island, Polygon};
+
+/// Longest miter allowed at a sharp corner, as a multiple of the offset
+/// distance; sharper corners are beveled
+const MITER_LIMIT: f64 = 2.0;
+
+impl Polygon {
+    /// Grow the polygon by `delta` (shrink it when negative), independent of
+    /// its winding.
+    ///
+    /// Corners are mitered, or beveled when the miter would exceed twice the
+    /// offset. Self-intersections are resolved and regions thinner than
+    /// twice an inset vanish, so the result may hold several contours, or
+    /// none. Outlines come back counter-clockwise; an outset that closes a
+    /// concave gap yields clockwise holes.
+    pub fn offset(&self, delta: f64) -> Vec<Polygon> {
+        let mut outline = self.clone();
+        if outline.is_clockwise() {
+            outline.reverse();
+        }
+        overlay(&[offset_path(&outline.points, delta)], &[], |winding, _| winding > 0)
+    }
+}
+
+impl Island {
+    /// Grow the island by `delta` (shrink it when negative): the outline
+    /// moves outward and the holes shrink. Holes that break through the
+    /// outline merge with the outside, and an inset can split the island
+    /// into several.
+    pub fn offset(&self, delta: f64) -> Vec<Island> {
+        // With the solid on the left of every path, moving each edge to its
+        // right grows the island and shrinks its holes alike
+        let paths: Vec<Vec<Point2<f64>>> = island_paths(std::slice::from_ref(self))
+            .iter()
+            .map(|points| offset_path(points, delta))
+            .collect();
+
+        into_islands(overlay(&paths, &[], |winding, _| winding > 0))
+    }
+}
+
+/// Move every edge of a closed path `delta` to its right and join the
+/// moved edges. The result may self-intersect; regions it winds around
+/// negatively are the ones the offset collapsed.
+fn offset_path(points: &[Point2<f64>], delta: f64) -> Vec<Point2<f64>> {
+    let mut points = points.to_vec();
+    points.dedup();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let n = points.len();
+    if n < 3 || delta == 0.0 {
+        return points;
+    }
+
+    let normals: Vec<Vector2<f64>> = (0..n)
+        .map(|i| {
+            let d = (points[(i + 1) % n] - points[i]).normalize();
+            Vector2::new(d.y, -d.x)
+        })
+        .collect();
+
+    let mut path = Vec::with_capacity(2 * n);
+    for i in 0..n {
+        let p = points[i];
+        let (before, after) = (normals[(i + n - 1) % n], normals[i]);
+        let cross = before.x * after.y - before.y * after.x;
+
+        if cross * delta >= 0.0 && before.dot(&after) > 1.0 - 1e-12 {
+            // Straight on
+            path.push(p + after * delta);
+        } else if cross * delta < 0.0 {
+            // The moved edges overlap here; routing through the corner
+            // leaves a loop that resolving the path removes
+            path.extend([p + before * delta, p, p + after * delta]);
+        } else {
+            let cos = before.dot(&after);
+            if (2.0 / (1.0 + cos)).sqrt() <= MITER_LIMIT {
+                path.push(p + (before + after) * (delta / (1.0 + cos)));
+            } else {
+                path.extend([p + before * delta, p + after * delta]);
+            }
+        }
+    }
+    path
+}
@@ -0,0 +1,346 @@
+use nalgebra::Point2;
+use std::collections::BTreeMap;
+use super::{compare_position, nest_contours, Island, Polygon};
+
+/// Grid steps per millimetre; coordinates are snapped to this grid so that
+/// intersections and coincident edges compare exactly
+const SCALE: f64 = 1e6;
+
+type IPoint = (i64, i64);
+
+/// Deduplicated edge from the lexicographically smaller endpoint to the
+/// larger, with the net number of times each operand runs along it that way
+struct Edge {
+    from: IPoint,
+    to: IPoint,
+    weight: [i32; 2],
+}
+
+/// Resolve the regions enclosed by two sets of closed paths.
+///
+/// `keep` receives the winding numbers of the subject and clip paths around
+/// a point and decides whether it belongs to the result. Paths may
+/// self-intersect and overlap each other. The returned contours have the
+/// kept region on their left: outlines counter-clockwise, holes clockwise.
+pub(crate) fn overlay<F>(subject: &[Vec<Point2<f64>>], clip: &[Vec<Point2<f64>>], keep: F) -> Vec<Polygon>
+where
+    F: Fn(i32, i32) -> bool,
+{
+    let mut segments = Vec::new();
+    for (operand, paths) in [subject, clip].into_iter().enumerate() {
+        for path in paths {
+            let points: Vec<IPoint> = path.iter().map(quantize).collect();
+            for i in 0..points.len() {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                if a != b {
+                    segments.push((a, b, operand));
+                }
+            }
+        }
+    }
+
+    let edges = split_and_merge(&segments);
+
+    // An edge bounds the result where exactly one of its sides is kept; it
+    // is directed so the kept side is on its left
+    let spans = [SpanIndex::new(&edges, |p| p.1), SpanIndex::new(&edges, |p| p.0)];
+    let mut boundary = Vec::new();
+    for (i, edge) in edges.iter().enumerate() {
+        let (left, right) = side_windings(&edges, &spans, i);
+        match (keep(left[0], left[1]), keep(right[0], right[1])) {
+            (true, false) => boundary.push((edge.from, edge.to)),
+            (false, true) => boundary.push((edge.to, edge.from)),
+            _ => {}
+        }
+    }
+
+    trace_loops(&boundary)
+        .into_iter()
+        .map(|points| Polygon::new(points.iter().map(|&(x, y)| Point2::new(x as f64 / SCALE, y as f64 / SCALE)).collect()))
+        .collect()
+}
+
+/// Outline and hole point lists of the islands, wound with the solid on
+/// their left
+pub(crate) fn island_paths(islands: &[Island]) -> Vec<Vec<Point2<f64>>> {
+    let mut paths = Vec::new();
+    for island in islands {
+        let mut island = island.clone();
+        island.normalize();
+        paths.push(island.outline.points);
+        paths.extend(island.holes.into_iter().map(|hole| hole.points));
+    }
+    paths
+}
+
+/// Group overlay output into normalized islands in position order
+pub(crate) fn into_islands(contours: Vec<Polygon>) -> Vec<Island> {
+    let mut islands = nest_contours(contours);
+    for island in &mut islands {
+        island.normalize();
+    }
+    islands.sort_by(|a, b| compare_position(&a.outline, &b.outline));
+    islands
+}
+
+fn quantize(p: &Point2<f64>) -> IPoint {
+    ((p.x * SCALE).round() as i64, (p.y * SCALE).round() as i64)
+}
+
+/// Twice the signed area of the triangle a, b, c: positive when c is left of a→b
+fn orient(a: IPoint, b: IPoint, c: IPoint) -> i128 {
+    (b.0 - a.0) as i128 * (c.1 - a.1) as i128 - (b.1 - a.1) as i128 * (c.0 - a.0) as i128
+}
+
+/// Position of p along a→b, scaled by |b - a|²
+fn along(a: IPoint, b: IPoint, p: IPoint) -> i128 {
+    (b.0 - a.0) as i128 * (p.0 - a.0) as i128 + (b.1 - a.1) as i128 * (p.1 - a.1) as i128
+}
+
+/// Whether p lies on a→b strictly between its endpoints
+fn inside_segment(a: IPoint, b: IPoint, p: IPoint) -> bool {
+    orient(a, b, p) == 0 && along(a, b, p) > 0 && along(a, b, p) < along(a, b, b)
+}
+
+/// Split the segments wherever they cross or touch each other, then merge
+/// the identical pieces this produces into weighted edges
+fn split_and_merge(segments: &[(IPoint, IPoint, usize)]) -> Vec<Edge> {
+    let mut cuts: Vec<Vec<IPoint>> = vec![Vec::new(); segments.len()];
+
+    // Sweep in X so only segments with overlapping extents are compared
+    let min_x = |i: usize| segments[i].0 .0.min(segments[i].1 .0);
+    let max_x = |i: usize| segments[i].0 .0.max(segments[i].1 .0);
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by_key(|&i| min_x(i));
+
+    for (k, &i) in order.iter().enumerate() {
+        let (a, b, _) = segments[i];
+        for &j in &order[k + 1..] {
+            if min_x(j) > max_x(i) {
+                break;
+            }
+            let (c, d, _) = segments[j];
+            if a.1.max(b.1) < c.1.min(d.1) || c.1.max(d.1) < a.1.min(b.1) {
+                continue;
+            }
+
+            let (o1, o2) = (orient(a, b, c), orient(a, b, d));
+            let (o3, o4) = (orient(c, d, a), orient(c, d, b));
+            if (o1 > 0 && o2 < 0 || o1 < 0 && o2 > 0) && (o3 > 0 && o4 < 0 || o3 < 0 && o4 > 0) {
+                let t = o3 as f64 / (o3 - o4) as f64;
+                let p = (
+                    (a.0 as f64 + t * (b.0 - a.0) as f64).round() as i64,
+                    (a.1 as f64 + t * (b.1 - a.1) as f64).round() as i64,
+                );
+                cuts[i].push(p);
+                cuts[j].push(p);
+                continue;
+            }
+
+            // Touching or collinear overlap: cut each at the other's endpoints
+            for p in [c, d] {
+                if inside_segment(a, b, p) {
+                    cuts[i].push(p);
+                }
+            }
+            for p in [a, b] {
+                if inside_segment(c, d, p) {
+                    cuts[j].push(p);
+                }
+            }
+        }
+    }
+
+    let mut merged: BTreeMap<(IPoint, IPoint), [i32; 2]> = BTreeMap::new();
+    for (i, &(a, b, operand)) in segments.iter().enumerate() {
+        let mut points = std::mem::take(&mut cuts[i]);
+        points.retain(|&p| p != a && p != b);
+        points.sort_by_key(|&p| along(a, b, p));
+        points.dedup();
+
+        let chain: Vec<IPoint> = std::iter::once(a).chain(points).chain(std::iter::once(b)).collect();
+        for pair in chain.windows(2) {
+            let (p, q) = (pair[0], pair[1]);
+            let (key, sign) = if p < q { ((p, q), 1) } else { ((q, p), -1) };
+            merged.entry(key).or_insert([0, 0])[operand] += sign;
+        }
+    }
+
+    merged
+        .into_iter()
+        .filter(|(_, weight)| *weight != [0, 0])
+        .map(|((from, to), weight)| Edge { from, to, weight })
+        .collect()
+}
+
+/// Edges bucketed by the range of one coordinate they span, to find those
+/// a ray along the other axis may cross
+struct SpanIndex {
+    min: i64,
+    width: i64,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl SpanIndex {
+    fn new(edges: &[Edge], coordinate: fn(IPoint) -> i64) -> Self {
+        let span = |edge: &Edge| {
+            let (a, b) = (coordinate(edge.from), coordinate(edge.to));
+            (a.min(b), a.max(b))
+        };
+        let min = edges.iter().map(|e| span(e).0).min().unwrap_or(0);
+        let max = edges.iter().map(|e| span(e).1).max().unwrap_or(0);
+        let count = (edges.len() / 4).max(1);
+        let width = ((max - min) / count as i64).max(1) + 1;
+
+        let mut buckets = vec![Vec::new(); count];
+        for (i, edge) in edges.iter().enumerate() {
+            let (lo, hi) = span(edge);
+            for bucket in &mut buckets[((lo - min) / width) as usize..=((hi - min) / width) as usize] {
+                bucket.push(i);
+            }
+        }
+        SpanIndex { min, width, buckets }
+    }
+
+    /// Edges whose span may include `value`
+    fn candidates(&self, value: i64) -> &[usize] {
+        let bucket = (value - self.min).div_euclid(self.width);
+        usize::try_from(bucket).ok().and_then(|b| self.buckets.get(b)).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Winding numbers of both operands just left and just right of edge `index`
+fn side_windings(edges: &[Edge], spans: &[SpanIndex; 2], index: usize) -> ([i32; 2], [i32; 2]) {
+    let edge = &edges[index];
+
+    // Cast the ray in +X from the edge's midpoint; horizontal edges are
+    // handled in a frame rotated by 90 degrees, which keeps winding numbers.
+    // Coordinates are doubled so the midpoint stays on the grid.
+    let rotate = edge.from.1 == edge.to.1;
+    let frame = |p: IPoint| -> (i128, i128) {
+        let (x, y) = (2 * p.0 as i128, 2 * p.1 as i128);
+        if rotate {
+            (-y, x)
+        } else {
+            (x, y)
+        }
+    };
+    let (from, to) = (frame(edge.from), frame(edge.to));
+    let m = ((from.0 + to.0) / 2, (from.1 + to.1) / 2);
+
+    // Only edges spanning the ray's line can cross it; in the rotated frame
+    // that line is vertical in the original one
+    let line = m.1.div_euclid(2) as i64;
+    let candidates = spans[usize::from(rotate)].candidates(line);
+
+    // Winding just to the +X side of the midpoint, leaving out the edge itself
+    let mut beyond = [0, 0];
+    for &i in candidates {
+        if i == index {
+            continue;
+        }
+        let other = &edges[i];
+        let (u, v) = (frame(other.from), frame(other.to));
+        let side = (v.0 - u.0) * (m.1 - u.1) - (v.1 - u.1) * (m.0 - u.0);
+        let sign = if u.1 <= m.1 && v.1 > m.1 && side > 0 {
+            1
+        } else if u.1 > m.1 && v.1 <= m.1 && side < 0 {
+            -1
+        } else {
+            0
+        };
+        for (total, weight) in beyond.iter_mut().zip(other.weight) {
+            *total += sign * weight;
+        }
+    }
+
+    // Crossing the edge towards -X adds its own weight when it runs upwards
+    let upward = to.1 > from.1;
+    let step = if upward { 1 } else { -1 };
+    let before = [beyond[0] + step * edge.weight[0], beyond[1] + step * edge.weight[1]];
+
+    // An upward edge has -X on its left
+    if upward {
+        (before, beyond)
+    } else {
+        (beyond, before)
+    }
+}
+
+/// Join directed boundary edges into closed loops. Where several edges
+/// leave a vertex, the sharpest left turn is taken so regions touching at a
+/// point come out as separate loops.
+fn trace_loops(edges: &[(IPoint, IPoint)]) -> Vec<Vec<IPoint>> {
+    let mut outgoing: BTreeMap<IPoint, Vec<usize>> = BTreeMap::new();
+    for (i, &(from, _)) in edges.iter().enumerate() {
+        outgoing.entry(from).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut loops = Vec::new();
+
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut points = vec![edges[start].0];
+        let mut current = start;
+
+        loop {
+            let (from, to) = edges[current];
+            let heading = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+            let next = outgoing.get(&to).and_then(|candidates| {
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|&c| !used[c] || c == start)
+                    .max_by(|&a, &b| turn(heading, edges[a]).total_cmp(&turn(heading, edges[b])))
+            });
+
+            match next {
+                Some(next) if next != start => {
+                    used[next] = true;
+                    points.push(to);
+                    current = next;
+                }
+                // Back at the start, or stranded by inconsistent input
+                _ => break,
+            }
+        }
+
+        let points = remove_collinear(points);
+        if points.len() >= 3 {
+            loops.push(points);
+        }
+    }
+
+    loops
+}
+
+/// Signed angle from `heading` to the direction of `edge`, left turns positive
+fn turn(heading: (f64, f64), edge: (IPoint, IPoint)) -> f64 {
+    let d = ((edge.1 .0 - edge.0 .0) as f64, (edge.1 .1 - edge.0 .1) as f64);
+    let cross = heading.0 * d.1 - heading.1 * d.0;
+    let dot = heading.0 * d.0 + heading.1 * d.1;
+    cross.atan2(dot)
+}
+
+/// Drop vertices that lie on the line through their neighbours
+fn remove_collinear(mut points: Vec<IPoint>) -> Vec<IPoint> {
+    let mut i = 0;
+    let mut since_removal = 0;
+    while points.len() >= 3 && since_removal < points.len() {
+        let n = points.len();
+        let (prev, cur, next) = (points[(i + n - 1) % n], points[i % n], points[(i + 1) % n]);
+        if orient(prev, cur, next) == 0 {
+            points.remove(i % n);
+            since_removal = 0;
+        } else {
+            i += 1;
+            since_removal += 1;
+        }
+        i %= points.len().max(1);
+    }
+    points
+}
@@ -0,0 +1,509 @@
+use nalgebra::Point2;
+
+/// Closed 2D polygon; the last point connects back to the first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<Point2<f64>>,
+}
+
+/// Axis-aligned 2D rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox2D {
+    pub min: Point2<f64>,
+    pub max: Point2<f64>,
+}
+
+/// Straight 2D segment, e.g. an infill line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineSegment2D {
+    pub start: Point2<f64>,
+    pub end: Point2<f64>,
+}
+
+/// Connected printable region of a layer: an outline with optional holes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Island {
+    pub outline: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Point2<f64>>) -> Self {
+        Polygon { points }
+    }
+
+    /// Build a polygon with near-duplicate points removed, see
+    /// [`Polygon::dedup_points`]
+    pub fn new_cleaned(points: Vec<Point2<f64>>, tolerance: f64) -> Self {
+        let mut polygon = Polygon { points };
+        polygon.dedup_points(tolerance);
+        polygon
+    }
+
+    /// Remove points within `tolerance` of the point before them, including
+    /// a final point that repeats the first
+    pub fn dedup_points(&mut self, tolerance: f64) {
+        self.points.dedup_by(|p, kept| (*p - *kept).norm() <= tolerance);
+        while self.points.len() > 1 && (self.points[self.points.len() - 1] - self.points[0]).norm() <= tolerance {
+            self.points.pop();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Enclosed area (shoelace formula)
+    pub fn area(&self) -> f64 {
+        self.signed_area().abs()
+    }
+
+    /// Shoelace area, positive for counter-clockwise and negative for
+    /// clockwise point order
+    pub fn signed_area(&self) -> f64 {
+        let n = self.points.len();
+        let twice: f64 = (0..n)
+            .map(|i| {
+                let a = self.points[i];
+                let b = self.points[(i + 1) % n];
+                a.x * b.y - b.x * a.y
+            })
+            .sum();
+        twice / 2.0
+    }
+
+    /// Center of mass of the enclosed area. Polygons without area (fewer
+    /// than three points, or all on a line) fall back to the average of
+    /// their points; an empty polygon gives the origin.
+    pub fn centroid(&self) -> Point2<f64> {
+        let n = self.points.len();
+        if n == 0 {
+            return Point2::origin();
+        }
+
+        let area = self.signed_area();
+        if area.abs() < f64::EPSILON {
+            let sum = self.points.iter().fold(nalgebra::Vector2::zeros(), |sum, p| sum + p.coords);
+            return Point2::from(sum / n as f64);
+        }
+
+        let (mut cx, mut cy) = (0.0, 0.0);
+        for i in 0..n {
+            let a = self.points[i];
+            let b = self.points[(i + 1) % n];
+            let cross = a.x * b.y - b.x * a.y;
+            cx += (a.x + b.x) * cross;
+            cy += (a.y + b.y) * cross;
+        }
+        Point2::new(cx / (6.0 * area), cy / (6.0 * area))
+    }
+
+    /// Reverse the point order, flipping the winding
+    pub fn reverse(&mut self) {
+        self.points.reverse();
+    }
+
+    /// Drop points that deviate less than `tolerance` from the simplified
+    /// outline (Douglas-Peucker). The polygon is split at its first point and
+    /// the point farthest from it so the closing edge is simplified too; at
+    /// least 3 points are always kept.
+    pub fn simplify(&self, tolerance: f64) -> Polygon {
+        let n = self.points.len();
+        if n <= 3 || tolerance <= 0.0 {
+            return self.clone();
+        }
+
+        let far = (1..n)
+            .max_by(|&a, &b| {
+                let da = (self.points[a] - self.points[0]).norm_squared();
+                let db = (self.points[b] - self.points[0]).norm_squared();
+                da.total_cmp(&db)
+            })
+            .unwrap();
+
+        let mut keep = vec![false; n];
+        keep[0] = true;
+        keep[far] = true;
+        let closed: Vec<Point2<f64>> = self.points.iter().chain(&self.points[..1]).copied().collect();
+        douglas_peucker(&closed, 0, far, tolerance, &mut keep);
+        douglas_peucker(&closed, far, n, tolerance, &mut keep);
+
+        let mut points: Vec<Point2<f64>> = (0..n).filter(|&i| keep[i]).map(|i| self.points[i]).collect();
+
+        // Two anchors alone can't form a polygon; restore the farthest outlier
+        if points.len() < 3 {
+            let extra = (1..n)
+                .filter(|&i| i != far)
+                .max_by(|&a, &b| {
+                    let da = segment_distance(self.points[a], self.points[0], self.points[far]);
+                    let db = segment_distance(self.points[b], self.points[0], self.points[far]);
+                    da.total_cmp(&db)
+                })
+                .unwrap();
+            keep[extra] = true;
+            points = (0..n).filter(|&i| keep[i]).map(|i| self.points[i]).collect();
+        }
+
+        Polygon::new(points)
+    }
+
+    /// Subdivide edges longer than `max_spacing` into equal parts. Original
+    /// points are kept and new ones lie on the edges, so corners and area
+    /// are unchanged.
+    pub fn resample(&self, max_spacing: f64) -> Polygon {
+        let n = self.points.len();
+        if n < 2 || max_spacing <= 0.0 {
+            return self.clone();
+        }
+
+        let mut points = Vec::with_capacity(n);
+        for i in 0..n {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            let pieces = ((b - a).norm() / max_spacing).ceil().max(1.0) as usize;
+            points.extend((0..pieces).map(|k| a + (b - a) * (k as f64 / pieces as f64)));
+        }
+        Polygon::new(points)
+    }
+
+    /// Exactly `count` points evenly spaced along the outline, starting at
+    /// the first point. Unlike [`Polygon::resample`] this moves corners, so
+    /// area shrinks slightly.
+    pub fn resample_uniform(&self, count: usize) -> Polygon {
+        let n = self.points.len();
+        let perimeter = self.perimeter();
+        if n < 2 || count == 0 || perimeter == 0.0 {
+            return self.clone();
+        }
+
+        let step = perimeter / count as f64;
+        let mut points = Vec::with_capacity(count);
+        let mut edge = 0;
+        // Distance along the outline to the start of `edge`
+        let mut travelled = 0.0;
+        for k in 0..count {
+            let target = k as f64 * step;
+            let mut length = (self.points[(edge + 1) % n] - self.points[edge]).norm();
+            while travelled + length < target && edge + 1 < n {
+                travelled += length;
+                edge += 1;
+                length = (self.points[(edge + 1) % n] - self.points[edge]).norm();
+            }
+            let (a, b) = (self.points[edge], self.points[(edge + 1) % n]);
+            let t = if length > 0.0 { ((target - travelled) / length).clamp(0.0, 1.0) } else { 0.0 };
+            points.push(a + (b - a) * t);
+        }
+        Polygon::new(points)
+    }
+
+    /// Closest point on the outline to `p`, with the index of the edge it
+    /// lies on (edge i runs from point i to the next) and how far along that
+    /// edge it is, from 0 to 1. Panics on an empty polygon.
+    pub fn closest_point(&self, p: &Point2<f64>) -> (Point2<f64>, usize, f64) {
+        let n = self.points.len();
+        assert!(n > 0, "closest point on an empty polygon");
+
+        let mut best = (self.points[0], 0, 0.0);
+        let mut best_squared = f64::INFINITY;
+        for i in 0..n {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            // Skip edges whose bounding box is already too far away
+            let dx = (a.x.min(b.x) - p.x).max(p.x - a.x.max(b.x)).max(0.0);
+            let dy = (a.y.min(b.y) - p.y).max(p.y - a.y.max(b.y)).max(0.0);
+            if dx * dx + dy * dy >= best_squared {
+                continue;
+            }
+
+            let ab = b - a;
+            let length_squared = ab.norm_squared();
+            let t = if length_squared == 0.0 { 0.0 } else { ((p - a).dot(&ab) / length_squared).clamp(0.0, 1.0) };
+            let q = a + ab * t;
+            let d = (p - q).norm_squared();
+            if d < best_squared {
+                best_squared = d;
+                best = (q, i, t);
+            }
+        }
+        best
+    }
+
+    /// Distance from `p` to the outline, zero on it; infinite for an empty
+    /// polygon
+    pub fn distance_to_point(&self, p: &Point2<f64>) -> f64 {
+        if self.points.is_empty() {
+            return f64::INFINITY;
+        }
+        (p - self.closest_point(p).0).norm()
+    }
+
+    /// Rotate the point list so it starts at the lexicographically smallest
+    /// point (by X, then Y), giving the same start no matter where stitching began
+    pub fn normalize_start(&mut self) {
+        let start = (0..self.points.len()).min_by(|&a, &b| {
+            let (p, q) = (self.points[a], self.points[b]);
+            p.x.total_cmp(&q.x).then(p.y.total_cmp(&q.y))
+        });
+        if let Some(start) = start {
+            self.points.rotate_left(start);
+        }
+    }
+
+    /// Length of the closed outline, including the closing edge
+    pub fn perimeter(&self) -> f64 {
+        let n = self.points.len();
+        (0..n).map(|i| (self.points[(i + 1) % n] - self.points[i]).norm()).sum()
+    }
+
+    /// Bounding rectangle, or None for an empty polygon
+    pub fn bounds(&self) -> Option<BoundingBox2D> {
+        let first = *self.points.first()?;
+        Some(self.points.iter().fold(BoundingBox2D { min: first, max: first }, |b, p| b.expand(p)))
+    }
+
+    /// Lower-left corner of the bounding rectangle
+    pub fn min_corner(&self) -> Point2<f64> {
+        self.points.iter().fold(
+            Point2::new(f64::INFINITY, f64::INFINITY),
+            |m, p| Point2::new(m.x.min(p.x), m.y.min(p.y)),
+        )
+    }
+
+    /// Orientation from the sign of [`Polygon::signed_area`]; polygons
+    /// without area count as counter-clockwise
+    pub fn is_clockwise(&self) -> bool {
+        self.signed_area() < 0.0
+    }
+
+    /// Whether `p` lies inside the polygon (even-odd rule). Points exactly on
+    /// an edge or vertex count as inside.
+    pub fn contains_point(&self, p: &Point2<f64>) -> bool {
+        match self.bounds() {
+            Some(bounds) if bounds.contains_point(p) => self.contains_point_in_bounds(p),
+            _ => false,
+        }
+    }
+
+    /// [`Polygon::contains_point`] for callers that already know `p` is
+    /// within the polygon's bounds
+    pub(crate) fn contains_point_in_bounds(&self, p: &Point2<f64>) -> bool {
+        let n = self.points.len();
+        let mut inside = false;
+        for i in 0..n {
+            let a = self.points[i];
+            let b = self.points[(i + 1) % n];
+            if on_segment(p, a, b) {
+                return true;
+            }
+            // Half-open in Y so a ray through a vertex is counted once
+            if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+}
+
+impl LineSegment2D {
+    pub fn new(start: Point2<f64>, end: Point2<f64>) -> Self {
+        LineSegment2D { start, end }
+    }
+
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).norm()
+    }
+
+    /// Point where the two segments meet, endpoints included. Parallel
+    /// segments never intersect, even when collinear and overlapping, since
+    /// they share no single point.
+    pub fn intersect(&self, other: &LineSegment2D) -> Option<Point2<f64>> {
+        let d = self.end - self.start;
+        let e = other.end - other.start;
+        let denominator = d.x * e.y - d.y * e.x;
+        if denominator.abs() <= f64::EPSILON * d.norm() * e.norm() {
+            return None;
+        }
+
+        let w = other.start - self.start;
+        let t = (w.x * e.y - w.y * e.x) / denominator;
+        let u = (w.x * d.y - w.y * d.x) / denominator;
+        ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then(|| self.start + d * t)
+    }
+}
+
+impl BoundingBox2D {
+    /// Grow the box to include `p`
+    pub fn expand(self, p: &Point2<f64>) -> Self {
+        BoundingBox2D {
+            min: Point2::new(self.min.x.min(p.x), self.min.y.min(p.y)),
+            max: Point2::new(self.max.x.max(p.x), self.max.y.max(p.y)),
+        }
+    }
+
+    /// Whether `p` lies within the box or on its border
+    pub fn contains_point(&self, p: &Point2<f64>) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x) && (self.min.y..=self.max.y).contains(&p.y)
+    }
+
+    /// Whether `other` lies entirely within this box
+    pub fn contains(&self, other: &BoundingBox2D) -> bool {
+        self.min.x <= other.min.x && self.min.y <= other.min.y
+            && self.max.x >= other.max.x && self.max.y >= other.max.y
+    }
+
+    /// Whether the boxes overlap or touch
+    pub fn intersects(&self, other: &BoundingBox2D) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x
+            && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+
+    /// Smallest box containing both
+    pub fn union(self, other: &BoundingBox2D) -> Self {
+        self.expand(&other.min).expand(&other.max)
+    }
+
+    pub fn dimensions(&self) -> nalgebra::Vector2<f64> {
+        self.max - self.min
+    }
+}
+
+impl Island {
+    pub fn new(outline: Polygon) -> Self {
+        Island { outline, holes: Vec::new() }
+    }
+
+    /// Outline area minus the area of the holes
+    pub fn area(&self) -> f64 {
+        self.outline.area() - self.holes.iter().map(Polygon::area).sum::<f64>()
+    }
+
+    /// Put the island in canonical form: the outline runs counter-clockwise
+    /// and holes clockwise, every polygon starts at its smallest point and
+    /// holes are ordered by position, so output is reproducible
+    pub fn normalize(&mut self) {
+        if self.outline.is_clockwise() {
+            self.outline.reverse();
+        }
+        self.outline.normalize_start();
+        for hole in &mut self.holes {
+            if !hole.is_clockwise() {
+                hole.reverse();
+            }
+            hole.normalize_start();
+        }
+        self.holes.sort_by(compare_position);
+    }
+
+    /// Whether `p` is printable area: inside the outline and outside every
+    /// hole. Points on the outline count as inside and points on a hole's
+    /// edge as outside.
+    pub fn contains_point(&self, p: &Point2<f64>) -> bool {
+        self.outline.contains_point(p) && !self.holes.iter().any(|hole| hole.contains_point(p))
+    }
+
+    /// Distance from `p` to the nearest outline or hole edge, negative where
+    /// `p` is inside the island and positive outside it or in a hole
+    pub fn signed_distance(&self, p: &Point2<f64>) -> f64 {
+        let distance = std::iter::once(&self.outline)
+            .chain(&self.holes)
+            .map(|polygon| polygon.distance_to_point(p))
+            .fold(f64::INFINITY, f64::min);
+        if self.contains_point(p) {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    /// Pieces of `segment` that lie inside the island, in order from its
+    /// start. Where the segment runs along the boundary, the side just to
+    /// its left decides; a segment touching a corner from outside yields
+    /// nothing there.
+    pub fn clip_segment(&self, segment: &LineSegment2D) -> Vec<LineSegment2D> {
+        let d = segment.end - segment.start;
+        if d.norm_squared() == 0.0 {
+            return Vec::new();
+        }
+        let side = |p: Point2<f64>| d.x * (p.y - segment.start.y) - d.y * (p.x - segment.start.x);
+
+        // Where the boundary crosses the segment's line, as fractions along
+        // the segment. Points on the line count as right of it, so a
+        // boundary passing through a vertex crosses exactly once and one
+        // grazing a vertex crosses zero or two times.
+        let mut crossings = Vec::new();
+        for polygon in std::iter::once(&self.outline).chain(&self.holes) {
+            let n = polygon.points.len();
+            for i in 0..n {
+                let (a, b) = (polygon.points[i], polygon.points[(i + 1) % n]);
+                let (sa, sb) = (side(a), side(b));
+                if (sa > 0.0) != (sb > 0.0) {
+                    let crossing = a + (b - a) * (sa / (sa - sb));
+                    crossings.push((crossing - segment.start).dot(&d) / d.norm_squared());
+                }
+            }
+        }
+        crossings.sort_by(f64::total_cmp);
+
+        // Along the whole line, crossings alternate between entering and
+        // leaving the island
+        crossings
+            .chunks_exact(2)
+            .filter_map(|pair| {
+                let (t0, t1) = (pair[0].max(0.0), pair[1].min(1.0));
+                (t1 > t0).then(|| LineSegment2D::new(segment.start + d * t0, segment.start + d * t1))
+            })
+            .collect()
+    }
+}
+
+/// Stable ordering of polygons by bounding-box corner, then area
+pub(crate) fn compare_position(a: &Polygon, b: &Polygon) -> std::cmp::Ordering {
+    let (pa, pb) = (a.min_corner(), b.min_corner());
+    pa.x.total_cmp(&pb.x)
+        .then(pa.y.total_cmp(&pb.y))
+        .then(a.area().total_cmp(&b.area()))
+}
+
+/// Mark the points between `first` and `last` that must be kept to stay
+/// within `tolerance` of the original path
+fn douglas_peucker(points: &[Point2<f64>], first: usize, last: usize, tolerance: f64, keep: &mut [bool]) {
+    if last <= first + 1 {
+        return;
+    }
+
+    let (index, distance) = (first + 1..last)
+        .map(|i| (i, segment_distance(points[i], points[first], points[last])))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+
+    if distance > tolerance {
+        keep[index] = true;
+        douglas_peucker(points, first, index, tolerance, keep);
+        douglas_peucker(points, index, last, tolerance, keep);
+    }
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+fn segment_distance(p: Point2<f64>, a: Point2<f64>, b: Point2<f64>) -> f64 {
+    let ab = b - a;
+    let length_squared = ab.norm_squared();
+    if length_squared == 0.0 {
+        return (p - a).norm();
+    }
+    let t = ((p - a).dot(&ab) / length_squared).clamp(0.0, 1.0);
+    (p - (a + ab * t)).norm()
+}
+
+/// Whether `p` lies on the segment from `a` to `b`, within floating point noise
+fn on_segment(p: &Point2<f64>, a: Point2<f64>, b: Point2<f64>) -> bool {
+    let (ab, ap) = (b - a, p - a);
+    let cross = ab.x * ap.y - ab.y * ap.x;
+    let length_squared = ab.norm_squared();
+    if cross.abs() > 1e-12 * length_squared.max(1.0) {
+        return false;
+    }
+    let t = ap.dot(&ab);
+    t >= 0.0 && t <= length_squared
+}
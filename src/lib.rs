@@ -8,8 +8,13 @@ pub mod error;
 pub mod config;
 pub mod geometry;
 pub mod slicer;
+pub mod toolpath;
 pub mod gcode;
 pub mod commands;
 
 pub use error::{SlicerError, Result};
 pub use config::SlicerConfig;
+pub use gcode::{GCodeGenerator, GCodeStats};
+pub use geometry::{Island, Mesh, Polygon};
+pub use slicer::{Layer, SliceEngine};
+pub use toolpath::{LayerPaths, PathRole, ToolPath};
@@ -0,0 +1,82 @@
+use nalgebra::Vector2;
+use super::Layer;
+use crate::geometry::boolean::{difference, union};
+use crate::geometry::{Island, Polygon};
+
+/// Solid skin printed over thin air, spanning between anchors on the layer
+/// below
+#[derive(Debug, Clone)]
+pub struct Bridge {
+    pub region: Island,
+    /// Direction in degrees that lines across the bridge should run
+    pub angle: f64,
+}
+
+/// Find the parts of `solid` skin that bridge a gap in the layer `below`.
+///
+/// A region with nothing under it is a bridge when its edge rests on
+/// `below` on two opposite sides, at least a line width on each. Lines run
+/// in the direction where the most edge is anchored at both ends, so they
+/// span from one anchor to the other. Unsupported regions narrower than a
+/// line, or only anchored on one side, are left as they are.
+pub fn detect_bridges(solid: &[Island], below: &Layer, line_width: f64) -> Vec<Bridge> {
+    if solid.is_empty() || below.islands.is_empty() {
+        return Vec::new();
+    }
+
+    let unsupported = difference(solid, &below.islands);
+    let shrunk: Vec<Island> = unsupported.iter().flat_map(|island| island.offset(-line_width / 2.0)).collect();
+    let grown: Vec<Island> = shrunk.iter().flat_map(|island| island.offset(line_width / 2.0)).collect();
+
+    union(&grown, &[])
+        .into_iter()
+        .filter_map(|region| {
+            let anchors = anchored_edges(&region, below, line_width);
+            let (angle, score) = (0..180)
+                .map(|degrees| (degrees as f64, anchored_both_ends(&anchors, degrees as f64)))
+                .fold((0.0, 0.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+            (score >= line_width).then_some(Bridge { region, angle })
+        })
+        .collect()
+}
+
+/// Pieces of the region's edge, at most a line width long, that rest on the
+/// layer below, as their outward normal scaled by their length
+fn anchored_edges(region: &Island, below: &Layer, line_width: f64) -> Vec<Vector2<f64>> {
+    // Outlines run counter-clockwise and holes clockwise, so the region is
+    // always on the left of each edge
+    let polygons: Vec<Polygon> = std::iter::once(&region.outline)
+        .chain(&region.holes)
+        .map(|polygon| polygon.resample(line_width))
+        .collect();
+
+    let mut anchors = Vec::new();
+    for polygon in &polygons {
+        let n = polygon.points.len();
+        for i in 0..n {
+            let (a, b) = (polygon.points[i], polygon.points[(i + 1) % n]);
+            let edge = b - a;
+            let length = edge.norm();
+            if length == 0.0 {
+                continue;
+            }
+            let normal = Vector2::new(edge.y, -edge.x) / length;
+            let probe = nalgebra::center(&a, &b) + normal * (line_width / 2.0);
+            if below.islands.iter().any(|island| island.contains_point(&probe)) {
+                anchors.push(normal * length);
+            }
+        }
+    }
+    anchors
+}
+
+/// Anchored edge length facing each way along `angle`, the smaller of the
+/// two
+fn anchored_both_ends(anchors: &[Vector2<f64>], angle: f64) -> f64 {
+    let direction = Vector2::new(angle.to_radians().cos(), angle.to_radians().sin());
+    let (forward, backward) = anchors.iter().fold((0.0, 0.0), |(forward, backward), anchor| {
+        let along = anchor.dot(&direction);
+        if along > 0.0 { (forward + along, backward) } else { (forward, backward - along) }
+    });
+    f64::min(forward, backward)
+}
@@ -0,0 +1,33 @@
+use crate::geometry::boolean::union;
+use crate::geometry::{Island, Polygon};
+
+/// Loops of a brim around the first layer's islands, innermost first.
+///
+/// Enough loops to cover `width` are laid `line_width` apart, the first
+/// touching the islands. Where loops from neighbouring islands would
+/// overlap they are merged into one, so nothing is printed twice. Unless
+/// `only_outside` is set, the brim is also laid inside holes.
+pub fn generate_brim(islands: &[Island], width: f64, line_width: f64, only_outside: bool) -> Vec<Polygon> {
+    if width <= 0.0 || line_width <= 0.0 {
+        return Vec::new();
+    }
+    let count = (width / line_width - 1e-9).ceil() as usize;
+
+    let footprint: Vec<Island> = if only_outside {
+        islands.iter().map(|island| Island::new(island.outline.clone())).collect()
+    } else {
+        islands.to_vec()
+    };
+
+    let mut loops = Vec::new();
+    for index in 0..count {
+        let distance = line_width / 2.0 + index as f64 * line_width;
+        let grown: Vec<Island> = footprint.iter().flat_map(|island| island.offset(distance)).collect();
+        for island in union(&grown, &[]) {
+            loops.push(island.outline);
+            loops.extend(island.holes);
+        }
+    }
+
+    loops
+}
@@ -0,0 +1,58 @@
+use crate::geometry::boolean::{difference, union};
+use crate::geometry::{nest_contours, Island};
+
+/// Times the inset is halved for an island it would remove before the
+/// island is left as it is
+const INSET_RETRIES: usize = 3;
+
+/// Inset `islands` by `inset` mm, moving outlines in and holes out.
+///
+/// An island the full inset would remove is inset by half as much, and so
+/// on, keeping it at about the smallest size it can have; failing that it
+/// is left as it is. Returns the islands and how many were kept that way.
+pub fn inset_islands(islands: &[Island], inset: f64) -> (Vec<Island>, usize) {
+    let mut kept = 0;
+    let mut result = Vec::with_capacity(islands.len());
+    for island in islands {
+        let shrunk = island.offset(-inset);
+        if !shrunk.is_empty() {
+            result.extend(shrunk);
+            continue;
+        }
+        kept += 1;
+        let smaller = (1..=INSET_RETRIES)
+            .map(|halvings| island.offset(-inset / 2f64.powi(halvings as i32)))
+            .find(|shrunk| !shrunk.is_empty());
+        result.extend(smaller.unwrap_or_else(|| vec![island.clone()]));
+    }
+    (result, kept)
+}
+
+/// Grow `islands` by `distance` mm, or shrink them when it is negative as
+/// `inset_islands` does. Islands grown into each other merge. Returns the
+/// islands and how many were kept from vanishing.
+pub fn offset_islands(islands: &[Island], distance: f64) -> (Vec<Island>, usize) {
+    if distance < 0.0 {
+        return inset_islands(islands, -distance);
+    }
+    let grown: Vec<Island> = islands.iter().flat_map(|island| island.offset(distance)).collect();
+    (union(&grown, &[]), 0)
+}
+
+/// Grow the holes of `islands` by `distance` mm, or shrink them when it is
+/// negative; holes shrunk away are filled in. An island its grown holes
+/// would leave nothing of keeps the holes it had.
+pub fn offset_holes(islands: &[Island], distance: f64) -> Vec<Island> {
+    islands
+        .iter()
+        .flat_map(|island| {
+            if island.holes.is_empty() {
+                return vec![island.clone()];
+            }
+            let solid = Island::new(island.outline.clone());
+            let holes: Vec<Island> = island.holes.iter().flat_map(|hole| nest_contours(hole.offset(distance))).collect();
+            let result = difference(&[solid], &holes);
+            if result.is_empty() { vec![island.clone()] } else { result }
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+use nalgebra::{Point2, Rotation2, Vector2};
+use std::collections::HashMap;
+use crate::geometry::boolean::intersection;
+use crate::geometry::{BoundingBox2D, Island, LineSegment2D, Polygon};
+
+/// Area left for infill inside an island's walls: the area inside
+/// `perimeters` lines of `line_width`, grown by `overlap` mm so the infill
+/// bonds to the innermost wall. It never reaches past the middle of the
+/// outer wall.
+pub fn infill_region(island: &Island, perimeters: usize, line_width: f64, overlap: f64) -> Vec<Island> {
+    let inner = island.offset(-(perimeters as f64 * line_width));
+    if overlap <= 0.0 || inner.is_empty() {
+        return inner;
+    }
+    let grown: Vec<Island> = inner.iter().flat_map(|island| island.offset(overlap)).collect();
+    intersection(&grown, &island.offset(-line_width / 2.0))
+}
+
+/// Parallel lines `spacing` apart at `angle_degrees` from the X axis,
+/// clipped to `region` and linked end to end into zigzag paths wherever the
+/// link between neighbouring lines stays inside the region.
+///
+/// Lines lie on a fixed grid through the origin, so they line up across
+/// islands and layers.
+pub fn rectilinear_infill(region: &[Island], spacing: f64, angle_degrees: f64) -> Vec<Vec<Point2<f64>>> {
+    if spacing <= 0.0 || region.is_empty() {
+        return Vec::new();
+    }
+
+    // Work in a frame where the lines run along X
+    let to_frame = Rotation2::new(-angle_degrees.to_radians());
+    let rotated: Vec<Island> = region.iter().map(|island| rotate_island(island, &to_frame)).collect();
+    let Some(bounds) = rotated
+        .iter()
+        .filter_map(|island| island.outline.bounds())
+        .reduce(|a, b| a.union(&b))
+    else {
+        return Vec::new();
+    };
+
+    let scanlines = scanlines(&rotated, &bounds, spacing);
+    let paths = link_zigzag(&rotated, scanlines);
+
+    let from_frame = to_frame.inverse();
+    paths
+        .into_iter()
+        .map(|path| path.into_iter().map(|p| from_frame * p).collect())
+        .collect()
+}
+
+/// Lines as `rectilinear_infill` lays them, but each piece on its own and
+/// all running the same way, in order across the grid, so every line is
+/// laid against the one before it
+pub fn monotonic_infill(region: &[Island], spacing: f64, angle_degrees: f64) -> Vec<Vec<Point2<f64>>> {
+    if spacing <= 0.0 || region.is_empty() {
+        return Vec::new();
+    }
+
+    let to_frame = Rotation2::new(-angle_degrees.to_radians());
+    let rotated: Vec<Island> = region.iter().map(|island| rotate_island(island, &to_frame)).collect();
+    let Some(bounds) = rotated
+        .iter()
+        .filter_map(|island| island.outline.bounds())
+        .reduce(|a, b| a.union(&b))
+    else {
+        return Vec::new();
+    };
+
+    let from_frame = to_frame.inverse();
+    scanlines(&rotated, &bounds, spacing)
+        .into_iter()
+        .flatten()
+        .map(|piece| vec![from_frame * piece.start, from_frame * piece.end])
+        .collect()
+}
+
+/// Walls of a grid of pointy-topped hexagons, clipped to `region` and
+/// merged end to end into polylines.
+///
+/// `spacing` is `line_width / density` as for rectilinear infill; cells are
+/// sized so their walls cover the same fraction of the area as lines that
+/// far apart. `shifted` moves the grid half a cell along X, so layers can
+/// alternate between two positions.
+pub fn honeycomb_infill(region: &[Island], spacing: f64, shifted: bool) -> Vec<Vec<Point2<f64>>> {
+    if spacing <= 0.0 || region.is_empty() {
+        return Vec::new();
+    }
+    let Some(bounds) = region
+        .iter()
+        .filter_map(|island| island.outline.bounds())
+        .reduce(|a, b| a.union(&b))
+    else {
+        return Vec::new();
+    };
+
+    // Each cell owns three of its six walls, so wall length per cell is 3s
+    // over an area of 3√3/2 s²
+    let side = 2.0 * spacing / 3f64.sqrt();
+    let (dx, dy) = (3f64.sqrt() * side, 1.5 * side);
+    let shift = if shifted { dx / 2.0 } else { 0.0 };
+    let corner = |center: Point2<f64>, degrees: f64| {
+        let angle = degrees.to_radians();
+        center + Vector2::new(angle.cos(), angle.sin()) * side
+    };
+
+    let rows = (bounds.min.y / dy).floor() as i64 - 1..=(bounds.max.y / dy).ceil() as i64 + 1;
+    let mut walls = Vec::new();
+    for row in rows {
+        let offset = shift + if row.rem_euclid(2) == 1 { dx / 2.0 } else { 0.0 };
+        let columns = ((bounds.min.x - offset) / dx).floor() as i64 - 1..=((bounds.max.x - offset) / dx).ceil() as i64 + 1;
+        for column in columns {
+            let center = Point2::new(column as f64 * dx + offset, row as f64 * dy);
+            // The upper right, right and lower right walls; neighbours own
+            // the rest
+            for (from, to) in [(90.0, 30.0), (30.0, -30.0), (-30.0, -90.0)] {
+                let wall = LineSegment2D::new(corner(center, from), corner(center, to));
+                walls.extend(region.iter().flat_map(|island| island.clip_segment(&wall)));
+            }
+        }
+    }
+
+    merge_segments(walls, side * 1e-6)
+}
+
+/// Chain segments that share endpoints (within `tolerance`) into polylines,
+/// dropping the joint where neighbouring segments are collinear
+fn merge_segments(segments: Vec<LineSegment2D>, tolerance: f64) -> Vec<Vec<Point2<f64>>> {
+    let key = |p: Point2<f64>| ((p.x / tolerance).round() as i64, (p.y / tolerance).round() as i64);
+    let mut ends: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, segment) in segments.iter().enumerate() {
+        ends.entry(key(segment.start)).or_default().push(i);
+        ends.entry(key(segment.end)).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    // Follow unused segments away from `from`, appending their far ends
+    let extend = |path: &mut Vec<Point2<f64>>, used: &mut Vec<bool>| loop {
+        let from = path[path.len() - 1];
+        let Some(&next) = ends[&key(from)].iter().find(|&&i| !used[i]) else {
+            break;
+        };
+        used[next] = true;
+        let segment = &segments[next];
+        let far = if key(segment.start) == key(from) { segment.end } else { segment.start };
+        push_point(path, far);
+    };
+
+    let mut paths = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        let mut path = vec![segment.start, segment.end];
+        extend(&mut path, &mut used);
+        path.reverse();
+        extend(&mut path, &mut used);
+        paths.push(path);
+    }
+    paths
+}
+
+/// Append `point`, replacing the last point instead when it lies on the
+/// straight line from the one before to `point`
+fn push_point(path: &mut Vec<Point2<f64>>, point: Point2<f64>) {
+    if let [.., a, b] = path[..] {
+        let (ab, bp) = (b - a, point - b);
+        if (ab.x * bp.y - ab.y * bp.x).abs() <= 1e-9 * ab.norm() * bp.norm() && ab.dot(&bp) > 0.0 {
+            path.pop();
+        }
+    }
+    path.push(point);
+}
+
+pub(super) fn rotate_island(island: &Island, rotation: &Rotation2<f64>) -> Island {
+    let rotate = |polygon: &Polygon| Polygon::new(polygon.points.iter().map(|p| rotation * p).collect());
+    Island { outline: rotate(&island.outline), holes: island.holes.iter().map(rotate).collect() }
+}
+
+/// Pieces of each horizontal grid line inside the region, bottom to top and
+/// left to right
+fn scanlines(region: &[Island], bounds: &BoundingBox2D, spacing: f64) -> Vec<Vec<LineSegment2D>> {
+    let first = (bounds.min.y / spacing).ceil() as i64;
+    let last = (bounds.max.y / spacing).floor() as i64;
+    let (left, right) = (bounds.min.x - 1.0, bounds.max.x + 1.0);
+
+    (first..=last)
+        .map(|k| {
+            let y = k as f64 * spacing;
+            let line = LineSegment2D::new(Point2::new(left, y), Point2::new(right, y));
+            let mut pieces: Vec<LineSegment2D> = region.iter().flat_map(|island| island.clip_segment(&line)).collect();
+            pieces.sort_by(|a, b| a.start.x.total_cmp(&b.start.x));
+            pieces
+        })
+        .filter(|pieces| !pieces.is_empty())
+        .collect()
+}
+
+/// Chain scanline pieces into zigzags, reversing every other one, and start
+/// a new path where no neighbour can be reached without leaving the region
+fn link_zigzag(region: &[Island], scanlines: Vec<Vec<LineSegment2D>>) -> Vec<Vec<Point2<f64>>> {
+    let mut remaining: Vec<Vec<Option<LineSegment2D>>> =
+        scanlines.into_iter().map(|line| line.into_iter().map(Some).collect()).collect();
+    let mut paths = Vec::new();
+
+    for row in 0..remaining.len() {
+        for column in 0..remaining[row].len() {
+            let Some(piece) = remaining[row][column].take() else {
+                continue;
+            };
+            let mut path = vec![piece.start, piece.end];
+            let mut leftwards = true;
+
+            for next_row in remaining.iter_mut().skip(row + 1) {
+                let end = path[path.len() - 1];
+                // The piece in the next row whose near end is closest
+                let candidate = next_row
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, piece)| {
+                        let piece = (*piece)?;
+                        let near = if leftwards { piece.end } else { piece.start };
+                        Some((i, piece, (near - end).norm()))
+                    })
+                    .min_by(|a, b| a.2.total_cmp(&b.2));
+                let Some((i, piece, _)) = candidate else {
+                    break;
+                };
+
+                let (near, far) = if leftwards { (piece.end, piece.start) } else { (piece.start, piece.end) };
+                if !stays_inside(region, end, near) {
+                    break;
+                }
+                next_row[i] = None;
+                path.extend([near, far]);
+                leftwards = !leftwards;
+            }
+
+            paths.push(path);
+        }
+    }
+
+    paths
+}
+
+/// Whether the straight move from `a` to `b` lies within the region. Links
+/// often run right along the boundary, where clipping judges by the side to
+/// the left of the move, so both directions are tried.
+fn stays_inside(region: &[Island], a: Point2<f64>, b: Point2<f64>) -> bool {
+    let inside = |link: LineSegment2D| -> f64 {
+        region.iter().flat_map(|island| island.clip_segment(&link)).map(|piece| piece.length()).sum()
+    };
+    let length = (b - a).norm();
+    let inside = inside(LineSegment2D::new(a, b)).max(inside(LineSegment2D::new(b, a)));
+    inside >= length * (1.0 - 1e-6)
+}
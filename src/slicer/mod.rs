@@ -1,29 +1,176 @@
-use crate::geometry::{Mesh, LineSegment};
+use crate::geometry::{compare_position, convex_hull, nest_contours, BoundingBox2D, Island, Mesh, LineSegment, Polygon, Triangle};
+use crate::config::{SlicePosition, SlicerConfig};
+use crate::geometry::arc_fit::fit_circle;
+use crate::geometry::boolean::close_gaps;
 use crate::error::{SlicerError, Result};
-use nalgebra::Point3;
-use rayon::prelude::*;
-use std::collections::HashMap;
+use crate::toolpath::{LayerPaths, PathPlanner};
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
+
+mod bridge;
+mod brim;
+mod compensate;
+mod infill;
+mod modifier;
+mod overhang;
+mod paths;
+mod shield;
+mod skin;
+mod stitch;
+mod stream;
+mod support;
+
+pub use bridge::{detect_bridges, Bridge};
+pub use brim::generate_brim;
+pub use compensate::{inset_islands, offset_holes, offset_islands};
+pub use infill::{honeycomb_infill, infill_region, monotonic_infill, rectilinear_infill};
+pub use modifier::split_by_modifiers;
+pub use overhang::{detect_overhangs, LayerOverhang, OverhangReport};
+pub use paths::{gap_fill, generate_perimeters, split_thin_walls, PerimeterKind, PerimeterLoop, ThinPath};
+pub use shield::generate_draft_shield;
+pub use skin::{classify_infill, classify_layer, InfillRegions};
+pub(crate) use skin::classify_islands;
+pub use stitch::{build_contours, merge_duplicate_segments, StitchedContours};
+pub use stream::LayerIter;
+pub use support::{generate_supports, SupportLayer};
+pub use crate::geometry::{ContourNode, ContourTree};
 
 #[derive(Debug, Clone)]
 pub struct Layer {
-    pub z: f64,
-    pub contours: Vec<Contour>,
+    /// Top of the layer, measured from the bed: the model's bottom, or Z=0
+    /// for a model sunk into the bed
+    pub z_height: f64,
+    pub layer_index: usize,
+    pub islands: Vec<Island>,
+    pub warnings: Vec<LayerWarning>,
+    /// Contour points before simplification
+    pub stitched_points: usize,
 }
 
-#[derive(Debug, Clone)]
-pub struct Contour {
-    pub points: Vec<Point3<f64>>,
-    pub is_outer: bool,
+/// Problems found while slicing a layer that didn't stop it from being printed
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerWarning {
+    /// A chain of segments that could not be closed and was left out
+    OpenContour { gap: f64, points: usize },
+    /// Islands and holes smaller than `min_area` mm² that were removed
+    DroppedFeatures { islands: usize, holes: usize, min_area: f64 },
+    /// Nothing to print on this layer although there is model above it
+    EmptyLayer,
+    /// Islands too small for an `inset` in mm from size or elephant foot
+    /// compensation, kept at a smaller inset
+    KeptSmallFeatures { islands: usize, inset: f64 },
 }
 
-pub struct Slicer {
+impl fmt::Display for LayerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerWarning::OpenContour { gap, points } => {
+                write!(f, "open contour with {} points, gap {:.4} mm", points, gap)
+            }
+            LayerWarning::DroppedFeatures { islands, holes, min_area } => {
+                write!(f, "dropped {} islands and {} holes under {:.4} mm²", islands, holes, min_area)
+            }
+            LayerWarning::EmptyLayer => write!(f, "empty layer inside the model"),
+            LayerWarning::KeptSmallFeatures { islands, inset } => {
+                write!(f, "kept {} islands too small for a {:.4} mm inset at a smaller one", islands, inset)
+            }
+        }
+    }
+}
+
+impl Layer {
+    /// True if the layer has nothing to print
+    pub fn is_empty(&self) -> bool {
+        self.islands.is_empty()
+    }
+
+    pub fn island_count(&self) -> usize {
+        self.islands.len()
+    }
+
+    /// Printed area: island outlines minus their holes
+    pub fn total_area(&self) -> f64 {
+        self.islands.iter().map(Island::area).sum()
+    }
+
+    /// Combined length of all outlines and holes
+    pub fn perimeter_length(&self) -> f64 {
+        self.polygons().map(Polygon::perimeter).sum()
+    }
+
+    /// 2D bounds of everything printed on the layer, or None if it is empty
+    pub fn bounding_box(&self) -> Option<BoundingBox2D> {
+        self.islands
+            .iter()
+            .filter_map(|island| island.outline.bounds())
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Convex hull of every island outline, e.g. for skirts; degenerate for
+    /// an empty layer
+    pub fn convex_hull(&self) -> Polygon {
+        let points: Vec<_> = self.islands.iter().flat_map(|island| island.outline.points.iter().copied()).collect();
+        convex_hull(&points)
+    }
+
+    fn polygons(&self) -> impl Iterator<Item = &Polygon> {
+        self.islands.iter().flat_map(|island| std::iter::once(&island.outline).chain(&island.holes))
+    }
+
+    /// Number of contour points across all islands
+    pub fn point_count(&self) -> usize {
+        self.polygons().map(Polygon::len).sum()
+    }
+
+    /// Number of closed contours, outlines and holes, across all islands
+    pub fn contour_count(&self) -> usize {
+        self.polygons().count()
+    }
+
+    /// Every hole across all islands, with its diameter if it is round
+    /// within `tolerance`
+    pub fn hole_diameters(&self, tolerance: f64) -> Vec<Option<f64>> {
+        self.islands
+            .iter()
+            .flat_map(|island| &island.holes)
+            .map(|hole| fit_circle(hole, tolerance).map(|(_, radius)| 2.0 * radius))
+            .collect()
+    }
+
+    /// Number of contours that could not be closed on this layer
+    pub fn open_contours(&self) -> usize {
+        self.warnings
+            .iter()
+            .filter(|w| matches!(w, LayerWarning::OpenContour { .. }))
+            .count()
+    }
+}
+
+/// Distance within which a vertex counts as lying on a slicing plane
+const PLANE_TOLERANCE: f64 = 1e-10;
+
+/// Least distance a plane through vertices is lowered by whatever
+/// `plane_epsilon` is, so faces lying in it (such as the model's top) are
+/// never cut edge-on
+const MIN_PLANE_OFFSET: f64 = 1e-9;
+
+pub struct SliceEngine {
     mesh: Mesh,
-    layer_height: f64,
+    config: SlicerConfig,
+    /// Z of every triangle vertex, sorted, to find planes that hit vertices
+    vertex_zs: Vec<f64>,
+    /// Worker threads for slicing; 0 means all cores
+    threads: usize,
+    #[cfg(feature = "parallel")]
+    pool: Option<Arc<rayon::ThreadPool>>,
 }
 
-impl Slicer {
-    pub fn new(mesh: Mesh, layer_height: f64) -> Result<Self> {
-        if layer_height <= 0.0 {
+impl SliceEngine {
+    pub fn new(mesh: Mesh, config: SlicerConfig) -> Result<Self> {
+        if config.layer_height <= 0.0 || config.first_layer_height <= 0.0 {
             return Err(SlicerError::InvalidParameter(
                 "Layer height must be positive".to_string()
             ));
@@ -31,98 +178,370 @@ impl Slicer {
 
         mesh.validate()?;
 
-        Ok(Slicer {
+        let mut vertex_zs: Vec<f64> = mesh.triangles
+            .iter()
+            .flat_map(|t| t.vertices.iter().map(|v| v.z))
+            .collect();
+        vertex_zs.sort_unstable_by(f64::total_cmp);
+
+        let threads = config.threads;
+        SliceEngine {
             mesh,
-            layer_height,
-        })
+            config,
+            vertex_zs,
+            threads: 0,
+            #[cfg(feature = "parallel")]
+            pool: None,
+        }
+        .with_threads(threads)
+    }
+
+    /// Limit slicing to `threads` worker threads; 0 uses all cores and 1
+    /// slices sequentially on the calling thread. Without the `parallel`
+    /// feature slicing is always sequential.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self> {
+        self.threads = threads;
+
+        #[cfg(feature = "parallel")]
+        {
+            self.pool = if threads > 1 {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| SlicerError::InvalidParameter(format!("Could not start {} threads: {}", threads, e)))?;
+                Some(Arc::new(pool))
+            } else {
+                None
+            };
+        }
+
+        Ok(self)
+    }
+
+    /// Number of layers sliced at the same time
+    pub fn thread_count(&self) -> usize {
+        #[cfg(feature = "parallel")]
+        if self.threads == 0 {
+            return rayon::current_num_threads();
+        }
+        self.threads.max(1)
+    }
+
+    /// Slice the given layers, in parallel unless limited to one thread
+    fn map_layers<F>(&self, layers: Range<usize>, slice: F) -> Vec<Result<Layer>>
+    where
+        F: Fn(usize) -> Result<Layer> + Sync + Send,
+    {
+        #[cfg(feature = "parallel")]
+        if self.threads != 1 {
+            use rayon::prelude::*;
+            let run = || layers.clone().into_par_iter().map(&slice).collect();
+            return match &self.pool {
+                Some(pool) => pool.install(run),
+                None => run(),
+            };
+        }
+
+        layers.map(slice).collect()
+    }
+
+    /// Check that the mesh, and the brim and draft shield around it, fit the
+    /// build volume before slicing. Both are assumed to surround the
+    /// model's widest extent.
+    pub fn check_fit(&self, build_volume: [f64; 3]) -> Result<()> {
+        let mut bounds = self.mesh.bounds.clone();
+        let mut margin = self.config.brim_width.max(0.0);
+        if self.config.draft_shield {
+            margin = margin.max(self.config.draft_shield_distance + self.config.nozzle_diameter / 2.0);
+        }
+        bounds.min.x -= margin;
+        bounds.min.y -= margin;
+        bounds.max.x += margin;
+        bounds.max.y += margin;
+        bounds.check_fit(build_volume)
+    }
+
+    /// Height of the model above the bed
+    pub fn model_height(&self) -> f64 {
+        self.mesh.bounds.max.z - self.bed_z()
+    }
+
+    /// Number of layers the model will be sliced into
+    pub fn layer_count(&self) -> usize {
+        self.count_layers(self.model_height())
+    }
+
+    fn count_layers(&self, height: f64) -> usize {
+        let first = self.config.first_layer_height;
+
+        if height <= 0.0 {
+            0
+        } else if height <= first {
+            1
+        } else {
+            1 + ((height - first) / self.config.layer_height - 1e-9).ceil() as usize
+        }
+    }
+
+    /// Bottom and top of a layer, measured from the bed. The first layer is
+    /// `first_layer_height` thick and every later one `layer_height`.
+    pub fn layer_bounds(&self, layer_index: usize) -> (f64, f64) {
+        let first = self.config.first_layer_height;
+        if layer_index == 0 {
+            (0.0, first)
+        } else {
+            let bottom = first + (layer_index - 1) as f64 * self.config.layer_height;
+            (bottom, bottom + self.config.layer_height)
+        }
+    }
+
+    /// Index of the layer whose top is nearest `height` mm above the bed,
+    /// the later one on a tie, or None for a model without layers
+    pub fn layer_at_height(&self, height: f64) -> Option<usize> {
+        (0..self.layer_count())
+            .map(|index| (index, (self.layer_bounds(index).1 - height).abs()))
+            .reduce(|nearest, layer| if layer.1 <= nearest.1 { layer } else { nearest })
+            .map(|(index, _)| index)
     }
 
     pub fn slice(&self) -> Result<Vec<Layer>> {
-        let min_z = self.mesh.bounds.min.z;
-        let max_z = self.mesh.bounds.max.z;
-        let num_layers = ((max_z - min_z) / self.layer_height).ceil() as usize;
+        self.slice_cancellable(&AtomicBool::new(false))
+    }
 
-        if num_layers == 0 {
+    /// Slice the model, giving up with `SlicerError::Cancelled` once `cancel`
+    /// is set. The flag is checked before every layer and between the stages
+    /// of each layer; layers already sliced are dropped.
+    pub fn slice_cancellable(&self, cancel: &AtomicBool) -> Result<Vec<Layer>> {
+        if self.layer_count() == 0 {
             return Err(SlicerError::SlicingError("Model has no height".to_string()));
         }
+        log::info!("Slicing {} layers...", self.layer_count());
 
-        println!("Slicing {} layers...", num_layers);
+        // All layers at once gives the thread pool the most parallel work
+        self.layers_iter().cancel_on(cancel).lookahead(usize::MAX).collect()
+    }
 
-        let layers: Vec<Layer> = (0..num_layers)
-            .into_par_iter()
-            .map(|i| {
-                let z = min_z + (i as f64 + 0.5) * self.layer_height;
-                self.slice_layer(z)
-            })
-            .collect();
+    /// Slice the model and lay out the paths printing each layer: walls,
+    /// infill, support and the travel between them, in printing order
+    pub fn generate_paths(&self) -> Result<Vec<LayerPaths>> {
+        let layers = self.slice()?;
+        let mut paths = Vec::with_capacity(layers.len());
+        PathPlanner::new(&self.config).plan(layers.into_iter().map(Ok), |_, _, layer_paths| {
+            paths.push(layer_paths);
+            Ok(())
+        })?;
+        Ok(paths)
+    }
 
-        Ok(layers)
+    /// Slice only the part of the model between `z_min` and `z_max` mm above
+    /// the bed. The slab is treated as if it rested on the bed: layers are
+    /// indexed from 0, the first one is `first_layer_height` thick, and
+    /// heights are reported from the slab's bottom.
+    pub fn slice_range(&self, z_min: f64, z_max: f64) -> Result<Vec<Layer>> {
+        self.slice_range_cancellable(z_min, z_max, &AtomicBool::new(false))
     }
 
-    fn slice_layer(&self, z: f64) -> Layer {
-        let mut segments: Vec<LineSegment> = self.mesh.triangles
-            .iter()
-            .filter_map(|triangle| triangle.intersect_plane(z))
-            .collect();
+    /// `slice_range`, giving up with `SlicerError::Cancelled` once `cancel`
+    /// is set, as for `slice_cancellable`
+    pub fn slice_range_cancellable(&self, z_min: f64, z_max: f64, cancel: &AtomicBool) -> Result<Vec<Layer>> {
+        let layers = self.layers_iter_range(z_min, z_max)?;
+        log::info!("Slicing {} layers...", layers.layer_count());
+        layers.cancel_on(cancel).lookahead(usize::MAX).collect()
+    }
 
-        let contours = build_contours(&mut segments);
+    /// Layers in ascending Z, sliced on demand a few at a time so only a
+    /// small window of them is held in memory
+    pub fn layers_iter(&self) -> LayerIter<'_> {
+        LayerIter::new(self, 0.0, self.model_height())
+    }
 
-        Layer { z, contours }
+    /// Streaming counterpart of `slice_range`
+    pub fn layers_iter_range(&self, z_min: f64, z_max: f64) -> Result<LayerIter<'_>> {
+        let height = self.model_height();
+        if z_min >= z_max {
+            return Err(SlicerError::InvalidParameter(format!(
+                "Z range {}:{} is empty or inverted", z_min, z_max
+            )));
+        }
+        if z_min < 0.0 || z_max > height + 1e-9 {
+            return Err(SlicerError::InvalidParameter(format!(
+                "Z range {}:{} is outside the model (0 to {:.3} mm)", z_min, z_max, height
+            )));
+        }
+        Ok(LayerIter::new(self, z_min, z_max))
     }
-}
 
-fn build_contours(segments: &mut Vec<LineSegment>) -> Vec<Contour> {
-    let mut contours = Vec::new();
-    let epsilon = 1e-6;
-
-    while !segments.is_empty() {
-        let mut current_contour = vec![segments[0].start, segments[0].end];
-        segments.remove(0);
-
-        // Try to build a closed contour
-        let mut progress = true;
-        while progress && !segments.is_empty() {
-            progress = false;
-            let last_point = *current_contour.last().unwrap();
-
-            // Find a segment that connects to the current contour
-            for i in 0..segments.len() {
-                let seg = &segments[i];
-                
-                if distance_2d(&last_point, &seg.start) < epsilon {
-                    current_contour.push(seg.end);
-                    segments.remove(i);
-                    progress = true;
-                    break;
-                } else if distance_2d(&last_point, &seg.end) < epsilon {
-                    current_contour.push(seg.start);
-                    segments.remove(i);
-                    progress = true;
-                    break;
-                }
+    /// Slice layer `i` of a slab starting `base` mm up in model space and
+    /// `slab_top` mm tall. The layer's height is reported from the slab's bottom.
+    fn slice_indexed(&self, i: usize, base: f64, slab_top: f64, cancel: &AtomicBool) -> Result<Layer> {
+        check_cancelled(cancel)?;
+        let (bottom, top) = self.layer_bounds(i);
+
+        // A slab thinner than the first layer is printed as one layer of its
+        // own thickness, cut through its middle
+        if i == 0 && slab_top < top {
+            let mut layer = self.slice_layer(i, self.nudge_plane(base + slab_top / 2.0), cancel)?;
+            layer.z_height = slab_top;
+            return Ok(layer);
+        }
+
+        // The last layer may reach past the slab; keep its plane inside it
+        let plane = match self.config.slice_position {
+            SlicePosition::Middle => (bottom + top) / 2.0,
+            SlicePosition::Top => top.min(slab_top),
+        };
+        let mut layer = self.slice_layer(i, self.nudge_plane(base + plane), cancel)?;
+        layer.z_height = top;
+        Ok(layer)
+    }
+
+    /// Move a plane that passes through mesh vertices slightly down, so flat
+    /// faces and edges at that height (such as the model's top, or every
+    /// millimeter of a model drawn on a grid) aren't cut edge-on. Only the
+    /// cut moves; the layer keeps its nominal height.
+    fn nudge_plane(&self, z: f64) -> f64 {
+        let first = self.vertex_zs.partition_point(|&v| v < z - PLANE_TOLERANCE);
+        let on_plane = self.vertex_zs.get(first).is_some_and(|&v| v <= z + PLANE_TOLERANCE);
+
+        if on_plane {
+            z - (self.config.plane_epsilon * self.config.layer_height).max(MIN_PLANE_OFFSET)
+        } else {
+            z
+        }
+    }
+
+    /// Height of the print bed in model space. A mesh that crosses Z=0 has
+    /// been sunk into the bed on purpose, so nothing below Z=0 is sliced;
+    /// otherwise slicing starts at the model's lowest point.
+    fn bed_z(&self) -> f64 {
+        let bounds = &self.mesh.bounds;
+        if bounds.min.z < 0.0 && bounds.max.z > 0.0 {
+            0.0
+        } else {
+            bounds.min.z
+        }
+    }
+
+    fn slice_layer(&self, layer_index: usize, z: f64, cancel: &AtomicBool) -> Result<Layer> {
+        let segments: Vec<LineSegment> = self.mesh.triangles
+            .iter()
+            .filter_map(|triangle| {
+                let segment = triangle.intersect_plane_snapped(z, self.config.snap_grid)?;
+                Some(orient_segment(segment, triangle))
+            })
+            .collect();
+        let segments = merge_duplicate_segments(&segments);
+        check_cancelled(cancel)?;
+
+        let stitched = build_contours(&segments, self.config.contour_merge_tolerance());
+        let mut contours = stitched.closed;
+        let mut warnings = Vec::new();
+
+        // Close chains whose ends nearly meet; report the rest
+        let max_gap = self.config.max_gap_close();
+        for chain in stitched.open {
+            let gap = (chain.points[0] - chain.points[chain.len() - 1]).norm();
+            if gap <= max_gap && chain.len() >= 3 {
+                log::debug!("Layer {}: closed a {:.4} mm contour gap", layer_index, gap);
+                contours.push(chain);
+            } else {
+                warnings.push(LayerWarning::OpenContour { gap, points: chain.len() });
             }
         }
 
-        // Check if contour is closed
-        let first = current_contour.first().unwrap();
-        let last = current_contour.last().unwrap();
-        let is_closed = distance_2d(first, last) < epsilon;
+        let stitched_points = contours.iter().map(|c| c.len()).sum();
+        let contours: Vec<_> = contours
+            .into_iter()
+            .map(|mut c| {
+                c.dedup_points(self.config.contour_merge_tolerance());
+                c.simplify(self.config.resolution)
+            })
+            .collect();
 
-        if is_closed && current_contour.len() > 2 {
-            current_contour.pop(); // Remove duplicate last point
+        // Islands are normalized and sorted below so identical input gives
+        // identical G-code
+        let mut islands = nest_contours(contours);
+        let gap = self.config.close_gaps();
+        if gap > 0.0 {
+            islands = close_gaps(&islands, gap);
         }
 
-        if current_contour.len() >= 3 {
-            contours.push(Contour {
-                points: current_contour,
-                is_outer: true, // Simplified - proper implementation would determine this
+        let min_area = self.config.min_feature_area();
+        let (dropped_islands, dropped_holes) = drop_small_features(&mut islands, min_area);
+        if dropped_islands + dropped_holes > 0 {
+            warnings.push(LayerWarning::DroppedFeatures {
+                islands: dropped_islands,
+                holes: dropped_holes,
+                min_area,
             });
         }
+
+        // Size compensation: every outline, then the holes, then the first
+        // layer, which is squished wider than the rest
+        let xy = self.config.xy_compensation;
+        if xy != 0.0 {
+            let kept;
+            (islands, kept) = offset_islands(&islands, xy);
+            if kept > 0 {
+                warnings.push(LayerWarning::KeptSmallFeatures { islands: kept, inset: -xy });
+            }
+        }
+        if self.config.hole_compensation != 0.0 {
+            islands = offset_holes(&islands, self.config.hole_compensation);
+        }
+        let inset = self.config.elephant_foot_compensation;
+        if layer_index == 0 && inset > 0.0 {
+            let kept;
+            (islands, kept) = inset_islands(&islands, inset);
+            if kept > 0 {
+                warnings.push(LayerWarning::KeptSmallFeatures { islands: kept, inset });
+            }
+        }
+
+        for island in &mut islands {
+            island.normalize();
+        }
+        islands.sort_by(|a, b| compare_position(&a.outline, &b.outline));
+
+        Ok(Layer { z_height: z, layer_index, islands, warnings, stitched_points })
+    }
+}
+
+/// Remove holes and then islands whose area is under `min_area`; a dropped
+/// hole is simply filled in by its island. Returns the number of islands and
+/// holes removed.
+fn drop_small_features(islands: &mut Vec<Island>, min_area: f64) -> (usize, usize) {
+    let mut holes = 0;
+    for island in islands.iter_mut() {
+        let before = island.holes.len();
+        island.holes.retain(|hole| hole.area() >= min_area);
+        holes += before - island.holes.len();
     }
 
-    contours
+    let before = islands.len();
+    islands.retain(|island| island.area() >= min_area);
+    (before - islands.len(), holes)
 }
 
-fn distance_2d(p1: &Point3<f64>, p2: &Point3<f64>) -> f64 {
-    ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2)).sqrt()
+/// Point a segment so the solid lies to its left, using the triangle's
+/// winding rather than its stored normal, which STL files often leave zero
+fn orient_segment(segment: LineSegment, triangle: &Triangle) -> LineSegment {
+    let [a, b, c] = triangle.vertices;
+    let normal = (b - a).cross(&(c - a));
+    let direction = segment.end - segment.start;
+
+    // The outward normal should point to the right of the direction of travel
+    if direction.y * normal.x - direction.x * normal.y < 0.0 {
+        LineSegment { start: segment.end, end: segment.start }
+    } else {
+        segment
+    }
+}
+
+fn check_cancelled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::Relaxed) {
+        Err(SlicerError::Cancelled)
+    } else {
+        Ok(())
+    }
 }
@@ -0,0 +1,34 @@
+use crate::config::Modifier;
+use crate::geometry::boolean::{difference, intersection};
+use crate::geometry::Island;
+
+/// Split `region` by the modifiers spanning height `z` that set a value,
+/// pairing each part with the value it takes: `base` outside them all and
+/// the modifier's inside, applied in order so the later of two overlapping
+/// modifiers wins. Parts cut away entirely are left out.
+pub fn split_by_modifiers<T: Copy>(
+    region: &[Island],
+    z: f64,
+    modifiers: &[Modifier],
+    base: T,
+    value: impl Fn(&Modifier) -> Option<T>,
+) -> Vec<(Vec<Island>, T)> {
+    let mut parts = vec![(region.to_vec(), base)];
+    for modifier in modifiers.iter().filter(|modifier| modifier.spans(z)) {
+        let Some(value) = value(modifier) else {
+            continue;
+        };
+        let footprint = [modifier.footprint()];
+        let inside: Vec<Island> = parts.iter().flat_map(|(islands, _)| intersection(islands, &footprint)).collect();
+        if inside.is_empty() {
+            continue;
+        }
+        parts = parts
+            .into_iter()
+            .map(|(islands, value)| (difference(&islands, &footprint), value))
+            .filter(|(islands, _)| !islands.is_empty())
+            .collect();
+        parts.push((inside, value));
+    }
+    parts
+}
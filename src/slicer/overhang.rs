@@ -0,0 +1,84 @@
+use std::borrow::Borrow;
+use super::Layer;
+use crate::config::SlicerConfig;
+use crate::geometry::boolean::difference;
+use crate::geometry::Island;
+
+/// Part of one layer that would print over thin air
+#[derive(Debug, Clone)]
+pub struct LayerOverhang {
+    pub layer_index: usize,
+    pub z_height: f64,
+    pub regions: Vec<Island>,
+    /// Area of `regions` in mm²
+    pub area: f64,
+}
+
+/// Unsupported regions of a stack of layers; layers that are fully
+/// supported are left out
+#[derive(Debug, Clone, Default)]
+pub struct OverhangReport {
+    pub layers: Vec<LayerOverhang>,
+}
+
+impl OverhangReport {
+    /// Unsupported regions of a layer, if it has any
+    pub fn get(&self, layer_index: usize) -> Option<&LayerOverhang> {
+        self.layers.iter().find(|layer| layer.layer_index == layer_index)
+    }
+
+    /// Unsupported area summed over all layers in mm²
+    pub fn total_area(&self) -> f64 {
+        self.layers.iter().fold(0.0, |sum, layer| sum + layer.area)
+    }
+
+    /// Up to `count` layers with the most unsupported area, worst first
+    pub fn worst_layers(&self, count: usize) -> Vec<&LayerOverhang> {
+        let mut layers: Vec<&LayerOverhang> = self.layers.iter().collect();
+        layers.sort_by(|a, b| b.area.total_cmp(&a.area).then(a.layer_index.cmp(&b.layer_index)));
+        layers.truncate(count);
+        layers
+    }
+
+    pub fn needs_support(&self) -> bool {
+        !self.layers.is_empty()
+    }
+}
+
+/// Find where each layer overhangs the one below by more than the model can
+/// bridge on its own.
+///
+/// A wall leaning out by up to `overhang_angle` degrees from vertical
+/// supports itself, so every layer may stick out past the one below by its
+/// thickness times the tangent of that angle. Overhanging regions smaller
+/// than the minimum feature area are ignored. The first layer rests on the
+/// bed, and holds up the second by its outline before elephant foot
+/// compensation.
+pub fn detect_overhangs<L: Borrow<Layer>>(layers: &[L], config: &SlicerConfig) -> OverhangReport {
+    let slope = config.overhang_angle.to_radians().tan();
+    let min_area = config.min_feature_area();
+
+    let layers = layers
+        .windows(2)
+        .filter_map(|pair| {
+            let (below, layer) = (pair[0].borrow(), pair[1].borrow());
+            let mut reach = (layer.z_height - below.z_height) * slope;
+            // The first layer is inset against elephant foot only to squish
+            // back out to the model's outline, which is what holds up the next
+            if below.layer_index == 0 {
+                reach += config.elephant_foot_compensation;
+            }
+            let supported: Vec<Island> = below.islands.iter().flat_map(|island| island.offset(reach)).collect();
+
+            let mut regions = difference(&layer.islands, &supported);
+            regions.retain(|island| island.area() >= min_area);
+            if regions.is_empty() {
+                return None;
+            }
+            let area = regions.iter().map(Island::area).sum();
+            Some(LayerOverhang { layer_index: layer.layer_index, z_height: layer.z_height, regions, area })
+        })
+        .collect();
+
+    OverhangReport { layers }
+}
@@ -0,0 +1,194 @@
+use nalgebra::{Point2, Rotation2};
+use super::infill::rotate_island;
+use crate::geometry::boolean::{difference, union};
+use crate::geometry::{Island, LineSegment2D, Polygon};
+
+/// Narrowest gap filled, as a fraction of the line width
+const MIN_GAP_WIDTH: f64 = 0.3;
+/// Widest gap filled, as a fraction of the line width
+const MAX_GAP_WIDTH: f64 = 1.5;
+/// Narrowest and widest single lines printed along thin walls, as fractions
+/// of the line width
+const MIN_THIN_WALL_WIDTH: f64 = 0.5;
+const MAX_THIN_WALL_WIDTH: f64 = 2.0;
+
+/// Which wall of an island a loop is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerimeterKind {
+    /// The outermost wall, forming the printed surface
+    External,
+    Internal,
+    /// A single line along a sliver left between walls
+    GapFill,
+}
+
+/// One closed extrusion loop of an island's walls
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerimeterLoop {
+    pub polygon: Polygon,
+    pub kind: PerimeterKind,
+    /// Walls between this loop and the surface; 0 for external loops
+    pub index: usize,
+}
+
+/// Open extrusion along the middle of a thin region, with the region's
+/// width at each point so the flow can follow it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThinPath {
+    pub points: Vec<Point2<f64>>,
+    pub widths: Vec<f64>,
+    pub kind: PerimeterKind,
+}
+
+/// Walls of an island, outermost first: up to `count` loops around the
+/// outline and around each hole, spaced `line_width` apart with the first
+/// centered half a line width inside the surface. Where the island is too
+/// thin for more walls, the inner loops are left out.
+pub fn generate_perimeters(island: &Island, count: usize, line_width: f64) -> Vec<PerimeterLoop> {
+    let mut loops = Vec::new();
+
+    for index in 0..count {
+        let inset = line_width / 2.0 + index as f64 * line_width;
+        let kind = if index == 0 { PerimeterKind::External } else { PerimeterKind::Internal };
+
+        let walls = island.offset(-inset);
+        if walls.is_empty() {
+            break;
+        }
+        for wall in walls {
+            for polygon in std::iter::once(wall.outline).chain(wall.holes) {
+                loops.push(PerimeterLoop { polygon, kind, index });
+            }
+        }
+    }
+
+    loops
+}
+
+/// Split off the parts of an island narrower than two walls and print them
+/// as single external lines along their middle, each point as wide as the
+/// island is there (clamped to between half and twice `line_width`).
+///
+/// Returns the rest of the island, which gets walls as usual, and the thin
+/// wall lines. An island with no thin parts is returned as it is.
+pub fn split_thin_walls(island: &Island, line_width: f64) -> (Vec<Island>, Vec<ThinPath>) {
+    // Opening by a line width keeps exactly the parts two walls fit in
+    let opened: Vec<Island> = island.offset(-line_width).iter().flat_map(|island| island.offset(line_width)).collect();
+    let opened = union(&opened, &[]);
+
+    let (min, max) = (line_width * MIN_THIN_WALL_WIDTH, line_width * MAX_THIN_WALL_WIDTH);
+    let walls: Vec<ThinPath> = difference(std::slice::from_ref(island), &opened)
+        .iter()
+        .flat_map(|thin| centerlines(thin, line_width / 4.0, line_width * MIN_GAP_WIDTH, max))
+        .filter(|(points, _)| (points[points.len() - 1] - points[0]).norm() >= line_width)
+        .map(|(points, widths)| ThinPath {
+            points,
+            widths: widths.into_iter().map(|width| width.clamp(min, max)).collect(),
+            kind: PerimeterKind::External,
+        })
+        .collect();
+
+    if walls.is_empty() {
+        (vec![island.clone()], walls)
+    } else {
+        (opened, walls)
+    }
+}
+
+/// Lines down the slivers that `generate_perimeters` leaves between walls
+/// where an island is too thin for another loop, such as the middle of a
+/// rib slightly narrower than `count` walls on each side. Slivers between
+/// 0.3 and 1.5 line widths wide are filled.
+pub fn gap_fill(island: &Island, count: usize, line_width: f64) -> Vec<ThinPath> {
+    let half = line_width / 2.0;
+    let mut paths = Vec::new();
+
+    // Area inside the walls printed so far
+    let mut inner = island.offset(-line_width);
+    for _ in 1..count {
+        if inner.is_empty() {
+            break;
+        }
+        // The next wall covers whatever it can reach from its centerline
+        let centers: Vec<Island> = inner.iter().flat_map(|island| island.offset(-half)).collect();
+        let covered: Vec<Island> = centers.iter().flat_map(|island| island.offset(half)).collect();
+        for gap in difference(&inner, &covered) {
+            let thin = centerlines(&gap, line_width / 4.0, line_width * MIN_GAP_WIDTH, line_width * MAX_GAP_WIDTH);
+            paths.extend(
+                thin.into_iter()
+                    // Skip the specks left in corners
+                    .filter(|(points, _)| (points[points.len() - 1] - points[0]).norm() >= line_width)
+                    .map(|(points, widths)| ThinPath { points, widths, kind: PerimeterKind::GapFill }),
+            );
+        }
+        inner = centers.iter().flat_map(|island| island.offset(-half)).collect();
+    }
+
+    paths
+}
+
+/// Approximate centerlines of a thin region with its width along them.
+///
+/// The region is cut across its long axis every `step`; the middle of each
+/// cut between `min_width` and `max_width` long is a centerline point, and
+/// points on neighbouring cuts that overlap are chained. Regions that curve
+/// round are only followed where they cross the cuts steeply enough.
+fn centerlines(region: &Island, step: f64, min_width: f64, max_width: f64) -> Vec<(Vec<Point2<f64>>, Vec<f64>)> {
+    // Long axis from the spread of evenly spaced outline points
+    let samples = region.outline.resample(step).points;
+    if samples.len() < 3 {
+        return Vec::new();
+    }
+    let mean = samples.iter().fold(nalgebra::Vector2::zeros(), |sum, p| sum + p.coords) / samples.len() as f64;
+    let (mut xx, mut xy, mut yy) = (0.0, 0.0, 0.0);
+    for p in &samples {
+        let d = p.coords - mean;
+        xx += d.x * d.x;
+        xy += d.x * d.y;
+        yy += d.y * d.y;
+    }
+    let axis = 0.5 * (2.0 * xy).atan2(xx - yy);
+
+    // Cuts run along Y in a frame with the long axis along X
+    let to_frame = Rotation2::new(-axis);
+    let rotated = rotate_island(region, &to_frame);
+    let Some(bounds) = rotated.outline.bounds() else {
+        return Vec::new();
+    };
+
+    let mut chains: Vec<(Vec<Point2<f64>>, Vec<f64>)> = Vec::new();
+    // Chains extended by the previous cut, with the Y span of that cut
+    let mut open: Vec<(usize, f64, f64)> = Vec::new();
+    let mut x = bounds.min.x + step / 2.0;
+    while x < bounds.max.x {
+        let cut = LineSegment2D::new(Point2::new(x, bounds.min.y - 1.0), Point2::new(x, bounds.max.y + 1.0));
+        let mut next = Vec::new();
+        for piece in rotated.clip_segment(&cut) {
+            let width = piece.length();
+            if width < min_width || width > max_width {
+                continue;
+            }
+            let (low, high) = (piece.start.y.min(piece.end.y), piece.start.y.max(piece.end.y));
+            let middle = nalgebra::center(&piece.start, &piece.end);
+            let chain = match open.iter().position(|&(_, a, b)| low <= b && a <= high) {
+                Some(i) => open.swap_remove(i).0,
+                None => {
+                    chains.push((Vec::new(), Vec::new()));
+                    chains.len() - 1
+                }
+            };
+            chains[chain].0.push(middle);
+            chains[chain].1.push(width);
+            next.push((chain, low, high));
+        }
+        open = next;
+        x += step;
+    }
+
+    let from_frame = to_frame.inverse();
+    chains
+        .into_iter()
+        .filter(|(points, _)| points.len() > 1)
+        .map(|(points, widths)| (points.into_iter().map(|p| from_frame * p).collect(), widths))
+        .collect()
+}
@@ -0,0 +1,19 @@
+use super::Layer;
+use crate::geometry::{convex_hull, Island, Polygon};
+use std::borrow::Borrow;
+
+/// Loop of a draft shield `distance` mm outside everything printed on
+/// `layers`: the convex hull of all their islands, grown. None when there
+/// is nothing to surround.
+pub fn generate_draft_shield<L: Borrow<Layer>>(layers: &[L], distance: f64) -> Option<Polygon> {
+    let points: Vec<_> = layers
+        .iter()
+        .flat_map(|layer| layer.borrow().islands.iter().flat_map(|island| island.outline.points.iter().copied()))
+        .collect();
+    let hull = convex_hull(&points);
+    if hull.len() < 3 {
+        return None;
+    }
+    // Growing a convex outline keeps it one convex loop
+    Island::new(hull).offset(distance).into_iter().next().map(|island| island.outline)
+}
@@ -0,0 +1,113 @@
+use super::{detect_bridges, infill_region, Bridge, Layer};
+use crate::config::SlicerConfig;
+use crate::geometry::boolean::{difference, intersection, union};
+use crate::geometry::Island;
+
+/// Infill area of one island, split into solid skin (where the island is a
+/// top or bottom surface, or close to one), skin bridging a gap in the layer
+/// below, and sparse infill
+#[derive(Debug, Clone, Default)]
+pub struct InfillRegions {
+    pub solid: Vec<Island>,
+    /// The part of `solid` the layer above leaves uncovered, forming a top
+    /// surface
+    pub top: Vec<Island>,
+    pub bridges: Vec<Bridge>,
+    pub sparse: Vec<Island>,
+}
+
+/// Classify the infill area of every island on `layer`, in island order.
+///
+/// `below` and `above` hold the neighbouring layers, nearest first; only the
+/// first `bottom_solid_layers` and `top_solid_layers` of them are looked at.
+/// Whatever is not covered by all of those is solid. With fewer neighbours
+/// than that on either side (the bottom and top of the model) the whole
+/// layer is solid. Solid skin over a gap in the layer right below, anchored
+/// on both sides, is split off as bridges.
+pub fn classify_layer(layer: &Layer, below: &[&Layer], above: &[&Layer], config: &SlicerConfig) -> Vec<InfillRegions> {
+    classify_islands(&layer.islands, config.perimeters(), below, above, config)
+}
+
+/// `classify_layer` for some of a layer's islands, inside `perimeters`
+/// walls
+pub(crate) fn classify_islands(
+    islands: &[Island],
+    perimeters: usize,
+    below: &[&Layer],
+    above: &[&Layer],
+    config: &SlicerConfig,
+) -> Vec<InfillRegions> {
+    let (bottom, top) = (config.bottom_solid_layers(), config.top_solid_layers());
+    let line_width = config.nozzle_diameter;
+    let all_solid = below.len() < bottom || above.len() < top;
+    let neighbours: Vec<&Layer> = below.iter().take(bottom).chain(above.iter().take(top)).copied().collect();
+
+    islands
+        .iter()
+        .map(|island| {
+            let region = infill_region(island, perimeters, line_width, config.infill_overlap_mm());
+            if all_solid {
+                return with_bridges(region, Vec::new(), below.first().copied(), above.first().copied(), line_width);
+            }
+
+            let mut covered = region.clone();
+            for neighbour in &neighbours {
+                if covered.is_empty() {
+                    break;
+                }
+                covered = intersection(&covered, &neighbour.islands);
+            }
+
+            // Slivers narrower than a line along sloped walls can't be
+            // printed as skin; leave them to the sparse infill
+            let exposed = difference(&region, &covered);
+            let solid = open(&exposed, line_width / 2.0);
+            let sparse = if solid.is_empty() { region } else { difference(&region, &solid) };
+            with_bridges(solid, sparse, below.first().copied(), above.first().copied(), line_width)
+        })
+        .collect()
+}
+
+/// Split the bridges over gaps in `below` off the solid skin, and find the
+/// top surface in what is left of it, out of reach of `above`
+fn with_bridges(
+    solid: Vec<Island>,
+    sparse: Vec<Island>,
+    below: Option<&Layer>,
+    above: Option<&Layer>,
+    line_width: f64,
+) -> InfillRegions {
+    let bridges = below.map_or_else(Vec::new, |below| detect_bridges(&solid, below, line_width));
+    let solid = if bridges.is_empty() {
+        solid
+    } else {
+        let spans: Vec<Island> = bridges.iter().map(|bridge| bridge.region.clone()).collect();
+        difference(&solid, &spans)
+    };
+    let top = match above {
+        Some(above) if !solid.is_empty() => difference(&solid, &above.islands),
+        _ => solid.clone(),
+    };
+    InfillRegions { solid, top, bridges, sparse }
+}
+
+/// Classify the infill of a whole stack of layers, as `classify_layer` with
+/// each layer's neighbours
+pub fn classify_infill(layers: &[Layer], config: &SlicerConfig) -> Vec<Vec<InfillRegions>> {
+    let (bottom, top) = (config.bottom_solid_layers(), config.top_solid_layers());
+    (0..layers.len())
+        .map(|i| {
+            let below: Vec<&Layer> = layers[..i].iter().rev().take(bottom).collect();
+            let above: Vec<&Layer> = layers[i + 1..].iter().take(top).collect();
+            classify_layer(&layers[i], &below, &above, config)
+        })
+        .collect()
+}
+
+/// Morphological open: drop the parts of `islands` narrower than twice
+/// `distance`
+fn open(islands: &[Island], distance: f64) -> Vec<Island> {
+    let shrunk: Vec<Island> = islands.iter().flat_map(|island| island.offset(-distance)).collect();
+    let grown: Vec<Island> = shrunk.iter().flat_map(|island| island.offset(distance)).collect();
+    union(&grown, &[])
+}
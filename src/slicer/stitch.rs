@@ -0,0 +1,160 @@
+use crate::geometry::{LineSegment, Polygon};
+use nalgebra::{Point2, Point3};
+use std::collections::HashMap;
+
+type Cell = (i64, i64);
+type PointKey = (u64, u64);
+
+/// Result of stitching a layer's segments
+#[derive(Debug, Clone, Default)]
+pub struct StitchedContours {
+    pub closed: Vec<Polygon>,
+    /// Chains whose ends never met, in path order
+    pub open: Vec<Polygon>,
+}
+
+/// Exact key of a point's XY coordinates, treating -0.0 and 0.0 alike
+fn point_key(p: &Point3<f64>) -> PointKey {
+    ((p.x + 0.0).to_bits(), (p.y + 0.0).to_bits())
+}
+
+/// Remove the duplicate segments left by doubled surfaces and internal walls.
+///
+/// Segments must be oriented consistently (outlines counter-clockwise) and
+/// have quantized endpoints. Identical copies running the same way collapse
+/// into one; copies running opposite ways cancel in pairs, since they come
+/// from the two sides of a face inside the model.
+pub fn merge_duplicate_segments(segments: &[LineSegment]) -> Vec<LineSegment> {
+    // Net direction of each undirected segment, keyed in canonical order
+    let mut net: HashMap<(PointKey, PointKey), (usize, i64)> = HashMap::with_capacity(segments.len());
+    for (i, seg) in segments.iter().enumerate() {
+        let (a, b) = (point_key(&seg.start), point_key(&seg.end));
+        let (key, direction) = if a <= b { ((a, b), 1) } else { ((b, a), -1) };
+        net.entry(key).or_insert((i, 0)).1 += direction;
+    }
+
+    // Keep the order of first appearance so the output is deterministic
+    let mut kept: Vec<(usize, i64)> = net.into_values().filter(|&(_, n)| n != 0).collect();
+    kept.sort_unstable();
+
+    kept.into_iter()
+        .map(|(i, n)| {
+            let seg = &segments[i];
+            let forward = point_key(&seg.start) <= point_key(&seg.end);
+            if forward == (n > 0) {
+                seg.clone()
+            } else {
+                LineSegment { start: seg.end, end: seg.start }
+            }
+        })
+        .collect()
+}
+
+/// Spatial hash of segment endpoints on a grid of `tolerance`-sized cells
+struct EndpointIndex {
+    cells: HashMap<Cell, Vec<usize>>,
+    tolerance: f64,
+}
+
+impl EndpointIndex {
+    fn new(segments: &[LineSegment], tolerance: f64) -> Self {
+        let mut index = EndpointIndex {
+            cells: HashMap::with_capacity(segments.len() * 2),
+            tolerance,
+        };
+        for (i, seg) in segments.iter().enumerate() {
+            index.cells.entry(index.cell(&seg.start)).or_default().push(i);
+            index.cells.entry(index.cell(&seg.end)).or_default().push(i);
+        }
+        index
+    }
+
+    fn cell(&self, p: &Point3<f64>) -> Cell {
+        ((p.x / self.tolerance).floor() as i64, (p.y / self.tolerance).floor() as i64)
+    }
+
+    /// Lowest-indexed unused segment with an endpoint within tolerance of `p`,
+    /// together with its opposite endpoint
+    fn find(&self, p: &Point3<f64>, segments: &[LineSegment], used: &[bool]) -> Option<(usize, Point3<f64>)> {
+        let (cx, cy) = self.cell(p);
+        let mut best: Option<(usize, Point3<f64>)> = None;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let Some(ids) = self.cells.get(&(cx + dx, cy + dy)) else { continue };
+                for &i in ids {
+                    if used[i] || best.is_some_and(|(b, _)| b <= i) {
+                        continue;
+                    }
+                    let seg = &segments[i];
+                    if distance_2d(p, &seg.start) < self.tolerance {
+                        best = Some((i, seg.end));
+                    } else if distance_2d(p, &seg.end) < self.tolerance {
+                        best = Some((i, seg.start));
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
+
+/// Chain plane-intersection segments into contours.
+///
+/// Endpoints closer than `tolerance` are considered connected. They are
+/// bucketed in a spatial hash so each connection is found in constant time,
+/// and consumed segments are tombstoned rather than removed. Chains that
+/// don't close are extended in both directions and returned as open.
+pub fn build_contours(segments: &[LineSegment], tolerance: f64) -> StitchedContours {
+    let index = EndpointIndex::new(segments, tolerance);
+    let mut used = vec![false; segments.len()];
+    let mut result = StitchedContours::default();
+
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+
+        let mut current_contour = vec![segments[first].start, segments[first].end];
+
+        // Extend the chain until no unused segment connects to its end
+        while let Some((i, next)) = index.find(current_contour.last().unwrap(), segments, &used) {
+            used[i] = true;
+            current_contour.push(next);
+        }
+
+        // Check if contour is closed
+        let first_point = current_contour.first().unwrap();
+        let last_point = current_contour.last().unwrap();
+        let is_closed = distance_2d(first_point, last_point) < tolerance;
+
+        if is_closed && current_contour.len() > 2 {
+            current_contour.pop(); // Remove duplicate last point
+        } else if !is_closed {
+            // Grow the open chain backwards from its start as well
+            current_contour.reverse();
+            while let Some((i, next)) = index.find(current_contour.last().unwrap(), segments, &used) {
+                used[i] = true;
+                current_contour.push(next);
+            }
+            current_contour.reverse();
+        }
+
+        let polygon = Polygon::new(
+            current_contour.iter().map(|p| Point2::new(p.x, p.y)).collect()
+        );
+        if !is_closed {
+            result.open.push(polygon);
+        } else if polygon.len() >= 3 {
+            result.closed.push(polygon);
+        }
+    }
+
+    result
+}
+
+fn distance_2d(p1: &Point3<f64>, p2: &Point3<f64>) -> f64 {
+    ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2)).sqrt()
+}
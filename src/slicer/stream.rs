@@ -0,0 +1,144 @@
+use super::{Layer, LayerWarning, SliceEngine};
+use crate::error::Result;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+
+static NOT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Iterator over the layers of a model in ascending Z.
+///
+/// Layers are sliced in batches of `lookahead`, in parallel when the engine
+/// has more than one thread, and handed out one
+/// by one, so at most one batch is held in memory. The iterator stops after
+/// the first error.
+pub struct LayerIter<'a> {
+    engine: &'a SliceEngine,
+    cancel: &'a AtomicBool,
+    /// Model-space height of the slab's bottom
+    base: f64,
+    slab_top: f64,
+    next_index: usize,
+    count: usize,
+    lookahead: usize,
+    pending: VecDeque<Result<Layer>>,
+    /// Empty layers dropped off the top that had warnings
+    trimmed: Vec<Layer>,
+    /// Whether a layer with something to print was handed out
+    printed: bool,
+}
+
+impl<'a> LayerIter<'a> {
+    pub(super) fn new(engine: &'a SliceEngine, z_min: f64, z_max: f64) -> Self {
+        LayerIter {
+            engine,
+            cancel: &NOT_CANCELLED,
+            base: engine.bed_z() + z_min,
+            slab_top: z_max - z_min,
+            next_index: 0,
+            count: engine.count_layers(z_max - z_min),
+            lookahead: engine.thread_count(),
+            pending: VecDeque::new(),
+            trimmed: Vec::new(),
+            printed: false,
+        }
+    }
+
+    /// Stop with `SlicerError::Cancelled` once `cancel` is set
+    pub fn cancel_on(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Number of layers sliced ahead (defaults to the engine's thread
+    /// count); 1 slices strictly one layer at a time
+    pub fn lookahead(mut self, layers: usize) -> Self {
+        self.lookahead = layers.max(1);
+        self
+    }
+
+    /// Number of layers planned; trailing empty ones are dropped, so the
+    /// iterator may yield fewer
+    pub fn layer_count(&self) -> usize {
+        self.count
+    }
+
+    /// Layers sliced but not yet handed out
+    pub fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Trailing empty layers dropped so far that had warnings, such as
+    /// features too small to print, to report apart from the layers printed
+    pub fn trimmed(&self) -> &[Layer] {
+        &self.trimmed
+    }
+
+    fn refill(&mut self) {
+        let end = self.count.min(self.next_index.saturating_add(self.lookahead));
+        let (engine, base, slab_top, cancel) = (self.engine, self.base, self.slab_top, self.cancel);
+
+        let batch = engine.map_layers(self.next_index..end, |i| engine.slice_indexed(i, base, slab_top, cancel));
+
+        self.pending.extend(batch);
+        self.next_index = end;
+    }
+}
+
+impl Iterator for LayerIter<'_> {
+    type Item = Result<Layer>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() && self.next_index < self.count {
+            self.refill();
+        }
+
+        // An empty layer is only handed out if something printable follows;
+        // trailing empty layers (the top falling just short of a plane, or
+        // only features too small to print) are dropped. This may slice
+        // beyond the lookahead across a long gap. A model with nothing
+        // printable at all keeps its layers with warnings, to show why.
+        let printed = self.printed;
+        let is_kept = |result: &Result<Layer>| is_kept(result, printed);
+        if !self.pending.front().is_some_and(is_kept) {
+            while !self.pending.iter().any(is_kept) && self.next_index < self.count {
+                self.refill();
+            }
+            if !self.pending.iter().any(is_kept) {
+                let warned = self.pending.drain(..).flatten().filter(|layer| !layer.warnings.is_empty());
+                self.trimmed.extend(warned);
+                return None;
+            }
+        }
+
+        match self.pending.pop_front()? {
+            Ok(mut layer) => {
+                self.printed |= !layer.is_empty();
+                if layer.is_empty() && layer.warnings.is_empty() {
+                    layer.warnings.push(LayerWarning::EmptyLayer);
+                }
+                Some(Ok(layer))
+            }
+            Err(e) => {
+                // Drop whatever was sliced ahead and end the iteration
+                self.pending.clear();
+                self.next_index = self.count;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Trailing empty layers may be dropped, so this is only an upper bound
+        let remaining = self.pending.len() + self.count - self.next_index;
+        (0, Some(remaining))
+    }
+}
+
+/// Whether a layer ends a run of empty ones: it has something to print, or
+/// something to report before anything was `printed`, or it is an error
+fn is_kept(result: &Result<Layer>, printed: bool) -> bool {
+    match result {
+        Ok(layer) => !layer.is_empty() || (!printed && !layer.warnings.is_empty()),
+        Err(_) => true,
+    }
+}
@@ -0,0 +1,113 @@
+use std::borrow::Borrow;
+use super::{detect_overhangs, Layer};
+use crate::config::SlicerConfig;
+use crate::geometry::boolean::{difference, intersection, union};
+use crate::geometry::Island;
+
+/// Support on one layer: sparse columns, and denser interface where they
+/// meet the model
+#[derive(Debug, Clone, Default)]
+pub struct SupportLayer {
+    pub base: Vec<Island>,
+    pub interface: Vec<Island>,
+    /// Support volume in mm³ that blockers removed from the layer
+    pub blocked_volume: f64,
+}
+
+impl SupportLayer {
+    pub fn is_empty(&self) -> bool {
+        self.base.is_empty() && self.interface.is_empty()
+    }
+
+    /// All support regions on the layer
+    pub fn regions(&self) -> impl Iterator<Item = &Island> {
+        self.base.iter().chain(&self.interface)
+    }
+}
+
+/// Support for each of `layers`, in the same order.
+///
+/// Overhangs found by `detect_overhangs` are carried straight down to the
+/// bed, stopping where they land on the model and keeping `support_xy_gap`
+/// away from it. The `support_z_gap_layers` right under an overhang are
+/// left empty so the support breaks away from it. Support within
+/// `support_interface_layers` under the model, or
+/// `support_bottom_interface_layers` over it, becomes interface.
+///
+/// Parts of the model inside a support enforcer are held up whatever their
+/// overhang, and support blockers clear their footprint on the layers they
+/// span.
+pub fn generate_supports<L: Borrow<Layer>>(layers: &[L], config: &SlicerConfig) -> Vec<SupportLayer> {
+    let report = detect_overhangs(layers, config);
+    let area = |islands: &[Island]| islands.iter().fold(0.0, |sum, island| sum + island.area());
+
+    let mut supports = vec![Vec::new(); layers.len()];
+    let mut blocked = vec![0.0; layers.len()];
+    // Support wanted under the layer above, working down from the top
+    let mut carried: Vec<Island> = Vec::new();
+    for i in (0..layers.len()).rev() {
+        if let Some(above) = layers.get(i + 1 + config.support_z_gap_layers).map(Borrow::borrow) {
+            if let Some(overhang) = report.get(above.layer_index) {
+                carried = union(&carried, &overhang.regions);
+            }
+            for enforcer in config.support_enforcers.iter().filter(|enforcer| enforcer.spans(above.z_height)) {
+                carried = union(&carried, &intersection(&above.islands, &[enforcer.footprint()]));
+            }
+        }
+        if carried.is_empty() {
+            continue;
+        }
+
+        // The gap is only left around the support printed on this layer, so
+        // support under a gentle slope still reaches the bed
+        let layer = layers[i].borrow();
+        carried = difference(&carried, &layer.islands);
+        let model: Vec<Island> = layer.islands.iter().flat_map(|island| island.offset(config.support_xy_gap)).collect();
+        let mut support = difference(&carried, &model);
+
+        let blockers: Vec<Island> = config
+            .support_blockers
+            .iter()
+            .filter(|blocker| blocker.spans(layer.z_height))
+            .map(|blocker| blocker.footprint())
+            .collect();
+        if !blockers.is_empty() {
+            let unblocked = area(&support);
+            support = difference(&support, &blockers);
+            let thickness = match i {
+                0 => config.first_layer_height,
+                _ => layer.z_height - layers[i - 1].borrow().z_height,
+            };
+            blocked[i] = (unblocked - area(&support)) * thickness;
+        }
+        supports[i] = support;
+    }
+
+    let model = |range: std::ops::Range<usize>| -> Vec<Island> {
+        let islands: Vec<Island> = layers[range].iter().flat_map(|layer| layer.borrow().islands.clone()).collect();
+        union(&islands, &[])
+    };
+    supports
+        .into_iter()
+        .enumerate()
+        .zip(blocked)
+        .map(|((i, support), blocked_volume)| {
+            if support.is_empty() {
+                return SupportLayer { blocked_volume, ..SupportLayer::default() };
+            }
+            // Model the support holds up, past the Z gap, and rests on
+            let first_above = (i + 1 + config.support_z_gap_layers).min(layers.len());
+            let above = model(first_above..(first_above + config.support_interface_layers).min(layers.len()));
+            let below = model(i.saturating_sub(config.support_bottom_interface_layers)..i);
+            let near_model = union(&above, &below);
+            if near_model.is_empty() {
+                return SupportLayer { base: support, interface: Vec::new(), blocked_volume };
+            }
+            SupportLayer {
+                base: difference(&support, &near_model),
+                interface: intersection(&support, &near_model),
+                blocked_volume,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,31 @@
+use nalgebra::{Point2, Vector2};
+use super::seam::split_mix;
+use crate::geometry::Polygon;
+
+/// `polygon` with points about `point_distance` apart, each moved across
+/// the outline by up to `thickness` either way.
+///
+/// The moves are drawn from `seed`, so the same seed gives the same loop.
+pub(super) fn fuzz_loop(polygon: &Polygon, thickness: f64, point_distance: f64, seed: u64) -> Polygon {
+    let points = polygon.resample(point_distance).points;
+    let n = points.len();
+    if n < 3 {
+        return polygon.clone();
+    }
+
+    let mut state = seed;
+    let fuzzed = (0..n)
+        .map(|i| {
+            state = split_mix(state);
+            // Top 53 bits as a fraction in [0, 1), then spread over
+            // [-thickness, thickness]
+            let shift = ((state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0) * thickness;
+            let tangent = points[(i + 1) % n] - points[(i + n - 1) % n];
+            match tangent.try_normalize(f64::EPSILON) {
+                Some(tangent) => points[i] + Vector2::new(-tangent.y, tangent.x) * shift,
+                None => points[i],
+            }
+        })
+        .collect::<Vec<Point2<f64>>>();
+    Polygon::new(fuzzed)
+}
@@ -0,0 +1,102 @@
+//! Tool paths: every move a layer is printed with, in order, between
+//! slicing and G-code
+
+mod fuzzy;
+mod order;
+mod plan;
+mod seam;
+
+pub(crate) use plan::PathPlanner;
+
+use nalgebra::Point2;
+
+/// What an extrusion is part of, which decides how it is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathRole {
+    /// The outermost wall, forming the printed surface
+    ExternalPerimeter,
+    Perimeter,
+    Infill,
+    SolidInfill,
+    Support,
+    Skirt,
+    Brim,
+    Bridge,
+    /// The wall standing around the print against drafts
+    DraftShield,
+    /// A single line along a sliver left between walls
+    GapFill,
+    /// The column beside the print that tool changes purge on
+    WipeTower,
+}
+
+impl PathRole {
+    /// Lowercase name for reports
+    pub fn name(self) -> &'static str {
+        match self {
+            PathRole::ExternalPerimeter => "external perimeter",
+            PathRole::Perimeter => "perimeter",
+            PathRole::Infill => "infill",
+            PathRole::SolidInfill => "solid infill",
+            PathRole::Support => "support",
+            PathRole::Skirt => "skirt",
+            PathRole::Brim => "brim",
+            PathRole::Bridge => "bridge",
+            PathRole::DraftShield => "draft shield",
+            PathRole::GapFill => "gap fill",
+            PathRole::WipeTower => "wipe tower",
+        }
+    }
+
+    /// Feature type PrusaSlicer writes in `;TYPE:` comments, which G-code
+    /// viewers color the preview by
+    pub fn feature_type(self) -> &'static str {
+        match self {
+            PathRole::ExternalPerimeter => "External perimeter",
+            PathRole::Perimeter => "Perimeter",
+            PathRole::Infill => "Internal infill",
+            PathRole::SolidInfill => "Solid infill",
+            PathRole::Support => "Support material",
+            PathRole::Skirt | PathRole::Brim | PathRole::DraftShield => "Skirt/Brim",
+            PathRole::Bridge => "Bridge infill",
+            PathRole::GapFill => "Gap fill",
+            PathRole::WipeTower => "Wipe tower",
+        }
+    }
+}
+
+/// One move of the nozzle
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolPath {
+    /// Extrude along `points`, starting from where the nozzle is
+    Extrude {
+        points: Vec<Point2<f64>>,
+        role: PathRole,
+        /// Line width in mm
+        width: f64,
+        /// Scale on the filament fed for the line width
+        flow_multiplier: f64,
+    },
+    /// Move the nozzle without extruding
+    Travel { from: Point2<f64>, to: Point2<f64> },
+}
+
+impl ToolPath {
+    /// The role of an extrusion, or None for travel
+    pub fn role(&self) -> Option<PathRole> {
+        match self {
+            ToolPath::Extrude { role, .. } => Some(*role),
+            ToolPath::Travel { .. } => None,
+        }
+    }
+}
+
+/// The paths that print one layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerPaths {
+    pub layer_index: usize,
+    /// Height of the nozzle while printing the layer
+    pub z: f64,
+    /// Every extrusion with a travel to its start before it
+    pub paths: Vec<ToolPath>,
+}
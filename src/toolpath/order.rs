@@ -0,0 +1,122 @@
+use nalgebra::Point2;
+use crate::slicer::{PerimeterLoop, ThinPath};
+
+/// An extrusion from one end to the other, which may be printed either way
+/// round
+pub(super) trait OpenPath {
+    fn points(&self) -> &[Point2<f64>];
+    fn reverse(&mut self);
+}
+
+impl OpenPath for Vec<Point2<f64>> {
+    fn points(&self) -> &[Point2<f64>] {
+        self
+    }
+
+    fn reverse(&mut self) {
+        <[Point2<f64>]>::reverse(self);
+    }
+}
+
+impl OpenPath for ThinPath {
+    fn points(&self) -> &[Point2<f64>] {
+        &self.points
+    }
+
+    fn reverse(&mut self) {
+        self.points.reverse();
+        self.widths.reverse();
+    }
+}
+
+/// A path carrying something along, such as its role
+impl<P: OpenPath, T> OpenPath for (P, T) {
+    fn points(&self) -> &[Point2<f64>] {
+        self.0.points()
+    }
+
+    fn reverse(&mut self) {
+        self.0.reverse();
+    }
+}
+
+/// Distance from `from` to the nearest of `points`, and that point
+pub(super) fn closest_point(points: &[Point2<f64>], from: Point2<f64>) -> (f64, Point2<f64>) {
+    points
+        .iter()
+        .map(|&point| ((point - from).norm(), point))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap_or((f64::INFINITY, from))
+}
+
+/// Wall loops with those around holes first. Walls keep their order from
+/// the surface in (or out), and among loops at the same depth the nozzle
+/// goes to the nearest next, starting from `from`.
+pub(super) fn order_walls(mut loops: Vec<PerimeterLoop>, from: Point2<f64>) -> Vec<PerimeterLoop> {
+    // Holes run clockwise; the sort is stable, so depths stay in order
+    loops.sort_by_key(|perimeter| !perimeter.polygon.is_clockwise());
+
+    let mut position = from;
+    let mut ordered = Vec::with_capacity(loops.len());
+    let same_run = |a: &PerimeterLoop, b: &PerimeterLoop| {
+        a.index == b.index && a.polygon.is_clockwise() == b.polygon.is_clockwise()
+    };
+    for run in loops.chunk_by(same_run) {
+        ordered.extend(nearest_first(run.to_vec(), &mut position, |perimeter, from| {
+            closest_point(&perimeter.polygon.points, from)
+        }));
+    }
+    ordered
+}
+
+/// Open paths in the order a nozzle at `from` reaches them by always going
+/// to the nearest end of a path not yet printed, each turned to start at
+/// that end
+pub(super) fn order_paths<P: OpenPath>(paths: Vec<P>, from: Point2<f64>) -> Vec<P> {
+    let ends = |path: &P| (path.points().first().copied(), path.points().last().copied());
+    let mut position = from;
+    let mut ordered = nearest_first(paths, &mut position, |path, from| match ends(path) {
+        (Some(first), Some(last)) => {
+            let (to_first, to_last) = ((first - from).norm(), (last - from).norm());
+            if to_last < to_first { (to_last, first) } else { (to_first, last) }
+        }
+        _ => (f64::INFINITY, from),
+    });
+
+    // Replay the walk to turn round the paths entered at their far end
+    let mut position = from;
+    for path in &mut ordered {
+        if let (Some(first), Some(last)) = ends(path) {
+            if (last - position).norm() < (first - position).norm() {
+                path.reverse();
+                position = first;
+            } else {
+                position = last;
+            }
+        }
+    }
+    ordered
+}
+
+/// Greedy nearest-neighbour order of `items`, with `visit` giving the
+/// travel from a position to an item and where the nozzle is left after it.
+/// `position` ends up at the exit of the last item.
+fn nearest_first<T>(
+    mut items: Vec<T>,
+    position: &mut Point2<f64>,
+    visit: impl Fn(&T, Point2<f64>) -> (f64, Point2<f64>),
+) -> Vec<T> {
+    let mut ordered = Vec::with_capacity(items.len());
+    while !items.is_empty() {
+        let (next, exit) = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (i, visit(item, *position)))
+            .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+            .map(|(i, (_, exit))| (i, exit))
+            .unwrap();
+        *position = exit;
+        ordered.push(items.remove(next));
+    }
+    ordered
+}
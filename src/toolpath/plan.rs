@@ -0,0 +1,471 @@
+use super::fuzzy::fuzz_loop;
+use super::order::{closest_point, order_paths, order_walls, OpenPath};
+use super::seam::SeamTarget;
+use super::{LayerPaths, PathRole, ToolPath};
+use crate::slicer::{
+    classify_islands, gap_fill, generate_brim, generate_draft_shield, generate_perimeters, generate_supports, honeycomb_infill,
+    monotonic_infill, rectilinear_infill, split_by_modifiers, split_thin_walls, InfillRegions, Layer, PerimeterKind,
+    SupportLayer, ThinPath,
+};
+use crate::geometry::boolean::{difference, intersection};
+use crate::geometry::{Island, Polygon};
+use crate::config::{InfillPattern, PerimeterOrder, SeamPosition, SlicerConfig};
+use crate::error::Result;
+use nalgebra::Point2;
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+
+/// Lays out the paths of each layer in turn, carrying where the nozzle
+/// was left from one layer to the next
+pub(crate) struct PathPlanner<'a> {
+    config: &'a SlicerConfig,
+    /// Where the last extrusion left the nozzle
+    position: Point2<f64>,
+    /// Loop of the draft shield, once the layers it surrounds are known
+    shield: Option<Polygon>,
+}
+
+impl<'a> PathPlanner<'a> {
+    pub fn new(config: &'a SlicerConfig) -> Self {
+        PathPlanner { config, position: Point2::origin(), shield: None }
+    }
+
+    /// Plan `layers` bottom up, handing each layer's paths to `emit` along
+    /// with its islands, as cut by modifiers, and their infill regions
+    pub fn plan<I, L>(&mut self, layers: I, mut emit: impl FnMut(&[Island], &[InfillRegions], LayerPaths) -> Result<()>) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<L>>,
+        L: Borrow<Layer>,
+    {
+        // Support hangs down from the layers above, and the draft shield
+        // surrounds them all, so the whole stack is needed before the first
+        // layer can be planned
+        let layers = layers.into_iter();
+        if self.config.support_material || self.config.draft_shield {
+            let layers: Vec<L> = layers.collect::<Result<_>>()?;
+            let supports = if self.config.support_material { generate_supports(&layers, self.config) } else { Vec::new() };
+            if self.config.draft_shield {
+                self.shield = generate_draft_shield(&layers, self.config.draft_shield_distance);
+            }
+            self.plan_layers(layers.into_iter().map(Ok), &supports, &mut emit)
+        } else {
+            self.plan_layers(layers, &[], &mut emit)
+        }
+    }
+
+    /// Plan each layer with the support regions at its position in
+    /// `supports`, if any
+    fn plan_layers<I, L>(
+        &mut self,
+        layers: I,
+        supports: &[SupportLayer],
+        emit: &mut impl FnMut(&[Island], &[InfillRegions], LayerPaths) -> Result<()>,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<L>>,
+        L: Borrow<Layer>,
+    {
+        // Skin detection looks at the layers on either side, so a layer is
+        // planned once those above it have arrived, and the ones below are
+        // kept until nothing needs them. Combined infill looks across its
+        // whole group of layers, and at their neighbours in turn.
+        let combine = self.config.infill_combine_layers();
+        let bottom = self.config.bottom_solid_layers() + combine - 1;
+        let top = self.config.top_solid_layers() + combine - 1;
+        let mut window: VecDeque<L> = VecDeque::new();
+        let mut classified: VecDeque<Option<Classified>> = VecDeque::new();
+        let mut next = 0;
+        let mut planned = 0;
+        let none = SupportLayer::default();
+        let support = |index: usize| supports.get(index).unwrap_or(&none);
+        for layer in layers {
+            window.push_back(layer?);
+            classified.push_back(None);
+            if window.len() - next > top {
+                self.plan_window(&window, &mut classified, next, support(planned), planned, emit)?;
+                planned += 1;
+                next += 1;
+                if next > bottom {
+                    window.pop_front();
+                    classified.pop_front();
+                    next -= 1;
+                }
+            }
+        }
+        for current in next..window.len() {
+            self.plan_window(&window, &mut classified, current, support(planned), planned, emit)?;
+            planned += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Plan `window[current]`, with the layers before and after it as its
+    /// neighbours. `classified` caches each layer's regions, as the layers
+    /// of a combined infill group are all needed by each of them.
+    fn plan_window<L: Borrow<Layer>>(
+        &mut self,
+        window: &VecDeque<L>,
+        classified: &mut VecDeque<Option<Classified>>,
+        current: usize,
+        support: &SupportLayer,
+        layer_index: usize,
+        emit: &mut impl FnMut(&[Island], &[InfillRegions], LayerPaths) -> Result<()>,
+    ) -> Result<()> {
+        // Layers after the first are grouped for combined infill, which
+        // only goes where every layer of the group has sparse infill
+        let combine = self.config.infill_combine_layers();
+        let group = if combine > 1 && layer_index > 0 {
+            let start = current - (layer_index - 1) % combine;
+            start..(start + combine).min(window.len())
+        } else {
+            current..current + 1
+        };
+        for index in group.clone() {
+            if classified[index].is_none() {
+                classified[index] = Some(self.classify(window, index));
+            }
+        }
+        let sparse = |index: usize| -> Vec<Island> {
+            let layer = classified[index].as_ref().expect("group layers are classified");
+            layer.infill.iter().flat_map(|regions| regions.sparse.iter().cloned()).collect()
+        };
+        let shared = if group.len() > 1 {
+            group.clone().skip(1).fold(sparse(group.start), |shared, index| intersection(&shared, &sparse(index)))
+        } else {
+            Vec::new()
+        };
+        let combined = Combined { shared, layers: if current + 1 == group.end { group.len() } else { 1 } };
+
+        let layer = window[current].borrow();
+        let regions = classified[current].as_ref().expect("the current layer is classified");
+        let paths = self.plan_layer(layer, regions, &combined, support, layer_index);
+        emit(&regions.islands, &regions.infill, paths)
+    }
+
+    /// Islands of `window[current]` and their regions. Modifiers changing
+    /// the wall count cut the islands, and each part gets walls of its own.
+    fn classify<L: Borrow<Layer>>(&self, window: &VecDeque<L>, current: usize) -> Classified {
+        let below: Vec<&Layer> = window.range(..current).rev().map(Borrow::borrow).collect();
+        let above: Vec<&Layer> = window.range(current + 1..).map(Borrow::borrow).collect();
+        let layer = window[current].borrow();
+
+        let config = self.config;
+        let parts = split_by_modifiers(&layer.islands, layer.z_height, &config.modifiers, config.perimeters(), |modifier| {
+            modifier.perimeters
+        });
+        let mut classified = Classified { islands: Vec::new(), infill: Vec::new(), perimeters: Vec::new() };
+        for (part, count) in parts {
+            classified.infill.extend(classify_islands(&part, count, &below, &above, config));
+            classified.perimeters.extend(std::iter::repeat_n(count, part.len()));
+            classified.islands.extend(part);
+        }
+        classified
+    }
+
+    /// Paths for `layer`, whose islands are printed with the infill regions
+    /// and wall counts at the same positions
+    fn plan_layer(
+        &mut self,
+        layer: &Layer,
+        classified: &Classified,
+        combined: &Combined,
+        support: &SupportLayer,
+        layer_index: usize,
+    ) -> LayerPaths {
+        let Classified { islands, infill, perimeters } = classified;
+        let mut paths = Vec::new();
+        let line_width = self.config.nozzle_diameter;
+        let seam = SeamTarget {
+            position: self.config.seam_position,
+            hide_in_corners: self.config.hide_seam_in_corners,
+            anchor: rear_most(layer),
+            layer_index,
+        };
+        let mut loop_index = 0;
+        // The shield goes up first, so the layer is printed in its lee
+        let shield = self.shield.clone().filter(|_| self.config.draft_shield_height.covers(layer.z_height));
+        if let Some(shield) = shield {
+            self.extrude_loop(&mut paths, &shield, PathRole::DraftShield);
+        }
+        if layer_index == 0 && self.config.brim_width > 0.0 {
+            let brim = generate_brim(&layer.islands, self.config.brim_width, line_width, self.config.brim_only_outside);
+            for polygon in &brim {
+                self.extrude_loop(&mut paths, polygon, PathRole::Brim);
+            }
+        }
+
+        // Islands are visited nearest first, starting from wherever the
+        // previous layer left off
+        let mut remaining: Vec<usize> = (0..islands.len()).collect();
+        while !remaining.is_empty() {
+            let next = if self.config.optimize_travel {
+                let from = self.position;
+                let distance = |k: usize| closest_point(&islands[remaining[k]].outline.points, from).0;
+                (0..remaining.len()).min_by(|&a, &b| distance(a).total_cmp(&distance(b))).unwrap()
+            } else {
+                0
+            };
+            let i = remaining.remove(next);
+            let island = IslandPlan {
+                island: &islands[i],
+                regions: &infill[i],
+                perimeters: perimeters[i],
+                z: layer.z_height,
+                combined,
+            };
+            self.plan_island(&mut paths, island, &seam, &mut loop_index, layer_index);
+        }
+
+        // Support lines run the same way on every layer so they stack, and
+        // interface lines cross them. Both are spaced for the nozzle of the
+        // extruder printing them.
+        let support_width = self.config.extruder_nozzle_diameter(self.config.support_extruder);
+        let mut lines = Vec::new();
+        if let Some(spacing) = self.line_spacing(support_width, self.config.support_density) {
+            lines.extend(rectilinear_infill(&support.base, spacing, 0.0));
+        }
+        if let Some(spacing) = self.line_spacing(support_width, self.config.support_interface_density) {
+            lines.extend(rectilinear_infill(&support.interface, spacing, 90.0));
+        }
+        for line in self.ordered(lines) {
+            self.extrude(&mut paths, line, PathRole::Support, support_width, 1.0);
+        }
+
+        // Never drive the nozzle closer to the bed than the first layer
+        let z = layer.z_height.max(self.config.first_layer_height);
+        LayerPaths { layer_index, z, paths }
+    }
+
+    /// An island's walls, then its thin walls and gap fill, then its infill
+    /// and bridges
+    fn plan_island(
+        &mut self,
+        paths: &mut Vec<ToolPath>,
+        plan: IslandPlan,
+        seam: &SeamTarget,
+        loop_index: &mut usize,
+        layer_index: usize,
+    ) {
+        let IslandPlan { island, regions, perimeters: count, z, combined } = plan;
+        let line_width = self.config.nozzle_diameter;
+        if self.config.spiral_layer(layer_index) {
+            // Only the outside wall, carrying on from the nearest point to
+            // where the loop below closed
+            let nearest = SeamTarget { position: SeamPosition::Nearest, hide_in_corners: false, anchor: seam.anchor, layer_index };
+            let outlines = generate_perimeters(island, 1, line_width).into_iter().filter(|perimeter| !perimeter.polygon.is_clockwise());
+            for perimeter in outlines {
+                let polygon = nearest.place(&perimeter.polygon, self.position, *loop_index);
+                *loop_index += 1;
+                self.extrude_loop(paths, &polygon, PathRole::ExternalPerimeter);
+            }
+            return;
+        }
+        let (thick, thin_walls) = if self.config.detect_thin_walls {
+            split_thin_walls(island, line_width)
+        } else {
+            (vec![island.clone()], Vec::new())
+        };
+
+        for part in &thick {
+            let mut loops = generate_perimeters(part, count, line_width);
+            // Loops too short and too small to print cleanly are left out
+            let (min_length, min_area) = (self.config.min_loop_length(), line_width * line_width / 2.0);
+            loops.retain(|perimeter| perimeter.polygon.perimeter() >= min_length || perimeter.polygon.area() >= min_area);
+            if self.config.perimeter_order == PerimeterOrder::Inner {
+                loops.reverse();
+            }
+            if self.config.optimize_travel {
+                loops = order_walls(loops, self.position);
+            }
+            for perimeter in &loops {
+                let mut polygon = seam.place(&perimeter.polygon, self.position, *loop_index);
+                if self.config.fuzzy_skin && layer_index > 0 && perimeter.kind == PerimeterKind::External {
+                    let seed = self.config.fuzzy_skin_seed ^ ((layer_index as u64) << 32 | *loop_index as u64);
+                    let (thickness, spacing) = (self.config.fuzzy_skin_thickness, self.config.fuzzy_skin_point_distance);
+                    polygon = fuzz_loop(&polygon, thickness, spacing, seed);
+                }
+                *loop_index += 1;
+                self.extrude_loop(paths, &polygon, wall_role(perimeter.kind));
+            }
+        }
+        let gaps = thick.iter().flat_map(|part| gap_fill(part, count, line_width));
+        let thin_paths: Vec<ThinPath> = thin_walls.into_iter().chain(gaps).collect();
+        for path in self.ordered(thin_paths) {
+            self.extrude_thin(paths, &path);
+        }
+
+        // Solid skin runs diagonally, crossing itself from layer to layer
+        let solid_angle = 45.0 + 90.0 * (layer_index % 2) as f64;
+        // Monotonic top skin keeps its own order, so is left out of the rest
+        let monotonic = self.config.monotonic_top_infill && !regions.top.is_empty();
+        let solid = if monotonic { difference(&regions.solid, &regions.top) } else { regions.solid.clone() };
+        let mut lines: Vec<_> = rectilinear_infill(&solid, line_width, solid_angle)
+            .into_iter()
+            .map(|line| (line, (PathRole::SolidInfill, 1.0)))
+            .collect();
+        // Sparse infill its whole group shares is left to the group's top
+        // layer, which prints it for all of them at once
+        let (own, shared) = if combined.shared.is_empty() {
+            (regions.sparse.clone(), Vec::new())
+        } else {
+            (difference(&regions.sparse, &combined.shared), intersection(&regions.sparse, &combined.shared))
+        };
+        let shared = if combined.layers > 1 { shared } else { Vec::new() };
+        for (sparse, flow) in [(own, 1.0), (shared, combined.layers as f64)] {
+            // Modifiers set the density of the sparse infill inside them;
+            // the lines stay on one grid, so they meet up across the parts
+            let densities = split_by_modifiers(&sparse, z, &self.config.modifiers, self.config.infill_percentage, |modifier| {
+                modifier.infill_density
+            });
+            for (sparse, density) in &densities {
+                let Some(spacing) = self.line_spacing(self.config.nozzle_diameter, *density) else {
+                    continue;
+                };
+                let sparse_lines = match self.config.infill_pattern {
+                    InfillPattern::Rectilinear => {
+                        rectilinear_infill(sparse, spacing, self.config.layer_infill_angle(layer_index))
+                    }
+                    InfillPattern::Honeycomb => {
+                        // Stack the walls for a few layers, then shift
+                        let every = self.config.honeycomb_shift_layers;
+                        honeycomb_infill(sparse, spacing, every > 0 && (layer_index / every) % 2 == 1)
+                    }
+                };
+                lines.extend(sparse_lines.into_iter().map(|line| (line, (PathRole::Infill, flow))));
+            }
+        }
+        for (line, (role, flow)) in self.ordered(lines) {
+            self.extrude(paths, line, role, line_width, flow);
+        }
+        if monotonic {
+            for line in monotonic_infill(&regions.top, line_width, solid_angle) {
+                self.extrude(paths, line, PathRole::SolidInfill, line_width, 1.0);
+            }
+        }
+
+        // Bridges are laid from anchor to anchor
+        for bridge in &regions.bridges {
+            let lines = rectilinear_infill(std::slice::from_ref(&bridge.region), line_width, bridge.angle);
+            for line in self.ordered(lines) {
+                self.extrude(paths, line, PathRole::Bridge, line_width, 1.0);
+            }
+        }
+    }
+
+    /// `paths` in travel order, when travel is optimized
+    fn ordered<P: OpenPath>(&self, paths: Vec<P>) -> Vec<P> {
+        if self.config.optimize_travel { order_paths(paths, self.position) } else { paths }
+    }
+
+    /// Distance between lines `line_width` wide filling `percentage` of an
+    /// area, or None when it is 0
+    fn line_spacing(&self, line_width: f64, percentage: u8) -> Option<f64> {
+        if percentage == 0 {
+            return None;
+        }
+        Some(line_width * 100.0 / percentage as f64)
+    }
+
+    /// Travel to the start of `points` and extrude along them, feeding
+    /// `flow_multiplier` times the filament for the width
+    fn extrude(
+        &mut self,
+        paths: &mut Vec<ToolPath>,
+        points: Vec<Point2<f64>>,
+        role: PathRole,
+        width: f64,
+        flow_multiplier: f64,
+    ) {
+        let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+            return;
+        };
+        paths.push(ToolPath::Travel { from: self.position, to: first });
+        paths.push(ToolPath::Extrude { points, role, width, flow_multiplier });
+        self.position = last;
+    }
+
+    /// Travel to the start of a closed polygon and extrude around it
+    fn extrude_loop(&mut self, paths: &mut Vec<ToolPath>, polygon: &Polygon, role: PathRole) {
+        let mut points = polygon.points.clone();
+        // Close contour
+        if points.len() > 2 {
+            points.push(points[0]);
+        }
+        self.extrude(paths, points, role, self.config.nozzle_diameter, 1.0);
+    }
+
+    /// Travel to the start of a thin path and extrude along it, one
+    /// extrusion for each run of segments of the same width
+    fn extrude_thin(&mut self, paths: &mut Vec<ToolPath>, path: &ThinPath) {
+        let Some(&first) = path.points.first() else {
+            return;
+        };
+        let role = wall_role(path.kind);
+        paths.push(ToolPath::Travel { from: self.position, to: first });
+        for i in 1..path.points.len() {
+            let width = (path.widths[i - 1] + path.widths[i]) / 2.0;
+            match paths.last_mut() {
+                Some(ToolPath::Extrude { points, width: last_width, .. }) if *last_width == width => {
+                    points.push(path.points[i]);
+                }
+                _ => paths.push(ToolPath::Extrude {
+                    points: vec![path.points[i - 1], path.points[i]],
+                    role,
+                    width,
+                    flow_multiplier: 1.0,
+                }),
+            }
+        }
+        self.position = *path.points.last().unwrap_or(&first);
+    }
+}
+
+/// An island to plan, with what it is printed with
+struct IslandPlan<'a> {
+    island: &'a Island,
+    regions: &'a InfillRegions,
+    /// Wall loops around the island
+    perimeters: usize,
+    /// Height of the layer, for picking the modifiers that apply
+    z: f64,
+    combined: &'a Combined,
+}
+
+/// A layer's islands, as cut by modifiers, with the infill regions and wall
+/// counts at the same positions
+struct Classified {
+    islands: Vec<Island>,
+    infill: Vec<InfillRegions>,
+    perimeters: Vec<usize>,
+}
+
+/// Sparse infill combined across the group of layers a layer belongs to
+struct Combined {
+    /// Where every layer of the group has sparse infill
+    shared: Vec<Island>,
+    /// Layers the combined infill is printed for: the group's size on its
+    /// top layer, and 1 on the others, which leave it out
+    layers: usize,
+}
+
+/// Role of a wall of `kind`
+fn wall_role(kind: PerimeterKind) -> PathRole {
+    match kind {
+        PerimeterKind::External => PathRole::ExternalPerimeter,
+        PerimeterKind::Internal => PathRole::Perimeter,
+        PerimeterKind::GapFill => PathRole::GapFill,
+    }
+}
+
+/// Rear-most corner of a layer's outlines, the leftmost of any ties; seams
+/// aligned to it line up from layer to layer
+fn rear_most(layer: &Layer) -> Point2<f64> {
+    layer
+        .islands
+        .iter()
+        .flat_map(|island| &island.outline.points)
+        .copied()
+        .max_by(|a, b| a.y.total_cmp(&b.y).then(b.x.total_cmp(&a.x)))
+        .unwrap_or_else(Point2::origin)
+}
@@ -0,0 +1,84 @@
+use nalgebra::Point2;
+use crate::config::SeamPosition;
+use crate::geometry::Polygon;
+
+/// Smallest turn in degrees at a concave corner for it to hide the seam;
+/// flatter ones, such as along a polygonized curve, hide nothing
+const MIN_CORNER_TURN: f64 = 30.0;
+/// Points closer than this in mm to a corner are looked past when measuring
+/// its turn, so near-duplicate points don't make up corners
+const MIN_EDGE: f64 = 0.01;
+
+/// Where the seam of the loops on one layer should go
+pub(super) struct SeamTarget {
+    pub position: SeamPosition,
+    pub hide_in_corners: bool,
+    /// Model-space point aligned seams are drawn to
+    pub anchor: Point2<f64>,
+    pub layer_index: usize,
+}
+
+impl SeamTarget {
+    /// `polygon` with its points rotated so the loop starts at the seam.
+    ///
+    /// `from` is where the tool is, and `loop_index` counts the loops placed
+    /// on the layer so far, so random seams come out the same on every run.
+    pub fn place(&self, polygon: &Polygon, from: Point2<f64>, loop_index: usize) -> Polygon {
+        let n = polygon.points.len();
+        if n < 3 {
+            return polygon.clone();
+        }
+
+        let corners: Vec<usize> = if self.hide_in_corners { concave_corners(polygon) } else { Vec::new() };
+        let candidates: Vec<usize> = if corners.is_empty() { (0..n).collect() } else { corners };
+        let point = |i: usize| polygon.points[i];
+
+        let start = match self.position {
+            SeamPosition::Nearest => nearest(&candidates, |i| (point(i) - from).norm()),
+            SeamPosition::Aligned => nearest(&candidates, |i| (point(i) - self.anchor).norm()),
+            SeamPosition::Rear => nearest(&candidates, |i| -point(i).y),
+            SeamPosition::Random => {
+                let seed = (self.layer_index as u64) << 32 | loop_index as u64;
+                candidates[(split_mix(seed) % candidates.len() as u64) as usize]
+            }
+        };
+
+        let mut points = polygon.points.clone();
+        points.rotate_left(start);
+        Polygon::new(points)
+    }
+}
+
+/// Candidate with the lowest cost, the first of any ties
+fn nearest(candidates: &[usize], cost: impl Fn(usize) -> f64) -> usize {
+    candidates.iter().copied().min_by(|&a, &b| cost(a).total_cmp(&cost(b))).unwrap_or(0)
+}
+
+/// Corners where the loop turns away from the printed region, which lies on
+/// its left (outlines run counter-clockwise and holes clockwise)
+fn concave_corners(polygon: &Polygon) -> Vec<usize> {
+    let points = &polygon.points;
+    let n = points.len();
+    // First point at least `MIN_EDGE` from `points[i]`, stepping by `step`
+    let neighbour = |i: usize, step: usize| {
+        (1..n).map(|k| points[(i + k * step) % n]).find(|p| (p - points[i]).norm() >= MIN_EDGE)
+    };
+    (0..n)
+        .filter(|&i| {
+            let (Some(prev), Some(next)) = (neighbour(i, n - 1), neighbour(i, 1)) else {
+                return false;
+            };
+            let (a, b) = (points[i] - prev, next - points[i]);
+            // Signed turn, negative to the right
+            a.perp(&b).atan2(a.dot(&b)) < -MIN_CORNER_TURN.to_radians()
+        })
+        .collect()
+}
+
+/// SplitMix64: a well-mixed hash of `seed`
+pub(super) fn split_mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
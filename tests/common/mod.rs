@@ -0,0 +1,223 @@
+//! Mesh and polygon fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use nalgebra::{Point2, Point3};
+use rustslicer::geometry::{Mesh, Polygon, Triangle};
+
+fn triangle(a: Point3<f64>, b: Point3<f64>, c: Point3<f64>) -> Triangle {
+    let normal = (b - a).cross(&(c - a)).normalize();
+    Triangle { vertices: [a, b, c], normal }
+}
+
+/// Axis-aligned box with outward-facing triangles
+pub fn cuboid(min: [f64; 3], max: [f64; 3]) -> Mesh {
+    hexahedron(|x, y, z| {
+        Point3::new(
+            if x == 0 { min[0] } else { max[0] },
+            if y == 0 { min[1] } else { max[1] },
+            if z == 0 { min[2] } else { max[2] },
+        )
+    })
+}
+
+/// Block standing on a `width` by `depth` footprint at the origin whose +X
+/// face leans out by `angle` degrees from vertical
+pub fn wedge(width: f64, depth: f64, height: f64, angle: f64) -> Mesh {
+    let lean = height * angle.to_radians().tan();
+    hexahedron(|x, y, z| {
+        let top = z == 1;
+        Point3::new(
+            if x == 0 { 0.0 } else if top { width + lean } else { width },
+            if y == 0 { 0.0 } else { depth },
+            if top { height } else { 0.0 },
+        )
+    })
+}
+
+/// Square pyramid on a `base` wide footprint at the origin, cut off
+/// `height` up where it is `top` wide
+pub fn pyramid(base: f64, top: f64, height: f64) -> Mesh {
+    let inset = (base - top) / 2.0;
+    hexahedron(|x, y, z| {
+        let (near, far) = if z == 1 { (inset, base - inset) } else { (0.0, base) };
+        Point3::new(if x == 0 { near } else { far }, if y == 0 { near } else { far }, if z == 1 { height } else { 0.0 })
+    })
+}
+
+/// Six-sided solid with corners `p(x, y, z)`, each index 0 or 1, laid out
+/// like a box's
+fn hexahedron(p: impl Fn(usize, usize, usize) -> Point3<f64>) -> Mesh {
+    // Each face as a counter-clockwise quad seen from outside
+    let quads = [
+        [p(0, 0, 0), p(0, 1, 0), p(1, 1, 0), p(1, 0, 0)], // bottom
+        [p(0, 0, 1), p(1, 0, 1), p(1, 1, 1), p(0, 1, 1)], // top
+        [p(0, 0, 0), p(1, 0, 0), p(1, 0, 1), p(0, 0, 1)], // front
+        [p(0, 1, 0), p(0, 1, 1), p(1, 1, 1), p(1, 1, 0)], // back
+        [p(0, 0, 0), p(0, 0, 1), p(0, 1, 1), p(0, 1, 0)], // left
+        [p(1, 0, 0), p(1, 1, 0), p(1, 1, 1), p(1, 0, 1)], // right
+    ];
+
+    let mut triangles = Vec::new();
+    for [a, b, c, d] in quads {
+        triangles.push(triangle(a, b, c));
+        triangles.push(triangle(a, c, d));
+    }
+
+    Mesh::new(triangles)
+}
+
+/// Cube of the given edge length with its bottom face centered at the origin
+pub fn cube(size: f64) -> Mesh {
+    let h = size / 2.0;
+    cuboid([-h, -h, 0.0], [h, h, size])
+}
+
+/// Counter-clockwise axis-aligned rectangle from `min` to `max`
+pub fn rectangle(min: [f64; 2], max: [f64; 2]) -> Polygon {
+    Polygon::new(vec![
+        Point2::new(min[0], min[1]),
+        Point2::new(max[0], min[1]),
+        Point2::new(max[0], max[1]),
+        Point2::new(min[0], max[1]),
+    ])
+}
+
+/// Counter-clockwise square `size` across with its corner at `min`
+pub fn square(min: [f64; 2], size: f64) -> Polygon {
+    rectangle(min, [min[0] + size, min[1] + size])
+}
+
+/// UV sphere resting on Z=0
+pub fn sphere(radius: f64, segments: usize, rings: usize) -> Mesh {
+    let vertex = |ring: usize, seg: usize| {
+        if ring == 0 {
+            return Point3::new(0.0, 0.0, 0.0);
+        }
+        if ring == rings {
+            return Point3::new(0.0, 0.0, 2.0 * radius);
+        }
+        let seg = seg % segments;
+        let theta = std::f64::consts::PI * ring as f64 / rings as f64;
+        let phi = 2.0 * std::f64::consts::PI * seg as f64 / segments as f64;
+        Point3::new(
+            radius * theta.sin() * phi.cos(),
+            radius * theta.sin() * phi.sin(),
+            radius - radius * theta.cos(),
+        )
+    };
+
+    let mut triangles = Vec::new();
+    for ring in 0..rings {
+        for seg in 0..segments {
+            let a = vertex(ring, seg);
+            let b = vertex(ring, seg + 1);
+            let c = vertex(ring + 1, seg + 1);
+            let d = vertex(ring + 1, seg);
+            if ring != 0 {
+                triangles.push(triangle(a, b, d));
+            }
+            if ring != rings - 1 {
+                triangles.push(triangle(b, c, d));
+            }
+        }
+    }
+
+    Mesh::new(triangles)
+}
+
+/// Vertical prism over a counter-clockwise outline that is star-shaped
+/// around `center` (the caps are fanned from it)
+pub fn prism(outline: &[Point2<f64>], center: Point2<f64>, height: f64) -> Mesh {
+    let n = outline.len();
+    let at = |p: Point2<f64>, z: f64| Point3::new(p.x, p.y, z);
+    let mut triangles = Vec::new();
+
+    for i in 0..n {
+        let a = outline[i];
+        let b = outline[(i + 1) % n];
+        triangles.push(triangle(at(center, 0.0), at(b, 0.0), at(a, 0.0)));
+        triangles.push(triangle(at(center, height), at(a, height), at(b, height)));
+        triangles.push(triangle(at(a, 0.0), at(b, 0.0), at(b, height)));
+        triangles.push(triangle(at(a, 0.0), at(b, height), at(a, height)));
+    }
+
+    Mesh::new(triangles)
+}
+
+/// Steps along X, each `width` wide, `depth` deep and `rise` higher than
+/// the one before, as touching cuboids
+pub fn staircase(steps: usize, width: f64, depth: f64, rise: f64) -> Mesh {
+    let blocks = (0..steps)
+        .map(|i| cuboid([i as f64 * width, 0.0, 0.0], [(i + 1) as f64 * width, depth, (i + 1) as f64 * rise]))
+        .collect();
+    Mesh::merge(blocks)
+}
+
+/// T standing on its trunk: a `trunk` wide column `height` tall under a
+/// `width` wide, 2 mm thick crossbar, all `depth` deep and centered on X
+/// `width / 2`
+pub fn tee(width: f64, trunk: f64, depth: f64, height: f64) -> Mesh {
+    let (left, right) = ((width - trunk) / 2.0, (width + trunk) / 2.0);
+    Mesh::merge(vec![
+        cuboid([left, 0.0, 0.0], [right, depth, height]),
+        cuboid([0.0, 0.0, height], [width, depth, height + 2.0]),
+    ])
+}
+
+/// Table of `width` along X: two legs `leg` thick at either end, `height`
+/// tall, under a top `thickness` thick
+pub fn table(width: f64, depth: f64, leg: f64, height: f64, thickness: f64) -> Mesh {
+    Mesh::merge(vec![
+        cuboid([0.0, 0.0, 0.0], [leg, depth, height]),
+        cuboid([width - leg, 0.0, 0.0], [width, depth, height]),
+        cuboid([0.0, 0.0, height], [width, depth, height + thickness]),
+    ])
+}
+
+/// Vertical tube (a washer when short) around `center` with `segments`
+/// sides on each wall
+pub fn tube(center: Point2<f64>, inner_radius: f64, outer_radius: f64, height: f64, segments: usize) -> Mesh {
+    let at = |radius: f64, i: usize, z: f64| {
+        let angle = 2.0 * std::f64::consts::PI * (i % segments) as f64 / segments as f64;
+        Point3::new(center.x + radius * angle.cos(), center.y + radius * angle.sin(), z)
+    };
+    let outer = |i, z| at(outer_radius, i, z);
+    let inner = |i, z| at(inner_radius, i, z);
+
+    let mut triangles = Vec::new();
+    for i in 0..segments {
+        let j = i + 1;
+        let quads = [
+            [outer(i, 0.0), outer(j, 0.0), outer(j, height), outer(i, height)], // outer wall
+            [inner(j, 0.0), inner(i, 0.0), inner(i, height), inner(j, height)], // inner wall
+            [inner(i, height), outer(i, height), outer(j, height), inner(j, height)], // top
+            [inner(i, 0.0), inner(j, 0.0), outer(j, 0.0), outer(i, 0.0)], // bottom
+        ];
+        for [a, b, c, d] in quads {
+            triangles.push(triangle(a, b, c));
+            triangles.push(triangle(a, c, d));
+        }
+    }
+
+    Mesh::new(triangles)
+}
+
+/// Gear outline centered on the origin with square-ish teeth
+pub fn gear_outline(teeth: usize, root_radius: f64, tip_radius: f64) -> Vec<Point2<f64>> {
+    let step = 2.0 * std::f64::consts::PI / (teeth * 4) as f64;
+    (0..teeth * 4)
+        .map(|i| {
+            let r = if (i / 2) % 2 == 0 { root_radius } else { tip_radius };
+            let angle = i as f64 * step;
+            Point2::new(r * angle.cos(), r * angle.sin())
+        })
+        .collect()
+}
+
+pub fn shoelace_area(points: &[Point2<f64>]) -> f64 {
+    let n = points.len();
+    let twice: f64 = (0..n)
+        .map(|i| points[i].x * points[(i + 1) % n].y - points[(i + 1) % n].x * points[i].y)
+        .sum();
+    twice.abs() / 2.0
+}
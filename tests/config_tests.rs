@@ -1,4 +1,6 @@
-use rustslicer::config::SlicerConfig;
+mod common;
+
+use rustslicer::config::{DraftShieldHeight, Extruder, FirmwareFlavor, SlicerConfig};
 use tempfile::NamedTempFile;
 
 #[test]
@@ -34,3 +36,209 @@ fn test_config_merge() {
     assert_eq!(config.nozzle_temperature, 220);
     assert_eq!(config.bed_temperature, 70);
 }
+
+#[test]
+fn test_objects_load_with_transforms() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("block.stl");
+    common::cuboid([0.0, 0.0, 0.0], [20.0, 10.0, 5.0])
+        .write_stl_file(path.to_str().unwrap())
+        .unwrap();
+
+    let toml = format!(
+        r#"
+        [[objects]]
+        path = "{}"
+        translate = [50.0, 60.0, 0.0]
+        rotate = [0.0, 0.0, 90.0]
+        scale = 2.0
+        copies = 2
+        "#,
+        path.display()
+    );
+    let config: SlicerConfig = toml::from_str(&toml).unwrap();
+    config.validate().unwrap();
+
+    let copies = config.objects[0].load(5.0).unwrap();
+    assert_eq!(copies.len(), 2);
+
+    // Scaled to 40x20x10, then turned a quarter so X and Y swap
+    let dims = copies[0].bounds.dimensions();
+    assert!((dims.x - 20.0).abs() < 1e-4 && (dims.y - 40.0).abs() < 1e-4 && (dims.z - 10.0).abs() < 1e-4);
+    assert!(copies[0].bounds.min.z.abs() < 1e-9);
+    assert!((copies[1].bounds.min.x - copies[0].bounds.max.x - 5.0).abs() < 1e-4);
+}
+
+#[test]
+fn test_object_without_copies_is_rejected() {
+    let config: SlicerConfig = toml::from_str(
+        r#"
+        [[objects]]
+        path = "part.stl"
+        copies = 0
+        "#,
+    )
+    .unwrap();
+
+    let message = config.validate().unwrap_err().to_string();
+    assert!(message.contains("Object 1 (part.stl)"), "{}", message);
+}
+
+#[test]
+fn test_support_boxes_load_and_must_fit_build_volume() {
+    let config: SlicerConfig = toml::from_str(
+        r#"
+        [[support_blockers]]
+        min = [0.0, 0.0, 0.0]
+        max = [4.0, 4.0, 10.0]
+
+        [[support_enforcers]]
+        min = [10.0, 10.0, 0.0]
+        max = [20.0, 20.0, 300.0]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.support_blockers[0].max, [4.0, 4.0, 10.0]);
+    assert!(config.support_blockers[0].spans(10.0));
+
+    // The enforcer reaches above the 250 mm build volume
+    let message = config.validate().unwrap_err().to_string();
+    assert!(message.contains("[20.0, 20.0, 300.0]"), "{}", message);
+}
+
+#[test]
+fn test_draft_shield_height_is_full_or_millimeters() {
+    let height = |value: &str| toml::from_str::<SlicerConfig>(&format!("draft_shield_height = {}", value)).map(|c| c.draft_shield_height);
+    assert_eq!(height("\"full\"").unwrap(), DraftShieldHeight::Full);
+    assert_eq!(height("20").unwrap(), DraftShieldHeight::Height(20.0));
+    assert_eq!(height("7.5").unwrap(), DraftShieldHeight::Height(7.5));
+    assert!(height("\"half\"").is_err());
+
+    // It saves the way it was written
+    let config = SlicerConfig { draft_shield_height: DraftShieldHeight::Full, ..SlicerConfig::default() };
+    assert!(toml::to_string(&config).unwrap().contains("draft_shield_height = \"full\""));
+}
+
+#[test]
+fn test_unknown_gcode_flavor_lists_the_supported_ones() {
+    let config: SlicerConfig = toml::from_str("gcode_flavor = \"reprapfirmware\"").unwrap();
+    assert_eq!(config.gcode_flavor, FirmwareFlavor::RepRapFirmware);
+
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), "gcode_flavor = \"sailfish\"\n").unwrap();
+    let message = SlicerConfig::load_from_file(file.path()).unwrap_err().to_string();
+    for name in ["sailfish", "marlin", "klipper", "reprapfirmware"] {
+        assert!(message.contains(name), "{}", message);
+    }
+}
+
+#[test]
+fn test_accelerations_beyond_the_machine_are_clamped_with_a_warning() {
+    let config = SlicerConfig {
+        acceleration: Some(1500.0),
+        infill_acceleration: Some(5000.0),
+        max_acceleration: Some(3000.0),
+        jerk: Some(20.0),
+        max_jerk: Some(10.0),
+        ..SlicerConfig::default()
+    };
+    config.validate().unwrap();
+    let warnings = config.limit_warnings();
+    assert_eq!(warnings.len(), 2, "{:?}", warnings);
+    assert!(warnings[0].starts_with("infill_acceleration of 5000"), "{:?}", warnings);
+    assert_eq!(config.infill_acceleration(), Some(3000.0));
+    assert_eq!(config.perimeter_acceleration(), Some(1500.0));
+    assert_eq!(config.jerk(), Some(10.0));
+
+    let negative = SlicerConfig { travel_acceleration: Some(-1.0), ..SlicerConfig::default() };
+    assert!(negative.validate().is_err());
+    assert!(SlicerConfig::default().limit_warnings().is_empty());
+}
+
+#[test]
+fn test_pressure_advance_settings_are_checked_against_the_flavor() {
+    let config = SlicerConfig {
+        gcode_flavor: FirmwareFlavor::RepRapFirmware,
+        pressure_advance: Some(0.04),
+        linear_advance_k: Some(0.05),
+        ..SlicerConfig::default()
+    };
+    config.validate().unwrap();
+    let warnings = config.limit_warnings();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].starts_with("linear_advance_k is left out"), "{:?}", warnings);
+
+    let marlin = SlicerConfig { gcode_flavor: FirmwareFlavor::Marlin, ..config };
+    assert!(marlin.limit_warnings().is_empty());
+    assert_eq!(marlin.pressure_advance(), Some(0.05));
+    assert_eq!(marlin.infill_pressure_advance(), Some(0.05));
+
+    let negative = SlicerConfig { infill_pressure_advance: Some(-0.01), ..SlicerConfig::default() };
+    assert!(negative.validate().is_err());
+
+    // Infill can't have its own without one for the rest to switch back to
+    let infill_only = SlicerConfig { infill_pressure_advance: Some(0.02), ..SlicerConfig::default() };
+    let error = infill_only.validate().unwrap_err().to_string();
+    assert!(error.contains("infill_pressure_advance needs pressure_advance"), "{}", error);
+    let klipper_k = SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, linear_advance_k: Some(0.05), ..infill_only.clone() };
+    assert!(klipper_k.validate().is_err());
+    SlicerConfig { gcode_flavor: FirmwareFlavor::Marlin, ..klipper_k }.validate().unwrap();
+}
+
+#[test]
+fn test_features_map_only_to_extruders_there_are() {
+    let config = SlicerConfig { support_extruder: 1, ..SlicerConfig::default() };
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("support_extruder"), "{}", error);
+
+    let second = Extruder { nozzle_diameter: Some(0.6), filament_diameter: Some(2.85), ..Extruder::default() };
+    let config = SlicerConfig { extruders: vec![Extruder::default(), second], ..config };
+    config.validate().unwrap();
+    assert_eq!(config.extruder_nozzle_diameter(1), 0.6);
+    assert_eq!(config.extruder_filament_diameter(0), config.filament_diameter);
+
+    let parsed: SlicerConfig = toml::from_str("support_extruder = 1\n[[extruders]]\n[[extruders]]\noffset = [25.0, 0.0]\nstandby_temperature = 150\n").unwrap();
+    parsed.validate().unwrap();
+    assert_eq!(parsed.extruders[1].offset, [25.0, 0.0]);
+    assert_eq!(parsed.extruders[1].standby_temperature, Some(150));
+}
+
+#[test]
+fn test_temperatures_beyond_the_machine_are_refused() {
+    let limited = SlicerConfig { max_nozzle_temperature: Some(250), max_bed_temperature: Some(100), ..SlicerConfig::default() };
+    limited.validate().unwrap();
+
+    let hot = SlicerConfig { nozzle_temperature: 300, ..limited.clone() };
+    let error = hot.validate().unwrap_err().to_string();
+    assert!(error.contains("300°C") && error.contains("max_nozzle_temperature"), "{}", error);
+
+    let hot_extruder = Extruder { temperature: Some(260), ..Extruder::default() };
+    assert!(SlicerConfig { extruders: vec![hot_extruder], ..limited.clone() }.validate().is_err());
+
+    let hot_bed = SlicerConfig { first_layer_bed_temperature: Some(110), ..limited };
+    let error = hot_bed.validate().unwrap_err().to_string();
+    assert!(error.contains("max_bed_temperature"), "{}", error);
+}
+
+#[test]
+fn test_overlapping_height_overrides_are_rejected() {
+    let config: SlicerConfig = toml::from_str(
+        "[[overrides]]\nz_min = 10.0\nz_max = 20.0\ntemperature = 200\n\n[[overrides]]\nz_min = 0.0\nz_max = 10.0\nfan_speed = 50.0\n",
+    )
+    .unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.overrides[0].temperature, Some(200));
+    assert_eq!(config.overrides[1].temperature, None);
+    assert_eq!(config.layer_nozzle_temperature(60, 10.1), 200);
+    assert_eq!(config.layer_nozzle_temperature(100, 20.1), config.nozzle_temperature);
+    assert_eq!(config.layer_fan_speed_at(5, 1.1), 50.0);
+
+    let mut overlapping = config.clone();
+    overlapping.overrides[1].z_max = 12.0;
+    let message = overlapping.validate().unwrap_err().to_string();
+    assert!(message.contains("overlap"), "{}", message);
+
+    let mut empty = config;
+    empty.overrides[0].z_max = 10.0;
+    assert!(empty.validate().is_err());
+}
@@ -0,0 +1,2362 @@
+mod common;
+
+use rustslicer::config::{Combing, CommentLevel, DraftShieldHeight, Extruder, FirmwareFlavor, HeatOrder, HeightOverride, PerimeterOrder, SeamPosition, SlicerConfig, SupportBox};
+use rustslicer::gcode::{comb_path, extrusion_length, fan_pwm, number_lines, post_process, verify, GCodeGenerator, GCodeStats, Metadata, PrintObject, ViolationKind};
+use nalgebra::{Point2, Vector2};
+use rustslicer::geometry::{BoundingBox2D, Island, Mesh, Polygon};
+use rustslicer::slicer::SliceEngine;
+use rustslicer::toolpath::PathRole;
+
+fn generate(config: SlicerConfig, mesh: Mesh) -> String {
+    let layers = SliceEngine::new(mesh, config.clone()).unwrap().slice().unwrap();
+    GCodeGenerator::new(config).generate_to_string(&layers).unwrap()
+}
+
+/// Each line of `gcode` with the feedrate in mm/min the printer moves at
+/// while running it, carried over from the last move that set one
+fn with_feedrates(gcode: &str) -> Vec<(&str, f64)> {
+    let mut feedrate = 0.0;
+    gcode
+        .lines()
+        .map(|line| {
+            let code = line.split(';').next().unwrap();
+            if let Some(word) = code.split_whitespace().find(|word| word.starts_with('F')) {
+                feedrate = word[1..].parse().unwrap();
+            }
+            (line, feedrate)
+        })
+        .collect()
+}
+
+#[test]
+fn test_layer_z_steps_from_first_layer_height() {
+    let gcode = generate(SlicerConfig::default(), common::cube(10.0));
+
+    let z_values: Vec<&str> = gcode
+        .lines()
+        .filter(|line| line.starts_with("G1 Z") && !line.contains(';'))
+        .map(|line| line.split_whitespace().nth(1).unwrap())
+        .take(3)
+        .collect();
+
+    assert_eq!(z_values, ["Z0.300", "Z0.500", "Z0.700"]);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_gcode_is_reproducible_across_runs_and_thread_counts() {
+    // Several islands per layer so their order matters
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 5.0]),
+        common::cuboid([20.0, 0.0, 0.0], [30.0, 10.0, 5.0]),
+        common::cuboid([0.0, 20.0, 0.0], [10.0, 30.0, 5.0]),
+        common::sphere(5.0, 24, 12),
+    ]);
+    let config = SlicerConfig::default();
+
+    let first = generate(config.clone(), mesh.clone());
+    assert_eq!(first, generate(config.clone(), mesh.clone()));
+
+    let single_threaded = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(|| generate(config.clone(), mesh.clone()));
+    assert_eq!(first, single_threaded);
+}
+
+#[test]
+fn test_streamed_gcode_matches_collected_layers() {
+    let config = SlicerConfig::default();
+    let mesh = common::sphere(10.0, 32, 16);
+    let expected = generate(config.clone(), mesh.clone());
+
+    let engine = SliceEngine::new(mesh, config.clone()).unwrap();
+    let output = tempfile::NamedTempFile::new().unwrap();
+    GCodeGenerator::new(config)
+        .generate_streaming(engine.layers_iter().lookahead(3), output.path())
+        .unwrap();
+
+    assert_eq!(std::fs::read_to_string(output.path()).unwrap(), expected);
+}
+
+#[test]
+fn test_cancelled_stream_removes_partial_output() {
+    use std::sync::atomic::AtomicBool;
+
+    let config = SlicerConfig::default();
+    let engine = SliceEngine::new(common::cube(10.0), config.clone()).unwrap();
+    let cancel = AtomicBool::new(true);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.gcode");
+
+    let result = GCodeGenerator::new(config).generate_streaming(engine.layers_iter().cancel_on(&cancel), &path);
+
+    assert!(matches!(result, Err(rustslicer::SlicerError::Cancelled)));
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_thin_shim_prints_one_layer() {
+    let shim = common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 0.15]);
+    let gcode = generate(SlicerConfig::default(), shim);
+
+    let layer_markers = gcode
+        .lines()
+        .filter_map(|line| line.strip_prefix("; Layer "))
+        .filter(|rest| rest.parse::<usize>().is_ok());
+    assert_eq!(layer_markers.count(), 1);
+
+    // The layer is only 0.15 mm tall, but the nozzle stays at first layer height
+    assert!(gcode.lines().any(|line| line.starts_with("G1 Z0.300")));
+}
+
+#[test]
+fn test_gcode_never_goes_below_first_layer_height() {
+    let mesh = common::cuboid([0.0, 0.0, -50.0], [10.0, 10.0, -40.0]);
+    let gcode = generate(SlicerConfig::default(), mesh);
+
+    let z_values: Vec<f64> = gcode
+        .lines()
+        .filter(|line| line.starts_with("G1 Z") && !line.contains(';'))
+        .map(|line| line.split_whitespace().nth(1).unwrap()[1..].parse().unwrap())
+        .collect();
+
+    assert_eq!(z_values.len(), 50);
+    assert_eq!(z_values[0], 0.3);
+    assert!(z_values.iter().all(|&z| z >= 0.3));
+}
+
+#[test]
+fn test_perimeter_order_decides_which_wall_comes_first() {
+    let first_travel = |order| {
+        let config = SlicerConfig { perimeters: Some(2), perimeter_order: order, ..SlicerConfig::default() };
+        let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 10.0]));
+        gcode
+            .lines()
+            .skip_while(|line| *line != "; Layer 0")
+            .find(|line| line.starts_with("G1 X"))
+            .unwrap()
+            .to_string()
+    };
+
+    // Seams are aligned to the rear left corner
+    assert!(first_travel(PerimeterOrder::Outer).starts_with("G1 X0.200 Y9.800"));
+    assert!(first_travel(PerimeterOrder::Inner).starts_with("G1 X0.600 Y9.400"));
+}
+
+#[test]
+fn test_wall_roles_get_their_own_feedrates() {
+    let config = SlicerConfig {
+        perimeters: Some(2),
+        perimeter_order: PerimeterOrder::Outer,
+        perimeter_speed: Some(50.0),
+        external_perimeter_speed: Some(25.0),
+        first_layer_speed: Some(10.0),
+        infill_percentage: 0,
+        top_solid_layers: Some(0),
+        bottom_solid_layers: Some(0),
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 1.0]));
+
+    // Extrusion feedrates per layer, in print order
+    let mut layers: Vec<Vec<f64>> = Vec::new();
+    for (line, feedrate) in with_feedrates(&gcode) {
+        if line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok() {
+            layers.push(Vec::new());
+        } else if line.starts_with("G1 X") && line.contains(" E") {
+            let layer = layers.last_mut().unwrap();
+            if layer.last() != Some(&feedrate) {
+                layer.push(feedrate);
+            }
+        }
+    }
+
+    assert_eq!(layers[0], [600.0]);
+    for layer in &layers[1..] {
+        assert_eq!(layer, &[1500.0, 3000.0]);
+    }
+}
+
+#[test]
+fn test_infill_passes_follow_density() {
+    let config = SlicerConfig {
+        infill_percentage: 20,
+        infill_angle: 0.0,
+        infill_speed: Some(80.0),
+        top_solid_layers: Some(0),
+        bottom_solid_layers: Some(0),
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [40.0, 40.0, 1.0]));
+
+    // Y of each infill pass on the first layer, which runs along X, from
+    // whichever side the walls ended nearest
+    let infill: Vec<&str> = with_feedrates(&gcode)
+        .into_iter()
+        .skip_while(|(line, _)| *line != "; Layer 0")
+        .skip_while(|(line, _)| *line != "; Infill")
+        .take_while(|(line, _)| !line.is_empty())
+        .filter(|(line, _)| line.starts_with("G1 X") && line.contains(" E"))
+        .map(|(line, feedrate)| {
+            assert_eq!(feedrate, 4800.0, "{}", line);
+            line
+        })
+        .collect();
+
+    let mut rows: Vec<f64> = infill
+        .iter()
+        .map(|line| line.split_whitespace().nth(2).unwrap()[1..].parse().unwrap())
+        .collect();
+    rows.dedup();
+    assert_eq!(rows.len(), 19);
+    for pair in rows.windows(2) {
+        assert!(((pair[1] - pair[0]).abs() - 2.0).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_infill_direction_turns_each_layer() {
+    let config = SlicerConfig {
+        infill_angle: 30.0,
+        infill_angle_increment: 60.0,
+        first_layer_height: 0.2,
+        top_solid_layers: Some(0),
+        bottom_solid_layers: Some(0),
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 0.6]));
+
+    // Direction of the longest infill move on each layer, modulo 180°
+    let mut directions = Vec::new();
+    let mut infill = false;
+    let mut last: Option<(f64, f64)> = None;
+    for line in gcode.lines() {
+        if line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok() {
+            directions.push((0.0, 0.0));
+            infill = false;
+        } else if line == "; Infill" {
+            infill = true;
+        } else if line.starts_with("G1 X") {
+            let mut words = line.split_whitespace().skip(1);
+            let x: f64 = words.next().unwrap()[1..].parse().unwrap();
+            let y: f64 = words.next().unwrap()[1..].parse().unwrap();
+            if let (true, true, Some((px, py))) = (infill, line.contains(" E"), last) {
+                let length = (x - px).hypot(y - py);
+                let direction = directions.last_mut().unwrap();
+                if length > direction.0 {
+                    *direction = (length, (y - py).atan2(x - px).to_degrees().rem_euclid(180.0));
+                }
+            }
+            last = Some((x, y));
+        }
+    }
+
+    assert_eq!(directions.len(), 3);
+    for (direction, expected) in directions.iter().zip([30.0, 90.0, 150.0]) {
+        assert!((direction.1 - expected).abs() < 0.1, "{:?}", directions);
+    }
+}
+
+#[test]
+fn test_thin_rib_prints_as_one_line() {
+    let extrusion_paths = |detect_thin_walls| {
+        let config = SlicerConfig { detect_thin_walls, ..SlicerConfig::default() };
+        let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [10.0, 0.5, 0.6]));
+        let layer: Vec<String> = gcode
+            .lines()
+            .skip_while(|line| *line != "; Layer 1")
+            .take_while(|line| !line.is_empty())
+            .filter(|line| line.starts_with("G1 X"))
+            .map(str::to_string)
+            .collect();
+        // Each path starts with a travel move
+        let paths = layer.iter().filter(|line| !line.contains(" E")).count();
+        (paths, layer)
+    };
+
+    let (paths, layer) = extrusion_paths(true);
+    assert_eq!(paths, 1);
+    assert!(layer.iter().all(|line| line.contains(" Y0.250 ")));
+
+    // Without detection the rib gets a wall loop squeezed inside it
+    let (paths, layer) = extrusion_paths(false);
+    assert_eq!(paths, 1);
+    assert!(layer.iter().any(|line| !line.contains(" Y0.250 ")));
+}
+
+#[test]
+fn test_infill_reaches_into_walls_by_overlap() {
+    // Infill ends relative to the 0.8 mm the two walls take up
+    let infill_span = |infill_overlap_percent| {
+        let config = SlicerConfig {
+            infill_overlap_percent,
+            infill_angle: 0.0,
+            top_solid_layers: Some(0),
+            bottom_solid_layers: Some(0),
+            ..SlicerConfig::default()
+        };
+        let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 1.0]));
+        let xs: Vec<f64> = gcode
+            .lines()
+            .skip_while(|line| *line != "; Layer 0")
+            .skip_while(|line| *line != "; Infill")
+            .take_while(|line| !line.is_empty())
+            .filter(|line| line.starts_with("G1 X"))
+            .map(|line| line.split_whitespace().nth(1).unwrap()[1..].parse().unwrap())
+            .collect();
+        (xs.iter().copied().fold(f64::INFINITY, f64::min), xs.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+    };
+
+    assert_eq!(infill_span(0.0), (0.8, 19.2));
+    // 15 % of a 0.4 mm line
+    assert_eq!(infill_span(15.0), (0.74, 19.26));
+}
+
+#[test]
+fn test_brim_is_labelled_on_first_layer_only() {
+    let config = SlicerConfig { brim_width: 3.0, ..SlicerConfig::default() };
+    let gcode = generate(config, common::cuboid([10.0, 10.0, 0.0], [20.0, 20.0, 1.0]));
+    assert_eq!(gcode.matches("; Brim").count(), 1);
+
+    // Loops start with a travel move; the walls that follow start inside
+    // the cuboid
+    let brim = gcode
+        .lines()
+        .skip_while(|line| *line != "; Brim")
+        .skip(1)
+        .take_while(|line| !line.starts_with(';'))
+        .filter(|line| line.starts_with("G1 X"))
+        .filter(|line| !line.contains(" E"))
+        .filter(|line| line.split_whitespace().nth(1).unwrap()[1..].parse::<f64>().unwrap() < 10.0)
+        .count();
+    assert!(gcode.find("; Brim").unwrap() > gcode.find("; Layer 0").unwrap());
+    assert!(gcode.find("; Brim").unwrap() < gcode.find("; Layer 1").unwrap());
+    assert_eq!(brim, 8);
+}
+
+#[test]
+fn test_support_is_printed_at_its_own_speed() {
+    let support_lines = |support_material| {
+        let config = SlicerConfig { support_material, support_speed: Some(35.0), ..SlicerConfig::default() };
+        let gcode = generate(config, common::tee(10.0, 2.0, 4.0, 5.0));
+        with_feedrates(&gcode)
+            .into_iter()
+            .skip_while(|(line, _)| *line != "; Layer 5")
+            .skip_while(|(line, _)| *line != "; Support")
+            .take_while(|(line, _)| !line.is_empty())
+            .filter(|(line, _)| line.starts_with("G1 X") && line.contains(" E"))
+            .map(|(line, feedrate)| (line.to_string(), feedrate))
+            .collect::<Vec<_>>()
+    };
+
+    let lines = support_lines(true);
+    assert!(!lines.is_empty());
+    assert!(lines.iter().all(|(_, feedrate)| *feedrate == 2100.0));
+    assert!(support_lines(false).is_empty());
+}
+
+#[test]
+fn test_support_interface_lines_are_dense_and_cross() {
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        support_material: true,
+        support_density: 20,
+        support_interface_density: 80,
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::tee(10.0, 2.0, 4.0, 8.1));
+
+    // Distinct coordinates across the support lines on a layer
+    let support_rows = |layer: &str, axis: usize| {
+        let mut rows: Vec<String> = gcode
+            .lines()
+            .skip_while(|line| *line != layer)
+            .skip_while(|line| *line != "; Support")
+            .take_while(|line| !line.is_empty())
+            .filter(|line| line.starts_with("G1 X") && line.contains(" E"))
+            .map(|line| line.split_whitespace().nth(axis).unwrap().to_string())
+            .collect();
+        rows.sort();
+        rows.dedup();
+        rows
+    };
+
+    // Sparse lines along X 2 mm apart on Y in 4 mm deep columns; interface
+    // lines along Y every 0.5 mm across two 3.4 mm wide columns
+    assert_eq!(support_rows("; Layer 30", 2).len(), 2);
+    assert_eq!(support_rows("; Layer 38", 1).len(), 2 * 7);
+}
+
+#[test]
+fn test_support_blocker_leaves_arm_without_support_paths() {
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        support_material: true,
+        support_blockers: vec![SupportBox { min: [0.0, 0.0, 0.0], max: [4.0, 4.0, 20.0] }],
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::tee(10.0, 2.0, 4.0, 8.1));
+
+    let mut in_support = false;
+    let mut support_moves = 0;
+    for line in gcode.lines() {
+        if line.starts_with(';') {
+            in_support = line == "; Support";
+        } else if in_support && line.starts_with("G1 X") && line.contains(" E") {
+            let x: f64 = line.split_whitespace().nth(1).unwrap()[1..].parse().unwrap();
+            assert!(x >= 6.6 - 1e-3, "{}", line);
+            support_moves += 1;
+        }
+    }
+    assert!(support_moves > 0);
+}
+
+#[test]
+fn test_support_mapped_to_second_extruder_is_bracketed_by_tool_changes() {
+    let second = Extruder { offset: [20.0, 0.0], temperature: Some(220), standby_temperature: Some(150), ..Extruder::default() };
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        support_material: true,
+        support_extruder: 1,
+        extruders: vec![Extruder::default(), second],
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config.clone(), common::tee(10.0, 2.0, 4.0, 8.1));
+    let lines: Vec<&str> = gcode.lines().take_while(|line| *line != "; End sequence").collect();
+
+    let (mut tool, mut in_support) = (0, false);
+    // E each extruder was put away at
+    let mut put_away = [-5.0, -5.0];
+    let (mut changes, mut support_moves) = (0, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let e = || line.split_whitespace().find(|word| word.starts_with('E')).unwrap()[1..].parse::<f64>().unwrap();
+        if let Some(next) = line.strip_prefix('T').and_then(|n| n.parse::<usize>().ok()) {
+            // Heated before and waited for after; the other left on standby
+            assert!(lines[i - 1].starts_with(&format!("M104 T{} S{}", next, if next == 1 { 220 } else { 210 })));
+            assert!(lines[i + 1].starts_with(&format!("M109 T{} S", next)));
+            if tool == 1 {
+                assert_eq!(lines[i - 2], "M104 T1 S150 ; Standby temperature");
+            }
+            // Picking up its own E where it left it
+            assert_eq!(lines[i + 2], format!("G92 E{:.5}", put_away[next]));
+            tool = next;
+            changes += 1;
+        } else if line.ends_with("; Retract") && lines[i - 1] == "; Tool change" {
+            put_away[tool] = e();
+        } else if line.starts_with(';') {
+            in_support = *line == "; Support" || (in_support && *line == "; Tool change");
+        } else if line.starts_with("G1 X") && line.contains(" E") {
+            assert_eq!(tool, usize::from(in_support), "{}", line);
+            if in_support {
+                // Shifted by the second extruder's offset
+                let x: f64 = line.split_whitespace().nth(1).unwrap()[1..].parse().unwrap();
+                assert!((-20.0..=-10.0).contains(&x), "{}", line);
+                support_moves += 1;
+            }
+        }
+    }
+    assert!(support_moves > 0);
+    // Into and out of the support on every layer that has it
+    assert!(changes >= 2 * 30 && changes % 2 == 0, "{} tool changes", changes);
+
+    // Offsets left to the firmware keep the coordinates
+    let firmware = generate(SlicerConfig { firmware_tool_offsets: true, ..config.clone() }, common::tee(10.0, 2.0, 4.0, 8.1));
+    assert!(!firmware.lines().any(|line| line.starts_with("G1 X-")));
+
+    // A single extruder prints as it always has
+    let single = SlicerConfig { extruders: vec![Extruder::default()], ..SlicerConfig::default() };
+    assert_eq!(generate(single, common::cube(10.0)), generate(SlicerConfig::default(), common::cube(10.0)));
+
+    // An extruder that isn't there, in a config never validated, is an
+    // error rather than a panic
+    let layers = SliceEngine::new(common::tee(10.0, 2.0, 4.0, 8.1), config.clone()).unwrap().slice().unwrap();
+    let missing = SlicerConfig { support_extruder: 2, ..config };
+    let error = GCodeGenerator::new(missing).generate_to_string(&layers).unwrap_err();
+    assert!(matches!(error, rustslicer::SlicerError::ConfigError(_)), "{}", error);
+}
+
+#[test]
+fn test_bridge_lines_run_leg_to_leg_slowly_with_fan() {
+    let config = SlicerConfig { first_layer_height: 0.2, bridge_speed: Some(20.0), fan_speed: 50.0, ..SlicerConfig::default() };
+    let gcode = generate(config, common::table(20.0, 10.0, 3.0, 5.1, 2.0));
+
+    let bridge: Vec<(&str, f64)> =
+        with_feedrates(&gcode).into_iter().skip_while(|(line, _)| *line != "; Bridge").skip(1).take_while(|(line, _)| *line != "M106 S128").collect();
+    assert_eq!(bridge[0].0, "M106 S255");
+    let moves: Vec<(f64, f64)> = bridge
+        .iter()
+        .filter(|(line, _)| line.starts_with("G1 X") && line.contains(" E"))
+        .map(|(line, feedrate)| {
+            assert_eq!(*feedrate, 1200.0, "{}", line);
+            let coordinate = |i: usize| line.split_whitespace().nth(i).unwrap()[1..].parse::<f64>().unwrap();
+            (coordinate(1), coordinate(2))
+        })
+        .collect();
+    assert!(!moves.is_empty());
+
+    // Lines across the 14 mm gap, linked by short steps along the legs
+    for pair in moves.windows(2) {
+        let (dx, dy) = ((pair[1].0 - pair[0].0).abs(), (pair[1].1 - pair[0].1).abs());
+        assert!(dy < 1e-3 || (dx < 1e-3 && dy <= 0.4 + 1e-3), "{:?}", pair);
+    }
+    assert!(moves.windows(2).any(|pair| (pair[1].0 - pair[0].0).abs() > 13.0));
+}
+
+/// Start of the first loop on each layer, which with walls printed outside
+/// in and no infill is the external perimeter
+fn first_loop_starts(gcode: &str) -> Vec<Point2<f64>> {
+    let mut starts = Vec::new();
+    let mut lines = gcode.lines();
+    while lines.by_ref().any(|line| line.starts_with("; Layer ")) {
+        let travel = lines.by_ref().find(|line| line.starts_with("G1 X")).unwrap();
+        let coordinate = |i: usize| travel.split_whitespace().nth(i).unwrap()[1..].parse::<f64>().unwrap();
+        starts.push(Point2::new(coordinate(1), coordinate(2)));
+    }
+    starts
+}
+
+#[test]
+fn test_aligned_seam_lines_up_on_cylinder() {
+    let config = SlicerConfig {
+        seam_position: SeamPosition::Aligned,
+        perimeter_order: PerimeterOrder::Outer,
+        infill_percentage: 0,
+        top_solid_layers: Some(0),
+        bottom_solid_layers: Some(0),
+        ..SlicerConfig::default()
+    };
+    let circle: Vec<Point2<f64>> = (0..48)
+        .map(|i| {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / 48.0;
+            Point2::new(10.0 + 8.0 * angle.cos(), 10.0 + 8.0 * angle.sin())
+        })
+        .collect();
+    let gcode = generate(config, common::prism(&circle, Point2::new(10.0, 10.0), 5.0));
+
+    let starts = first_loop_starts(&gcode);
+    assert_eq!(starts.len(), 25);
+    for pair in starts.windows(2) {
+        assert!((pair[1] - pair[0]).norm() < 0.4, "{:?}", pair);
+    }
+}
+
+#[test]
+fn test_seam_hides_in_concave_corner() {
+    // An L with its one concave corner at (10, 10), where the external wall
+    // turns at (9.8, 9.8)
+    let outline = [
+        Point2::new(5.0, 5.0),
+        Point2::new(15.0, 5.0),
+        Point2::new(15.0, 10.0),
+        Point2::new(10.0, 10.0),
+        Point2::new(10.0, 15.0),
+        Point2::new(5.0, 15.0),
+    ];
+    let starts = |position, hide_seam_in_corners| {
+        let config = SlicerConfig {
+            seam_position: position,
+            hide_seam_in_corners,
+            perimeter_order: PerimeterOrder::Outer,
+            infill_percentage: 0,
+            top_solid_layers: Some(0),
+            bottom_solid_layers: Some(0),
+            ..SlicerConfig::default()
+        };
+        first_loop_starts(&generate(config, common::prism(&outline, Point2::new(7.5, 7.5), 1.0)))
+    };
+
+    for position in [SeamPosition::Nearest, SeamPosition::Aligned, SeamPosition::Rear, SeamPosition::Random] {
+        for start in starts(position, true) {
+            assert!((start - Point2::new(9.8, 9.8)).norm() < 1e-3, "{:?}: {:?}", position, start);
+        }
+    }
+
+    // Otherwise rear seams go to the top edge
+    for start in starts(SeamPosition::Rear, false) {
+        assert!((start.y - 14.8).abs() < 1e-3, "{:?}", start);
+    }
+}
+
+#[test]
+fn test_path_ordering_cuts_travel_across_scattered_parts() {
+    // 30 small squares scattered over the bed
+    let mut seed = 12345u64;
+    let mut next = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as f64 / (1u64 << 31) as f64
+    };
+    let squares: Vec<Mesh> = (0..30)
+        .map(|_| {
+            let (x, y) = (5.0 + 190.0 * next(), 5.0 + 190.0 * next());
+            common::cuboid([x, y, 0.0], [x + 5.0, y + 5.0, 1.0])
+        })
+        .collect();
+    let layers = SliceEngine::new(Mesh::merge(squares), SlicerConfig::default()).unwrap().slice().unwrap();
+
+    let travel = |optimize_travel| {
+        GCodeGenerator::new(SlicerConfig { optimize_travel, ..SlicerConfig::default() }).measure_travel(layers.iter().map(Ok)).unwrap()
+    };
+    // More than 40% less travel
+    let (unordered, ordered) = (travel(false), travel(true));
+    assert!(ordered < 0.6 * unordered, "{} vs {}", ordered, unordered);
+}
+
+/// A C opening to +X, 10 mm square with a 4 mm gap between its arms
+fn c_outline(origin: Point2<f64>) -> Vec<Point2<f64>> {
+    [(0.0, 0.0), (10.0, 0.0), (10.0, 3.0), (3.0, 3.0), (3.0, 7.0), (10.0, 7.0), (10.0, 10.0), (0.0, 10.0)]
+        .iter()
+        .map(|&(x, y)| origin + nalgebra::Vector2::new(x, y))
+        .collect()
+}
+
+/// Whether every point along the polyline, every 0.05 mm, is in `island`
+fn stays_inside(island: &Island, path: &[Point2<f64>]) -> bool {
+    path.windows(2).all(|pair| {
+        let steps = ((pair[1] - pair[0]).norm() / 0.05).ceil().max(1.0) as usize;
+        (0..=steps).all(|k| island.contains_point(&(pair[0] + (pair[1] - pair[0]) * (k as f64 / steps as f64))))
+    })
+}
+
+#[test]
+fn test_comb_path_goes_round_concavity() {
+    let island = Island::new(Polygon::new(c_outline(Point2::origin())));
+    let regions = island.offset(-0.2);
+    let (from, to) = (Point2::new(8.0, 1.5), Point2::new(8.0, 8.5));
+
+    // The straight line crosses the gap between the arms
+    assert!(!stays_inside(&island, &[from, to]));
+    let path = comb_path(from, to, &regions).unwrap();
+    assert_eq!((path[0], path[path.len() - 1]), (from, to));
+    assert!(stays_inside(&island, &path), "{:?}", path);
+
+    // Round the inside of the C, not the outside
+    let length: f64 = path.windows(2).map(|pair| (pair[1] - pair[0]).norm()).sum();
+    assert!(length < 20.0, "{}", length);
+
+    // Within an arm the line is already inside
+    assert_eq!(comb_path(from, Point2::new(2.0, 1.5), &regions).unwrap().len(), 2);
+    assert!(comb_path(from, Point2::new(20.0, 1.5), &regions).is_none());
+}
+
+#[test]
+fn test_combed_travel_stays_inside_c() {
+    let outline = c_outline(Point2::new(10.0, 10.0));
+    let config = SlicerConfig { combing: Combing::All, ..SlicerConfig::default() };
+    let gcode = generate(config, common::prism(&outline, Point2::new(11.5, 15.0), 2.0));
+    let island = Island::new(Polygon::new(outline));
+
+    // Every travel without a retraction before it
+    let (mut position, mut retracted, mut combed) = (Point2::origin(), false, 0);
+    for line in gcode.lines() {
+        if line.ends_with("; Retract") {
+            retracted = true;
+        } else if line.ends_with("; Unretract") {
+            retracted = false;
+        } else if line.starts_with("G1 X") {
+            let coordinate = |i: usize| line.split_whitespace().nth(i).unwrap()[1..].parse::<f64>().unwrap();
+            let point = Point2::new(coordinate(1), coordinate(2));
+            if !line.contains(" E") && !retracted {
+                assert!(stays_inside(&island, &[position, point]), "{:?} to {:?}", position, point);
+                combed += 1;
+            }
+            position = point;
+        }
+    }
+    // Only the move onto the part from the origin leaves it
+    assert!(combed > 0);
+    assert_eq!(gcode.lines().filter(|line| line.ends_with("; Retract")).count(), 1);
+}
+
+#[test]
+fn test_retraction_skips_travel_within_an_island() {
+    // Two dense cuboids apart, so travel both stays inside islands and
+    // crosses between them
+    let retractions = |retraction_only_when_crossing_perimeters, retraction_minimum_travel| {
+        let config = SlicerConfig {
+            infill_percentage: 80,
+            retraction_minimum_travel,
+            retraction_only_when_crossing_perimeters,
+            ..SlicerConfig::default()
+        };
+        let mesh = Mesh::merge(vec![
+            common::cuboid([0.0, 0.0, 0.0], [15.0, 15.0, 2.0]),
+            common::cuboid([25.0, 0.0, 0.0], [40.0, 15.0, 2.0]),
+        ]);
+        let gcode = generate(config, mesh);
+        let layers = gcode.lines().filter(|line| line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok()).count();
+        // Less the retraction in the end sequence
+        (gcode.lines().filter(|line| line.starts_with("G1 E-")).count() - 1, layers)
+    };
+
+    let (every, layers) = retractions(false, 0.0);
+    let (crossing, _) = retractions(true, 0.0);
+    // At least the hop between the cuboids on each layer, and at most that
+    // and the move onto the layer
+    assert!((layers..=2 * layers).contains(&crossing), "{} on {} layers", crossing, layers);
+    assert!(every > 5 * crossing, "{} against {}", every, crossing);
+
+    // No travel is long enough
+    assert_eq!(retractions(false, 100.0).0, 0);
+}
+
+/// Filament fed per mm over the longest infill move of each layer, or None
+/// on layers without infill
+fn infill_e_per_mm(gcode: &str) -> Vec<Option<f64>> {
+    let mut layers = Vec::new();
+    let (mut position, mut e, mut infill) = (Point2::origin(), 0.0, false);
+    let mut longest: Option<(f64, f64)> = None;
+    for line in gcode.lines() {
+        if line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok() {
+            layers.push(longest.take());
+            infill = false;
+        } else if line.starts_with("; ") {
+            infill = line == "; Infill";
+        }
+        let words: Vec<&str> = line.split(';').next().unwrap().split_whitespace().collect();
+        let value = |axis: char| words.iter().find(|word| word.starts_with(axis)).map(|word| word[1..].parse::<f64>().unwrap());
+        let point = value('X').zip(value('Y')).map(|(x, y)| Point2::new(x, y));
+        // E also moves on its own when retracting, and is reset by G92
+        let next = value('E');
+        if let (Some(point), Some(next)) = (point, next) {
+            let length = (point - position).norm();
+            if infill && longest.is_none_or(|(longest, _)| length > longest) {
+                longest = Some((length, (next - e) / length));
+            }
+        }
+        e = next.unwrap_or(e);
+        position = point.unwrap_or(position);
+    }
+    layers.push(longest);
+    // Nothing comes before the first layer
+    layers.remove(0);
+    layers.into_iter().map(|longest| longest.map(|(_, e_per_mm)| e_per_mm)).collect()
+}
+
+#[test]
+fn test_combined_infill_feeds_more_filament_every_few_layers() {
+    let config = |infill_combine_every| SlicerConfig {
+        layer_height: 0.1,
+        first_layer_height: 0.1,
+        infill_angle: 0.0,
+        infill_angle_increment: 0.0,
+        top_solid_layers: Some(0),
+        bottom_solid_layers: Some(0),
+        infill_combine_every,
+        ..SlicerConfig::default()
+    };
+    // 0.1 mm layers combine three at a time at most under a 0.4 mm nozzle
+    assert_eq!(config(5).infill_combine_layers(), 3);
+
+    let mesh = || common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 0.7]);
+    let normal = infill_e_per_mm(&generate(config(1), mesh()));
+    let combined = infill_e_per_mm(&generate(config(5), mesh()));
+    assert_eq!(normal.len(), 7);
+    assert!(normal.iter().all(Option::is_some));
+
+    // The first layer stands alone, then each group's infill is printed on
+    // its top layer with the flow of all three
+    let base = normal[0].unwrap();
+    for (index, e_per_mm) in combined.iter().enumerate() {
+        if index % 3 == 0 {
+            let expected = if index == 0 { base } else { 3.0 * base };
+            assert!((e_per_mm.unwrap() - expected).abs() < 1e-3 * base, "layer {}: {:?}", index, e_per_mm);
+        } else {
+            assert_eq!(*e_per_mm, None, "layer {}", index);
+        }
+    }
+}
+
+#[test]
+fn test_small_pin_walls_print_slowly() {
+    let config = SlicerConfig {
+        external_perimeter_speed: Some(40.0),
+        small_perimeter_speed: Some(15.0),
+        ..SlicerConfig::default()
+    };
+    let center = Point2::new(30.0, 10.0);
+    let pin: Vec<Point2<f64>> = (0..24)
+        .map(|i| {
+            let angle = i as f64 * std::f64::consts::TAU / 24.0;
+            center + 0.75 * nalgebra::Vector2::new(angle.cos(), angle.sin())
+        })
+        .collect();
+    let mesh = Mesh::merge(vec![common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 2.0]), common::prism(&pin, center, 2.0)]);
+    let gcode = generate(config, mesh);
+
+    let moves: Vec<(Point2<f64>, f64)> = with_feedrates(&gcode)
+        .into_iter()
+        .skip_while(|(line, _)| *line != "; Layer 1")
+        .filter(|(line, _)| line.starts_with("G1 X") && line.contains(" E"))
+        .map(|(line, feedrate)| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            (Point2::new(words[1][1..].parse().unwrap(), words[2][1..].parse().unwrap()), feedrate)
+        })
+        .collect();
+    let (on_pin, on_square): (Vec<_>, Vec<_>) = moves.iter().partition(|(point, _)| point.x > 25.0);
+    assert!(!on_pin.is_empty());
+    // Only the pin's outer wall is left, at the small perimeter speed; its
+    // inner loop is too small to print
+    for (point, feedrate) in &on_pin {
+        assert_eq!(*feedrate, 900.0);
+        assert!(((point - center).norm() - 0.55).abs() < 0.05, "{:?}", point);
+    }
+    assert!(on_square.iter().any(|(_, feedrate)| *feedrate == 2400.0));
+    assert!(on_square.iter().all(|(_, feedrate)| *feedrate != 900.0));
+}
+
+#[test]
+fn test_draft_shield_loop_stands_first_on_every_layer() {
+    let config = |draft_shield_height| SlicerConfig {
+        draft_shield: true,
+        draft_shield_distance: 5.0,
+        draft_shield_height,
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config(DraftShieldHeight::Full), common::pyramid(10.0, 2.0, 5.0));
+
+    // Length of the shield loops on each layer: the extrusion after each
+    // shield label, up to the next travel
+    let mut shields: Vec<Vec<f64>> = Vec::new();
+    let (mut position, mut in_shield) = (Point2::origin(), false);
+    for line in gcode.lines() {
+        if line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok() {
+            shields.push(Vec::new());
+        } else if line == "; Draft shield" {
+            shields.last_mut().unwrap().push(0.0);
+            in_shield = true;
+        }
+        if !line.starts_with("G1 X") {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let point = Point2::new(words[1][1..].parse().unwrap(), words[2][1..].parse::<f64>().unwrap());
+        let extruding = line.contains(" E");
+        if let Some(loops) = shields.last_mut() {
+            match loops.last_mut() {
+                Some(length) if in_shield && extruding => *length += (point - position).norm(),
+                Some(&mut length) if in_shield && length > 0.0 => in_shield = false,
+                None if extruding => panic!("extrusion before the shield: {}", line),
+                _ => {}
+            }
+        }
+        position = point;
+    }
+
+    assert_eq!(shields.len(), 25);
+    // 5 mm around the 10 mm base, the corners somewhere between rounded
+    // and square
+    let (rounded, square) = (4.0 * 10.0 + std::f64::consts::TAU * 5.0, 4.0 * 20.0);
+    for (index, loops) in shields.iter().enumerate() {
+        assert_eq!(loops.len(), 1, "layer {}", index);
+        assert!((rounded..=square).contains(&loops[0]), "layer {}: {}", index, loops[0]);
+        assert!((loops[0] - shields[0][0]).abs() < 1e-6);
+    }
+
+    // A lower shield stops partway up
+    let gcode = generate(config(DraftShieldHeight::Height(2.0)), common::pyramid(10.0, 2.0, 5.0));
+    assert_eq!(gcode.lines().filter(|line| *line == "; Draft shield").count(), 9);
+}
+
+#[test]
+fn test_string_writer_and_file_output_agree_with_their_stats() {
+    let config = SlicerConfig { retraction_distance: 0.0, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let generator = GCodeGenerator::new(config.clone());
+    let gcode = generator.generate_to_string(&layers).unwrap();
+
+    let mut written = Vec::new();
+    let stats = generator.generate_to_writer(&layers, &mut written).unwrap();
+    assert_eq!(String::from_utf8(written).unwrap(), gcode);
+    assert_eq!(stats.bytes, gcode.len() as u64);
+    let moves = gcode.lines().filter(|line| line.starts_with("G0 ") || line.starts_with("G1 ")).count();
+    assert_eq!(stats.moves, moves);
+    assert!(stats.filament_length > 0.0);
+
+    let output = tempfile::NamedTempFile::new().unwrap();
+    assert_eq!(generator.generate_to_file(&layers, output.path()).unwrap(), stats);
+    assert_eq!(std::fs::read_to_string(output.path()).unwrap(), gcode);
+
+    // Everything but the start sequence goes twice as fast, so takes about
+    // half as long
+    let fast = SlicerConfig { print_speed: 120.0, travel_speed: 240.0, ..config };
+    let fast_stats = GCodeGenerator::new(fast).generate_to_writer(&layers, std::io::sink()).unwrap();
+    let ratio = fast_stats.print_time / stats.print_time;
+    assert!((0.45..0.6).contains(&ratio), "{} s against {} s", fast_stats.print_time, stats.print_time);
+}
+
+#[test]
+fn test_extrusion_length_fills_the_line_volume() {
+    // 100 mm of a 0.4 mm wide, 0.2 mm high line from 1.75 mm filament
+    assert!((extrusion_length(100.0, 0.4, 0.2, 1.0, 1.75) - 3.3260).abs() < 5e-5);
+    assert!((extrusion_length(100.0, 0.4, 0.2, 0.95, 1.75) - 3.1597).abs() < 5e-5);
+    assert!((extrusion_length(100.0, 0.4, 0.2, 1.0, 2.85) - 1.2540).abs() < 5e-5);
+    assert_eq!(extrusion_length(0.0, 0.4, 0.2, 1.0, 1.75), 0.0);
+}
+
+#[test]
+fn test_solid_layer_feeds_filament_for_its_volume() {
+    let config = SlicerConfig { first_layer_height: 0.2, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 0.2]), config.clone()).unwrap().slice().unwrap();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, std::io::sink()).unwrap();
+
+    // The lines fill the square to within the gaps between them and the
+    // walls
+    let expected = 20.0 * 20.0 * 0.2 / (std::f64::consts::PI * (1.75f64 / 2.0).powi(2));
+    assert!((stats.filament_length / expected - 1.0).abs() < 0.05, "{} mm against {} mm", stats.filament_length, expected);
+
+    // The first layer flow scales all of it
+    let squashed = SlicerConfig { first_layer_flow: 1.2, ..config };
+    let stats_squashed = GCodeGenerator::new(squashed).generate_to_writer(&layers, std::io::sink()).unwrap();
+    assert!((stats_squashed.filament_length / stats.filament_length - 1.2).abs() < 1e-6);
+}
+
+#[test]
+fn test_z_hop_lifts_for_each_retracted_travel_and_drops_back() {
+    let config = SlicerConfig { retraction_z_lift: 0.4, retraction_extra_restart: 0.2, max_feedrate_z: Some(12.0), ..SlicerConfig::default() };
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 1.0]),
+        common::cuboid([20.0, 0.0, 0.0], [30.0, 10.0, 1.0]),
+    ]);
+    let gcode = generate(config.clone(), mesh.clone());
+
+    let (mut layer_z, mut lifted, mut retracted) = (0.0, false, false);
+    let mut pairs = 0;
+    for line in gcode.lines().take_while(|line| *line != "; End sequence") {
+        let z = || line.split_whitespace().nth(1).unwrap()[1..].parse::<f64>().unwrap();
+        if line.ends_with("; Retract") {
+            assert!(!retracted);
+            retracted = true;
+        } else if line.ends_with("; Lift") {
+            assert!(retracted && !lifted);
+            assert!((z() - layer_z - 0.4).abs() < 1e-6);
+            // At the Z axis' own speed, and back to travel speed after
+            assert!(line.contains(" F720 "), "{}", line);
+            lifted = true;
+        } else if line.ends_with("; Drop") {
+            assert!(lifted);
+            assert!((z() - layer_z).abs() < 1e-6, "{} on a layer at {}", line, layer_z);
+            lifted = false;
+        } else if line.ends_with("; Unretract") {
+            assert!(retracted && !lifted);
+            assert!(line.starts_with("G1 E0.20000 "));
+            retracted = false;
+            pairs += 1;
+        } else if line.starts_with("G1 Z") {
+            layer_z = z();
+        } else if line.contains(" E") && line.starts_with("G1 X") {
+            // Nothing is printed while retracted or lifted
+            assert!(!retracted && !lifted, "{}", line);
+        }
+    }
+    // At least the hop between the cuboids on each layer
+    let layers = gcode.lines().filter(|line| line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok()).count();
+    assert!(pairs >= layers, "{} retractions on {} layers", pairs, layers);
+    assert!(gcode.lines().filter(|line| line.starts_with("G1 X")).all(|line| !line.split_whitespace().any(|word| word == "F720")));
+
+    // Its own speed goes before the machine's limit
+    let gcode = generate(SlicerConfig { travel_speed_z: Some(5.0), ..config }, mesh);
+    assert!(gcode.lines().filter(|line| line.ends_with("; Lift") || line.ends_with("; Drop")).all(|line| line.contains(" F300 ")));
+}
+
+#[test]
+fn test_wipe_retracts_back_along_the_last_path() {
+    let config = SlicerConfig { wipe: true, wipe_distance: 3.0, retraction_distance: 1.5, ..SlicerConfig::default() };
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 1.0]),
+        common::cuboid([20.0, 0.0, 0.0], [30.0, 10.0, 1.0]),
+    ]);
+    let gcode = generate(config, mesh);
+    let lines: Vec<&str> = gcode.lines().collect();
+    let point = |line: &str| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        Point2::new(words[1][1..].parse::<f64>().unwrap(), words[2][1..].parse::<f64>().unwrap())
+    };
+
+    let mut wipes = 0;
+    for (i, line) in lines.iter().enumerate().filter(|(_, line)| **line == "G92 E0") {
+        if !lines[i + 1].ends_with("; Wipe") {
+            continue;
+        }
+        wipes += 1;
+        // Back over what was just printed, reaching the full retraction
+        let wipe: Vec<&str> = lines[i + 1..].iter().copied().take_while(|line| line.ends_with("; Wipe")).collect();
+        let mut from = point(lines[i - 1]);
+        let mut length = 0.0;
+        for line in &wipe {
+            length += (point(line) - from).norm();
+            from = point(line);
+        }
+        let last = wipe.last().unwrap();
+        if (length - 3.0).abs() < 1e-2 {
+            assert!(last.contains(" E-1.50000 "), "{}", last);
+        } else {
+            assert!(length < 3.0, "{} after {}", length, lines[i - 1]);
+            assert!(lines[i + 1 + wipe.len()].ends_with("; Retract"), "{:?}", line);
+        }
+    }
+    assert!(wipes > 0);
+}
+
+#[test]
+fn test_prime_line_purges_along_the_front_of_the_bed() {
+    let config = SlicerConfig { prime_line: true, prime_line_length: 80.0, ..SlicerConfig::default() };
+    let gcode = generate(config.clone(), common::cuboid([100.0, 100.0, 0.0], [110.0, 110.0, 10.0]));
+    let prime: Vec<&str> = gcode
+        .lines()
+        .skip_while(|line| *line != "; Prime line")
+        .take_while(|line| *line != "; Layer 0")
+        .filter(|line| line.starts_with("G1 X"))
+        .collect();
+    let point = |line: &str| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        Point2::new(words[1][1..].parse::<f64>().unwrap(), words[2][1..].parse::<f64>().unwrap())
+    };
+
+    // Travel to the start, then there and back a line's width apart
+    assert_eq!(prime.len(), 4);
+    let (mut from, mut e, mut length) = (point(prime[0]), 0.0, 0.0);
+    for line in &prime[1..] {
+        let to = point(line);
+        assert!((0.0..=220.0).contains(&to.x) && (0.0..10.0).contains(&to.y), "{}", line);
+        let segment = (to - from).norm();
+        let next_e: f64 = line.split_whitespace().nth(3).unwrap()[1..].parse().unwrap();
+        // At the first layer's height and flow
+        let expected = extrusion_length(segment, 0.4, 0.3, 1.0, 1.75);
+        assert!((next_e - e - expected).abs() < 2e-5, "{} for {} mm", line, segment);
+        (from, e, length) = (to, next_e, length + segment);
+    }
+    assert!((length - (2.0 * 80.0 + 0.4)).abs() < 1e-6);
+
+    // Left out with a warning where the model stands in its way
+    let layers = SliceEngine::new(common::cuboid([20.0, 3.0, 0.0], [30.0, 13.0, 10.0]), config.clone()).unwrap().slice().unwrap();
+    let mut gcode = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut gcode).unwrap();
+    assert!(!String::from_utf8(gcode).unwrap().contains("; Prime line"));
+    assert_eq!(stats.warnings.len(), 1);
+
+    // and refused where it doesn't fit on the bed
+    assert!(SlicerConfig { prime_line_length: 215.0, ..config }.validate().is_err());
+}
+
+#[test]
+fn test_coasting_finishes_long_paths_without_extruding() {
+    let config = SlicerConfig { coast_distance: 1.0, ..SlicerConfig::default() };
+    let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 1.0]));
+    let point = |line: &str| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        Point2::new(words[1][1..].parse::<f64>().unwrap(), words[2][1..].parse::<f64>().unwrap())
+    };
+
+    // Where the path being printed started, and the last point reached
+    let (mut start, mut at) = (Point2::origin(), Point2::origin());
+    let (mut coasted, mut closed_loops) = (0.0, 0);
+    let mut lines = gcode.lines().take_while(|line| *line != "; End sequence").peekable();
+    while let Some(line) = lines.next() {
+        if !line.starts_with("G1 X") {
+            continue;
+        }
+        let to = point(line);
+        if line.ends_with("; Coast") {
+            assert!(!line.contains(" E"), "{}", line);
+            coasted += (to - at).norm();
+            // The whole of the path is moved over, the last stretch dry
+            if !lines.peek().is_some_and(|next| next.ends_with("; Coast")) {
+                assert!((coasted - 1.0).abs() < 2e-3, "coasted {} mm", coasted);
+                if (to - start).norm() < 1e-3 {
+                    closed_loops += 1;
+                }
+                coasted = 0.0;
+            }
+        } else if !line.contains(" E") {
+            start = to;
+        }
+        at = to;
+    }
+    // At least both walls of each layer
+    let layers = gcode.lines().filter(|line| line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok()).count();
+    assert!(closed_loops >= 2 * layers, "{} loops on {} layers", closed_loops, layers);
+
+    // Paths shorter than twice the distance are printed in full
+    let config = SlicerConfig { coast_distance: 50.0, ..SlicerConfig::default() };
+    let gcode = generate(config, common::cube(10.0));
+    assert!(!gcode.contains("; Coast"));
+}
+
+#[test]
+fn test_coasting_keeps_the_extra_restart_in_step() {
+    let config = SlicerConfig { coast_distance: 0.5, retraction_extra_restart: 0.2, ..SlicerConfig::default() };
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 1.0]),
+        common::cuboid([20.0, 0.0, 0.0], [30.0, 10.0, 1.0]),
+    ]);
+    let layers = SliceEngine::new(mesh, config.clone()).unwrap().slice().unwrap();
+    let mut gcode = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut gcode).unwrap();
+    let gcode = String::from_utf8(gcode).unwrap();
+    let plain = GCodeGenerator::new(SlicerConfig { coast_distance: 0.0, ..config }).generate_to_writer(&layers, std::io::sink()).unwrap();
+    assert!(stats.filament_length < plain.filament_length);
+
+    // Filament fed over the print, following resets of E
+    let (mut e, mut fed, mut restarts) = (0.0, 0.0, 0);
+    let printing = &gcode[gcode.find("; Layer 0").unwrap()..gcode.find("; End sequence").unwrap()];
+    for line in printing.lines() {
+        let code = line.split(';').next().unwrap();
+        let Some(word) = code.split_whitespace().find(|word| word.starts_with('E')) else {
+            continue;
+        };
+        let value: f64 = word[1..].parse().unwrap();
+        if code.starts_with("G92") {
+            e = value;
+            continue;
+        }
+        if line.ends_with("; Unretract") {
+            // Back from the retraction with the extra on top, not less
+            // for the coasting before it
+            assert_eq!(value, 0.2, "{}", line);
+            restarts += 1;
+        }
+        fed += value - e;
+        e = value;
+    }
+    assert!(restarts > 0);
+    // Every retraction is undone, so what is fed beyond the extrusions is
+    // the extra restart of each
+    let expected = stats.filament_length + 0.2 * restarts as f64;
+    assert!((fed - expected).abs() < 1e-3, "{} mm fed against {} mm", fed, expected);
+}
+
+#[test]
+fn test_each_section_moves_at_its_speed_and_feedrates_are_not_repeated() {
+    let config = SlicerConfig {
+        perimeters: Some(2),
+        perimeter_order: PerimeterOrder::Outer,
+        external_perimeter_speed: Some(20.0),
+        perimeter_speed: Some(30.0),
+        solid_infill_speed: Some(40.0),
+        infill_speed: Some(50.0),
+        travel_speed: 150.0,
+        first_layer_speed: Some(10.0),
+        retraction_distance: 0.0,
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 3.0]));
+    let lines = with_feedrates(&gcode);
+
+    // Wall and infill extrusion feedrates, and travel ones, on a layer
+    let layer = |index: usize| {
+        let label = format!("; Layer {}", index);
+        let (mut walls, mut infill, mut travel) = (Vec::new(), Vec::new(), Vec::new());
+        let mut in_infill = false;
+        for (line, feedrate) in lines.iter().skip_while(|(line, _)| *line != label).skip(1).take_while(|(line, _)| !line.is_empty()) {
+            if *line == "; Infill" {
+                in_infill = true;
+            } else if line.starts_with("G1 X") && line.contains(" E") {
+                let feedrates: &mut Vec<f64> = if in_infill { &mut infill } else { &mut walls };
+                if feedrates.last() != Some(feedrate) {
+                    feedrates.push(*feedrate);
+                }
+            } else if line.starts_with("G1 X") {
+                travel.push(*feedrate);
+            }
+        }
+        (walls, infill, travel)
+    };
+
+    let (walls, infill, travel) = layer(0);
+    assert_eq!((walls, infill), (vec![600.0], vec![600.0]));
+    assert!(travel.iter().all(|&feedrate| feedrate == 9000.0));
+    // Bottom skin, then sparse infill in the middle
+    for (index, infill_feedrate) in [(2, 2400.0), (7, 3000.0)] {
+        let (walls, infill, travel) = layer(index);
+        assert_eq!(walls, [1200.0, 1800.0], "layer {}", index);
+        assert_eq!(infill, [infill_feedrate], "layer {}", index);
+        assert!(!travel.is_empty() && travel.iter().all(|&feedrate| feedrate == 9000.0));
+    }
+
+    // A feedrate is only given when it changes
+    for pair in lines.windows(2) {
+        if pair[1].0.split(';').next().unwrap().contains(" F") {
+            assert_ne!(pair[0].1, pair[1].1, "{}", pair[1].0);
+        }
+    }
+}
+
+#[test]
+fn test_first_layer_temperatures_heat_in_order_and_give_way() {
+    let heating = |heat_order| {
+        let config = SlicerConfig {
+            nozzle_temperature: 240,
+            bed_temperature: 100,
+            first_layer_nozzle_temperature: Some(245),
+            first_layer_bed_temperature: Some(110),
+            heat_order,
+            ..SlicerConfig::default()
+        };
+        let gcode = generate(config, common::cube(10.0));
+        let commands: Vec<String> = gcode
+            .lines()
+            .filter(|line| ["M104", "M109", "M140", "M190"].iter().any(|code| line.starts_with(code)))
+            .map(|line| line.split(" ;").next().unwrap().to_string())
+            .collect();
+        (gcode, commands)
+    };
+
+    let (gcode, commands) = heating(HeatOrder::Together);
+    assert_eq!(commands[..4], ["M140 S110", "M104 S245", "M190 S110", "M109 S245"]);
+    // Back to the regular temperatures between the first two layers, and
+    // off at the end
+    assert_eq!(commands[4..], ["M104 S240", "M140 S100", "M104 S0", "M140 S0"]);
+    let layer_1 = gcode.find("; Layer 1\n").unwrap();
+    let change = gcode.find("M104 S240").unwrap();
+    assert!(gcode.find("; Layer 0\n").unwrap() < change && change < layer_1);
+
+    assert_eq!(heating(HeatOrder::BedFirst).1[..4], ["M140 S110", "M190 S110", "M104 S245", "M109 S245"]);
+    assert_eq!(heating(HeatOrder::NozzleFirst).1[..4], ["M104 S245", "M109 S245", "M140 S110", "M190 S110"]);
+
+    // Without overrides nothing changes after the first layer
+    let plain = generate(SlicerConfig::default(), common::cube(10.0));
+    assert_eq!(plain.lines().filter(|line| line.starts_with("M104")).count(), 2);
+}
+
+#[test]
+fn test_fan_stays_off_on_the_first_layer_then_ramps_up() {
+    // Fan commands with the layer they come on
+    let fan_changes = |config: SlicerConfig| {
+        let gcode = generate(config, common::cube(5.0));
+        let mut layer = None;
+        let mut changes = Vec::new();
+        for line in gcode.lines() {
+            if let Some(index) = line.strip_prefix("; Layer ") {
+                layer = index.parse::<usize>().ok();
+            } else if line.starts_with("M106") || line.starts_with("M107") {
+                changes.push((layer, line.split(" ;").next().unwrap().to_string()));
+            }
+        }
+        changes
+    };
+
+    let default = fan_changes(SlicerConfig::default());
+    assert_eq!(default[0], (None, "M107".to_string()));
+    assert_eq!(default[1], (Some(1), "M106 S255".to_string()));
+    assert_eq!(default.len(), 3, "{:?}", default);
+
+    let ramped = fan_changes(SlicerConfig { fan_speed: 60.0, disable_fan_first_layers: 2, fan_ramp_layers: 3, ..SlicerConfig::default() });
+    let layers: Vec<_> = ramped[1..ramped.len() - 1].iter().map(|(layer, command)| (layer.unwrap(), command.as_str())).collect();
+    assert_eq!(layers, [(2, "M106 S51"), (3, "M106 S102"), (4, "M106 S153")]);
+
+    assert_eq!(fan_pwm(100.0), 255);
+    assert_eq!(fan_pwm(50.0), 128);
+    assert_eq!(fan_pwm(0.0), 0);
+}
+
+#[test]
+fn test_small_layers_slow_down_for_the_minimum_layer_time() {
+    let config = SlicerConfig { min_layer_time: 8.0, min_print_speed: 5.0, ..SlicerConfig::default() };
+    let mesh = common::pyramid(20.0, 2.0, 10.0);
+    let layers = SliceEngine::new(mesh.clone(), config.clone()).unwrap().slice().unwrap();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, std::io::sink()).unwrap();
+
+    // The wide bottom takes long enough; the narrow top is slowed down more
+    // the smaller it gets
+    let (first_slowed, _) = stats.slowed_layers[0];
+    assert!(first_slowed > 0, "{:?}", stats.slowed_layers);
+    assert!(stats.slowed_layers.windows(2).all(|pair| pair[1].0 == pair[0].0 + 1));
+    let (_, top_factor) = *stats.slowed_layers.last().unwrap();
+    assert!(top_factor < stats.slowed_layers[0].1 && top_factor < 0.5, "{:?}", stats.slowed_layers);
+
+    // No extrusion on the top layer runs at its profile speed, nor below the
+    // minimum print speed
+    let gcode = generate(config.clone(), mesh.clone());
+    let top = gcode.rfind("; Layer ").unwrap();
+    let extrusions: Vec<f64> = with_feedrates(&gcode[top..])
+        .into_iter()
+        .filter(|(line, _)| line.starts_with("G1 X") && line.contains(" E") && !line.contains("Wipe"))
+        .map(|(_, feedrate)| feedrate)
+        .collect();
+    assert!(!extrusions.is_empty());
+    assert!(extrusions.iter().all(|&f| f < config.external_perimeter_speed() * 60.0 && f >= 5.0 * 60.0 - 1e-9), "{:?}", extrusions);
+
+    // A minimum print speed too fast to fill the time waits out the rest
+    let dwell = SlicerConfig { min_layer_time: 30.0, min_print_speed: 40.0, min_layer_time_dwell: true, ..SlicerConfig::default() };
+    let gcode = generate(dwell.clone(), mesh);
+    let top = &gcode[gcode.rfind("; Layer ").unwrap()..];
+    assert!(top.lines().any(|line| line.starts_with("G4 P") && line.ends_with("; Wait for the layer to cool")), "{}", top);
+
+    // but a layer with nothing printed on it doesn't wait
+    let gap = Mesh::merge(vec![common::cuboid([90.0, 90.0, 0.0], [110.0, 110.0, 2.0]), common::cuboid([90.0, 90.0, 4.0], [110.0, 110.0, 6.0])]);
+    let gcode = generate(dwell, gap);
+    let blocks: Vec<&str> = gcode.split("\n; Layer ").filter(|block| block.starts_with(|c: char| c.is_ascii_digit())).collect();
+    let empty: Vec<&&str> = blocks.iter().filter(|block| !block.lines().any(|line| line.starts_with("G1 X") && line.contains(" E"))).collect();
+    assert!(!empty.is_empty());
+    assert!(empty.iter().all(|block| !block.contains("G4 P")), "{:?}", empty);
+}
+
+#[test]
+fn test_custom_gcode_is_written_at_layer_changes_and_around_the_object() {
+    let config = SlicerConfig {
+        layer_change_gcode: "TIMELAPSE_TAKE_FRAME ; layer {layer_num} at {layer_z}".to_string(),
+        before_object_gcode: "M117 Printing at {nozzle_temperature}C".to_string(),
+        after_object_gcode: "\nG1 X0 Y200 ; Park {unknown}\n".to_string(),
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config, common::cube(5.0));
+
+    let lines: Vec<&str> = gcode.lines().collect();
+    let mut layers = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(index) = line.strip_prefix("; Layer ").filter(|index| index.parse::<usize>().is_ok()) {
+            let z_move = lines[i..].iter().find_map(|line| line.strip_prefix("G1 Z")).unwrap();
+            let z: f64 = z_move.split(' ').next().unwrap().parse().unwrap();
+            assert_eq!(lines[i + 1], format!("TIMELAPSE_TAKE_FRAME ; layer {} at {:.3}", index, z));
+            layers += 1;
+        }
+    }
+    assert_eq!(lines.iter().filter(|line| line.starts_with("TIMELAPSE_TAKE_FRAME")).count(), layers);
+    assert_eq!(layers, 25);
+
+    let before = gcode.find("M117 Printing at 210C").unwrap();
+    assert!(before < gcode.find("; Layer 0").unwrap());
+    let park = gcode.find("\nG1 X0 Y200 ; Park {unknown}\n").unwrap();
+    assert!(gcode.rfind("; Layer ").unwrap() < park && park < gcode.find("; End sequence").unwrap());
+
+    let plain = generate(SlicerConfig::default(), common::cube(5.0));
+    assert!(!plain.contains("TIMELAPSE") && !plain.contains("M117") && !plain.contains("Park"));
+}
+
+#[test]
+fn test_each_flavor_writes_its_own_commands() {
+    let flavored = |gcode_flavor| {
+        let config = SlicerConfig { gcode_flavor, acceleration: Some(1500.0), fan_speed: 50.0, ..SlicerConfig::default() };
+        generate(config, common::table(20.0, 10.0, 3.0, 5.1, 2.0))
+    };
+    let has = |gcode: &str, prefix: &str| gcode.lines().any(|line| line.starts_with(prefix));
+
+    let marlin = flavored(FirmwareFlavor::Marlin);
+    for prefix in ["M104 S210", "M109 S210", "M140 S60", "M190 S60", "M204 S1500", "M106 S128", "G1 E-5.00000"] {
+        assert!(has(&marlin, prefix), "{}", prefix);
+    }
+    for prefix in ["SET_", "TEMPERATURE_WAIT", "G10", "G11"] {
+        assert!(!has(&marlin, prefix), "{}", prefix);
+    }
+
+    let klipper = flavored(FirmwareFlavor::Klipper);
+    let layers = klipper.lines().filter(|line| line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok()).count();
+    assert!(has(&klipper, &format!("SET_PRINT_STATS_INFO TOTAL_LAYER_COUNT={}", layers)));
+    for layer in 1..=layers {
+        assert!(has(&klipper, &format!("SET_PRINT_STATS_INFO CURRENT_LAYER={}", layer)));
+    }
+    for prefix in [
+        "SET_HEATER_TEMPERATURE HEATER=extruder TARGET=210",
+        "TEMPERATURE_WAIT SENSOR=heater_bed MINIMUM=60",
+        "SET_VELOCITY_LIMIT ACCEL=1500",
+        "M106 S128",
+        "TURN_OFF_HEATERS",
+    ] {
+        assert!(has(&klipper, prefix), "{}", prefix);
+    }
+    for prefix in ["M104", "M109", "M140", "M190", "M204"] {
+        assert!(!has(&klipper, prefix), "{}", prefix);
+    }
+
+    // Counted from what was printed when the layers come without a count
+    let config = SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, ..SlicerConfig::default() };
+    let stack = SliceEngine::new(common::cube(5.0), config.clone()).unwrap().slice().unwrap();
+    let mut stream = stack.iter();
+    let mut output = Vec::new();
+    GCodeGenerator::new(config).generate_to_writer(std::iter::from_fn(|| stream.next()), &mut output).unwrap();
+    let gcode = String::from_utf8(output).unwrap();
+    assert!(has(&gcode, &format!("SET_PRINT_STATS_INFO TOTAL_LAYER_COUNT={}", stack.len())));
+
+    let reprap = flavored(FirmwareFlavor::RepRapFirmware);
+    for prefix in ["M104 S210", "M204 P1500 T1500", "M106 P0 S0.50", "M106 P0 S1.00", "G10 ; Retract", "G11 ; Unretract", "M0 ; Stop"] {
+        assert!(has(&reprap, prefix), "{}", prefix);
+    }
+    assert!(!has(&reprap, "G1 E-"));
+
+    // Firmware retraction can be turned on for any flavor, or off for
+    // RepRapFirmware
+    let config = SlicerConfig { firmware_retraction: Some(true), ..SlicerConfig::default() };
+    assert!(has(&generate(config, common::table(20.0, 10.0, 3.0, 5.1, 2.0)), "G10 ; Retract"));
+    let config = SlicerConfig { gcode_flavor: FirmwareFlavor::RepRapFirmware, firmware_retraction: Some(false), ..SlicerConfig::default() };
+    assert!(!has(&generate(config, common::table(20.0, 10.0, 3.0, 5.1, 2.0)), "G10"));
+}
+
+#[test]
+fn test_arc_fitting_writes_round_walls_as_arcs() {
+    let outline: Vec<Point2<f64>> = (0..180)
+        .map(|i| {
+            let angle = i as f64 * 2.0 * std::f64::consts::PI / 180.0;
+            Point2::new(10.0 * angle.cos(), 10.0 * angle.sin())
+        })
+        .collect();
+    let mesh = common::prism(&outline, Point2::origin(), 10.0);
+    let lines = SlicerConfig { infill_percentage: 0, ..SlicerConfig::default() };
+    let arcs = SlicerConfig { arc_fitting: true, ..lines.clone() };
+    let layers = SliceEngine::new(mesh.clone(), arcs.clone()).unwrap().slice().unwrap();
+    let mut gcode = Vec::new();
+    let stats = GCodeGenerator::new(arcs.clone()).generate_to_writer(&layers, &mut gcode).unwrap();
+    let gcode = String::from_utf8(gcode).unwrap();
+
+    // Every arc runs around the cylinder's axis, from where the last move
+    // ended to a point as far from the center
+    let mut position = Point2::origin();
+    let mut arc_count = 0;
+    for line in gcode.lines() {
+        let words: Vec<&str> = line.split(';').next().unwrap().split_whitespace().collect();
+        let value = |axis: char| words.iter().find(|word| word.starts_with(axis)).map(|word| word[1..].parse::<f64>().unwrap());
+        if matches!(words.first(), Some(&"G2" | &"G3")) {
+            let center = position + nalgebra::Vector2::new(value('I').unwrap(), value('J').unwrap());
+            let end = Point2::new(value('X').unwrap(), value('Y').unwrap());
+            let radius = (position - center).norm();
+            assert!(((end - center).norm() - radius).abs() < 0.01, "{}", line);
+            // Halfway along, the arc is as far from the axis as its ends
+            let (start_angle, end_angle) = ((position - center).y.atan2((position - center).x), (end - center).y.atan2((end - center).x));
+            let mut sweep = end_angle - start_angle;
+            if line.starts_with("G3") {
+                sweep = sweep.rem_euclid(2.0 * std::f64::consts::PI);
+            } else {
+                sweep = -(-sweep).rem_euclid(2.0 * std::f64::consts::PI);
+            }
+            let middle_angle = start_angle + sweep / 2.0;
+            let middle = center + nalgebra::Vector2::new(middle_angle.cos(), middle_angle.sin()) * radius;
+            assert!((middle.coords.norm() - end.coords.norm()).abs() < 0.05, "{} from {}", line, position);
+            assert!((8.0..10.0).contains(&end.coords.norm()), "{}", line);
+            arc_count += 1;
+        }
+        if matches!(words.first(), Some(&"G0" | &"G1" | &"G2" | &"G3")) {
+            position = Point2::new(value('X').unwrap_or(position.x), value('Y').unwrap_or(position.y));
+        }
+    }
+    assert!(arc_count >= layers.len() * 2, "{} arcs", arc_count);
+
+    // The file shrinks, and the filament follows the arcs' true length
+    let mut faceted = Vec::new();
+    let faceted_stats = GCodeGenerator::new(lines).generate_to_writer(&layers, &mut faceted).unwrap();
+    assert!(!String::from_utf8(faceted).unwrap().lines().any(|line| line.starts_with("G2 ") || line.starts_with("G3 ")));
+    assert!((stats.bytes as f64) < faceted_stats.bytes as f64 * 0.7, "{} against {} bytes", stats.bytes, faceted_stats.bytes);
+    assert!((stats.filament_length / faceted_stats.filament_length - 1.0).abs() < 0.01);
+
+    // Klipper doesn't get arcs
+    let klipper = SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, ..arcs };
+    let gcode = GCodeGenerator::new(klipper).generate_to_string(&layers).unwrap();
+    assert!(!gcode.lines().any(|line| line.starts_with("G2 ") || line.starts_with("G3 ")));
+}
+
+#[test]
+fn test_extrusion_bounds_take_in_the_sides_of_arcs() {
+    // A circle around X140 Y100 starting and ending at its +X side, well
+    // away from the cube
+    let config = SlicerConfig {
+        before_object_gcode: "G92 E0\nG1 X150 Y100 F3000\nG2 X150 Y100 I-10 J0 E5".to_string(),
+        ..SlicerConfig::default()
+    };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let stats = GCodeGenerator::new(config).generate_to_writer(&layers, std::io::sink()).unwrap();
+    let bounds = stats.extrusion_bounds.unwrap();
+    assert!((bounds.max.x - 150.0).abs() < 1e-9 && (bounds.max.y - 110.0).abs() < 1e-9, "{:?}", bounds);
+}
+
+#[test]
+fn test_acceleration_switches_once_from_walls_to_infill() {
+    let config = SlicerConfig {
+        perimeter_acceleration: Some(1000.0),
+        infill_acceleration: Some(4000.0),
+        max_acceleration: Some(2500.0),
+        jerk: Some(8.0),
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config.clone(), common::cube(10.0));
+    assert!(gcode.contains("M205 X8 Y8 ; Set jerk"));
+
+    let start = gcode.find("; Layer 10\n").unwrap();
+    let layer: Vec<&str> = gcode[start..gcode.find("; Layer 11\n").unwrap()].lines().collect();
+    let is_extrusion = |line: &&str| line.starts_with("G1 X") && line.contains(" E");
+    let infill = layer.iter().position(|line| *line == "; Infill").unwrap();
+    let last_wall = layer[..infill].iter().rposition(is_extrusion).unwrap();
+    let first_infill = infill + layer[infill..].iter().position(is_extrusion).unwrap();
+    let switches: Vec<&&str> = layer[last_wall..first_infill].iter().filter(|line| line.starts_with("M204")).collect();
+    // The infill's 4000 mm/s² is clamped to the machine's 2500
+    assert_eq!(switches, [&"M204 S2500"]);
+    // and the walls switch back on the next layer
+    assert_eq!(layer.iter().filter(|line| line.starts_with("M204")).collect::<Vec<_>>(), [&"M204 S1000", &"M204 S2500"]);
+
+    // Without accelerations set, none are written
+    assert!(!generate(SlicerConfig::default(), common::cube(10.0)).contains("M204"));
+
+    // RepRapFirmware sets printing and travel acceleration together
+    let reprap = generate(SlicerConfig { gcode_flavor: FirmwareFlavor::RepRapFirmware, ..config }, common::cube(10.0));
+    assert!(reprap.lines().any(|line| line == "M204 P1000 T1000"));
+    assert!(reprap.contains("M566 X480 Y480 ; Set jerk"));
+}
+
+#[test]
+fn test_pressure_advance_is_set_at_the_start_and_lowered_for_infill() {
+    let config = SlicerConfig {
+        gcode_flavor: FirmwareFlavor::Klipper,
+        pressure_advance: Some(0.04),
+        infill_pressure_advance: Some(0.02),
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config.clone(), common::cube(10.0));
+    let heating = gcode.find("; Heating").unwrap();
+    assert!(gcode[..heating].contains("SET_PRESSURE_ADVANCE ADVANCE=0.04 ; Set pressure advance"));
+
+    let start = gcode.find("; Layer 10\n").unwrap();
+    let layer: Vec<&str> = gcode[start..gcode.find("; Layer 11\n").unwrap()].lines().collect();
+    let switches: Vec<&str> = layer.iter().copied().filter(|line| line.starts_with("SET_PRESSURE_ADVANCE")).collect();
+    assert_eq!(switches, ["SET_PRESSURE_ADVANCE ADVANCE=0.04", "SET_PRESSURE_ADVANCE ADVANCE=0.02"]);
+    let infill = layer.iter().position(|line| *line == "; Infill").unwrap();
+    let lowered = layer.iter().position(|line| *line == switches[1]).unwrap();
+    assert!(infill < lowered && !layer[infill..lowered].iter().any(|line| line.starts_with("G1 X") && line.contains(" E")));
+
+    // Marlin takes its linear advance K in place of the pressure advance
+    let marlin = generate(SlicerConfig { gcode_flavor: FirmwareFlavor::Marlin, linear_advance_k: Some(0.05), ..config.clone() }, common::cube(10.0));
+    assert!(marlin.contains("M900 K0.05 ; Set pressure advance"));
+    assert!(marlin.lines().any(|line| line == "M900 K0.02"));
+    assert!(!marlin.contains("SET_PRESSURE_ADVANCE"));
+
+    // RepRapFirmware sets it for the extruder drive
+    let reprap = generate(SlicerConfig { gcode_flavor: FirmwareFlavor::RepRapFirmware, ..config }, common::cube(10.0));
+    assert!(reprap.contains("M572 D0 S0.04 ; Set pressure advance"));
+    assert!(reprap.lines().any(|line| line == "M572 D0 S0.02"));
+    assert!(!reprap.contains("ADVANCE") && !reprap.contains("M900"));
+
+    // Left out when unset
+    let unset = generate(SlicerConfig::default(), common::cube(10.0));
+    assert!(!unset.contains("M900"));
+}
+
+#[test]
+fn test_pressure_advance_switches_back_and_forth_between_walls_and_infill() {
+    let config = SlicerConfig {
+        gcode_flavor: FirmwareFlavor::Klipper,
+        pressure_advance: Some(0.04),
+        infill_pressure_advance: Some(0.02),
+        // For the feature of each path
+        gcode_comments: CommentLevel::Verbose,
+        ..SlicerConfig::default()
+    };
+    // Each part's walls and then its infill, one part after the other
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 3.0]),
+        common::cuboid([20.0, 0.0, 0.0], [30.0, 10.0, 3.0]),
+    ]);
+    let gcode = generate(config, mesh);
+
+    let (mut advance, mut feature) = ("", "");
+    let mut switches = 0;
+    for line in gcode.lines().take_while(|line| *line != "; End sequence") {
+        if let Some(value) = line.strip_prefix("SET_PRESSURE_ADVANCE ADVANCE=") {
+            advance = value.split_whitespace().next().unwrap();
+            switches += 1;
+        } else if let Some(name) = line.strip_prefix(";TYPE:") {
+            feature = name;
+        } else if line.starts_with("G1 X") && line.contains(" E") {
+            let expected = if feature.ends_with("infill") { "0.02" } else { "0.04" };
+            assert_eq!(advance, expected, "{} in {}", line, feature);
+        }
+    }
+    // Twice for each part on every layer
+    let layers = gcode.lines().filter(|line| line.starts_with("; Layer ") && line[8..].parse::<usize>().is_ok()).count();
+    assert!(switches >= 4 * layers - 1, "{} switches on {} layers", switches, layers);
+}
+
+#[test]
+fn test_absurd_speeds_are_clamped_to_the_machine_limits() {
+    let config = SlicerConfig {
+        print_speed: 400.0,
+        travel_speed: 500.0,
+        retraction_speed: 200.0,
+        retraction_z_lift: 0.4,
+        max_feedrate: Some(300.0),
+        max_feedrate_z: Some(12.0),
+        max_feedrate_e: Some(60.0),
+        ..SlicerConfig::default()
+    };
+    let warnings = config.limit_warnings();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings[0].starts_with("print_speed of 400"), "{:?}", warnings);
+
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 2.0]),
+        common::cuboid([20.0, 0.0, 0.0], [30.0, 10.0, 2.0]),
+    ]);
+    let layers = SliceEngine::new(mesh, config.clone()).unwrap().slice().unwrap();
+    let mut gcode = Vec::new();
+    let stats = GCodeGenerator::new(config).generate_to_writer(&layers, &mut gcode).unwrap();
+    let gcode = String::from_utf8(gcode).unwrap();
+
+    let mut limited = [0; 3];
+    for line in gcode.lines() {
+        let words: Vec<&str> = line.split(';').next().unwrap().split_whitespace().collect();
+        let Some(feedrate) = words.iter().find(|word| word.starts_with('F')).map(|word| word[1..].parse::<f64>().unwrap()) else {
+            continue;
+        };
+        let has = |axis: char| words.iter().any(|word| word.starts_with(axis));
+        let (axis, max) = if has('X') || has('Y') { (0, 300.0) } else if has('Z') { (1, 12.0) } else { (2, 60.0) };
+        assert!(feedrate <= max * 60.0, "{}", line);
+        if feedrate == max * 60.0 {
+            limited[axis] += 1;
+        }
+    }
+    // Travel, lifts and retractions all run at the limit
+    assert!(limited.iter().all(|&count| count > 0), "{:?}", limited);
+    assert!(stats.clamped_moves > 0);
+
+    let layers = SliceEngine::new(common::cube(10.0), SlicerConfig::default()).unwrap().slice().unwrap();
+    let stats = GCodeGenerator::new(SlicerConfig::default()).generate_to_writer(&layers, std::io::sink()).unwrap();
+    assert_eq!(stats.clamped_moves, 0);
+}
+
+#[test]
+fn test_print_time_speeds_up_and_slows_down_at_the_acceleration() {
+    let config = SlicerConfig { max_acceleration: Some(1000.0), ..SlicerConfig::default() };
+    let time = |gcode: &str, config: &SlicerConfig| GCodeStats::from_gcode(gcode, config).print_time;
+
+    // 100 mm at 50 mm/s: 50 ms to reach full speed over 1.25 mm, 1.95 s at
+    // it and 50 ms to stop
+    let line = time("G1 F3000\nG1 X100 E5\n", &config);
+    assert!((line / 2.05 - 1.0).abs() < 0.01, "{} s", line);
+    // The same line split in two doesn't stop halfway
+    let split = time("G1 F3000\nG1 X50 E2.5\nG1 X100 E5\n", &config);
+    assert!((split / line - 1.0).abs() < 1e-9, "{} s", split);
+    // but turning back on itself does
+    let back = time("G1 F3000\nG1 X50 E2.5\nG1 X0 E5\n", &config);
+    assert!((back - 2.0 * (1.0 + 0.05)).abs() < 1e-9, "{} s", back);
+    // A move too short to reach full speed speeds up to halfway and slows
+    // down from there
+    let short = time("G1 F3000\nG1 X1 E0.05\n", &config);
+    assert!((short - 2.0 * (1.0_f64 / 1000.0).sqrt()).abs() < 1e-9, "{} s", short);
+    // The machine's top speed caps the feedrate
+    let capped = SlicerConfig { max_feedrate: Some(50.0), ..config.clone() };
+    assert!((time("G1 F6000\nG1 X100 E5\n", &capped) - line).abs() < 1e-9);
+    // An M204 lowers the acceleration, up to the machine's maximum
+    let slower = time("M204 S500\nG1 F3000\nG1 X100 E5\n", &config);
+    assert!((slower - (2.0 + 0.1)).abs() < 1e-9, "{} s", slower);
+    assert!((time("M204 S5000\nG1 F3000\nG1 X100 E5\n", &config) - line).abs() < 1e-9);
+}
+
+#[test]
+fn test_progress_counts_down_at_each_layer_change() {
+    let config = SlicerConfig { acceleration: Some(1500.0), ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let mut output = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut output).unwrap();
+    let gcode = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = gcode.lines().collect();
+
+    let minutes = (stats.print_time / 60.0).round();
+    assert_eq!(lines[1], format!("; estimated printing time = {}", rustslicer::gcode::format_duration(stats.print_time)));
+    assert_eq!(stats.bytes, gcode.len() as u64);
+
+    // One progress line before every layer and one at the end
+    let progress: Vec<(usize, (f64, f64))> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let words: Vec<&str> = line.strip_prefix("M73 ")?.split(' ').collect();
+            Some((i, (words[0][1..].parse().unwrap(), words[1][1..].parse().unwrap())))
+        })
+        .collect();
+    assert_eq!(progress.len(), layers.len() + 1);
+    for (layer, (i, _)) in progress[..layers.len()].iter().enumerate() {
+        assert_eq!(lines[i + 1], format!("; Layer {}", layer));
+    }
+    assert_eq!(progress[0].1, (0.0, minutes));
+    assert_eq!(progress.last().unwrap().1, (100.0, 0.0));
+    assert!(progress.windows(2).all(|pair| pair[0].1 .0 <= pair[1].1 .0 && pair[0].1 .1 >= pair[1].1 .1));
+
+    // The breakdown adds up to the total
+    assert_eq!(stats.layer_times.len(), layers.len());
+    let roles = stats.role_times.iter().fold(0.0, |sum, (_, time)| sum + time);
+    assert!((roles + stats.other_time - stats.print_time).abs() < 1e-6);
+    assert!(stats.role_times.iter().any(|(role, time)| *role == PathRole::Infill && *time > 0.0));
+    assert!(stats.time_before_layer(0) > 0.0 && stats.time_before_layer(0) < stats.time_before_layer(1));
+
+    let klipper = generate(SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, ..config.clone() }, common::cube(10.0));
+    assert!(klipper.lines().any(|line| line == "M73 P0"));
+    let quiet = generate(SlicerConfig { progress_updates: false, ..config }, common::cube(10.0));
+    assert!(!quiet.contains("M73") && !quiet.contains("estimated printing time"));
+}
+
+#[test]
+fn test_filament_used_is_measured_in_length_volume_weight_and_cost() {
+    // 100 mm of 1.75 mm filament is 0.2405 cm³, or 0.298 g of PLA
+    let config = SlicerConfig { filament_cost_per_kg: Some(20.0), ..SlicerConfig::default() };
+    let stats = GCodeStats::from_gcode("G1 F1200\nG1 X50 E100\nG1 X40 E95\n", &config);
+    let volume = 100.0 * std::f64::consts::PI * 0.875 * 0.875 / 1000.0;
+    assert!((stats.filament_length - 100.0).abs() < 1e-9);
+    assert!((stats.filament_volume - volume).abs() < 1e-9);
+    assert!((stats.filament_weight - volume * 1.24).abs() < 1e-9);
+    assert!((stats.filament_cost.unwrap() - volume * 1.24 * 0.02).abs() < 1e-9);
+
+    // Each extruder's filament by its own diameter
+    let thick = Extruder { filament_diameter: Some(2.85), ..Extruder::default() };
+    let two = SlicerConfig { extruders: vec![Extruder::default(), thick], ..config.clone() };
+    let stats = GCodeStats::from_gcode("G1 F1200\nG1 X10 E10\nT1\nG1 X20 E20\nT0\nG1 X30 E30\n", &two);
+    let area = |diameter: f64| std::f64::consts::PI * (diameter / 2.0).powi(2);
+    assert!((stats.filament_length - 30.0).abs() < 1e-9);
+    assert!((stats.filament_volume - (20.0 * area(1.75) + 10.0 * area(2.85)) / 1000.0).abs() < 1e-9);
+
+    // The header carries the same figures, named as PrusaSlicer names them
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let mut output = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut output).unwrap();
+    let gcode = String::from_utf8(output).unwrap();
+    let header: Vec<&str> = gcode.lines().take(6).collect();
+    assert!(header.contains(&format!("; filament used [mm] = {:.2}", stats.filament_length).as_str()));
+    assert!(header.contains(&format!("; filament used [cm3] = {:.2}", stats.filament_volume).as_str()));
+    assert!(header.contains(&format!("; filament used [g] = {:.2}", stats.filament_weight).as_str()));
+    assert!(header.contains(&format!("; filament cost = {:.2}", stats.filament_cost.unwrap()).as_str()));
+    assert!(stats.filament_weight > 0.0);
+
+    let quiet = generate(SlicerConfig { progress_updates: false, filament_cost_per_kg: None, ..config }, common::cube(10.0));
+    assert!(quiet.contains("; filament used [g] = ") && !quiet.contains("; filament cost"));
+}
+
+#[test]
+fn test_thumbnails_are_embedded_as_base64_pngs() {
+    use base64::Engine;
+
+    let config = SlicerConfig { thumbnail: true, thumbnail_sizes: vec![[32, 32], [300, 200]], ..SlicerConfig::default() };
+    let gcode = generate(config.clone(), common::pyramid(20.0, 4.0, 10.0));
+    let lines: Vec<&str> = gcode.lines().collect();
+    let begins: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].starts_with("; thumbnail begin ")).collect();
+    assert_eq!(begins.len(), 2);
+    // Thumbnails come before anything is printed
+    assert!(begins[1] < lines.iter().position(|line| *line == "; Layer 0").unwrap());
+
+    for (&begin, (width, height)) in begins.iter().zip([(32, 32), (300, 200)]) {
+        let header: Vec<&str> = lines[begin].split(' ').collect();
+        assert_eq!(header[3], format!("{}x{}", width, height));
+        let end = begin + lines[begin..].iter().position(|line| *line == "; thumbnail end").unwrap();
+        let encoded: String = lines[begin + 1..end].iter().map(|line| line.strip_prefix("; ").unwrap()).collect();
+        assert_eq!(encoded.len(), header[4].parse::<usize>().unwrap());
+        assert!(lines[begin + 1..end].iter().all(|line| line.len() <= 80));
+
+        let png = base64::engine::general_purpose::STANDARD.decode(&encoded).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (width, height));
+
+        // The pyramid fills the middle, lighter towards its top, and
+        // leaves the corners clear
+        let pixel = |x: u32, y: u32| &pixels[((y * width + x) * 4) as usize..][..4];
+        assert_eq!(pixel(0, 0)[3], 0);
+        assert_eq!(pixel(width / 2, height / 2)[3], 255);
+        assert_eq!(pixel(width / 2 + height * 3 / 8, height / 2)[3], 255);
+        assert!(pixel(width / 2, height / 2)[0] > pixel(width / 2 + height * 3 / 8, height / 2)[0]);
+    }
+
+    let plain = generate(SlicerConfig { thumbnail: false, ..config }, common::pyramid(20.0, 4.0, 10.0));
+    assert!(!plain.contains("thumbnail begin"));
+}
+
+#[test]
+fn test_verbose_comments_mark_each_path_with_its_role() {
+    let config = SlicerConfig { gcode_comments: CommentLevel::Verbose, brim_width: 3.0, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let mut output = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut output).unwrap();
+    let gcode = String::from_utf8(output).unwrap();
+
+    // Every role printed is typed, and nothing else
+    let mut types: Vec<&str> = gcode.lines().filter_map(|line| line.strip_prefix(";TYPE:")).collect();
+    types.sort();
+    types.dedup();
+    let mut roles: Vec<&str> = stats.role_times.iter().map(|(role, _)| role.feature_type()).collect();
+    roles.sort();
+    roles.dedup();
+    assert_eq!(types, roles);
+    assert!(types.contains(&"External perimeter") && types.contains(&"Skirt/Brim"));
+
+    // Each layer gives its height, then a type and width before its first
+    // extrusion
+    let layer = |index: usize| {
+        let start = gcode.find(&format!("; Layer {}\n", index)).unwrap();
+        let extrusion = |line: &&str| line.starts_with("G1 X") && line.contains(" E");
+        gcode[start..].lines().take_while(|line| !extrusion(line)).collect::<Vec<_>>()
+    };
+    for (index, height) in [(0, config.first_layer_height), (1, config.layer_height)] {
+        let lines = layer(index);
+        assert!(lines.contains(&format!(";HEIGHT:{:.3}", height).as_str()));
+        assert!(lines.iter().any(|line| line.starts_with(";TYPE:")));
+        assert!(lines.iter().any(|line| line.starts_with(";WIDTH:")));
+    }
+
+    // Normal comments have none of them
+    let normal = generate(SlicerConfig { gcode_comments: CommentLevel::Normal, ..config }, common::cube(10.0));
+    assert!(normal.contains("; Layer 0") && !normal.contains(";TYPE:") && !normal.contains(";WIDTH:"));
+}
+
+#[test]
+fn test_comments_off_leaves_only_commands() {
+    let config = SlicerConfig { layer_change_gcode: "M117 Layer {layer_num} ; show it".to_string(), ..SlicerConfig::default() };
+    let normal = generate(config.clone(), common::cube(10.0));
+    let off = generate(SlicerConfig { gcode_comments: CommentLevel::Off, ..config }, common::cube(10.0));
+
+    assert!(!off.contains(';'));
+    assert!(off.lines().all(|line| !line.is_empty() && line == line.trim_end()));
+    assert!(off.len() < normal.len());
+    // The same commands, and progress still at every layer change
+    let commands = |gcode: &str| -> Vec<String> {
+        gcode.lines().map(|line| line.split(';').next().unwrap().trim_end().to_string()).filter(|line| !line.is_empty()).collect()
+    };
+    assert_eq!(commands(&off), commands(&normal));
+    assert!(off.lines().any(|line| line == "M117 Layer 1"));
+    assert!(off.lines().filter(|line| line.starts_with("M73 ")).count() > 2);
+}
+
+#[test]
+fn test_pauses_and_filament_changes_come_once_between_layers() {
+    let config = SlicerConfig {
+        pause_layers: vec![3],
+        color_change_layers: vec![5],
+        pause_park_position: [10.0, 200.0],
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config.clone(), common::cube(10.0));
+    let lines: Vec<&str> = gcode.lines().collect();
+    let extrusion = |line: &&str| line.starts_with("G1 X") && line.contains(" E") && !line.contains("E-");
+
+    for (command, layer) in [("M601 ; Pause", 3), ("M600 ; Change filament", 5)] {
+        assert_eq!(lines.iter().filter(|line| **line == command).count(), 1, "{}", command);
+        let at = lines.iter().position(|line| *line == command).unwrap();
+        let marker = lines.iter().position(|line| *line == format!("; Layer {}", layer)).unwrap();
+        // After the layer's marker and before anything of it is printed,
+        // parked and primed
+        assert!(marker < at);
+        assert!(!lines[marker..at].iter().any(extrusion));
+        assert!(lines[marker..at].contains(&"G1 X10.000 Y200.000 ; Park"));
+        assert!(lines[at..].iter().take(4).any(|line| line.starts_with("G1 E8.00000") && line.ends_with("; Prime")));
+        let layer_z = lines[at..].iter().position(|line| line.starts_with("G1 Z") && !line.contains(';')).unwrap();
+        assert!(!lines[at..at + layer_z].iter().any(extrusion));
+    }
+
+    let klipper = generate(SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, ..config }, common::cube(10.0));
+    assert_eq!(klipper.lines().filter(|line| *line == "PAUSE ; Pause").count(), 1);
+}
+
+#[test]
+fn test_temperature_tower_changes_at_each_band() {
+    let band = |z_min: f64, temperature| HeightOverride { z_min, z_max: z_min + 10.0, temperature: Some(temperature), fan_speed: None };
+    let config = SlicerConfig {
+        nozzle_temperature: 210,
+        // Layers top out at every band's edge
+        first_layer_height: 0.2,
+        overrides: vec![band(0.0, 210), band(10.0, 200), band(20.0, 190)],
+        ..SlicerConfig::default()
+    };
+    let gcode = generate(config.clone(), common::cuboid([100.0, 100.0, 0.0], [110.0, 110.0, 30.0]));
+    let printing = &gcode[gcode.find("; Layer 0").unwrap()..gcode.find("; End sequence").unwrap()];
+    let lines: Vec<&str> = printing.lines().collect();
+
+    let changes: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].starts_with("M104")).collect();
+    assert_eq!(changes.len(), 2);
+    for (&at, (temperature, z)) in changes.iter().zip([(200, 10.2), (190, 20.2)]) {
+        assert_eq!(lines[at], format!("M104 S{} ; Set nozzle temperature", temperature));
+        // Set just before the first layer in the band
+        let layer_z = lines[at..].iter().find(|line| line.starts_with("G1 Z")).unwrap();
+        assert!(layer_z.starts_with(&format!("G1 Z{:.3}", z)), "{}", layer_z);
+    }
+
+    // Leaving a band goes back to the settings
+    let fan_band = HeightOverride { z_min: 2.0, z_max: 4.0, temperature: Some(200), fan_speed: Some(100.0) };
+    let config = SlicerConfig { fan_speed: 50.0, overrides: vec![fan_band], ..SlicerConfig::default() };
+    let gcode = generate(config, common::cube(10.0));
+    let changes: Vec<&str> = gcode[gcode.find("; Layer 2").unwrap()..gcode.find("; End sequence").unwrap()]
+        .lines()
+        .filter(|line| line.starts_with("M104") || line.starts_with("M106"))
+        .collect();
+    assert_eq!(changes, ["M104 S200 ; Set nozzle temperature", "M106 S255", "M104 S210 ; Set nozzle temperature", "M106 S128"]);
+}
+
+/// Where the nozzle is, X, Y, Z and E, after each move in `gcode`, taking
+/// left out coordinates from the move before. Moves that go nowhere are
+/// skipped.
+fn positions(gcode: &str) -> Vec<[f64; 4]> {
+    let mut position = [0.0; 4];
+    let mut positions = Vec::new();
+    for line in gcode.lines() {
+        // Compact G-code runs the words of a move together
+        let code: String = line.split(';').next().unwrap().chars().flat_map(|c| [c.is_ascii_uppercase().then_some(' '), Some(c)]).flatten().collect();
+        let mut words = code.split_whitespace();
+        let command = words.next().unwrap_or("");
+        if !["G1", "G2", "G3", "G92"].contains(&command) {
+            continue;
+        }
+        for word in words {
+            if let Some(axis) = "XYZE".find(&word[..1]) {
+                position[axis] = word[1..].parse().unwrap();
+            }
+        }
+        if command != "G92" && positions.last() != Some(&position) {
+            positions.push(position);
+        }
+    }
+    positions
+}
+
+#[test]
+fn test_compact_gcode_is_smaller_and_moves_the_same() {
+    // A box with its sparse infill along the axes, where most moves keep
+    // to one of them
+    let config = SlicerConfig { infill_angle: 0.0, gcode_comments: CommentLevel::Off, ..SlicerConfig::default() };
+    let mesh = || common::cube(20.0);
+    let layers = SliceEngine::new(mesh(), config.clone()).unwrap().slice().unwrap();
+    let write = |config: SlicerConfig| {
+        let mut output = Vec::new();
+        let stats = GCodeGenerator::new(config).generate_to_writer(&layers, &mut output).unwrap();
+        (String::from_utf8(output).unwrap(), stats)
+    };
+    let (plain, plain_stats) = write(config.clone());
+    let (compact, compact_stats) = write(SlicerConfig { compact_gcode: true, ..config });
+    let printed = |gcode: &str, start: &str| gcode[gcode.find(start).unwrap()..gcode.find("M104 S0").unwrap()].len() as f64;
+    let (compact_bytes, plain_bytes) = (printed(&compact, "G1Z0.3"), printed(&plain, "G1 Z0.3"));
+    assert!(compact_bytes <= 0.70 * plain_bytes, "{} of {} bytes", compact_bytes, plain_bytes);
+    assert!(compact.contains("G1E-5F2400"));
+
+    // Read back the same for the estimates
+    assert_eq!(compact_stats.moves, plain_stats.moves);
+    assert!((compact_stats.print_time - plain_stats.print_time).abs() < 1e-3 * plain_stats.print_time);
+    assert!((compact_stats.filament_length - plain_stats.filament_length).abs() < 1e-3);
+    let report = |gcode: &str| format!("{:?}", verify(gcode.as_bytes(), &SlicerConfig::default()).unwrap().violations);
+    assert_eq!(report(&compact), report(&plain));
+
+    let (plain, compact) = (positions(&plain), positions(&compact));
+    assert_eq!(plain.len(), compact.len());
+    for (a, b) in plain.iter().zip(&compact) {
+        assert!(a[..3].iter().zip(&b[..3]).all(|(a, b)| (a - b).abs() <= 0.001), "{:?} and {:?}", a, b);
+        assert!((a[3] - b[3]).abs() < 1e-9, "{:?} and {:?}", a, b);
+    }
+}
+
+#[test]
+fn test_coordinates_are_written_to_the_digits_asked_for() {
+    let config = SlicerConfig { xyz_decimals: 2, e_decimals: 4, ..SlicerConfig::default() };
+    let gcode = generate(config.clone(), common::cube(10.0));
+    let digits = |word: &str| word.split('.').nth(1).map_or(0, str::len);
+    let moves = gcode.lines().filter(|line| line.starts_with("G1 X"));
+    for word in moves.flat_map(|line| line.split(';').next().unwrap().split_whitespace().skip(1)) {
+        match &word[..1] {
+            "X" | "Y" => assert_eq!(digits(word), 2, "{}", word),
+            "E" => assert_eq!(digits(word), 4, "{}", word),
+            _ => {}
+        }
+    }
+
+    assert!(SlicerConfig { e_decimals: 12, ..config }.validate().is_err());
+}
+
+#[test]
+fn test_header_and_settings_read_back_as_the_profile() {
+    let config = SlicerConfig {
+        layer_height: 0.15,
+        nozzle_temperature: 215,
+        bed_temperature: 55,
+        infill_percentage: 35,
+        perimeter_speed: Some(40.0),
+        ..SlicerConfig::default()
+    };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let metadata = Metadata {
+        profile: Some("pla-fine".to_string()),
+        timestamp: Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)),
+    };
+    let gcode = GCodeGenerator::new(config.clone()).with_metadata(metadata).generate_to_string(&layers).unwrap();
+    let version = env!("CARGO_PKG_VERSION");
+    assert_eq!(gcode.lines().next().unwrap(), format!("; Generated by RustSlicer {} on 2023-11-14 at 22:13:20 UTC", version));
+    assert!(gcode.contains("\n; Profile: pla-fine\n"));
+
+    let header = |key: &str| -> f64 {
+        let line = gcode.lines().find(|line| line.starts_with(&format!(";{}:", key))).unwrap();
+        line[key.len() + 2..].parse().unwrap()
+    };
+    assert_eq!(header("LAYER_COUNT") as usize, layers.len());
+    // The outer walls run half a line inside the 10 mm cube
+    assert!((header("MAXX") - header("MINX") - (10.0 - config.nozzle_diameter)).abs() < 0.1);
+    assert!((header("MAXY") - header("MINY") - (10.0 - config.nozzle_diameter)).abs() < 0.1);
+    assert!((header("MINZ") - config.first_layer_height).abs() < 1e-9);
+    assert!((header("MAXZ") - layers.last().unwrap().z_height).abs() < 1e-3);
+
+    let section = &gcode[gcode.find("; prusaslicer_config = begin").unwrap()..gcode.find("; prusaslicer_config = end").unwrap()];
+    let setting = |name: &str| -> &str {
+        section.lines().find_map(|line| line.strip_prefix(&format!("; {} = ", name))).unwrap()
+    };
+    assert_eq!(setting("layer_height").parse::<f64>().unwrap(), config.layer_height);
+    assert_eq!(setting("nozzle_diameter").parse::<f64>().unwrap(), config.nozzle_diameter);
+    assert_eq!(setting("temperature").parse::<u16>().unwrap(), config.nozzle_temperature);
+    assert_eq!(setting("bed_temperature").parse::<u16>().unwrap(), config.bed_temperature);
+    assert_eq!(setting("fill_density"), "35%");
+    assert_eq!(setting("fill_pattern"), "rectilinear");
+    assert_eq!(setting("perimeter_speed").parse::<f64>().unwrap(), 40.0);
+    assert_eq!(setting("infill_speed").parse::<f64>().unwrap(), config.print_speed);
+    assert_eq!(setting("print_settings_id"), "pla-fine");
+
+    // Left without metadata, the same print gives the same G-code
+    let plain = GCodeGenerator::new(config.clone()).generate_to_string(&layers).unwrap();
+    assert_eq!(plain.lines().next().unwrap(), format!("; Generated by RustSlicer {}", version));
+    assert_eq!(plain, GCodeGenerator::new(config).generate_to_string(&layers).unwrap());
+}
+
+#[test]
+fn test_each_object_is_marked_on_every_layer_for_klipper_to_cancel() {
+    let config = SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, exclude_objects: true, ..SlicerConfig::default() };
+    let left = common::cuboid([80.0, 100.0, 0.0], [90.0, 110.0, 5.0]);
+    let right = common::cuboid([120.0, 100.0, 0.0], [130.0, 110.0, 5.0]);
+    let plate = Mesh::merge(vec![left.clone(), right.clone()]);
+    let layers = SliceEngine::new(plate.clone(), config.clone()).unwrap().slice().unwrap();
+
+    let objects = PrintObject::from_meshes([("left.stl", &left), ("right", &right)]);
+    let generator = GCodeGenerator::new(config.clone()).with_objects(objects);
+    let gcode = generator.generate_to_string(&layers).unwrap();
+    let defines: Vec<&str> = gcode.lines().filter(|line| line.starts_with("EXCLUDE_OBJECT_DEFINE")).collect();
+    assert_eq!(defines.len(), 2);
+    assert!(defines[0].starts_with("EXCLUDE_OBJECT_DEFINE NAME=left.stl CENTER=85.000,105.000 POLYGON=[["), "{}", defines[0]);
+
+    let layer_blocks: Vec<&str> = gcode.split("\n; Layer ").filter(|block| block.starts_with(|c: char| c.is_ascii_digit())).collect();
+    assert_eq!(layer_blocks.len(), layers.len());
+    for block in layer_blocks {
+        let markers: Vec<&str> = block.lines().filter(|line| line.starts_with("EXCLUDE_OBJECT_")).collect();
+        assert_eq!(markers.len(), 4, "{:?}", markers);
+        for name in ["left.stl", "right"] {
+            let start = markers.iter().position(|line| *line == format!("EXCLUDE_OBJECT_START NAME={}", name)).unwrap();
+            // Nothing of the other object comes in between
+            assert_eq!(markers[start + 1], format!("EXCLUDE_OBJECT_END NAME={}", name));
+        }
+        // Every extrusion is inside an object's markers
+        let mut inside = false;
+        for line in block.lines() {
+            if line.starts_with("EXCLUDE_OBJECT_") {
+                inside = line.starts_with("EXCLUDE_OBJECT_START");
+            }
+            assert!(inside || !(line.starts_with("G1 X") && line.contains(" E")), "{}", line);
+        }
+    }
+
+    // Separate shells of one mesh are told apart too
+    let shells = PrintObject::from_meshes([("plate", &plate)]);
+    assert_eq!(shells.iter().map(|object| object.name.as_str()).collect::<Vec<_>>(), ["plate_1", "plate_2"]);
+
+    // Other firmwares have no markers
+    let marlin = GCodeGenerator::new(SlicerConfig { gcode_flavor: FirmwareFlavor::Marlin, ..config })
+        .with_objects(shells)
+        .generate_to_string(&layers)
+        .unwrap();
+    assert!(!marlin.contains("EXCLUDE_OBJECT"));
+}
+
+#[test]
+fn test_verification_finds_each_kind_of_violation() {
+    let config = SlicerConfig { max_feedrate: Some(200.0), max_feedrate_z: Some(10.0), ..Default::default() };
+
+    // What the slicer writes breaks no limit, with or without Klipper's
+    // heater macros
+    for flavor in [FirmwareFlavor::Marlin, FirmwareFlavor::Klipper] {
+        let config = SlicerConfig { gcode_flavor: flavor, ..config.clone() };
+        let report = verify(generate(config.clone(), common::cuboid([100.0, 100.0, 0.0], [110.0, 110.0, 10.0])).as_bytes(), &config).unwrap();
+        assert!(report.moves > 100);
+        assert!(report.is_clean(), "{:?}", report.violations);
+    }
+
+    let cases = [
+        ("M104 S200\nG1 X221 Y10 Z0.2 F1200\n", ViolationKind::OutOfBounds, 2),
+        ("M104 S200\nG91\nG1 X200 F1200\nG1 X30\n", ViolationKind::OutOfBounds, 4),
+        ("M104 S200\nG1 X10 Y10 Z0.2 F1200\nG1 Z-0.5 F300\n", ViolationKind::BelowBed, 3),
+        ("G1 X10 Y10 Z0.2 F15000\n", ViolationKind::Feedrate, 1),
+        ("G1 X10 Y10 F1200\nG1 Z5\n", ViolationKind::Feedrate, 2),
+        ("G28\nG1 X10 Y10 Z0.2 F1200\nG1 X20 E1\n", ViolationKind::NoTemperature, 3),
+        // Only the first extruder was heated
+        ("M104 S200\nT1\nG1 X10 Y10 Z0.2 F1200\nM83\nG1 E2\n", ViolationKind::NoTemperature, 5),
+        ("M104 S150\nG1 X10 Y10 Z0.2 F1200\nG1 X20 E1\n", ViolationKind::ColdExtrusion, 3),
+        ("SET_HEATER_TEMPERATURE HEATER=extruder TARGET=210\nTURN_OFF_HEATERS\nG1 X10 Y10 Z0.2 F1200 E1\n", ViolationKind::ColdExtrusion, 3),
+    ];
+    for (gcode, kind, line) in cases {
+        let report = verify(gcode.as_bytes(), &config).unwrap();
+        assert_eq!(report.violations.len(), 1, "{}: {:?}", gcode, report.violations);
+        assert_eq!(report.violations[0].kind, kind, "{}", gcode);
+        assert_eq!(report.violations[0].line, line, "{}", gcode);
+        assert_eq!(report.out_of_bounds(), matches!(kind, ViolationKind::OutOfBounds | ViolationKind::BelowBed));
+    }
+
+    // Resetting E and retracting and priming back don't extrude
+    let report = verify("M104 S200\nG1 X10 Y10 Z0.2 F1200\nG92 E0\nG1 E0\n".as_bytes(), &config).unwrap();
+    assert!(report.is_clean(), "{:?}", report.violations);
+}
+
+/// E fed by each extruding move of `gcode` that starts and ends within
+/// `bounds`, with the line it is on
+fn extrusion_within(gcode: &str, bounds: &BoundingBox2D) -> Vec<(usize, f64)> {
+    let (mut position, mut e) = (Point2::origin(), 0.0);
+    let mut fed = Vec::new();
+    for (i, line) in gcode.lines().enumerate() {
+        let word = |letter: char| line.split_whitespace().find(|w| w.starts_with(letter)).and_then(|w| w[1..].parse::<f64>().ok());
+        if line.starts_with("G92") {
+            e = word('E').unwrap_or(e);
+        } else if line.starts_with("G1 ") {
+            let to = Point2::new(word('X').unwrap_or(position.x), word('Y').unwrap_or(position.y));
+            let inside = |p: Point2<f64>| bounds.min.x <= p.x && p.x <= bounds.max.x && bounds.min.y <= p.y && p.y <= bounds.max.y;
+            if let Some(next) = word('E') {
+                if next > e && to != position && inside(position) && inside(to) {
+                    fed.push((i, next - e));
+                }
+                e = next;
+            }
+            position = to;
+        }
+    }
+    fed
+}
+
+#[test]
+fn test_wipe_tower_takes_each_purge_up_to_the_last_tool_change() {
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        support_material: true,
+        support_extruder: 1,
+        extruders: vec![Extruder::default(), Extruder::default()],
+        wipe_tower: true,
+        wipe_tower_position: Some([60.0, 60.0]),
+        ..SlicerConfig::default()
+    };
+    let mesh = common::tee(10.0, 2.0, 4.0, 8.1);
+    let layers = SliceEngine::new(mesh.clone(), config.clone()).unwrap().slice().unwrap();
+    let mut output = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut output).unwrap();
+    let gcode = String::from_utf8(output).unwrap();
+    let tower = stats.wipe_tower.unwrap();
+    assert_eq!(tower.min, Point2::new(60.0, 60.0));
+
+    let blocks: Vec<&str> = gcode.split("\n; Layer ").filter(|block| block.starts_with(|c: char| c.is_ascii_digit())).collect();
+    assert_eq!(blocks.len(), layers.len());
+    let changes: Vec<usize> = blocks.iter().map(|block| block.matches("; Tool change").count()).collect();
+    let last = changes.iter().rposition(|&count| count > 0).unwrap();
+    // Support stops under the crossbar, and the tower with it. The tower
+    // stands on the bed, so it is printed on every layer up to there.
+    assert!(last + 1 < blocks.len());
+    for (index, block) in blocks.iter().enumerate() {
+        let on_tower = !extrusion_within(block, &tower).is_empty();
+        assert_eq!(on_tower, index <= last, "layer {}", index);
+    }
+
+    // Inside the tower's wall, each tool change purges the volume asked
+    // for, whatever the layer's flow
+    let line = config.nozzle_diameter;
+    let inside = BoundingBox2D { min: tower.min + Vector2::new(line, line), max: tower.max - Vector2::new(line, line) };
+    let purge = config.wipe_tower_purge_volume / (std::f64::consts::PI * (config.filament_diameter / 2.0).powi(2));
+    let fed = extrusion_within(&gcode, &inside);
+    let mut purges = 0;
+    for (i, _) in gcode.lines().enumerate().filter(|(_, line)| *line == "; Tool change") {
+        // The unbroken run of lines the new tool purges with
+        let run = &fed[fed.iter().position(|&(line, _)| line > i).unwrap()..];
+        let length = run.windows(2).position(|pair| pair[1].0 != pair[0].0 + 1).map_or(run.len(), |end| end + 1);
+        let total: f64 = run[..length].iter().map(|(_, e)| e).sum();
+        assert!((total - purge).abs() < 1e-3, "purged {} mm of filament, expected {}", total, purge);
+        purges += 1;
+    }
+    assert_eq!(purges, changes.iter().sum::<usize>());
+
+    // A filament change purges on the tower too, which then goes no higher
+    let config = SlicerConfig { color_change_layers: vec![5], extruders: Vec::new(), support_material: false, ..config };
+    let mut output = Vec::new();
+    let stats = GCodeGenerator::new(config.clone()).generate_to_writer(&layers, &mut output).unwrap();
+    let gcode = String::from_utf8(output).unwrap();
+    let blocks: Vec<&str> = gcode.split("\n; Layer ").filter(|block| block.starts_with(|c: char| c.is_ascii_digit())).collect();
+    let tower = stats.wipe_tower.unwrap();
+    let on_tower: Vec<bool> = blocks.iter().map(|block| !extrusion_within(block, &tower).is_empty()).collect();
+    assert_eq!(on_tower.iter().position(|&on| !on), Some(6));
+    assert_eq!(on_tower.iter().rposition(|&on| on), Some(5));
+    // The layers below the change print the tower with sparse fill, to
+    // build it up to the purge
+    let inside = BoundingBox2D { min: tower.min + Vector2::new(line, line), max: tower.max - Vector2::new(line, line) };
+    for (index, block) in blocks[..5].iter().enumerate() {
+        let fed: f64 = extrusion_within(block, &inside).iter().map(|(_, e)| e).sum();
+        assert!(fed > 0.0 && fed < purge / 2.0, "layer {} fed {} mm inside the tower", index, fed);
+    }
+    let fed: f64 = extrusion_within(blocks[5], &inside).iter().map(|(_, e)| e).sum();
+    assert!((fed - purge).abs() < 1e-3);
+
+    // Without changes there is no tower
+    let config = SlicerConfig { color_change_layers: Vec::new(), ..config };
+    let mut output = Vec::new();
+    assert!(GCodeGenerator::new(config).generate_to_writer(&layers, &mut output).unwrap().wipe_tower.is_none());
+}
+
+#[test]
+fn test_display_counts_layers_up_to_the_total() {
+    let config = SlicerConfig { display_progress: true, gcode_comments: CommentLevel::Off, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let gcode = GCodeGenerator::new(config.clone()).generate_to_string(&layers).unwrap();
+    let messages: Vec<&str> = gcode.lines().filter(|line| line.starts_with("M117")).collect();
+    assert_eq!(messages.len(), layers.len());
+    assert_eq!(messages[0], "M117 Layer 1/50");
+    assert_eq!(messages.last().copied(), Some(format!("M117 Layer {0}/{0}", layers.len()).as_str()));
+
+    // After the layer change G-code, on Klipper's display
+    let config = SlicerConfig { gcode_flavor: FirmwareFlavor::Klipper, layer_change_gcode: "G4 P0".to_string(), ..config };
+    let gcode = GCodeGenerator::new(config).generate_to_string(&layers).unwrap();
+    let lines: Vec<&str> = gcode.lines().collect();
+    let shown: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].starts_with("SET_DISPLAY_TEXT")).collect();
+    assert_eq!(shown.len(), layers.len());
+    assert!(shown.iter().all(|&i| lines[i - 1] == "G4 P0"));
+    assert_eq!(lines[shown[49]], "SET_DISPLAY_TEXT MSG=\"Layer 50/50\"");
+
+    // Left out unless asked for
+    let gcode = GCodeGenerator::new(SlicerConfig::default()).generate_to_string(&layers).unwrap();
+    assert!(!gcode.contains("M117") && !gcode.contains("Layer display"));
+}
+
+#[test]
+fn test_line_numbers_and_checksums_frame_every_command() {
+    let config = SlicerConfig { line_numbers_and_checksums: true, first_line_number: 10, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cuboid([100.0, 100.0, 0.0], [110.0, 110.0, 2.0]), config.clone()).unwrap().slice().unwrap();
+    let gcode = GCodeGenerator::new(config.clone()).generate_to_string(&layers).unwrap();
+    let plain = GCodeGenerator::new(SlicerConfig { line_numbers_and_checksums: false, ..config.clone() }).generate_to_string(&layers).unwrap();
+
+    // Worked out by hand: the XOR of every byte of "N9 M110 N9"
+    let framed: Vec<&str> = gcode.lines().filter(|line| !line.trim().is_empty() && !line.starts_with(';')).collect();
+    assert_eq!(framed[0], "N9 M110 N9*125");
+    for (i, line) in framed.iter().enumerate() {
+        let (body, checksum) = line.rsplit_once('*').unwrap_or_else(|| panic!("no checksum on {:?}", line));
+        let expected = body.bytes().fold(0u8, |sum, byte| sum ^ byte);
+        assert_eq!(checksum.parse::<u8>().unwrap(), expected, "{}", line);
+        assert!(body.starts_with(&format!("N{} ", 9 + i)), "{} out of order", line);
+        assert!(!body.contains(';'));
+    }
+
+    // The same commands in the same order, and the comments on their own
+    // lines as they were
+    let commands = |gcode: &str| -> Vec<String> {
+        gcode
+            .lines()
+            .map(|line| line.split(';').next().unwrap().trim())
+            .filter(|code| !code.is_empty())
+            .map(|code| code.split_once(' ').map_or(code, |(_, rest)| rest).rsplit_once('*').map_or(code, |(code, _)| code).to_string())
+            .collect()
+    };
+    let numbered = commands(&gcode);
+    let unnumbered: Vec<String> = plain.lines().map(|line| line.split(';').next().unwrap().trim()).filter(|code| !code.is_empty()).map(str::to_string).collect();
+    assert_eq!(numbered[1..], unnumbered[..]);
+    let comments = |gcode: &str| gcode.lines().filter(|line| line.starts_with(';')).count();
+    assert_eq!(comments(&gcode), comments(&plain));
+
+    // Still reads as the same G-code when checked
+    assert!(verify(gcode.as_bytes(), &config).unwrap().is_clean());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_post_process_script_output_replaces_the_file() {
+    let config = SlicerConfig::default();
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("cube.gcode");
+    GCodeGenerator::new(config.clone()).generate_to_file(&layers, &output).unwrap();
+    let written = std::fs::read_to_string(&output).unwrap();
+
+    // Given the file on stdin, printing it back with a marker on the end
+    let script = dir.path().join("mark.sh");
+    std::fs::write(&script, "#!/bin/sh\ncat\necho \"; marked $SLIC3R_LAYER_HEIGHT $SLIC3R_FILL_DENSITY\"\n").unwrap();
+    post_process(&output, &format!("sh {}", script.display()), &config).unwrap();
+    let processed = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(processed, format!("{}; marked 0.2 20%\n", written));
+
+    // Editing the file in place at the path it is given, printing nothing
+    post_process(&output, "echo '; edited' >>", &config).unwrap();
+    assert!(std::fs::read_to_string(&output).unwrap().ends_with("; marked 0.2 20%\n; edited\n"));
+
+    // A script failing leaves the file and says why
+    let result = post_process(&output, "echo broken >&2; exit 3 #", &config);
+    match result {
+        Err(rustslicer::SlicerError::GCodeError(message)) => assert!(message.contains("broken"), "{}", message),
+        other => panic!("expected a G-code error, got {:?}", other),
+    }
+    assert!(std::fs::read_to_string(&output).unwrap().ends_with("; edited\n"));
+
+    // Numbered after the scripts, the lines they add are framed with the
+    // rest and the file is as the generator would number it
+    GCodeGenerator::new(config.clone()).generate_to_file(&layers, &output).unwrap();
+    post_process(&output, "echo 'M400 ; marked' >>", &config).unwrap();
+    assert!(number_lines(&output, 0).is_err());
+    number_lines(&output, 10).unwrap();
+    let numbered = std::fs::read_to_string(&output).unwrap();
+    let last = numbered.lines().last().unwrap();
+    assert!(last.starts_with('N') && last.contains(" M400*"), "{}", last);
+    let config = SlicerConfig { line_numbers_and_checksums: true, first_line_number: 10, ..config };
+    let framed = GCodeGenerator::new(config).generate_to_string(&layers).unwrap();
+    assert_eq!(numbered, format!("{}{}\n", framed, last));
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn test_post_processor_changes_the_gcode_before_it_is_numbered() {
+    let config = SlicerConfig { line_numbers_and_checksums: true, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cube(10.0), config.clone()).unwrap().slice().unwrap();
+    let generator = GCodeGenerator::new(config)
+        .with_post_processor(Box::new(|gcode: String| Ok(gcode.replace("M107", "M106 S0") + "M400 ; marked\n")));
+    let gcode = generator.generate_to_string(&layers).unwrap();
+    assert!(!gcode.contains("M107"));
+    assert!(gcode.contains(" M106 S0*"));
+    let last = gcode.lines().last().unwrap();
+    assert!(last.contains(" M400*") && last.starts_with('N'), "{}", last);
+
+    // Its errors are the generator's
+    let failing = GCodeGenerator::new(SlicerConfig::default())
+        .with_post_processor(Box::new(|_| Err(rustslicer::SlicerError::GCodeError("rejected".to_string()))));
+    assert!(failing.generate_to_string(&layers).is_err());
+}
+
+#[test]
+fn test_spiral_vase_climbs_steadily_around_one_wall() {
+    // Without progress, which counts down to a different total
+    let config = SlicerConfig { spiral_vase: true, bottom_solid_layers: Some(3), progress_updates: false, ..SlicerConfig::default() };
+    let circle: Vec<Point2<f64>> = (0..64)
+        .map(|i| {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / 64.0;
+            Point2::new(100.0 + 15.0 * angle.cos(), 100.0 + 15.0 * angle.sin())
+        })
+        .collect();
+    let gcode = generate(config.clone(), common::prism(&circle, Point2::new(100.0, 100.0), 6.0));
+    let spiral = &gcode[gcode.find("\n; Layer 3\n").unwrap()..gcode.find("; End sequence").unwrap()];
+
+    // Every move climbs and feeds filament, with no retraction, travel or
+    // layer change between the loops
+    let moves = positions(spiral);
+    for pair in moves.windows(2) {
+        assert!(pair[1][2] > pair[0][2], "Z drops or stalls: {:?}", pair);
+        assert!(pair[1][3] > pair[0][3], "E stops: {:?}", pair);
+    }
+    assert!(!spiral.contains("Retract") && !spiral.contains("G10"));
+    assert!(!spiral.lines().any(|line| line.starts_with("G1 Z")));
+
+    // Each loop closes a layer up, where the one below closed
+    let loops: Vec<Vec<[f64; 4]>> = spiral
+        .split("\n; Layer ")
+        .filter(|block| block.starts_with(|c: char| c.is_ascii_digit()))
+        .map(positions)
+        .collect();
+    assert!(loops.len() > 10);
+    for pair in loops.windows(2) {
+        let (below, above) = (pair[0].last().unwrap(), pair[1].last().unwrap());
+        assert!((above[2] - below[2] - config.layer_height).abs() < 1e-3, "{:?} to {:?}", below, above);
+        assert!(Vector2::new(above[0] - below[0], above[1] - below[1]).norm() < 0.01, "{:?} to {:?}", below, above);
+    }
+
+    // Out of the flat bottom, the first loop's flow builds up from nothing
+    let e_per_mm = |loop_moves: &[[f64; 4]], i: usize| {
+        let (a, b) = (loop_moves[i - 1], loop_moves[i]);
+        (b[3] - a[3]) / Vector2::new(b[0] - a[0], b[1] - a[1]).norm()
+    };
+    // (after the travel onto it, which the helper reads from E0)
+    let (first, second) = (&loops[0], &loops[1]);
+    assert!(e_per_mm(first, 2) < 0.1 * e_per_mm(first, first.len() - 1));
+    assert!((e_per_mm(second, 2) - e_per_mm(second, second.len() - 1)).abs() < 1e-3);
+
+    // Only the layers above the bottom spiral
+    let flat = generate(SlicerConfig { spiral_vase: false, ..config }, common::prism(&circle, Point2::new(100.0, 100.0), 6.0));
+    let bottom = |gcode: &str| gcode[gcode.find("\n; Layer 0\n").unwrap()..gcode.find("\n; Layer 3\n").unwrap()].to_string();
+    assert_eq!(bottom(&gcode), bottom(&flat));
+}
@@ -1,5 +1,11 @@
-use rustslicer::geometry::{Triangle, LineSegment};
-use nalgebra::{Point3, Vector3};
+mod common;
+
+use rustslicer::geometry::{convex_hull, BoundingBox, Island, LineSegment2D, Mesh, Polygon, Triangle};
+use rustslicer::geometry::arc_fit::{fit_arcs, PathElement};
+use rustslicer::geometry::arrange::{arrange, arrange_around};
+use rustslicer::geometry::boolean::{difference, intersection, union};
+use nalgebra::{Isometry3, Matrix4, Point2, Point3, Vector3};
+use proptest::prelude::*;
 
 #[test]
 fn test_triangle_plane_intersection() {
@@ -35,3 +41,771 @@ fn test_triangle_vertices() {
     assert_eq!(triangle.vertices.len(), 3);
     assert_eq!(triangle.vertices[0], Point3::new(0.0, 0.0, 0.0));
 }
+
+#[test]
+fn test_cut_cube_at_mid_height() {
+    let cube = common::cube(10.0);
+    let (bottom, top) = cube.cut_at_z(5.0).unwrap();
+
+    assert!(bottom.is_watertight());
+    assert!(top.is_watertight());
+    assert!((bottom.bounds.max.z - 5.0).abs() < 1e-9);
+    assert!((top.bounds.min.z - 5.0).abs() < 1e-9);
+    assert!((bottom.volume() - 500.0).abs() < 1e-6);
+    assert!((bottom.volume() + top.volume() - cube.volume()).abs() < 1e-6);
+}
+
+#[test]
+fn test_cut_sphere_is_watertight() {
+    let sphere = common::sphere(10.0, 32, 16);
+    let (bottom, top) = sphere.cut_at_z(7.3).unwrap();
+
+    assert!(bottom.is_watertight());
+    assert!(top.is_watertight());
+    assert!((bottom.volume() + top.volume() - sphere.volume()).abs() < 1e-6);
+}
+
+#[test]
+fn test_cut_through_a_vertex_ring_closes_both_halves() {
+    // The sphere's middle ring of vertices lies exactly at its equator
+    let sphere = common::sphere(10.0, 32, 16);
+    let (bottom, top) = sphere.cut_at_z(10.0).unwrap();
+
+    assert!(bottom.is_watertight());
+    assert!(top.is_watertight());
+    assert!((bottom.volume() - top.volume()).abs() < 1e-6);
+    assert!((bottom.volume() + top.volume() - sphere.volume()).abs() < 1e-6);
+}
+
+#[test]
+fn test_cut_along_a_step_caps_each_half_with_its_own_section() {
+    // Cutting at the step leaves both blocks' faces there in the plane
+    let blocks = vec![common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 5.0]), common::cuboid([5.0, 5.0, 5.0], [15.0, 15.0, 10.0])];
+    let (bottom, top) = Mesh::merge(blocks).cut_at_z(5.0).unwrap();
+
+    assert!(bottom.is_watertight());
+    assert!(top.is_watertight());
+    assert!((bottom.volume() - 2000.0).abs() < 1e-6);
+    assert!((top.volume() - 500.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_cut_outside_model_fails() {
+    let cube = common::cube(10.0);
+    assert!(cube.cut_at_z(12.0).is_err());
+}
+
+fn assert_bounds_close(a: &BoundingBox, b: &BoundingBox) {
+    assert!((a.min - b.min).norm() < 1e-9, "min {:?} vs {:?}", a.min, b.min);
+    assert!((a.max - b.max).norm() < 1e-9, "max {:?} vs {:?}", a.max, b.max);
+}
+
+#[test]
+fn test_mirror_keeps_outward_normals() {
+    let mut cube = common::cube(10.0);
+    let volume = cube.volume();
+    cube.apply_matrix(&Matrix4::new_nonuniform_scaling(&Vector3::new(-1.0, 1.0, 1.0)));
+
+    assert!(cube.is_watertight());
+    assert!((cube.volume() - volume).abs() < 1e-9);
+    for triangle in &cube.triangles {
+        let [a, b, c] = triangle.vertices;
+        let face = (b - a).cross(&(c - a)).normalize();
+        assert!((face - triangle.normal).norm() < 1e-9);
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_isometry_inverse_restores_bounds(
+        angles in prop::array::uniform3(-3.2f64..3.2),
+        offset in prop::array::uniform3(-200.0f64..200.0),
+    ) {
+        let original = common::cube(20.0);
+        let m = Isometry3::new(Vector3::from(offset), Vector3::from(angles));
+
+        let mut mesh = original.clone();
+        mesh.apply_transform(&m);
+        mesh.apply_transform(&m.inverse());
+
+        assert_bounds_close(&mesh.bounds, &original.bounds);
+    }
+
+    #[test]
+    fn prop_affine_inverse_restores_bounds(
+        scale in prop::array::uniform3(0.1f64..10.0),
+        angles in prop::array::uniform3(-3.2f64..3.2),
+        offset in prop::array::uniform3(-200.0f64..200.0),
+    ) {
+        let original = common::sphere(5.0, 16, 8);
+        let m = Isometry3::new(Vector3::from(offset), Vector3::from(angles)).to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&Vector3::from(scale));
+
+        let mut mesh = original.clone();
+        mesh.apply_matrix(&m);
+        mesh.apply_matrix(&m.try_inverse().unwrap());
+
+        assert_bounds_close(&mesh.bounds, &original.bounds);
+    }
+}
+
+#[test]
+fn test_check_fit_reports_overflowing_axes() {
+    let mut cube = common::cuboid([0.0, 0.0, 0.0], [250.0, 100.0, 300.0]);
+
+    let message = cube.bounds.check_fit([220.0, 220.0, 250.0]).unwrap_err().to_string();
+    assert!(message.contains("X by 30.00 mm"), "{}", message);
+    assert!(message.contains("Z by 50.00 mm"), "{}", message);
+    assert!(!message.contains("Y by"), "{}", message);
+
+    cube.scale(0.5);
+    cube.center_xy(110.0, 110.0);
+    assert!(cube.bounds.check_fit([220.0, 220.0, 250.0]).is_ok());
+}
+
+#[test]
+fn test_arrange_separates_parts() {
+    let mut meshes: Vec<_> = (0..4).map(|_| common::cube(50.0)).collect();
+    arrange(&mut meshes, [220.0, 220.0, 250.0], 5.0).unwrap();
+
+    for (i, a) in meshes.iter().enumerate() {
+        assert!(a.bounds.check_fit([220.0, 220.0, 250.0]).is_ok());
+        for b in &meshes[i + 1..] {
+            let apart_x = a.bounds.max.x + 5.0 <= b.bounds.min.x + 1e-9
+                || b.bounds.max.x + 5.0 <= a.bounds.min.x + 1e-9;
+            let apart_y = a.bounds.max.y + 5.0 <= b.bounds.min.y + 1e-9
+                || b.bounds.max.y + 5.0 <= a.bounds.min.y + 1e-9;
+            assert!(apart_x || apart_y);
+        }
+    }
+}
+
+#[test]
+fn test_arrange_around_keeps_fixed_parts_and_clears_them() {
+    let mut meshes: Vec<_> = (0..4).map(|_| common::cube(50.0)).collect();
+    meshes[1].translate(Vector3::new(35.0, 25.0, 0.0));
+    let fixed = meshes[1].bounds.clone();
+    arrange_around(&mut meshes, &[false, true, false, false], [220.0, 220.0, 250.0], 5.0).unwrap();
+
+    assert_bounds_close(&meshes[1].bounds, &fixed);
+    for (i, a) in meshes.iter().enumerate() {
+        assert!(a.bounds.check_fit([220.0, 220.0, 250.0]).is_ok());
+        for b in &meshes[i + 1..] {
+            let apart_x = a.bounds.max.x + 5.0 <= b.bounds.min.x + 1e-9
+                || b.bounds.max.x + 5.0 <= a.bounds.min.x + 1e-9;
+            let apart_y = a.bounds.max.y + 5.0 <= b.bounds.min.y + 1e-9
+                || b.bounds.max.y + 5.0 <= a.bounds.min.y + 1e-9;
+            assert!(apart_x || apart_y);
+        }
+    }
+}
+
+#[test]
+fn test_arrange_names_parts_that_do_not_fit() {
+    let mut meshes: Vec<_> = (0..3).map(|_| common::cube(150.0)).collect();
+    let message = arrange(&mut meshes, [220.0, 220.0, 250.0], 5.0).unwrap_err().to_string();
+    assert!(message.contains("part 2, part 3"), "{}", message);
+}
+
+fn circle(radius: f64, points: usize) -> Polygon {
+    let step = 2.0 * std::f64::consts::PI / points as f64;
+    Polygon::new(
+        (0..points)
+            .map(|i| Point2::new(radius * (i as f64 * step).cos(), radius * (i as f64 * step).sin()))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_simplify_fine_circle_keeps_area() {
+    let tolerance = 0.0125;
+    let fine = circle(10.0, 20_000);
+    let simple = fine.simplify(tolerance);
+
+    assert!(simple.len() < fine.len() / 10, "kept {} points", simple.len());
+    let perimeter = 2.0 * std::f64::consts::PI * 10.0;
+    assert!((fine.area() - simple.area()).abs() < perimeter * tolerance);
+    assert!(!simple.is_clockwise());
+}
+
+#[test]
+fn test_simplify_never_drops_below_three_points() {
+    let simple = circle(0.01, 100).simplify(1.0);
+    assert_eq!(simple.len(), 3);
+    assert!(simple.area() > 0.0);
+
+    let square = common::square([0.0, 0.0], 1.0);
+    assert_eq!(square.simplify(0.0125), square);
+}
+
+fn triangle(a: [f64; 3], b: [f64; 3], c: [f64; 3]) -> Triangle {
+    Triangle {
+        vertices: [a, b, c].map(|[x, y, z]| Point3::new(x, y, z)),
+        normal: Vector3::z(),
+    }
+}
+
+#[test]
+fn test_intersection_through_vertex_with_others_straddling() {
+    // Every rotation of the vertex order gives the same segment
+    let (a, b, c) = ([0.0, 0.0, 5.0], [10.0, 0.0, 0.0], [10.0, 10.0, 10.0]);
+    for t in [triangle(a, b, c), triangle(b, c, a), triangle(c, a, b)] {
+        let segment = t.intersect_plane(5.0).unwrap();
+        let mut ends = [segment.start, segment.end];
+        ends.sort_by(|p, q| p.x.total_cmp(&q.x));
+        assert_eq!(ends[0], Point3::new(0.0, 0.0, 5.0));
+        assert_eq!(ends[1], Point3::new(10.0, 5.0, 5.0));
+    }
+}
+
+#[test]
+fn test_intersection_touching_vertex_only() {
+    let above = triangle([0.0, 0.0, 5.0], [10.0, 0.0, 8.0], [0.0, 10.0, 9.0]);
+    let below = triangle([0.0, 0.0, 5.0], [10.0, 0.0, 1.0], [0.0, 10.0, 2.0]);
+    assert!(above.intersect_plane(5.0).is_none());
+    assert!(below.intersect_plane(5.0).is_none());
+}
+
+#[test]
+fn test_edge_in_plane_is_emitted_once() {
+    // Two triangles sharing the edge (0,0,5)-(10,0,5), one on each side
+    let below = triangle([0.0, 0.0, 5.0], [10.0, 0.0, 5.0], [5.0, 5.0, 0.0]);
+    let above = triangle([10.0, 0.0, 5.0], [0.0, 0.0, 5.0], [5.0, 5.0, 10.0]);
+
+    let segment = below.intersect_plane(5.0).unwrap();
+    assert_eq!(segment.start, Point3::new(0.0, 0.0, 5.0));
+    assert_eq!(segment.end, Point3::new(10.0, 0.0, 5.0));
+    assert!(above.intersect_plane(5.0).is_none());
+
+    // A face lying in the plane contributes nothing
+    let flat = triangle([0.0, 0.0, 5.0], [10.0, 0.0, 5.0], [0.0, 10.0, 5.0]);
+    assert!(flat.intersect_plane(5.0).is_none());
+}
+
+#[test]
+fn test_signed_area_follows_winding() {
+    let mut square = common::square([0.0, 0.0], 2.0);
+    assert_eq!(square.signed_area(), 4.0);
+    assert!(!square.is_clockwise());
+
+    square.reverse();
+    assert_eq!(square.signed_area(), -4.0);
+    assert!(square.is_clockwise());
+    assert_eq!(square.area(), 4.0);
+}
+
+/// L-shaped polygon with its notch in the upper right
+fn l_shape() -> Polygon {
+    Polygon::new(vec![
+        Point2::new(0.0, 0.0),
+        Point2::new(4.0, 0.0),
+        Point2::new(4.0, 2.0),
+        Point2::new(2.0, 2.0),
+        Point2::new(2.0, 4.0),
+        Point2::new(0.0, 4.0),
+    ])
+}
+
+#[test]
+fn test_contains_point_in_concave_polygon() {
+    let l = l_shape();
+
+    assert!(l.contains_point(&Point2::new(1.0, 1.0)));
+    assert!(l.contains_point(&Point2::new(3.0, 1.0)));
+    assert!(l.contains_point(&Point2::new(1.0, 3.0)));
+    // In the notch, and level with the notch's corner
+    assert!(!l.contains_point(&Point2::new(3.0, 3.0)));
+    assert!(!l.contains_point(&Point2::new(3.0, 2.0 + 1e-9)));
+    assert!(!l.contains_point(&Point2::new(5.0, 1.0)));
+    assert!(!l.contains_point(&Point2::new(-1.0, 2.0)));
+}
+
+#[test]
+fn test_contains_point_on_edges_and_vertices() {
+    let l = l_shape();
+
+    for p in [
+        Point2::new(2.0, 0.0),
+        Point2::new(4.0, 1.0),
+        Point2::new(3.0, 2.0),
+        Point2::new(2.0, 3.0),
+        Point2::new(0.0, 0.0),
+        Point2::new(2.0, 2.0),
+        Point2::new(0.0, 4.0),
+    ] {
+        assert!(l.contains_point(&p), "{p} on the boundary should count as inside");
+    }
+}
+
+#[test]
+fn test_island_excludes_points_in_holes() {
+    let square = |min: f64, max: f64| common::rectangle([min, min], [max, max]);
+    let mut island = Island::new(square(0.0, 10.0));
+    island.holes.push(square(4.0, 6.0));
+
+    assert!(island.contains_point(&Point2::new(2.0, 2.0)));
+    assert!(island.contains_point(&Point2::new(0.0, 5.0)));
+    assert!(!island.contains_point(&Point2::new(5.0, 5.0)));
+    assert!(!island.contains_point(&Point2::new(4.0, 5.0)));
+    assert!(!island.contains_point(&Point2::new(11.0, 5.0)));
+}
+
+#[test]
+fn test_offset_square_outward_and_inward() {
+    let square = common::rectangle([0.0, 0.0], [10.0, 10.0]);
+
+    let grown = square.offset(1.0);
+    assert_eq!(grown.len(), 1);
+    assert!((grown[0].area() - 144.0).abs() < 1e-6);
+    assert!(!grown[0].is_clockwise());
+
+    let shrunk = square.offset(-2.0);
+    assert_eq!(shrunk.len(), 1);
+    assert!((shrunk[0].area() - 36.0).abs() < 1e-6);
+    assert!(shrunk[0].contains_point(&Point2::new(5.0, 5.0)));
+    assert!(!shrunk[0].contains_point(&Point2::new(1.0, 1.0)));
+
+    // Winding doesn't change the meaning of the sign
+    let mut reversed = square.clone();
+    reversed.reverse();
+    assert!((reversed.offset(1.0)[0].area() - 144.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_offset_l_shape() {
+    // 4x4 square minus its 2x2 upper right quarter: area 12
+    let l = l_shape();
+
+    let shrunk = l.offset(-0.5);
+    assert_eq!(shrunk.len(), 1);
+    // Arms 1 wide: 3x1 along the bottom plus 1x2 up the side
+    assert!((shrunk[0].area() - 5.0).abs() < 1e-6);
+
+    let grown = l.offset(0.5);
+    assert_eq!(grown.len(), 1);
+    // 5x5 minus the notch, which keeps its 2x2 size
+    assert!((grown[0].area() - 21.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_thin_rectangle_vanishes_when_inset_past_its_width() {
+    let strip = common::rectangle([0.0, 0.0], [10.0, 0.5]);
+
+    assert!(strip.offset(-0.4).is_empty());
+    assert_eq!(strip.offset(-0.2).len(), 1);
+}
+
+#[test]
+fn test_inset_splits_a_dumbbell() {
+    // Two 4x4 squares joined by a 1 mm wide bridge
+    let dumbbell = Polygon::new(vec![
+        Point2::new(0.0, 0.0),
+        Point2::new(4.0, 0.0),
+        Point2::new(4.0, 1.5),
+        Point2::new(8.0, 1.5),
+        Point2::new(8.0, 0.0),
+        Point2::new(12.0, 0.0),
+        Point2::new(12.0, 4.0),
+        Point2::new(8.0, 4.0),
+        Point2::new(8.0, 2.5),
+        Point2::new(4.0, 2.5),
+        Point2::new(4.0, 4.0),
+        Point2::new(0.0, 4.0),
+    ]);
+
+    let parts = dumbbell.offset(-0.6);
+    assert_eq!(parts.len(), 2);
+    for part in parts {
+        assert!((part.area() - 2.8 * 2.8).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_island_offset_moves_holes_the_other_way() {
+    let square = |min: f64, max: f64| common::rectangle([min, min], [max, max]);
+    let mut island = Island::new(square(0.0, 10.0));
+    island.holes.push(square(4.0, 6.0));
+
+    let shrunk = island.offset(-0.5);
+    assert_eq!(shrunk.len(), 1);
+    assert_eq!(shrunk[0].holes.len(), 1);
+    assert!((shrunk[0].outline.area() - 81.0).abs() < 1e-6);
+    assert!((shrunk[0].holes[0].area() - 9.0).abs() < 1e-6);
+
+    // Growing the island by more than the hole's half-width fills it
+    let grown = island.offset(1.5);
+    assert_eq!(grown.len(), 1);
+    assert!(grown[0].holes.is_empty());
+    assert!((grown[0].area() - 169.0).abs() < 1e-6);
+
+    // Insetting far enough to reach the hole leaves a ring too thin to keep
+    assert!(island.offset(-2.5).is_empty());
+}
+
+fn square_island(min: [f64; 2], size: f64) -> Island {
+    Island::new(common::square(min, size))
+}
+
+#[test]
+fn test_boolean_overlapping_squares() {
+    let a = [square_island([0.0, 0.0], 4.0)];
+    let b = [square_island([2.0, 2.0], 4.0)];
+
+    let merged = union(&a, &b);
+    assert_eq!(merged.len(), 1);
+    assert!((merged[0].area() - 28.0).abs() < 1e-9);
+    assert_eq!(merged[0].outline.len(), 8);
+
+    let common = intersection(&a, &b);
+    assert_eq!(common.len(), 1);
+    assert!((common[0].area() - 4.0).abs() < 1e-9);
+    assert!(common[0].contains_point(&Point2::new(3.0, 3.0)));
+
+    let rest = difference(&a, &b);
+    assert_eq!(rest.len(), 1);
+    assert!((rest[0].area() - 12.0).abs() < 1e-9);
+    assert!(!rest[0].contains_point(&Point2::new(3.0, 3.0)));
+}
+
+#[test]
+fn test_difference_of_centered_square_makes_a_hole() {
+    let result = difference(&[square_island([0.0, 0.0], 10.0)], &[square_island([3.0, 3.0], 4.0)]);
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].holes.len(), 1);
+    assert!(!result[0].outline.is_clockwise());
+    assert!(result[0].holes[0].is_clockwise());
+    assert!((result[0].area() - 84.0).abs() < 1e-9);
+
+    // Filling the hole back in restores the plain square
+    let filled = union(&result, &[square_island([3.0, 3.0], 4.0)]);
+    assert_eq!(filled.len(), 1);
+    assert!(filled[0].holes.is_empty());
+    assert_eq!(filled[0].outline.len(), 4);
+}
+
+#[test]
+fn test_boolean_disjoint_inputs() {
+    let a = [square_island([0.0, 0.0], 2.0)];
+    let b = [square_island([5.0, 0.0], 2.0)];
+
+    assert_eq!(union(&a, &b).len(), 2);
+    assert!(intersection(&a, &b).is_empty());
+    assert_eq!(difference(&a, &b), a.to_vec());
+    assert!(difference(&a, &a).is_empty());
+}
+
+#[test]
+fn test_union_merges_regions_touching_along_an_edge() {
+    let merged = union(&[square_island([0.0, 0.0], 2.0)], &[square_island([2.0, 0.0], 2.0)]);
+
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].outline.len(), 4);
+    assert!((merged[0].area() - 8.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_convex_hull_drops_interior_and_collinear_points() {
+    let points = [
+        Point2::new(0.0, 0.0),
+        Point2::new(1.0, 0.0),
+        Point2::new(2.0, 0.0),
+        Point2::new(2.0, 2.0),
+        Point2::new(1.0, 1.0),
+        Point2::new(0.0, 2.0),
+        Point2::new(2.0, 2.0),
+    ];
+    let hull = convex_hull(&points);
+
+    assert_eq!(hull.len(), 4);
+    assert!(!hull.is_clockwise());
+    assert_eq!(hull.area(), 4.0);
+}
+
+#[test]
+fn test_convex_hull_of_degenerate_input() {
+    assert!(convex_hull(&[]).is_empty());
+    assert_eq!(convex_hull(&[Point2::new(1.0, 1.0); 3]).len(), 1);
+
+    let line: Vec<Point2<f64>> = (0..5).map(|i| Point2::new(i as f64, 2.0 * i as f64)).collect();
+    let hull = convex_hull(&line);
+    assert_eq!(hull.points, vec![line[0], line[4]]);
+    assert_eq!(hull.area(), 0.0);
+}
+
+#[test]
+fn test_footprint_hull_of_sphere() {
+    let hull = common::sphere(5.0, 24, 12).footprint_hull();
+
+    assert_eq!(hull.len(), 24);
+    // Regular 24-gon inscribed in the equator
+    let expected = 12.0 * 25.0 * (2.0 * std::f64::consts::PI / 24.0).sin();
+    assert!((hull.area() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn test_centroid_is_independent_of_winding() {
+    let mut square = common::rectangle([0.0, 0.0], [2.0, 2.0]);
+    square.points.iter_mut().for_each(|p| *p += nalgebra::Vector2::new(3.0, 1.0));
+    assert_eq!(square.centroid(), Point2::new(4.0, 2.0));
+
+    square.reverse();
+    assert_eq!(square.centroid(), Point2::new(4.0, 2.0));
+    assert_eq!(square.perimeter(), 8.0);
+}
+
+#[test]
+fn test_centroid_of_concave_polygon() {
+    // Three 2x2 quarters centered at (1, 1), (3, 1) and (1, 3)
+    let centroid = l_shape().centroid();
+    assert!((centroid - Point2::new(5.0 / 3.0, 5.0 / 3.0)).norm() < 1e-12);
+    assert_eq!(l_shape().perimeter(), 16.0);
+    assert_eq!(l_shape().signed_area(), 12.0);
+}
+
+#[test]
+fn test_centroid_of_degenerate_polygon() {
+    let segment = Polygon::new(vec![Point2::new(0.0, 0.0), Point2::new(4.0, 2.0)]);
+
+    assert_eq!(segment.signed_area(), 0.0);
+    assert_eq!(segment.centroid(), Point2::new(2.0, 1.0));
+    // Out along the segment and back
+    assert!((segment.perimeter() - 2.0 * 20f64.sqrt()).abs() < 1e-12);
+    assert_eq!(Polygon::new(Vec::new()).centroid(), Point2::origin());
+}
+
+fn segment(a: (f64, f64), b: (f64, f64)) -> LineSegment2D {
+    LineSegment2D::new(Point2::new(a.0, a.1), Point2::new(b.0, b.1))
+}
+
+#[test]
+fn test_segment_intersection() {
+    let horizontal = segment((0.0, 0.0), (4.0, 0.0));
+
+    assert_eq!(horizontal.intersect(&segment((1.0, -1.0), (1.0, 1.0))), Some(Point2::new(1.0, 0.0)));
+    // Touching at an endpoint counts
+    assert_eq!(horizontal.intersect(&segment((4.0, 0.0), (5.0, 3.0))), Some(Point2::new(4.0, 0.0)));
+    assert_eq!(horizontal.intersect(&segment((2.0, 0.0), (2.0, 3.0))), Some(Point2::new(2.0, 0.0)));
+    // Missing, parallel, and collinear overlapping
+    assert_eq!(horizontal.intersect(&segment((5.0, -1.0), (5.0, 1.0))), None);
+    assert_eq!(horizontal.intersect(&segment((0.0, 1.0), (4.0, 1.0))), None);
+    assert_eq!(horizontal.intersect(&segment((2.0, 0.0), (6.0, 0.0))), None);
+}
+
+#[test]
+fn test_clip_segment_through_concave_island() {
+    let island = Island::new(l_shape());
+
+    // Across both arms of the L at y = 3 only the left arm is inside
+    let pieces = island.clip_segment(&segment((-1.0, 3.0), (5.0, 3.0)));
+    assert_eq!(pieces, vec![segment((0.0, 3.0), (2.0, 3.0))]);
+
+    // Starting inside
+    let pieces = island.clip_segment(&segment((1.0, 1.0), (5.0, 1.0)));
+    assert_eq!(pieces, vec![segment((1.0, 1.0), (4.0, 1.0))]);
+
+    // Through the concave corner at (2, 2) and on through the notch
+    let pieces = island.clip_segment(&segment((0.0, 0.0), (4.0, 4.0)));
+    assert_eq!(pieces, vec![segment((0.0, 0.0), (2.0, 2.0))]);
+}
+
+#[test]
+fn test_clip_segment_grazing_a_vertex() {
+    let diamond = Island::new(Polygon::new(vec![
+        Point2::new(0.0, -2.0),
+        Point2::new(2.0, 0.0),
+        Point2::new(0.0, 2.0),
+        Point2::new(-2.0, 0.0),
+    ]));
+
+    // Touching the top corner from outside
+    assert!(diamond.clip_segment(&segment((-3.0, 2.0), (3.0, 2.0))).is_empty());
+    // Through the side corners
+    let pieces = diamond.clip_segment(&segment((-3.0, 0.0), (3.0, 0.0)));
+    assert_eq!(pieces, vec![segment((-2.0, 0.0), (2.0, 0.0))]);
+}
+
+#[test]
+fn test_clip_segment_skips_holes() {
+    let mut island = Island::new(common::rectangle([0.0, 0.0], [10.0, 10.0]));
+    island.holes.push(Polygon::new(vec![
+        Point2::new(4.0, 4.0),
+        Point2::new(4.0, 6.0),
+        Point2::new(6.0, 6.0),
+        Point2::new(6.0, 4.0),
+    ]));
+
+    assert!(island.clip_segment(&segment((4.5, 5.0), (5.5, 5.0))).is_empty());
+
+    let pieces = island.clip_segment(&segment((-1.0, 5.0), (11.0, 5.0)));
+    assert_eq!(pieces, vec![segment((0.0, 5.0), (4.0, 5.0)), segment((6.0, 5.0), (10.0, 5.0))]);
+}
+
+#[test]
+fn test_resample_keeps_corners_and_area() {
+    let square = common::rectangle([0.0, 0.0], [100.0, 100.0]);
+    let resampled = square.resample(1.0);
+
+    assert_eq!(resampled.len(), 400);
+    assert!((resampled.area() - square.area()).abs() < 1e-9);
+    for corner in &square.points {
+        assert!(resampled.points.contains(corner));
+    }
+
+    let n = resampled.len();
+    let longest = (0..n)
+        .map(|i| (resampled.points[(i + 1) % n] - resampled.points[i]).norm())
+        .fold(0.0, f64::max);
+    assert!(longest <= 1.0 + 1e-12);
+
+    // Edges already short enough are left alone
+    assert_eq!(l_shape().resample(5.0), l_shape());
+}
+
+#[test]
+fn test_resample_uniform_spacing() {
+    let resampled = common::rectangle([0.0, 0.0], [4.0, 2.0]).resample_uniform(6);
+
+    assert_eq!(resampled.len(), 6);
+    let expected = [(0.0, 0.0), (2.0, 0.0), (4.0, 0.0), (4.0, 2.0), (2.0, 2.0), (0.0, 2.0)];
+    for (p, (x, y)) in resampled.points.iter().zip(expected) {
+        assert!((p - Point2::new(x, y)).norm() < 1e-12);
+    }
+}
+
+#[test]
+fn test_repeated_points_do_not_change_area_or_winding() {
+    let a = Point2::new(0.0, 0.0);
+    let (b, c) = (Point2::new(0.0, 1e-3), Point2::new(1e-3, 0.0));
+
+    // Clockwise sliver with its first vertex repeated three times
+    let mut triangle = Polygon::new(vec![a, a, a, b, c]);
+    assert!((triangle.area() - 5e-7).abs() < 1e-18);
+    assert!(triangle.is_clockwise());
+
+    triangle.dedup_points(1e-9);
+    assert_eq!(triangle.points, vec![a, b, c]);
+    assert!(triangle.is_clockwise());
+}
+
+#[test]
+fn test_dedup_drops_duplicated_closing_point() {
+    let mut points = common::rectangle([0.0, 0.0], [2.0, 1.0]).points;
+    points.push(points[0]);
+    points.push(points[0] + nalgebra::Vector2::new(1e-12, 0.0));
+
+    let cleaned = Polygon::new_cleaned(points, 1e-9);
+    assert_eq!(cleaned, common::rectangle([0.0, 0.0], [2.0, 1.0]));
+    assert_eq!(cleaned.signed_area(), 2.0);
+
+    // Nothing left to orient
+    let point = Polygon::new_cleaned(vec![Point2::origin(); 4], 1e-9);
+    assert_eq!(point.len(), 1);
+    assert_eq!(point.area(), 0.0);
+    assert!(!point.is_clockwise());
+}
+
+#[test]
+fn test_fit_arcs_finds_a_drilled_hole() {
+    let hole = circle(2.5, 64);
+    let elements = fit_arcs(&hole, 0.0125);
+
+    assert_eq!(elements.len(), 1);
+    let PathElement::Arc(arc) = elements[0] else {
+        panic!("expected an arc, got {:?}", elements[0]);
+    };
+    assert!(arc.is_full_circle());
+    assert!(!arc.clockwise);
+    assert!((arc.radius - 2.5).abs() < 1e-9);
+    assert!(arc.center.coords.norm() < 1e-9);
+    assert!((arc.start() - hole.points[0]).norm() < 1e-9);
+}
+
+#[test]
+fn test_fit_arcs_leaves_straight_edges_alone() {
+    let elements = fit_arcs(&l_shape(), 0.0125);
+
+    let mut expected = l_shape().points;
+    expected.push(expected[0]);
+    assert_eq!(elements, vec![PathElement::Line(expected)]);
+
+    // An octagon's corners lie on a circle, but its edges are far from it
+    assert!(fit_arcs(&circle(10.0, 8), 0.0125).iter().all(|e| matches!(e, PathElement::Line(_))));
+}
+
+#[test]
+fn test_fit_arcs_on_a_slot() {
+    // 10 mm straights joined by half circles of radius 2, clockwise
+    let half = |cx: f64, from: f64| {
+        (0..=16).map(move |i| {
+            let angle = from + std::f64::consts::PI * i as f64 / 16.0;
+            Point2::new(cx + 2.0 * angle.cos(), 2.0 * angle.sin())
+        })
+    };
+    let mut points: Vec<Point2<f64>> = half(10.0, -std::f64::consts::FRAC_PI_2).chain(half(0.0, std::f64::consts::FRAC_PI_2)).collect();
+    points.reverse();
+    let slot = Polygon::new(points);
+
+    let elements = fit_arcs(&slot, 0.0125);
+    let arcs: Vec<_> = elements
+        .iter()
+        .filter_map(|e| match e {
+            PathElement::Arc(arc) => Some(arc),
+            PathElement::Line(_) => None,
+        })
+        .collect();
+
+    assert_eq!(arcs.len(), 2);
+    for arc in arcs {
+        assert!(arc.clockwise);
+        assert!((arc.radius - 2.0).abs() < 1e-9);
+        assert!((arc.sweep() - std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    // The elements join up into the closed contour
+    let mut at = slot.points[0];
+    for element in &elements {
+        let (start, end) = match element {
+            PathElement::Line(points) => (points[0], points[points.len() - 1]),
+            PathElement::Arc(arc) => (arc.start(), arc.end()),
+        };
+        assert!((start - at).norm() < 1e-9);
+        at = end;
+    }
+    assert!((at - slot.points[0]).norm() < 1e-9);
+}
+
+#[test]
+fn test_closest_point_on_edge() {
+    let l = l_shape();
+
+    // On the edge from (4, 2) to (2, 2)
+    let (closest, edge, t) = l.closest_point(&Point2::new(3.0, 2.0));
+    assert_eq!((closest, edge, t), (Point2::new(3.0, 2.0), 2, 0.5));
+    assert_eq!(l.distance_to_point(&Point2::new(3.0, 2.0)), 0.0);
+
+    // Out in the notch
+    assert_eq!(l.closest_point(&Point2::new(3.0, 2.5)), (Point2::new(3.0, 2.0), 2, 0.5));
+    assert_eq!(l.closest_point(&Point2::new(2.5, 3.5)), (Point2::new(2.0, 3.5), 3, 0.75));
+    assert_eq!(l.distance_to_point(&Point2::new(3.0, 2.5)), 0.5);
+
+    // Below the bottom edge
+    assert_eq!(l.closest_point(&Point2::new(1.0, -2.0)), (Point2::new(1.0, 0.0), 0, 0.25));
+}
+
+#[test]
+fn test_island_signed_distance() {
+    let mut island = Island::new(common::rectangle([0.0, 0.0], [10.0, 10.0]));
+    island.holes.push(Polygon::new(vec![
+        Point2::new(4.0, 4.0),
+        Point2::new(4.0, 6.0),
+        Point2::new(6.0, 6.0),
+        Point2::new(6.0, 4.0),
+    ]));
+
+    assert_eq!(island.signed_distance(&Point2::new(1.0, 5.0)), -1.0);
+    assert_eq!(island.signed_distance(&Point2::new(3.0, 5.0)), -1.0);
+    assert_eq!(island.signed_distance(&Point2::new(5.0, 5.0)), 1.0);
+    assert_eq!(island.signed_distance(&Point2::new(12.0, 5.0)), 2.0);
+    assert_eq!(island.signed_distance(&Point2::new(10.0, 5.0)), 0.0);
+}
@@ -0,0 +1,1312 @@
+mod common;
+
+use nalgebra::Vector3;
+use rustslicer::config::{Modifier, ModifierShape, SlicerConfig, SupportBox};
+use nalgebra::{Point2, Point3, Vector2};
+use rustslicer::slicer::{build_contours, classify_infill, detect_overhangs, generate_supports, gap_fill, generate_brim, split_thin_walls, generate_perimeters, honeycomb_infill, infill_region, rectilinear_infill, ContourTree, Layer, LayerWarning, PerimeterKind, SliceEngine};
+use rustslicer::geometry::{Island, LineSegment, Mesh, Polygon};
+use rustslicer::toolpath::{PathRole, ToolPath};
+
+fn slice(mesh: Mesh, layer_height: f64) -> Vec<Layer> {
+    let config = SlicerConfig {
+        layer_height,
+        first_layer_height: layer_height,
+        ..SlicerConfig::default()
+    };
+    SliceEngine::new(mesh, config).unwrap().slice().unwrap()
+}
+
+fn layer_area(layer: &Layer) -> f64 {
+    layer.total_area()
+}
+
+#[test]
+fn test_z_sink_enlarges_first_layer() {
+    let mut mesh = common::sphere(10.0, 64, 32);
+    mesh.drop_to_bed();
+    let flat = slice(mesh.clone(), 0.2);
+
+    mesh.translate(Vector3::new(0.0, 0.0, -0.2));
+    let sunk = slice(mesh, 0.2);
+
+    assert!(sunk[0].z_height > 0.0);
+    assert!(layer_area(&sunk[0]) > layer_area(&flat[0]));
+}
+
+#[test]
+fn test_decimated_sphere_slices_match_original() {
+    const AREA_TOLERANCE: f64 = 0.05;
+
+    let original = common::sphere(10.0, 128, 64);
+    let mut decimated = original.clone();
+    decimated.decimate(4000);
+
+    assert!(decimated.triangles.len() <= 4000);
+    assert!(decimated.is_watertight());
+
+    let layers_a = slice(original, 0.5);
+    let layers_b = slice(decimated, 0.5);
+    assert_eq!(layers_a.len(), layers_b.len());
+
+    // Skip the polar caps where a tiny absolute change is a large fraction
+    for (a, b) in layers_a.iter().zip(&layers_b).skip(2).take(layers_a.len() - 4) {
+        let area_a = layer_area(a);
+        let area_b = layer_area(b);
+        assert!(
+            (area_a - area_b).abs() / area_a < AREA_TOLERANCE,
+            "layer at z={} differs: {} vs {}", a.z_height, area_a, area_b
+        );
+    }
+}
+
+#[test]
+fn test_layers_are_indexed_in_order() {
+    let layers = slice(common::cube(10.0), 0.5);
+
+    assert_eq!(layers.len(), 20);
+    for (i, layer) in layers.iter().enumerate() {
+        assert_eq!(layer.layer_index, i);
+        assert_eq!(layer.islands.len(), 1);
+        assert!((layer_area(layer) - 100.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_first_layer_height_is_honored() {
+    let engine = SliceEngine::new(common::cube(10.0), SlicerConfig::default()).unwrap();
+    let layers = engine.slice().unwrap();
+
+    assert_eq!(layers.len(), engine.layer_count());
+    assert!((layers[0].z_height - 0.3).abs() < 1e-9);
+    assert!((layers[1].z_height - 0.5).abs() < 1e-9);
+    assert!((layers[2].z_height - 0.7).abs() < 1e-9);
+    assert_eq!(layers[0].islands.len(), 1);
+}
+
+#[test]
+fn test_heights_map_to_the_nearest_layer() {
+    let engine = SliceEngine::new(common::cube(10.0), SlicerConfig::default()).unwrap();
+    let layers = engine.slice().unwrap();
+
+    // Tops at 0.3, 0.5, 0.7, ... 9.9 and 10.1
+    assert_eq!(engine.layer_at_height(0.0), Some(0));
+    assert_eq!(engine.layer_at_height(0.62), Some(2));
+    assert_eq!(engine.layer_at_height(0.58), Some(1));
+    assert_eq!(engine.layer_at_height(100.0), Some(layers.len() - 1));
+    for layer in &layers {
+        assert_eq!(engine.layer_at_height(layer.z_height), Some(layer.layer_index));
+    }
+}
+
+#[test]
+fn test_gear_layer_area_matches_outline() {
+    let outline = common::gear_outline(24, 18.0, 20.0);
+    let expected = common::shoelace_area(&outline);
+    let layers = slice(common::prism(&outline, Point2::origin(), 5.0), 0.5);
+
+    // Snapping to the micron grid moves each vertex by under a micron
+    for layer in &layers {
+        assert_eq!(layer.islands.len(), 1);
+        assert!((layer_area(layer) - expected).abs() < 1e-4 * expected);
+    }
+}
+
+#[test]
+fn test_stitching_shuffled_reversed_segments() {
+    let outline = common::gear_outline(36, 18.0, 20.0);
+    let n = outline.len();
+    let at = |p: Point2<f64>| Point3::new(p.x, p.y, 1.0);
+
+    // Deterministic shuffle, with every third segment reversed
+    let mut segments: Vec<LineSegment> = (0..n)
+        .map(|i| (i * 37) % n)
+        .map(|i| {
+            let (a, b) = (at(outline[i]), at(outline[(i + 1) % n]));
+            if i % 3 == 0 {
+                LineSegment { start: b, end: a }
+            } else {
+                LineSegment { start: a, end: b }
+            }
+        })
+        .collect();
+    segments.rotate_left(5);
+
+    let contours = build_contours(&segments, 1e-6).closed;
+    assert_eq!(contours.len(), 1);
+    assert_eq!(contours[0].len(), n);
+    assert!((contours[0].area() - common::shoelace_area(&outline)).abs() < 1e-9);
+}
+
+fn polyline(points: &[(f64, f64)]) -> Vec<LineSegment> {
+    points
+        .windows(2)
+        .map(|w| LineSegment {
+            start: Point3::new(w[0].0, w[0].1, 1.0),
+            end: Point3::new(w[1].0, w[1].1, 1.0),
+        })
+        .collect()
+}
+
+#[test]
+fn test_open_chain_is_grown_in_both_directions() {
+    // Starting from the middle segment must still recover the whole chain
+    let mut segments = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 5.0)]);
+    segments.swap(0, 2);
+
+    let stitched = build_contours(&segments, 1e-6);
+    assert!(stitched.closed.is_empty());
+    assert_eq!(stitched.open.len(), 1);
+    assert_eq!(stitched.open[0].len(), 5);
+}
+
+#[test]
+fn test_merge_tolerance_joins_nearby_endpoints() {
+    let segments = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.001)]);
+
+    assert_eq!(build_contours(&segments, 1e-6).open.len(), 1);
+
+    let stitched = build_contours(&segments, 0.01);
+    assert_eq!(stitched.closed.len(), 1);
+    assert!(stitched.open.is_empty());
+}
+
+#[test]
+fn test_small_gaps_are_closed_and_large_ones_reported() {
+    // A cube with one side face missing leaves a chain open by 10 mm per layer
+    let mut mesh = common::cube(10.0);
+    mesh.triangles.retain(|t| t.normal.x < 0.5);
+
+    let mut config = SlicerConfig { layer_height: 2.0, first_layer_height: 2.0, ..Default::default() };
+    let layers = SliceEngine::new(mesh.clone(), config.clone()).unwrap().slice().unwrap();
+    assert_eq!(layers.len(), 5);
+    for layer in &layers {
+        assert!(layer.islands.is_empty());
+        assert_eq!(layer.open_contours(), 1);
+        let LayerWarning::OpenContour { gap, .. } = layer.warnings[0] else {
+            panic!("expected an open contour warning, got {:?}", layer.warnings);
+        };
+        assert!((gap - 10.0).abs() < 1e-9);
+    }
+
+    config.max_gap_close = Some(10.0);
+    let layers = SliceEngine::new(mesh, config).unwrap().slice().unwrap();
+    for layer in &layers {
+        assert_eq!(layer.open_contours(), 0);
+        assert!((layer.islands[0].area() - 100.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_slice_can_be_cancelled_from_another_thread() {
+    use rustslicer::SlicerError;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    // Far too much work to finish before the flag is set
+    let config = SlicerConfig { layer_height: 0.001, first_layer_height: 0.001, ..Default::default() };
+    let engine = SliceEngine::new(common::sphere(50.0, 256, 128), config).unwrap();
+    let cancel = AtomicBool::new(false);
+
+    let start = Instant::now();
+    let result = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        engine.slice_cancellable(&cancel)
+    });
+
+    assert!(matches!(result, Err(SlicerError::Cancelled)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_slice_position_moves_the_first_layer_plane() {
+    use rustslicer::config::SlicePosition;
+
+    // Wedge with a 45° slope: its width at height z is 10 - z
+    let outline = [Point2::new(0.0, 0.0), Point2::new(10.0, 0.0), Point2::new(0.0, 10.0)];
+    let mut wedge = common::prism(&outline, Point2::new(10.0 / 3.0, 10.0 / 3.0), 10.0);
+    wedge.apply_transform(&nalgebra::Isometry3::rotation(Vector3::x() * std::f64::consts::FRAC_PI_2));
+    wedge.drop_to_bed();
+
+    let first_layer_width = |slice_position| {
+        let config = SlicerConfig { slice_position, ..Default::default() };
+        let layers = SliceEngine::new(wedge.clone(), config).unwrap().slice().unwrap();
+        assert!((layers[0].z_height - 0.3).abs() < 1e-9);
+        let xs = layers[0].islands[0].outline.points.iter().map(|p| p.x);
+        xs.clone().fold(f64::MIN, f64::max) - xs.fold(f64::MAX, f64::min)
+    };
+
+    let top = first_layer_width(SlicePosition::Top);
+    let middle = first_layer_width(SlicePosition::Middle);
+    assert!((top - 9.7).abs() < 1e-4);
+    assert!((middle - top - 0.15).abs() < 1e-4);
+}
+
+#[test]
+fn test_dense_sphere_has_no_open_contours() {
+    let mesh = common::sphere(20.0, 320, 160);
+    assert!(mesh.triangles.len() >= 100_000);
+
+    for layer in slice(mesh, 0.2) {
+        assert_eq!(layer.open_contours(), 0, "layer {} has open contours", layer.layer_index);
+        assert!(layer.islands.len() <= 1);
+    }
+}
+
+#[test]
+fn test_slice_range_is_dropped_to_the_bed() {
+    let config = SlicerConfig { layer_height: 0.5, first_layer_height: 0.3, ..Default::default() };
+    let engine = SliceEngine::new(common::sphere(10.0, 48, 24), config.clone()).unwrap();
+    let full = engine.slice().unwrap();
+    let range = engine.slice_range(10.0, 15.0).unwrap();
+
+    // 0.3 mm first layer, then 0.5 mm layers until the 5 mm slab is covered
+    assert_eq!(range.len(), 11);
+    assert!((range[0].z_height - 0.3).abs() < 1e-9);
+    assert!((range[10].z_height - 5.3).abs() < 1e-9);
+    for (i, layer) in range.iter().enumerate() {
+        assert_eq!(layer.layer_index, i);
+    }
+
+    // The range's first layer is the equator, the widest cross-section
+    let widest = full.iter().map(layer_area).fold(0.0, f64::max);
+    assert!(layer_area(&range[0]) > 0.99 * widest);
+    assert!(layer_area(&range[10]) < layer_area(&range[0]));
+}
+
+#[test]
+fn test_slice_range_rejects_bad_bounds() {
+    let engine = SliceEngine::new(common::cube(10.0), SlicerConfig::default()).unwrap();
+
+    for (z_min, z_max) in [(5.0, 2.0), (3.0, 3.0), (-1.0, 5.0), (5.0, 12.0)] {
+        assert!(
+            matches!(engine.slice_range(z_min, z_max), Err(rustslicer::SlicerError::InvalidParameter(_))),
+            "{}:{} was accepted", z_min, z_max
+        );
+    }
+}
+
+#[test]
+fn test_layers_iter_holds_a_bounded_window() {
+    let config = SlicerConfig { layer_height: 0.002, first_layer_height: 0.002, ..Default::default() };
+    let engine = SliceEngine::new(common::cube(10.0), config).unwrap();
+
+    let mut layers = engine.layers_iter().lookahead(8);
+    assert_eq!(layers.layer_count(), 5000);
+
+    let mut count = 0;
+    let mut previous_z = 0.0;
+    while let Some(layer) = layers.next() {
+        let layer = layer.unwrap();
+        assert!(layers.buffered() < 8, "{} layers buffered", layers.buffered());
+        assert_eq!(layer.layer_index, count);
+        assert!(layer.z_height > previous_z);
+        previous_z = layer.z_height;
+        count += 1;
+    }
+    assert_eq!(count, 5000);
+}
+
+#[test]
+fn test_coincident_cubes_give_a_single_island() {
+    let mesh = Mesh::merge(vec![common::cube(10.0), common::cube(10.0)]);
+
+    for layer in slice(mesh, 1.0) {
+        assert_eq!(layer.contour_count(), 1);
+        assert!((layer_area(&layer) - 100.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_internal_wall_between_touching_cubes_cancels() {
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 10.0]),
+        common::cuboid([10.0, 0.0, 0.0], [20.0, 10.0, 10.0]),
+    ]);
+
+    for layer in slice(mesh, 1.0) {
+        assert_eq!(layer.contour_count(), 1);
+        assert!((layer_area(&layer) - 200.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_slivers_below_min_feature_size_are_dropped() {
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 5.0]),
+        common::cuboid([20.0, 0.0, 0.0], [20.2, 0.2, 5.0]),
+    ]);
+
+    // The 0.2 mm sliver is below the default 0.4 mm nozzle square
+    for layer in slice(mesh.clone(), 1.0) {
+        assert_eq!(layer.islands.len(), 1);
+        assert!(matches!(
+            layer.warnings[..],
+            [LayerWarning::DroppedFeatures { islands: 1, holes: 0, min_area }] if (min_area - 0.16).abs() < 1e-12
+        ));
+    }
+
+    let config = SlicerConfig { layer_height: 1.0, first_layer_height: 1.0, min_feature_size: Some(0.0), ..Default::default() };
+    for layer in SliceEngine::new(mesh, config).unwrap().slice().unwrap() {
+        assert_eq!(layer.islands.len(), 2);
+        assert!(layer.warnings.is_empty());
+    }
+}
+
+#[test]
+fn test_round_holes_are_measured_and_others_counted() {
+    let round = common::tube(Point2::new(20.0, 20.0), 2.5, 10.0, 2.0, 64);
+    for layer in slice(round, 0.5) {
+        let holes = layer.hole_diameters(0.0125);
+        assert_eq!(holes.len(), 1, "layer {}", layer.layer_index);
+        assert!((holes[0].unwrap() - 5.0).abs() < 0.01, "layer {}: {:?}", layer.layer_index, holes);
+    }
+
+    let hexagonal = common::tube(Point2::new(20.0, 20.0), 2.5, 10.0, 2.0, 6);
+    for layer in slice(hexagonal, 0.5) {
+        assert_eq!(layer.hole_diameters(0.0125), [None]);
+    }
+}
+
+#[test]
+fn test_trailing_empty_layers_are_trimmed() {
+    use rustslicer::config::SlicePosition;
+
+    // The last layer spans 9.9 to 10.1 mm; its middle misses the 9.95 mm top
+    let config = SlicerConfig {
+        layer_height: 0.2,
+        first_layer_height: 0.1,
+        slice_position: SlicePosition::Middle,
+        ..Default::default()
+    };
+    let engine = SliceEngine::new(common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 9.95]), config).unwrap();
+    let layers = engine.slice().unwrap();
+
+    assert_eq!(layers.len(), engine.layer_count() - 1);
+    assert!(layers.iter().all(|layer| !layer.is_empty()));
+}
+
+#[test]
+fn test_trailing_layers_with_only_warnings_are_trimmed_and_reported() {
+    // Above the block only a sliver too small to print is left
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 5.0]),
+        common::cuboid([20.0, 0.0, 0.0], [20.2, 0.2, 8.0]),
+    ]);
+    let config = SlicerConfig { layer_height: 1.0, first_layer_height: 1.0, ..Default::default() };
+    let engine = SliceEngine::new(mesh, config).unwrap();
+    let mut layers = engine.layers_iter();
+    let printed: Vec<Layer> = layers.by_ref().collect::<Result<_, _>>().unwrap();
+
+    assert_eq!(printed.len(), 5);
+    assert!(printed.iter().all(|layer| !layer.is_empty()));
+    let trimmed: Vec<usize> = layers.trimmed().iter().map(|layer| layer.layer_index).collect();
+    assert_eq!(trimmed, [5, 6, 7]);
+    for layer in layers.trimmed() {
+        assert!(matches!(layer.warnings[..], [LayerWarning::DroppedFeatures { islands: 1, .. }]));
+    }
+}
+
+#[test]
+fn test_interior_empty_layers_are_kept_and_reported() {
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 2.0]),
+        common::cuboid([0.0, 0.0, 4.0], [10.0, 10.0, 6.0]),
+    ]);
+    let layers = slice(mesh, 0.5);
+
+    assert_eq!(layers.len(), 12);
+    let empty: Vec<usize> = layers.iter().filter(|l| l.is_empty()).map(|l| l.layer_index).collect();
+    assert_eq!(empty, [4, 5, 6, 7]);
+    for index in empty {
+        assert_eq!(layers[index].warnings, vec![LayerWarning::EmptyLayer]);
+    }
+}
+
+#[test]
+fn test_model_thinner_than_first_layer_gives_one_layer() {
+    let shim = common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 0.15]);
+    let layers = SliceEngine::new(shim, SlicerConfig::default()).unwrap().slice().unwrap();
+
+    assert_eq!(layers.len(), 1);
+    assert!((layers[0].z_height - 0.15).abs() < 1e-9);
+    assert!((layer_area(&layers[0]) - 100.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_layer_statistics_with_nested_holes() {
+    // A 10 mm frame with a 6 mm hole, an island inside the hole, and a
+    // separate island off to the side
+    let frame = Island { outline: common::square([0.0, 0.0], 10.0), holes: vec![common::square([2.0, 2.0], 6.0)] };
+    let inner = Island::new(common::square([4.0, 4.0], 2.0));
+    let side = Island { outline: common::square([20.0, -5.0], 4.0), holes: vec![common::square([21.0, -4.0], 1.0)] };
+    let layer = Layer {
+        z_height: 0.3,
+        layer_index: 0,
+        islands: vec![frame, inner, side],
+        warnings: Vec::new(),
+        stitched_points: 0,
+    };
+
+    assert!(!layer.is_empty());
+    assert_eq!(layer.island_count(), 3);
+    assert_eq!(layer.contour_count(), 5);
+    assert_eq!(layer.point_count(), 20);
+    assert!((layer.total_area() - (100.0 - 36.0 + 4.0 + 16.0 - 1.0)).abs() < 1e-12);
+    assert!((layer.perimeter_length() - (40.0 + 24.0 + 8.0 + 16.0 + 4.0)).abs() < 1e-12);
+
+    let bounds = layer.bounding_box().unwrap();
+    assert_eq!(bounds.min, Point2::new(0.0, -5.0));
+    assert_eq!(bounds.max, Point2::new(24.0, 10.0));
+
+    let empty = Layer { islands: Vec::new(), ..layer };
+    assert!(empty.is_empty());
+    assert_eq!(empty.bounding_box(), None);
+    assert_eq!(empty.total_area(), 0.0);
+}
+
+#[test]
+fn test_planes_through_grid_vertices_give_clean_layers() {
+    // A 10 mm cube built from 1 mm slabs puts vertices on every fourth plane
+    let slabs: Vec<Mesh> = (0..10)
+        .map(|k| common::cuboid([0.0, 0.0, k as f64], [10.0, 10.0, k as f64 + 1.0]))
+        .collect();
+    let layers = slice(Mesh::merge(slabs), 0.25);
+
+    assert_eq!(layers.len(), 40);
+    for (i, layer) in layers.iter().enumerate() {
+        assert_eq!(layer.z_height, 0.25 * (i + 1) as f64);
+        assert_eq!(layer.contour_count(), 1, "layer {}", i);
+        assert!(layer.warnings.is_empty(), "layer {}: {:?}", i, layer.warnings);
+        assert!((layer.total_area() - 100.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_planes_through_faces_give_clean_layers_without_plane_epsilon() {
+    // Every second plane lies in the faces between the slabs, and the last
+    // one in the top
+    let config = SlicerConfig { layer_height: 0.5, first_layer_height: 0.5, plane_epsilon: 0.0, ..Default::default() };
+    let slabs: Vec<Mesh> = (0..10)
+        .map(|k| common::cuboid([0.0, 0.0, k as f64], [10.0, 10.0, k as f64 + 1.0]))
+        .collect();
+    let layers = SliceEngine::new(Mesh::merge(slabs), config).unwrap().slice().unwrap();
+
+    assert_eq!(layers.len(), 20);
+    for (i, layer) in layers.iter().enumerate() {
+        assert!(layer.warnings.is_empty(), "layer {}: {:?}", i, layer.warnings);
+        assert!((layer.total_area() - 100.0).abs() < 1e-6, "layer {}", i);
+    }
+}
+
+#[test]
+fn test_layers_below_the_origin_are_reported_from_the_model_bottom() {
+    let mesh = common::cuboid([-5.0, -5.0, -10.0], [5.0, 5.0, -0.5]);
+    let engine = SliceEngine::new(mesh, SlicerConfig::default()).unwrap();
+    let layers = engine.slice().unwrap();
+
+    assert_eq!(layers.len(), engine.layer_count());
+    for (i, layer) in layers.iter().enumerate() {
+        let expected = 0.3 + 0.2 * i as f64;
+        assert!((layer.z_height - expected).abs() < 1e-9, "layer {} at {}", i, layer.z_height);
+        assert!((layer.total_area() - 100.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_thread_counts_produce_identical_layers() {
+    let config = SlicerConfig::default();
+    let mesh = Mesh::merge(vec![
+        common::sphere(10.0, 48, 24),
+        common::cuboid([15.0, 0.0, 0.0], [25.0, 10.0, 15.0]),
+    ]);
+
+    let areas = |threads: usize| -> Vec<f64> {
+        let engine = SliceEngine::new(mesh.clone(), config.clone()).unwrap().with_threads(threads).unwrap();
+        engine.slice().unwrap().iter().map(Layer::total_area).collect()
+    };
+
+    let sequential = areas(1);
+    assert_eq!(areas(0), sequential);
+    assert_eq!(areas(4), sequential);
+}
+
+#[test]
+fn test_washer_gives_one_island_with_one_hole() {
+    let washer = common::tube(Point2::origin(), 5.0, 10.0, 3.0, 64);
+
+    for layer in slice(washer, 0.5) {
+        assert_eq!(layer.island_count(), 1);
+        assert_eq!(layer.islands[0].holes.len(), 1);
+        assert!(layer.islands[0].outline.area() > layer.islands[0].holes[0].area());
+    }
+}
+
+#[test]
+fn test_separate_washers_keep_their_own_holes() {
+    let plate = Mesh::merge(vec![
+        common::tube(Point2::new(0.0, 0.0), 3.0, 6.0, 2.0, 48),
+        common::tube(Point2::new(20.0, 0.0), 3.0, 6.0, 2.0, 48),
+    ]);
+
+    for layer in slice(plate, 0.5) {
+        assert_eq!(layer.island_count(), 2);
+        for island in &layer.islands {
+            assert_eq!(island.holes.len(), 1);
+            let hole_x = island.holes[0].points[0].x;
+            let outline_x = island.outline.points[0].x;
+            assert!((hole_x - outline_x).abs() < 6.0, "hole attached to the wrong island");
+        }
+    }
+}
+
+#[test]
+fn test_tube_within_a_tube_nests_a_new_island() {
+    let tubes = Mesh::merge(vec![
+        common::tube(Point2::origin(), 8.0, 10.0, 4.0, 64),
+        common::tube(Point2::origin(), 3.0, 5.0, 4.0, 64),
+    ]);
+
+    for layer in slice(tubes, 0.5) {
+        assert_eq!(layer.island_count(), 2);
+        assert_eq!(layer.contour_count(), 4);
+        assert!(layer.islands.iter().all(|island| island.holes.len() == 1));
+    }
+}
+
+#[test]
+fn test_outlines_run_counter_clockwise_and_holes_clockwise() {
+    let washer = || common::tube(Point2::new(5.0, 5.0), 2.0, 4.0, 2.0, 32);
+
+    let mut mirrored = washer();
+    mirrored.apply_matrix(&nalgebra::Matrix4::new_nonuniform_scaling(&nalgebra::Vector3::new(-1.0, 1.0, 1.0)));
+
+    // Inside-out: faces wound the wrong way, so the stitcher builds every
+    // contour backwards
+    let mut inverted = washer();
+    for triangle in &mut inverted.triangles {
+        triangle.vertices.swap(1, 2);
+    }
+
+    for mesh in [washer(), mirrored, inverted] {
+        for layer in slice(mesh, 0.5) {
+            let island = &layer.islands[0];
+            assert!(island.outline.signed_area() > 0.0);
+            assert_eq!(island.holes.len(), 1);
+            assert!(island.holes[0].signed_area() < 0.0);
+        }
+    }
+}
+
+#[test]
+fn test_convex_hull_of_plus_shaped_layer() {
+    let plus = [
+        (1.0, -3.0), (1.0, -1.0), (3.0, -1.0), (3.0, 1.0), (1.0, 1.0), (1.0, 3.0),
+        (-1.0, 3.0), (-1.0, 1.0), (-3.0, 1.0), (-3.0, -1.0), (-1.0, -1.0), (-1.0, -3.0),
+    ]
+    .map(|(x, y)| Point2::new(x, y));
+    let layers = slice(common::prism(&plus, Point2::origin(), 2.0), 0.5);
+
+    let hull = layers[0].convex_hull();
+    // The 6x6 square with 2x2 right triangles cut off each corner
+    assert_eq!(hull.len(), 8);
+    assert!((hull.area() - 28.0).abs() < 1e-6);
+    assert!((layers[0].total_area() - 20.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_hairline_gaps_between_islands_are_closed() {
+    let squares = |gap: f64| {
+        Mesh::merge(vec![
+            common::cuboid([0.0, 0.0, 0.0], [10.0, 10.0, 2.0]),
+            common::cuboid([10.0 + gap, 0.0, 0.0], [20.0 + gap, 10.0, 2.0]),
+        ])
+    };
+
+    let config = SlicerConfig { layer_height: 0.5, first_layer_height: 0.5, close_gaps: Some(0.1), ..SlicerConfig::default() };
+    let slice_closed = |mesh| SliceEngine::new(mesh, config.clone()).unwrap().slice().unwrap();
+    for layer in slice_closed(squares(0.05)) {
+        assert_eq!(layer.island_count(), 1);
+        assert!(layer.islands[0].holes.is_empty());
+        assert!((layer.total_area() - 200.5).abs() < 1e-6);
+    }
+    for layer in slice_closed(squares(1.0)) {
+        assert_eq!(layer.island_count(), 2);
+        assert!((layer.total_area() - 200.0).abs() < 1e-6);
+    }
+
+    // Off by default
+    assert!(slice(squares(0.05), 0.5).iter().all(|layer| layer.island_count() == 2));
+}
+
+#[test]
+fn test_contour_tree_records_nesting() {
+    // Three concentric squares, and a fourth beside them
+    let tree = ContourTree::new(vec![
+        common::square([4.0, 4.0], 2.0),
+        common::square([20.0, 0.0], 4.0),
+        common::square([0.0, 0.0], 10.0),
+        common::square([2.0, 2.0], 6.0),
+    ]);
+
+    let depths: Vec<usize> = tree.nodes().iter().map(|node| node.depth).collect();
+    assert_eq!(depths, [0, 1, 0, 2]);
+    assert_eq!(tree.roots().collect::<Vec<_>>(), [0, 2]);
+    assert_eq!(tree.nodes()[0].children, [1]);
+    assert_eq!(tree.nodes()[3].parent, Some(1));
+    assert_eq!(tree.nodes()[0].area, 100.0);
+
+    // The innermost square is an island of its own inside the hole
+    let islands = tree.into_islands();
+    assert_eq!(islands.len(), 3);
+    assert_eq!(islands[0].holes.len(), 1);
+}
+
+#[test]
+fn test_perimeters_are_concentric_insets() {
+    let island = Island::new(common::square([0.0, 0.0], 20.0));
+    let loops = generate_perimeters(&island, 3, 0.4);
+
+    assert_eq!(loops.len(), 3);
+    for (i, (perimeter, side)) in loops.iter().zip([19.6, 18.8, 18.0]).enumerate() {
+        assert_eq!(perimeter.index, i);
+        assert_eq!(perimeter.kind == PerimeterKind::External, i == 0);
+        assert!((perimeter.polygon.perimeter() - 4.0 * side).abs() < 1e-6);
+        assert!((perimeter.polygon.area() - side * side).abs() < 1e-6);
+        assert!((perimeter.polygon.centroid() - Point2::new(10.0, 10.0)).norm() < 1e-9);
+    }
+}
+
+#[test]
+fn test_perimeters_surround_holes_and_stop_when_collapsed() {
+    // 1 mm wide ring: room for one wall on each side, not two
+    let mut island = Island::new(common::square([0.0, 0.0], 10.0));
+    island.holes.push(common::square([1.0, 1.0], 8.0));
+
+    let loops = generate_perimeters(&island, 3, 0.4);
+    assert_eq!(loops.len(), 2);
+    assert!(loops.iter().all(|perimeter| perimeter.kind == PerimeterKind::External));
+    assert!((loops[0].polygon.area() - 9.6 * 9.6).abs() < 1e-6);
+    assert!((loops[1].polygon.area() - 8.4 * 8.4).abs() < 1e-6);
+}
+
+#[test]
+fn test_rectilinear_infill_spacing_and_zigzag() {
+    // Two walls of 0.4 mm with 0.1 mm overlap leave 0.7..39.3 to fill
+    let island = Island::new(common::square([0.0, 0.0], 40.0));
+    let region = infill_region(&island, 2, 0.4, 0.1);
+    assert_eq!(region.len(), 1);
+    assert!((region[0].area() - 38.6 * 38.6).abs() < 1e-6);
+
+    // 20 % density: lines 0.4 / 0.2 = 2 mm apart, linked into one zigzag
+    let paths = rectilinear_infill(&region, 2.0, 0.0);
+    assert_eq!(paths.len(), 1);
+    let passes: Vec<(Point2<f64>, Point2<f64>)> = paths[0].chunks(2).map(|pass| (pass[0], pass[1])).collect();
+    assert_eq!(passes.len(), 19);
+    for (i, (start, end)) in passes.iter().enumerate() {
+        assert!((start.y - 2.0 * (i + 1) as f64).abs() < 1e-9);
+        assert_eq!(start.y, end.y);
+        assert!(((end.x - start.x).abs() - 38.6).abs() < 1e-6);
+        // Every other pass runs back
+        assert_eq!(end.x > start.x, i % 2 == 0);
+    }
+}
+
+#[test]
+fn test_infill_overlap_is_clamped_to_outer_wall_middle() {
+    let island = Island::new(common::square([0.0, 0.0], 10.0));
+    // One wall and an overlap of a whole line width would reach the surface
+    let region = infill_region(&island, 1, 0.4, 0.4);
+    assert_eq!(region.len(), 1);
+    assert!((region[0].area() - 9.6 * 9.6).abs() < 1e-6);
+}
+
+#[test]
+fn test_rectilinear_infill_does_not_link_across_holes() {
+    let island = Island { outline: common::square([0.0, 0.0], 20.0), holes: vec![common::square([5.0, 5.0], 10.0)] };
+    let paths = rectilinear_infill(std::slice::from_ref(&island), 1.0, 30.0);
+    assert!(paths.len() > 1);
+
+    for path in &paths {
+        for pair in path.windows(2) {
+            let midpoint = nalgebra::center(&pair[0], &pair[1]);
+            // Links may run along the boundary
+            assert!(island.signed_distance(&midpoint) < 1e-9, "{:?} leaves the region", pair);
+        }
+    }
+}
+
+fn path_length(paths: &[Vec<Point2<f64>>]) -> f64 {
+    paths.iter().flat_map(|path| path.windows(2)).map(|pair| (pair[1] - pair[0]).norm()).sum()
+}
+
+#[test]
+fn test_honeycomb_covers_requested_density() {
+    let region = [Island::new(common::square([0.0, 0.0], 100.0))];
+    for density in [0.1, 0.2, 0.5] {
+        let paths = honeycomb_infill(&region, 0.4 / density, false);
+        let fraction = path_length(&paths) * 0.4 / region[0].area();
+        assert!((fraction - density).abs() < density * 0.03, "{} for {}", fraction, density);
+
+        // Merged into polylines that stay inside the region. Every corner
+        // joins three walls, so paths average at best three walls each
+        let segments: usize = paths.iter().map(|path| path.len() - 1).sum();
+        assert!(paths.len() * 2 < segments);
+        for path in &paths {
+            assert!(path.iter().all(|p| region[0].signed_distance(p) < 1e-9));
+        }
+    }
+}
+
+#[test]
+fn test_honeycomb_shift_moves_walls_half_a_cell() {
+    let region = [Island::new(common::square([0.0, 0.0], 30.0))];
+    // Midpoints of the whole vertical walls
+    let vertical_walls = |shifted| -> Vec<Point2<f64>> {
+        honeycomb_infill(&region, 2.0, shifted)
+            .iter()
+            .flat_map(|path| path.windows(2))
+            .filter(|pair| (pair[0].x - pair[1].x).abs() < 1e-9 && ((pair[0].y - pair[1].y).abs() - 4.0 / 3f64.sqrt()).abs() < 1e-6)
+            .map(|pair| nalgebra::center(&pair[0], &pair[1]))
+            .collect()
+    };
+    let (even, odd) = (vertical_walls(false), vertical_walls(true));
+    assert!(even.len() > 20 && odd.len() > 20);
+
+    // Cells 4 mm across: every shifted wall sits 2 mm beside an unshifted one
+    for wall in &odd {
+        assert!(even.iter().all(|other| (other - wall).norm() > 1.0));
+        assert!(even.iter().any(|other| ((other - wall).norm() - 2.0).abs() < 1e-6 && (other.y - wall.y).abs() < 1e-6));
+    }
+}
+
+#[test]
+fn test_skin_covers_exposed_step_tops() {
+    // Three 10 mm steps, 1 mm apart
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        top_solid_layers: Some(2),
+        bottom_solid_layers: Some(2),
+        ..SlicerConfig::default()
+    };
+    let engine = SliceEngine::new(common::staircase(3, 10.0, 10.0, 1.0), config.clone()).unwrap();
+    let layers = engine.slice().unwrap();
+    assert_eq!(layers.len(), 15);
+    let classes = classify_infill(&layers, &config);
+
+    let area = |islands: &[Island]| islands.iter().map(Island::area).sum::<f64>();
+    // X range of the solid skin on a layer, if any
+    let solid_span = |i: usize| {
+        let solid: Vec<&Island> = classes[i].iter().flat_map(|regions| &regions.solid).collect();
+        let bounds = solid.iter().filter_map(|island| island.outline.bounds()).reduce(|a, b| a.union(&b))?;
+        Some((bounds.min.x, bounds.max.x))
+    };
+    let near = |span: Option<(f64, f64)>, from: f64, to: f64| {
+        let (min, max) = span.unwrap();
+        (min - from).abs() < 1.0 && (max - to).abs() < 1.0
+    };
+
+    // Bottom and top layers are solid throughout
+    for i in [0, 1, 13, 14] {
+        assert!(classes[i].iter().all(|regions| regions.sparse.is_empty()), "layer {}", i);
+    }
+    // The two layers under each step's top are solid over that step only
+    for (layers, step) in [([3, 4], 0.0), ([8, 9], 10.0)] {
+        for i in layers {
+            assert!(near(solid_span(i), step, step + 10.0), "layer {}: {:?}", i, solid_span(i));
+            let sparse: f64 = classes[i].iter().map(|regions| area(&regions.sparse)).sum();
+            assert!(sparse > 50.0, "layer {}", i);
+        }
+    }
+    // Layers in between have no skin
+    for i in [2, 6, 7, 11] {
+        assert_eq!(solid_span(i), None, "layer {}", i);
+    }
+}
+
+#[test]
+fn test_gap_fill_runs_between_walls_of_thin_rib() {
+    // 1 mm rib: the outer wall leaves a 0.2 mm sliver, too thin for a second
+    let rib = Island::new(Polygon::new(vec![
+        Point2::new(0.0, 0.0),
+        Point2::new(10.0, 0.0),
+        Point2::new(10.0, 1.0),
+        Point2::new(0.0, 1.0),
+    ]));
+    assert_eq!(generate_perimeters(&rib, 2, 0.4).len(), 1);
+
+    let paths = gap_fill(&rib, 2, 0.4);
+    assert_eq!(paths.len(), 1);
+    let path = &paths[0];
+    assert_eq!(path.kind, PerimeterKind::GapFill);
+    assert!(path.points.iter().all(|p| (p.y - 0.5).abs() < 1e-6));
+    let (first, last) = (path.points[0].x.min(path.points[path.points.len() - 1].x), path.points[0].x.max(path.points[path.points.len() - 1].x));
+    assert!(first < 0.6 && last > 9.4, "{} to {}", first, last);
+    assert!(path.widths.iter().all(|w| (w - 0.2).abs() < 1e-6));
+
+    // Walls that fit leave no gap
+    assert!(gap_fill(&Island::new(common::square([0.0, 0.0], 10.0)), 2, 0.4).is_empty());
+}
+
+#[test]
+fn test_thin_rib_becomes_single_centerline() {
+    // A 4 mm block with a 0.5 mm rib sticking out of it
+    let island = Island::new(Polygon::new(vec![
+        Point2::new(0.0, 0.0),
+        Point2::new(4.0, 0.0),
+        Point2::new(4.0, 1.75),
+        Point2::new(14.0, 1.75),
+        Point2::new(14.0, 2.25),
+        Point2::new(4.0, 2.25),
+        Point2::new(4.0, 4.0),
+        Point2::new(0.0, 4.0),
+    ]));
+    let (thick, walls) = split_thin_walls(&island, 0.4);
+
+    assert_eq!(thick.len(), 1);
+    assert!((thick[0].area() - 16.0).abs() < 1e-3);
+    assert_eq!(walls.len(), 1);
+    let wall = &walls[0];
+    assert_eq!(wall.kind, PerimeterKind::External);
+    assert!(wall.points.iter().all(|p| (p.y - 2.0).abs() < 1e-6 && p.x > 4.0));
+    assert!(wall.widths.iter().all(|w| (w - 0.5).abs() < 1e-6));
+
+    // Nothing thin to split off
+    let (thick, walls) = split_thin_walls(&Island::new(common::square([0.0, 0.0], 10.0)), 0.4);
+    assert_eq!(thick, vec![Island::new(common::square([0.0, 0.0], 10.0))]);
+    assert!(walls.is_empty());
+}
+
+#[test]
+fn test_brim_loops_cover_width_and_merge_between_islands() {
+    // 2 mm of 0.4 mm lines
+    let single = generate_brim(&[Island::new(common::square([0.0, 0.0], 10.0))], 2.0, 0.4, true);
+    assert_eq!(single.len(), 5);
+    for (i, polygon) in single.iter().enumerate() {
+        let side = 10.0 + 2.0 * (0.2 + 0.4 * i as f64);
+        assert!((polygon.perimeter() - 4.0 * side).abs() < 1e-6);
+    }
+
+    // Squares 1 mm apart: the first loops are separate, the rest merge
+    let pair = [Island::new(common::square([0.0, 0.0], 10.0)), Island::new(common::square([11.0, 0.0], 10.0))];
+    let loops = generate_brim(&pair, 2.0, 0.4, true);
+    assert_eq!(loops.len(), 6);
+    for (i, a) in loops.iter().enumerate() {
+        for b in &loops[i + 1..] {
+            assert!(a.points.iter().all(|p| b.distance_to_point(p) > 0.4 - 1e-6));
+        }
+    }
+}
+
+#[test]
+fn test_brim_goes_inside_holes_unless_only_outside() {
+    let frame = Island { outline: common::square([0.0, 0.0], 20.0), holes: vec![common::square([5.0, 5.0], 10.0)] };
+    assert_eq!(generate_brim(std::slice::from_ref(&frame), 1.2, 0.4, true).len(), 3);
+
+    let loops = generate_brim(std::slice::from_ref(&frame), 1.2, 0.4, false);
+    assert_eq!(loops.len(), 6);
+    assert_eq!(loops.iter().filter(|polygon| polygon.is_clockwise()).count(), 3);
+}
+
+#[test]
+fn test_brim_counts_towards_build_volume() {
+    let config = SlicerConfig { brim_width: 5.0, ..SlicerConfig::default() };
+    let engine = SliceEngine::new(common::cuboid([2.0, 10.0, 0.0], [20.0, 20.0, 5.0]), config).unwrap();
+    assert!(engine.check_fit([220.0, 220.0, 250.0]).unwrap_err().to_string().contains("X by 3.00 mm"));
+
+    let engine = SliceEngine::new(common::cuboid([2.0, 10.0, 0.0], [20.0, 20.0, 5.0]), SlicerConfig::default()).unwrap();
+    assert!(engine.check_fit([220.0, 220.0, 250.0]).is_ok());
+}
+
+#[test]
+fn test_overhang_threshold_on_wedge() {
+    // 10 mm tall block whose side leans out at 45°
+    let report = |overhang_angle| {
+        let config = SlicerConfig { overhang_angle, first_layer_height: 0.2, ..SlicerConfig::default() };
+        let layers = SliceEngine::new(common::wedge(10.0, 10.0, 10.0, 45.0), config.clone()).unwrap().slice().unwrap();
+        detect_overhangs(&layers, &config)
+    };
+
+    assert_eq!(report(45.0).total_area(), 0.0);
+    assert!(!report(45.0).needs_support());
+
+    // At 30° each 0.2 mm layer sticks out 0.085 mm further than allowed
+    let strict = report(30.0);
+    assert!(strict.needs_support());
+    assert_eq!(strict.layers.len(), 49);
+    let layer = strict.get(10).unwrap();
+    let expected = 0.2 * (1.0 - 30f64.to_radians().tan()) * 10.0;
+    assert!((layer.area - expected).abs() < 1e-3, "{} vs {}", layer.area, expected);
+    assert_eq!(strict.worst_layers(3).len(), 3);
+}
+
+#[test]
+fn test_supports_hold_up_tee_arms_only() {
+    // Crossbar from 8.1 to 10.1 mm: its first layer is layer 40 at Z 8.2
+    let config = SlicerConfig { first_layer_height: 0.2, support_material: true, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::tee(10.0, 2.0, 4.0, 8.1), config.clone()).unwrap().slice().unwrap();
+    assert_eq!(layers[40].z_height, 8.2);
+    let supports = generate_supports(&layers, &config);
+    assert_eq!(supports.len(), layers.len());
+
+    // Columns from the bed up to a layer short of the arms, 0.6 mm clear of
+    // the trunk and each as wide as the arm less what it bridges itself
+    for (i, support) in supports.iter().enumerate() {
+        if i >= 39 {
+            assert!(support.is_empty(), "layer {}", i);
+            continue;
+        }
+        let columns: Vec<&Island> = support.regions().collect();
+        assert_eq!(columns.len(), 2, "layer {}", i);
+        let area: f64 = columns.iter().map(|column| column.area()).sum();
+        assert!((area - 2.0 * 3.4 * 4.0).abs() < 1e-3, "layer {}: {}", i, area);
+        for column in columns {
+            let bounds = column.outline.bounds().unwrap();
+            assert!(bounds.max.x <= 3.4 + 1e-6 || bounds.min.x >= 6.6 - 1e-6, "layer {}: {:?}", i, bounds);
+        }
+    }
+}
+
+#[test]
+fn test_support_interface_under_tee_arms() {
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        support_material: true,
+        support_interface_layers: 2,
+        ..SlicerConfig::default()
+    };
+    let layers = SliceEngine::new(common::tee(10.0, 2.0, 4.0, 8.1), config.clone()).unwrap().slice().unwrap();
+    let supports = generate_supports(&layers, &config);
+
+    // Two layers of interface under the arms, past the one layer Z gap
+    for (i, support) in supports.iter().enumerate().take(39) {
+        let interface: f64 = support.interface.iter().map(Island::area).sum();
+        let base: f64 = support.base.iter().map(Island::area).sum();
+        if i >= 37 {
+            assert!((interface - 27.2).abs() < 1e-3 && base < 1e-9, "layer {}", i);
+        } else {
+            assert!(interface < 1e-9 && (base - 27.2).abs() < 1e-3, "layer {}", i);
+        }
+    }
+}
+
+#[test]
+fn test_support_blocker_clears_one_tee_arm() {
+    let config = SlicerConfig {
+        first_layer_height: 0.2,
+        support_material: true,
+        support_blockers: vec![SupportBox { min: [0.0, 0.0, 0.0], max: [4.0, 4.0, 20.0] }],
+        ..SlicerConfig::default()
+    };
+    let layers = SliceEngine::new(common::tee(10.0, 2.0, 4.0, 8.1), config.clone()).unwrap().slice().unwrap();
+    let supports = generate_supports(&layers, &config);
+
+    // Only the right arm's column is left, and the left one is accounted for
+    for (i, support) in supports.iter().enumerate().take(39) {
+        let columns: Vec<&Island> = support.regions().collect();
+        assert_eq!(columns.len(), 1, "layer {}", i);
+        assert!(columns[0].outline.bounds().unwrap().min.x >= 6.6 - 1e-6, "layer {}", i);
+    }
+    let blocked = supports.iter().fold(0.0, |sum, layer| sum + layer.blocked_volume);
+    let expected = 3.4 * 4.0 * layers[38].z_height;
+    assert!((blocked - expected).abs() < 1e-3, "{} vs {}", blocked, expected);
+}
+
+#[test]
+fn test_support_enforcer_holds_up_self_supporting_wedge() {
+    // A 30° lean needs no support at the default 45° threshold
+    let supports = |support_enforcers| {
+        let config = SlicerConfig { first_layer_height: 0.2, support_enforcers, ..SlicerConfig::default() };
+        let layers = SliceEngine::new(common::wedge(10.0, 10.0, 10.0, 30.0), config.clone()).unwrap().slice().unwrap();
+        generate_supports(&layers, &config)
+    };
+    assert!(supports(Vec::new()).iter().all(|support| support.is_empty()));
+
+    // Forced under the leaning side, outside the block's 10 mm base
+    let enforced = supports(vec![SupportBox { min: [10.0, 0.0, 0.0], max: [20.0, 10.0, 20.0] }]);
+    assert!(enforced[0].regions().next().is_some());
+    for support in &enforced {
+        for region in support.regions() {
+            let bounds = region.outline.bounds().unwrap();
+            assert!(bounds.min.x >= 10.6 - 1e-6, "{:?}", bounds);
+        }
+    }
+}
+
+#[test]
+fn test_table_top_bridges_leg_to_leg() {
+    // Top from 5.1 mm: its first layer is layer 25 at Z 5.2
+    let config = SlicerConfig { first_layer_height: 0.2, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::table(20.0, 10.0, 3.0, 5.1, 2.0), config.clone()).unwrap().slice().unwrap();
+    assert!((layers[25].z_height - 5.2).abs() < 1e-9);
+    let infill = classify_infill(&layers, &config);
+
+    let bridges = &infill[25][0].bridges;
+    assert_eq!(bridges.len(), 1);
+    assert_eq!(bridges[0].angle, 0.0);
+    let bounds = bridges[0].region.outline.bounds().unwrap();
+    assert!((bounds.min.x - 3.0).abs() < 1e-6 && (bounds.max.x - 17.0).abs() < 1e-6, "{:?}", bounds);
+
+    // Only the first layer over the gap bridges
+    assert!(infill[26][0].bridges.is_empty());
+    assert!(infill[24].iter().all(|regions| regions.bridges.is_empty()));
+}
+
+#[test]
+fn test_generate_paths_chains_travel_and_extrusion() {
+    let config = SlicerConfig { first_layer_height: 0.2, ..SlicerConfig::default() };
+    let engine = SliceEngine::new(common::cube(10.0), config).unwrap();
+    let layers = engine.generate_paths().unwrap();
+    assert_eq!(layers.len(), engine.layer_count());
+    assert!((layers[0].z - 0.2).abs() < 1e-9);
+
+    let mut position = Point2::origin();
+    for (index, layer) in layers.iter().enumerate() {
+        assert_eq!(layer.layer_index, index);
+        // Each extrusion starts where the travel before it ends, and each
+        // travel where the last extrusion left off
+        for pair in layer.paths.chunks(2) {
+            let [ToolPath::Travel { from, to }, ToolPath::Extrude { points, width, .. }] = pair else {
+                panic!("{:?}", pair);
+            };
+            assert_eq!((*from, *to), (position, points[0]));
+            assert!((width - 0.4).abs() < 1e-9);
+            position = *points.last().unwrap();
+        }
+    }
+
+    let roles = |index: usize| {
+        let mut roles: Vec<PathRole> = layers[index].paths.iter().filter_map(ToolPath::role).collect();
+        roles.dedup();
+        roles
+    };
+    // Walls inside out, then the skin or the sparse infill
+    assert_eq!(roles(0), [PathRole::Perimeter, PathRole::ExternalPerimeter, PathRole::SolidInfill]);
+    assert_eq!(roles(25), [PathRole::Perimeter, PathRole::ExternalPerimeter, PathRole::Infill]);
+}
+
+#[test]
+fn test_modifiers_set_infill_density_in_their_corner() {
+    let modifier = |max: f64, infill_density: u8| Modifier {
+        shape: ModifierShape::Box,
+        min: [0.0, 0.0, 0.0],
+        max: [max, max, 10.0],
+        infill_density: Some(infill_density),
+        perimeters: None,
+    };
+    let config = SlicerConfig {
+        infill_percentage: 20,
+        infill_angle: 0.0,
+        infill_angle_increment: 0.0,
+        // The later modifier wins where the two overlap
+        modifiers: vec![modifier(8.0, 80), modifier(4.0, 40)],
+        ..SlicerConfig::default()
+    };
+    let engine = SliceEngine::new(common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 10.0]), config).unwrap();
+    let layers = engine.generate_paths().unwrap();
+
+    // Closest spacing between the rows of infill lines running along X
+    // with their middle in `area`
+    let spacing = |area: &dyn Fn(Point2<f64>) -> bool| {
+        let mut rows: Vec<f64> = layers[20]
+            .paths
+            .iter()
+            .filter_map(|path| match path {
+                ToolPath::Extrude { points, role: PathRole::Infill, .. } => Some(points),
+                _ => None,
+            })
+            .flat_map(|points| points.windows(2).map(|pair| (pair[0], pair[1])))
+            .filter(|(a, b)| (b.y - a.y).abs() < 1e-9 && (b.x - a.x).abs() > 0.5 && area(nalgebra::center(a, b)))
+            .map(|(a, _)| a.y)
+            .collect();
+        rows.sort_by(f64::total_cmp);
+        rows.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+        rows.windows(2).map(|pair| pair[1] - pair[0]).fold(f64::INFINITY, f64::min)
+    };
+
+    assert!((spacing(&|p| p.x > 9.0 || p.y > 9.0) - 2.0).abs() < 1e-6);
+    assert!((spacing(&|p| p.x > 4.5 && p.x < 7.5 && p.y < 7.5) - 0.5).abs() < 1e-6);
+    assert!((spacing(&|p| p.x < 3.5 && p.y < 3.5) - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_elephant_foot_compensation_insets_first_layer_only() {
+    let config = SlicerConfig { elephant_foot_compensation: 0.3, ..SlicerConfig::default() };
+    let mesh = Mesh::merge(vec![
+        common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 2.0]),
+        // Too thin for the inset, so kept a little smaller
+        common::cuboid([30.0, 0.0, 0.0], [30.5, 0.5, 2.0]),
+    ]);
+    let engine = SliceEngine::new(mesh, config).unwrap();
+    let layers = engine.generate_paths().unwrap();
+
+    // Width of the square's external perimeter on a layer
+    let square_width = |index: usize| {
+        let points: Vec<Point2<f64>> = layers[index]
+            .paths
+            .iter()
+            .filter_map(|path| match path {
+                ToolPath::Extrude { points, role: PathRole::ExternalPerimeter, .. } if points[0].x < 25.0 => Some(points),
+                _ => None,
+            })
+            .flatten()
+            .copied()
+            .collect();
+        let (min, max) = points.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), p| (min.min(p.x), max.max(p.x)));
+        max - min
+    };
+    assert!((square_width(1) - square_width(0) - 0.6).abs() < 1e-6, "{} {}", square_width(0), square_width(1));
+    assert!((square_width(1) - square_width(2)).abs() < 1e-9);
+
+    let sliced = engine.slice().unwrap();
+    assert_eq!(sliced[0].islands.len(), 2);
+    assert_eq!(sliced[0].warnings, vec![LayerWarning::KeptSmallFeatures { islands: 1, inset: 0.3 }]);
+    assert!(sliced[1].warnings.is_empty());
+}
+
+#[test]
+fn test_hole_and_xy_compensation_compose() {
+    let center = Point2::new(10.0, 10.0);
+    let diameters = |xy_compensation: f64, hole_compensation: f64| {
+        let config = SlicerConfig { xy_compensation, hole_compensation, ..SlicerConfig::default() };
+        let plate = common::tube(center, 2.5, 8.0, 2.0, 64);
+        let layers = SliceEngine::new(plate, config).unwrap().slice().unwrap();
+        let island = &layers[3].islands[0];
+        // Average diameter of a polygon around the center
+        let diameter = |polygon: &Polygon| {
+            let sum = polygon.points.iter().fold(0.0, |sum, p| sum + (p - center).norm());
+            2.0 * sum / polygon.len() as f64
+        };
+        assert_eq!(island.holes.len(), 1);
+        (diameter(&island.outline), diameter(&island.holes[0]))
+    };
+
+    let close = |(a, b): (f64, f64), (c, d): (f64, f64)| (a - c).abs() < 0.01 && (b - d).abs() < 0.01;
+    assert!(close(diameters(0.0, 0.0), (16.0, 5.0)));
+    assert!(close(diameters(0.0, 0.1), (16.0, 5.2)), "{:?}", diameters(0.0, 0.1));
+    // The hole shrinks with the outline growing, then grows back
+    assert!(close(diameters(0.1, 0.1), (16.2, 5.0)), "{:?}", diameters(0.1, 0.1));
+    // A hole that would swallow its island is left as it was
+    assert!(close(diameters(0.0, 10.0), (16.0, 5.0)), "{:?}", diameters(0.0, 10.0));
+}
+
+#[test]
+fn test_fuzzy_skin_roughens_external_walls_within_thickness() {
+    let paths = |fuzzy_skin: bool, fuzzy_skin_seed: u64| {
+        let config = SlicerConfig { fuzzy_skin, fuzzy_skin_thickness: 0.3, fuzzy_skin_seed, ..SlicerConfig::default() };
+        SliceEngine::new(common::cube(20.0), config).unwrap().generate_paths().unwrap()
+    };
+    let walls = |layers: &[rustslicer::LayerPaths], index: usize, role: PathRole| -> Vec<Vec<Point2<f64>>> {
+        layers[index]
+            .paths
+            .iter()
+            .filter_map(|path| match path {
+                ToolPath::Extrude { points, role: r, .. } if *r == role => Some(points.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let plain = paths(false, 0);
+    let fuzzy = paths(true, 7);
+    let outline = Polygon::new(walls(&plain, 10, PathRole::ExternalPerimeter)[0].clone());
+    let fuzzed = &walls(&fuzzy, 10, PathRole::ExternalPerimeter)[0];
+    let distances: Vec<f64> = fuzzed.iter().map(|p| outline.distance_to_point(p)).collect();
+    assert!(fuzzed.len() > 2 * outline.len());
+    assert!(distances.iter().all(|&d| d <= 0.3 + 1e-9));
+    assert!(distances.iter().any(|&d| d > 0.15));
+
+    // Inner walls and the first layer stay smooth
+    assert_eq!(walls(&fuzzy, 10, PathRole::Perimeter), walls(&plain, 10, PathRole::Perimeter));
+    assert_eq!(walls(&fuzzy, 0, PathRole::ExternalPerimeter), walls(&plain, 0, PathRole::ExternalPerimeter));
+
+    // The seed decides the surface
+    assert_eq!(paths(true, 7), fuzzy);
+    assert_ne!(paths(true, 8), fuzzy);
+}
+
+#[test]
+fn test_combined_infill_only_covers_what_its_layers_share() {
+    // The lower step ends partway through a group of layers, so its infill
+    // is printed layer by layer while the taller step's is combined
+    let config = SlicerConfig {
+        layer_height: 0.1,
+        first_layer_height: 0.1,
+        top_solid_layers: Some(0),
+        bottom_solid_layers: Some(0),
+        infill_combine_every: 3,
+        ..SlicerConfig::default()
+    };
+    let engine = SliceEngine::new(common::staircase(2, 10.0, 10.0, 0.5), config).unwrap();
+    let layers = engine.generate_paths().unwrap();
+
+    let mut split = 0;
+    for layer in &layers[1..] {
+        let top_of_group = layer.layer_index % 3 == 0;
+        for path in &layer.paths {
+            let ToolPath::Extrude { points, role: PathRole::Infill, flow_multiplier, .. } = path else {
+                continue;
+            };
+            if top_of_group {
+                assert!(*flow_multiplier == 1.0 || *flow_multiplier == 3.0);
+            } else {
+                assert_eq!(*flow_multiplier, 1.0);
+                assert!(points.iter().all(|point| point.x < 11.0), "layer {}: {:?}", layer.layer_index, points);
+                split += 1;
+            }
+        }
+    }
+    assert!(split > 0);
+    assert!(layers.iter().any(|layer| {
+        layer.paths.iter().any(|path| matches!(path, ToolPath::Extrude { flow_multiplier, .. } if *flow_multiplier == 3.0))
+    }));
+}
+
+#[test]
+fn test_monotonic_top_skin_runs_one_way_across_the_surface() {
+    let config = SlicerConfig { monotonic_top_infill: true, ..SlicerConfig::default() };
+    let engine = SliceEngine::new(common::cuboid([0.0, 0.0, 0.0], [20.0, 10.0, 2.0]), config).unwrap();
+    let layers = engine.generate_paths().unwrap();
+    let solid = |layer: &rustslicer::toolpath::LayerPaths| -> Vec<Vec<Point2<f64>>> {
+        layer
+            .paths
+            .iter()
+            .filter_map(|path| match path {
+                ToolPath::Extrude { points, role: PathRole::SolidInfill, .. } => Some(points.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Single lines on the top layer, each further across than the last and
+    // all pointing the same way
+    let top = layers.last().unwrap();
+    let lines = solid(top);
+    assert!(lines.len() > 10);
+    let angle = (45.0 + 90.0 * (top.layer_index % 2) as f64).to_radians();
+    let (along, across) = (Vector2::new(angle.cos(), angle.sin()), Vector2::new(-angle.sin(), angle.cos()));
+    let mut last = f64::NEG_INFINITY;
+    for line in &lines {
+        let [start, end] = line[..] else {
+            panic!("{:?}", line);
+        };
+        assert!((end - start).dot(&along) > 0.0);
+        let position = start.coords.dot(&across);
+        assert!(position > last, "{} after {}", position, last);
+        last = position;
+    }
+
+    // Skin under the top surface still zigzags
+    let below = &layers[layers.len() - 2];
+    assert!(solid(below).iter().any(|line| line.len() > 2));
+}
+
+#[test]
+fn test_elephant_foot_compensation_leaves_no_overhang_above_the_first_layer() {
+    let config = SlicerConfig { elephant_foot_compensation: 0.3, ..SlicerConfig::default() };
+    let layers = SliceEngine::new(common::cuboid([0.0, 0.0, 0.0], [20.0, 20.0, 2.0]), config.clone()).unwrap().slice().unwrap();
+    assert!(layers[0].total_area() < layers[1].total_area() - 1.0);
+    assert!(!detect_overhangs(&layers, &config).needs_support());
+}